@@ -0,0 +1,193 @@
+use super::avantage::{AvSeriesData, TimeSeries};
+use chrono::NaiveDate;
+use csv::Reader;
+use std::error::Error;
+
+/* A corporate action taking effect at the open of its ex-date
+Split ratio = new shares per old share (4.0 for a 4-for-1 split, 0.5 for a 1-for-2 reverse split)
+CashDividend amount = cash per share on the ex-date, in the share basis of that date
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CorporateAction {
+    Split { ex_date: NaiveDate, ratio: f64 },
+    CashDividend { ex_date: NaiveDate, amount: f64 },
+}
+
+impl CorporateAction {
+    pub fn ex_date(&self) -> NaiveDate {
+        match self {
+            CorporateAction::Split { ex_date, .. } | CorporateAction::CashDividend { ex_date, .. } => *ex_date,
+        }
+    }
+    fn same_event(&self, other: &CorporateAction) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other) && self.ex_date() == other.ex_date()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CorporateActions {
+    pub actions: Vec<CorporateAction>,
+}
+
+impl CorporateActions {
+    pub fn new(mut actions: Vec<CorporateAction>) -> Self {
+        actions.sort_by_key(|a| a.ex_date());
+        CorporateActions { actions }
+    }
+    // Splits (split coefficient != 1) and cash dividends reported by an adjusted Alpha Vantage series
+    pub fn from_av(data: &AvSeriesData) -> Self {
+        let mut actions = Vec::new();
+        for bar in &data.bars {
+            let ex_date = bar.timestamp.date();
+            if bar.split_coefficient > 0.0 && bar.split_coefficient != 1.0 {
+                actions.push(CorporateAction::Split { ex_date, ratio: bar.split_coefficient });
+            }
+            if bar.dividend > 0.0 {
+                actions.push(CorporateAction::CashDividend { ex_date, amount: bar.dividend });
+            }
+        }
+        CorporateActions::new(actions)
+    }
+    /* Loads actions from a csv of EX_DATE (YYYY-MM-DD),TYPE,VALUE rows
+    TYPE is "split" (VALUE = ratio, or "4:1" / "4-for-1") or "dividend" (VALUE = cash per share)
+    */
+    pub fn from_csv(csv_name: &str) -> Result<Self, Box<dyn Error>> {
+        let mut rdr = Reader::from_path(csv_name)?;
+        let mut actions = Vec::new();
+        for result in rdr.records() {
+            let record = result?;
+            let line = record.position().map_or(0, |p| p.line());
+            let field = |i: usize, name: &str| record.get(i).ok_or_else(|| format!("\nCorporateActions::from_csv() :: ERROR -> Line {} of {} has no {} column", line, csv_name, name));
+            let ex_date = NaiveDate::parse_from_str(field(0, "EX_DATE")?.trim(), "%Y-%m-%d")?;
+            let value = field(2, "VALUE")?.trim();
+            let action = match field(1, "TYPE")?.trim().to_lowercase().as_str() {
+                "split" => CorporateAction::Split { ex_date, ratio: parse_split_ratio(value)? },
+                "dividend" | "div" => CorporateAction::CashDividend { ex_date, amount: value.parse::<f64>()? },
+                other => return Err(format!("\nCorporateActions::from_csv() :: ERROR -> Unknown action type '{}' on {}; expected split or dividend", other, ex_date).into()),
+            };
+            actions.push(action);
+        }
+        Ok(CorporateActions::new(actions))
+    }
+    // Combines two sets of actions; an action in `other` replaces one of the same type on the same ex-date
+    pub fn merge(&self, other: &CorporateActions) -> Self {
+        let mut actions: Vec<CorporateAction> = self.actions.iter().filter(|a| !other.actions.iter().any(|o| o.same_event(a))).copied().collect();
+        actions.extend(other.actions.iter().copied());
+        CorporateActions::new(actions)
+    }
+    /* Sets every bar's cumulative back-adjustment factors from the actions going ex after it
+    Split: prices * 1 / ratio and volume * ratio
+    CashDividend: prices * (1 - amount / raw close of the last bar before the ex-date) (CRSP-style total return adjustment)
+    Actions after the last bar, and dividends without a prior close or at least as large as it, are skipped
+    */
+    pub fn apply(&self, ts: &mut TimeSeries) {
+        let (mut pf, mut vf) = (1.0, 1.0);
+        let mut pending = self.actions.iter().rev().peekable();
+        let last_date = match ts.bars.last() {
+            Some(bar) => bar.date,
+            None => return,
+        };
+        while pending.next_if(|a| a.ex_date() > last_date).is_some() {}
+        for bar in ts.bars.iter_mut().rev() {
+            while let Some(action) = pending.next_if(|a| a.ex_date() > bar.date) {
+                match *action {
+                    CorporateAction::Split { ratio, .. } => {
+                        pf /= ratio;
+                        vf *= ratio;
+                    },
+                    CorporateAction::CashDividend { ex_date, amount } => {
+                        if amount < bar.close {
+                            pf *= 1.0 - amount / bar.close;
+                        } else {
+                            eprintln!("\nCorporateActions::apply() :: Skipping {} dividend of {} not below the prior close {}", ex_date, amount, bar.close);
+                        }
+                    },
+                }
+            }
+            bar.price_factor = pf;
+            bar.volume_factor = vf;
+        }
+    }
+}
+
+fn parse_split_ratio(value: &str) -> Result<f64, Box<dyn Error>> {
+    let cleaned = value.to_lowercase().replace("-for-", ":").replace('/', ":");
+    let ratio = match cleaned.split_once(':') {
+        Some((new, old)) => new.trim().parse::<f64>()? / old.trim().parse::<f64>()?,
+        None => cleaned.trim().parse::<f64>()?,
+    };
+    if !(ratio.is_finite() && ratio > 0.0) {
+        return Err(format!("\nparse_split_ratio() :: ERROR -> Invalid split ratio '{}'", value).into());
+    }
+    Ok(ratio)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::avantage::{AvSeries, Bar};
+    use serde_json::Value;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn split_back_adjusts_prices_and_volume() {
+        let bars = vec![
+            Bar::new(date("2024-06-06"), 1200.0, 1220.0, 1190.0, 1208.0, 100.0),
+            Bar::new(date("2024-06-07"), 1210.0, 1215.0, 1195.0, 1200.0, 120.0),
+            Bar::new(date("2024-06-10"), 120.0, 122.0, 118.0, 121.0, 1500.0),
+        ];
+        let mut ts = TimeSeries::new("NVDA", bars);
+        CorporateActions::new(vec![CorporateAction::Split { ex_date: date("2024-06-10"), ratio: 10.0 }]).apply(&mut ts);
+        let adj = ts.adjusted();
+        assert!((adj.bars[0].close - 120.8).abs() < 1e-12);
+        assert_eq!(adj.bars[1].volume, 1200.0);
+        assert_eq!(adj.bars[2], ts.bars[2]);
+        // The raw bars are untouched
+        assert_eq!(ts.bars[1].close, 1200.0);
+    }
+
+    #[test]
+    fn dividends_match_alpha_vantage_adjusted_close() {
+        let doc: Value = serde_json::from_str(&std::fs::read_to_string("fixtures/av/IBM_daily_adjusted.json").unwrap()).unwrap();
+        let data = AvSeriesData::from_json("IBM", AvSeries::DailyAdjusted, &doc).unwrap();
+        let actions = CorporateActions::from_av(&data);
+        assert_eq!(actions.actions, vec![CorporateAction::CashDividend { ex_date: date("2024-05-09"), amount: 1.67 }]);
+        let mut ts = data.to_tseries();
+        actions.apply(&mut ts);
+        for (bar, av_bar) in ts.adjusted().bars.iter().zip(&data.bars) {
+            assert!((bar.close - av_bar.adjusted_close.unwrap()).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn merge_prefers_the_second_set() {
+        let av = CorporateActions::new(vec![
+            CorporateAction::Split { ex_date: date("2020-08-31"), ratio: 4.0 },
+            CorporateAction::CashDividend { ex_date: date("2020-08-07"), amount: 0.82 },
+        ]);
+        let manual = CorporateActions::new(vec![CorporateAction::CashDividend { ex_date: date("2020-08-07"), amount: 0.8 }]);
+        let merged = av.merge(&manual);
+        assert_eq!(merged.actions.len(), 2);
+        assert_eq!(merged.actions[0], manual.actions[0]);
+        assert_eq!(parse_split_ratio("4-for-1").unwrap(), 4.0);
+        assert_eq!(parse_split_ratio("1:2").unwrap(), 0.5);
+    }
+
+    #[test]
+    fn loads_actions_from_csv() {
+        let dir = std::env::temp_dir().join(format!("markets_adjust_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let csv_name = dir.join("TEST_actions.csv");
+        let csv_name = csv_name.to_str().unwrap();
+        std::fs::write(csv_name, "EX_DATE,TYPE,VALUE\n2024-06-10,split,10:1\n2024-06-03,dividend,0.01\n").unwrap();
+        let actions = CorporateActions::from_csv(csv_name).unwrap();
+        assert_eq!(actions.actions, vec![CorporateAction::CashDividend { ex_date: date("2024-06-03"), amount: 0.01 }, CorporateAction::Split { ex_date: date("2024-06-10"), ratio: 10.0 }]);
+        std::fs::write(csv_name, "EX_DATE,TYPE\n2024-06-10,split\n").unwrap();
+        let err = CorporateActions::from_csv(csv_name).unwrap_err().to_string();
+        assert!(err.contains("Line 2") && err.contains("no VALUE column"), "{}", err);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,940 @@
+use super::adjust::CorporateActions;
+use super::indicators::{compute_columns, indicator_from_name, DEFAULT_INDICATORS};
+use super::volatility::{ewma_vol, rolling_vol, RvEstimator, EWMA_LAMBDA};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use csv::{ReaderBuilder, Writer};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use super::utils::csv_value;
+
+const AVURL: &str = "https://www.alphavantage.co/query";
+const AVKEYFILE: &str = "dummy_key.txt";
+const AVKEYENV: &str = "ALPHAVANTAGE_API_KEY";
+const AVMAXREQUESTS: usize = 5;
+const DATEFMT: &str = "%Y-%m-%d";
+const AVDTFMT: &str = "%Y-%m-%d %H:%M:%S";
+pub const TRADING_DAYS: f64 = 252.0;
+pub const RVWINDOW: usize = 30;
+pub const ATRWINDOW: usize = 14;
+pub const LRWINDOW: usize = 20;
+pub const FDORDER: usize = 2;
+
+/* One daily OHLCV bar of raw (as traded) prices
+price_factor / volume_factor = cumulative back-adjustment factors for the splits and cash dividends going ex after
+this bar (1.0 when unadjusted; see adjust::CorporateActions::apply())
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bar {
+    pub date: NaiveDate,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub price_factor: f64,
+    pub volume_factor: f64,
+}
+
+impl Bar {
+    pub fn new(date: NaiveDate, open: f64, high: f64, low: f64, close: f64, volume: f64) -> Self {
+        Bar { date, open, high, low, close, volume, price_factor: 1.0, volume_factor: 1.0 }
+    }
+    // The bar in back-adjusted terms (factors folded in and reset to 1.0)
+    pub fn adjusted(&self) -> Bar {
+        let pf = self.price_factor;
+        Bar::new(self.date, self.open * pf, self.high * pf, self.low * pf, self.close * pf, self.volume * self.volume_factor)
+    }
+}
+
+// OHLCV bars of one ticker in chronological order (oldest first)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TimeSeries {
+    pub ticker: String,
+    pub bars: Vec<Bar>,
+}
+
+impl TimeSeries {
+    pub fn new(ticker: &str, mut bars: Vec<Bar>) -> Self {
+        bars.sort_by_key(|b| b.date);
+        bars.dedup_by_key(|b| b.date);
+        TimeSeries { ticker: ticker.to_string(), bars }
+    }
+    // Back-adjusted copy of the series; identical to the raw series when no corporate actions were applied
+    pub fn adjusted(&self) -> TimeSeries {
+        TimeSeries { ticker: self.ticker.clone(), bars: self.bars.iter().map(Bar::adjusted).collect() }
+    }
+    pub fn closes(&self) -> Vec<f64> {
+        self.bars.iter().map(|b| b.close).collect()
+    }
+    pub fn len(&self) -> usize {
+        self.bars.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.bars.is_empty()
+    }
+    pub fn periods_per_year(&self) -> f64 {
+        periods_per_year(&self.bars)
+    }
+    // Daily bars (including intraday bars aggregated to days), the only ones GARCH fits and volatility cones accept
+    pub fn is_daily(&self) -> bool {
+        self.periods_per_year() == TRADING_DAYS
+    }
+}
+
+/* Bars per year used to annualize volatility: TRADING_DAYS for daily bars, 52 for weekly and 12 for monthly ones
+Told apart by the median calendar-day gap between bars, as the av csv does not record which Alpha Vantage series it holds
+*/
+pub fn periods_per_year(bars: &[Bar]) -> f64 {
+    let mut gaps: Vec<i64> = bars.windows(2).map(|w| (w[1].date - w[0].date).num_days()).collect();
+    if gaps.is_empty() {
+        return TRADING_DAYS;
+    }
+    gaps.sort_unstable();
+    match gaps[gaps.len() / 2] {
+        ..=4 => TRADING_DAYS,
+        5..=10 => 52.0,
+        _ => 12.0,
+    }
+}
+
+// Daily log returns ln(C[t] / C[t-1]); one shorter than the series, in the order of the TimeSeries bars
+pub fn daily_returns(ts: &TimeSeries) -> Vec<f64> {
+    ts.bars.windows(2).map(|w| (w[1].close / w[0].close).ln()).collect()
+}
+
+/* Annualized close-to-close realized volatility of the `window` returns up to every bar (sample standard deviation * sqrt(periods per year)),
+aligned with the bars (NaN until window + 1 bars are available)
+*/
+pub fn rolling_realized_vol(ts: &TimeSeries, window: usize) -> Vec<f64> {
+    let mut vols = vec![f64::NAN; ts.len()];
+    if window < 2 {
+        return vols;
+    }
+    let periods = ts.periods_per_year();
+    for (i, recent) in daily_returns(ts).windows(window).enumerate() {
+        let avg = mean(recent);
+        let var = recent.iter().map(|r| (r - avg).powi(2)).sum::<f64>() / (window - 1) as f64;
+        vols[i + window] = (var * periods).sqrt();
+    }
+    vols
+}
+
+// True range of every bar: max(H - L, |H - C[t-1]|, |L - C[t-1]|), or H - L for the first bar
+pub fn calculate_true_range(ts: &TimeSeries) -> Vec<f64> {
+    ts.bars
+        .iter()
+        .enumerate()
+        .map(|(i, b)| {
+            let range = b.high - b.low;
+            if i == 0 {
+                return range;
+            }
+            let prev_close = ts.bars[i - 1].close;
+            range.max((b.high - prev_close).abs()).max((b.low - prev_close).abs())
+        })
+        .collect()
+}
+
+/* Wilder average true range at every bar, aligned with the bars (NaN until `window` true ranges are available)
+Seeded with the mean of the first `window` true ranges, then ATR = (ATR * (n - 1) + TR) / n
+*/
+pub fn rolling_avg_true_range(ts: &TimeSeries, window: usize) -> Vec<f64> {
+    let tr = calculate_true_range(ts);
+    let mut atrs = vec![f64::NAN; tr.len()];
+    if window == 0 || tr.len() < window {
+        return atrs;
+    }
+    let n = window as f64;
+    let mut atr = mean(&tr[..window]);
+    atrs[window - 1] = atr;
+    for (value, x) in atrs.iter_mut().zip(&tr).skip(window) {
+        atr = (atr * (n - 1.0) + x) / n;
+        *value = atr;
+    }
+    atrs
+}
+
+// ln(n!) summed exactly up to 20! and from Stirling's series beyond
+pub fn ln_factorial(n: u64) -> f64 {
+    if n <= 20 {
+        return (2..=n).map(|i| (i as f64).ln()).sum();
+    }
+    let x = n as f64;
+    x * x.ln() - x + 0.5 * (2.0 * std::f64::consts::PI * x).ln() + 1.0 / (12.0 * x) - 1.0 / (360.0 * x.powi(3)) + 1.0 / (1260.0 * x.powi(5))
+}
+
+// n choose k via ln_factorial()
+pub fn binomial_coefficient(n: u64, k: u64) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    (ln_factorial(n) - ln_factorial(k) - ln_factorial(n - k)).exp().round()
+}
+
+/* Backward finite difference of the given order at the latest point (unit spacing of one bar)
+sum over i = 0..=order of (-1)^i * C(order, i) * data[t - i]; approximates the order-th derivative
+*/
+pub fn back_finite_diff(data: &[f64], order: usize) -> f64 {
+    if data.len() <= order {
+        return f64::NAN;
+    }
+    let t = data.len() - 1;
+    (0..=order)
+        .map(|i| {
+            let sign = if i % 2 == 0 { 1.0 } else { -1.0 };
+            sign * binomial_coefficient(order as u64, i as u64) * data[t - i]
+        })
+        .sum()
+}
+
+pub fn mean(data: &[f64]) -> f64 {
+    if data.is_empty() {
+        return f64::NAN;
+    }
+    data.iter().sum::<f64>() / data.len() as f64
+}
+
+// Least-squares line through (0, data[0]) .. (n - 1, data[n - 1]) evaluated at the latest point
+pub fn linear_regression(data: &[f64]) -> f64 {
+    let n = data.len();
+    if n < 2 {
+        return f64::NAN;
+    }
+    let x_mean = (n - 1) as f64 / 2.0;
+    let y_mean = mean(data);
+    let (sxy, sxx) = data.iter().enumerate().fold((0.0, 0.0), |(sxy, sxx), (i, y)| {
+        let dx = i as f64 - x_mean;
+        (sxy + dx * (y - y_mean), sxx + dx * dx)
+    });
+    let slope = sxy / sxx;
+    y_mean + slope * ((n - 1) as f64 - x_mean)
+}
+
+/* Saves a TimeSeries as a csv of the raw OHLCV bars, their back-adjusted counterparts (AdjClose .. AdjVolume with the
+PriceFactor / VolumeFactor used) and the indicators plotted by graphing::generate_tseries_plot()
+Indicators are computed on the adjusted series so splits and dividends do not show up as jumps; every derived column
+at row t only uses bars up to t: AvgTrueRange (ATRWINDOW), RealizedVol (RVWINDOW), FiniteDiff (order FDORDER of Close),
+and rolling LinearReg / ChangeLinearReg / FiniteDiffLinearReg (LRWINDOW), then the OHLC realized volatility estimators
+(ParkinsonVol .. YangZhangVol over RVWINDOW) and EwmaVol (EWMA_LAMBDA, seeded over RVWINDOW), followed by the columns
+of every indicators::DEFAULT_INDICATORS entry
+*/
+pub fn tseries_to_csv(ts: &TimeSeries, csv_name: &str) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_path(csv_name)?;
+    let adj = ts.adjusted();
+    let mut indicators = DEFAULT_INDICATORS.iter().map(|name| indicator_from_name(name)).collect::<Result<Vec<_>, _>>()?;
+    let mut indicator_columns: Vec<(String, Vec<f64>)> = RvEstimator::ALL
+        .iter()
+        .filter(|est| **est != RvEstimator::CloseToClose)
+        .map(|est| (format!("{}Vol", est.label()), rolling_vol(&adj, RVWINDOW, *est)))
+        .collect();
+    indicator_columns.push(("EwmaVol".to_string(), ewma_vol(&adj, EWMA_LAMBDA, RVWINDOW)));
+    indicator_columns.extend(compute_columns(&adj, &mut indicators));
+    let mut header: Vec<String> = ["Date", "Close", "Open", "Low", "High", "Volume", "Change", "%Change", "Range", "AvgTrueRange", "RealizedVol", "FiniteDiff", "LinearReg", "ChangeLinearReg", "FiniteDiffLinearReg", "AdjClose", "AdjOpen", "AdjLow", "AdjHigh", "AdjVolume", "PriceFactor", "VolumeFactor"]
+        .iter()
+        .map(|h| h.to_string())
+        .collect();
+    header.extend(indicator_columns.iter().map(|(name, _)| name.clone()));
+    wtr.write_record(&header)?;
+    let closes = adj.closes();
+    let changes: Vec<f64> = (0..closes.len()).map(|i| if i == 0 { f64::NAN } else { closes[i] - closes[i - 1] }).collect();
+    let diffs: Vec<f64> = (0..closes.len()).map(|i| back_finite_diff(&closes[..=i], FDORDER)).collect();
+    let (atrs, vols) = (rolling_avg_true_range(&adj, ATRWINDOW), rolling_realized_vol(&adj, RVWINDOW));
+    let rolling = |data: &[f64], i: usize| {
+        let start = (i + 1).saturating_sub(LRWINDOW);
+        let window = &data[start..=i];
+        if window.len() < LRWINDOW || window.iter().any(|x| !x.is_finite()) {
+            f64::NAN
+        } else {
+            linear_regression(window)
+        }
+    };
+    for (i, (bar, adj_bar)) in ts.bars.iter().zip(&adj.bars).enumerate() {
+        let pct_change = if i == 0 { f64::NAN } else { 100.0 * changes[i] / closes[i - 1] };
+        let mut record = vec![
+            bar.date.format(DATEFMT).to_string(),
+            bar.close.to_string(),
+            bar.open.to_string(),
+            bar.low.to_string(),
+            bar.high.to_string(),
+            bar.volume.to_string(),
+            csv_value(changes[i]),
+            csv_value(pct_change),
+            csv_value(adj_bar.high - adj_bar.low),
+            csv_value(atrs[i]),
+            csv_value(vols[i]),
+            csv_value(diffs[i]),
+            csv_value(rolling(&closes, i)),
+            csv_value(rolling(&changes, i)),
+            csv_value(rolling(&diffs, i)),
+            adj_bar.close.to_string(),
+            adj_bar.open.to_string(),
+            adj_bar.low.to_string(),
+            adj_bar.high.to_string(),
+            adj_bar.volume.to_string(),
+            bar.price_factor.to_string(),
+            bar.volume_factor.to_string(),
+        ];
+        record.extend(indicator_columns.iter().map(|(_, values)| csv_value(values[i])));
+        wtr.write_record(&record)?;
+    }
+    wtr.flush()?;
+    Ok(())
+}
+
+/* Instantiates a TimeSeries from a csv written by tseries_to_csv(); derived columns are recomputed rather than read
+The ticker is taken from the file name (<TICKER>_av_<datetime>.csv); files without PriceFactor / VolumeFactor columns load unadjusted
+*/
+pub fn tseries_from_csv(csv_name: &str) -> Result<TimeSeries, Box<dyn Error>> {
+    let mut rdr = ReaderBuilder::new().has_headers(true).from_path(csv_name)?;
+    let headers = rdr.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|h| h == name).ok_or_else(|| format!("\ntseries_from_csv() :: ERROR -> {} has no {} column", csv_name, name));
+    let (date_col, open_col, high_col, low_col, close_col, volume_col) = (column("Date")?, column("Open")?, column("High")?, column("Low")?, column("Close")?, column("Volume")?);
+    let (pf_col, vf_col) = (column("PriceFactor").ok(), column("VolumeFactor").ok());
+    let mut bars = Vec::new();
+    for result in rdr.records() {
+        let record = result?;
+        let factor = |col: std::option::Option<usize>| -> Result<f64, Box<dyn Error>> {
+            match col {
+                Some(c) => Ok(record[c].trim().parse::<f64>()?),
+                None => Ok(1.0),
+            }
+        };
+        bars.push(Bar {
+            date: NaiveDate::parse_from_str(record[date_col].trim(), DATEFMT)?,
+            open: record[open_col].trim().parse::<f64>()?,
+            high: record[high_col].trim().parse::<f64>()?,
+            low: record[low_col].trim().parse::<f64>()?,
+            close: record[close_col].trim().parse::<f64>()?,
+            volume: record[volume_col].trim().parse::<f64>()?,
+            price_factor: factor(pf_col)?,
+            volume_factor: factor(vf_col)?,
+        });
+    }
+    let stem = Path::new(csv_name).file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let ticker = stem.split('_').next().unwrap_or_default();
+    Ok(TimeSeries::new(ticker, bars))
+}
+
+/* Alpha Vantage time series endpoints
+Daily = TIME_SERIES_DAILY (free tier, raw prices only)
+DailyAdjusted = TIME_SERIES_DAILY_ADJUSTED (adds adjusted close, dividend amount and split coefficient)
+Intraday = TIME_SERIES_INTRADAY at a bar interval
+Weekly / Monthly = TIME_SERIES_WEEKLY_ADJUSTED / TIME_SERIES_MONTHLY_ADJUSTED
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AvSeries {
+    Daily,
+    DailyAdjusted,
+    Intraday(AvInterval),
+    Weekly,
+    Monthly,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AvInterval {
+    Min1,
+    Min5,
+    Min15,
+    Min30,
+    Min60,
+}
+
+impl AvInterval {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AvInterval::Min1 => "1min",
+            AvInterval::Min5 => "5min",
+            AvInterval::Min15 => "15min",
+            AvInterval::Min30 => "30min",
+            AvInterval::Min60 => "60min",
+        }
+    }
+}
+
+// Parses a series name: daily, daily_adjusted, weekly, monthly or an intraday interval (1min, 5min, 15min, 30min, 60min)
+impl FromStr for AvSeries {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "daily" => Ok(AvSeries::Daily),
+            "daily_adjusted" | "adjusted" => Ok(AvSeries::DailyAdjusted),
+            "weekly" => Ok(AvSeries::Weekly),
+            "monthly" => Ok(AvSeries::Monthly),
+            "1min" => Ok(AvSeries::Intraday(AvInterval::Min1)),
+            "5min" => Ok(AvSeries::Intraday(AvInterval::Min5)),
+            "15min" => Ok(AvSeries::Intraday(AvInterval::Min15)),
+            "30min" => Ok(AvSeries::Intraday(AvInterval::Min30)),
+            "60min" => Ok(AvSeries::Intraday(AvInterval::Min60)),
+            _ => Err(format!("\nAvSeries::from_str() :: ERROR -> Unknown Alpha Vantage series '{}'; expected one of daily, daily_adjusted, weekly, monthly, 1min, 5min, 15min, 30min, 60min", s)),
+        }
+    }
+}
+
+impl AvSeries {
+    pub fn function(&self) -> &'static str {
+        match self {
+            AvSeries::Daily => "TIME_SERIES_DAILY",
+            AvSeries::DailyAdjusted => "TIME_SERIES_DAILY_ADJUSTED",
+            AvSeries::Intraday(_) => "TIME_SERIES_INTRADAY",
+            AvSeries::Weekly => "TIME_SERIES_WEEKLY_ADJUSTED",
+            AvSeries::Monthly => "TIME_SERIES_MONTHLY_ADJUSTED",
+        }
+    }
+}
+
+// One Alpha Vantage bar; adjusted_close, dividend and split_coefficient are only reported by the adjusted endpoints
+#[derive(Debug, Clone, PartialEq)]
+pub struct AvBar {
+    pub timestamp: NaiveDateTime,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub adjusted_close: Option<f64>,
+    pub volume: f64,
+    pub dividend: f64,
+    pub split_coefficient: f64,
+}
+
+// A parsed Alpha Vantage time series response, bars in chronological order
+#[derive(Debug, Clone, PartialEq)]
+pub struct AvSeriesData {
+    pub symbol: String,
+    pub series: AvSeries,
+    pub bars: Vec<AvBar>,
+}
+
+impl AvSeriesData {
+    /* Parses the "... Time Series ..." object of a response; fields are matched on their name after the
+    "N. " prefix since the numbering differs between endpoints ("5. volume" vs "6. volume")
+    */
+    pub fn from_json(symbol: &str, series: AvSeries, doc: &Value) -> Result<Self, AvError> {
+        let data = doc
+            .as_object()
+            .and_then(|o| o.iter().find(|(k, _)| k.contains("Time Series")))
+            .and_then(|(_, v)| v.as_object())
+            .ok_or_else(|| AvError::Parse(format!("no time series object in the response: {}", doc)))?;
+        let mut bars = Vec::new();
+        for (stamp, fields) in data {
+            let fields = fields.as_object().ok_or_else(|| AvError::Parse(format!("{} is not an object", stamp)))?;
+            let field = |name: &str| -> Result<std::option::Option<f64>, AvError> {
+                match fields.iter().find(|(k, _)| k.split_once(". ").map_or(k.as_str(), |(_, n)| n) == name) {
+                    Some((k, v)) => v.as_str().unwrap_or_default().parse::<f64>().map(Some)
+                        .map_err(|e| AvError::Parse(format!("{} '{}': {}", stamp, k, e))),
+                    None => Ok(None),
+                }
+            };
+            let required = |name: &str| field(name)?.ok_or_else(|| AvError::Parse(format!("{} has no '{}' field", stamp, name)));
+            let timestamp = NaiveDateTime::parse_from_str(stamp, AVDTFMT)
+                .or_else(|_| NaiveDate::parse_from_str(stamp, DATEFMT).map(|d| d.and_time(NaiveTime::MIN)))
+                .map_err(|e| AvError::Parse(format!("timestamp '{}': {}", stamp, e)))?;
+            bars.push(AvBar {
+                timestamp,
+                open: required("open")?,
+                high: required("high")?,
+                low: required("low")?,
+                close: required("close")?,
+                adjusted_close: field("adjusted close")?,
+                volume: required("volume")?,
+                dividend: field("dividend amount")?.unwrap_or(0.0),
+                split_coefficient: field("split coefficient")?.unwrap_or(1.0),
+            });
+        }
+        bars.sort_by_key(|b| b.timestamp);
+        Ok(AvSeriesData { symbol: symbol.to_string(), series, bars })
+    }
+    // Raw OHLCV bars keyed by date; intraday bars are aggregated into one bar per day (first open, max high, min low, last close, summed volume)
+    pub fn to_tseries(&self) -> TimeSeries {
+        let mut bars: Vec<Bar> = Vec::new();
+        for b in &self.bars {
+            let date = b.timestamp.date();
+            match bars.last_mut() {
+                Some(day) if day.date == date => {
+                    day.high = day.high.max(b.high);
+                    day.low = day.low.min(b.low);
+                    day.close = b.close;
+                    day.volume += b.volume;
+                },
+                _ => bars.push(Bar::new(date, b.open, b.high, b.low, b.close, b.volume)),
+            }
+        }
+        TimeSeries::new(&self.symbol, bars)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AvError {
+    // "Note" responses (HTTP 200) sent when the per-minute request rate is exceeded
+    Throttled(String),
+    // "Information" responses (HTTP 200) sent when the daily quota is spent or the series/outputsize is premium-only
+    Refused(String),
+    // "Error Message" responses (unknown symbol, bad parameters, invalid key)
+    Api(String),
+    Http(String),
+    Parse(String),
+}
+
+impl fmt::Display for AvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AvError::Throttled(msg) => write!(f, "\nAvClient :: ERROR -> Alpha Vantage throttled the request: {}", msg),
+            AvError::Refused(msg) => write!(f, "\nAvClient :: ERROR -> Alpha Vantage refused the request (daily quota spent, or a premium-only series or outputsize=full; a free key serves compact daily series): {}", msg),
+            AvError::Api(msg) => write!(f, "\nAvClient :: ERROR -> Alpha Vantage returned an error: {}", msg),
+            AvError::Http(msg) => write!(f, "\nAvClient :: ERROR -> HTTP request failed: {}", msg),
+            AvError::Parse(msg) => write!(f, "\nAvClient :: ERROR -> Could not parse the Alpha Vantage response: {}", msg),
+        }
+    }
+}
+
+impl Error for AvError {}
+
+// Classifies HTTP-200 bodies that carry no data
+fn av_response_error(doc: &Value) -> std::option::Option<AvError> {
+    let text = |key: &str| doc.get(key).map(|v| v.as_str().map_or_else(|| v.to_string(), str::to_string));
+    if let Some(msg) = text("Error Message") {
+        return Some(AvError::Api(msg));
+    }
+    text("Note").map(AvError::Throttled).or_else(|| text("Information").map(AvError::Refused))
+}
+
+/* Reads the API key from an environment variable, falling back to the first line of a key file
+(ALPHAVANTAGE_API_KEY and dummy_key.txt for get_underlying_av())
+*/
+pub fn load_api_key(env_var: &str, key_file: &str) -> Result<String, Box<dyn Error>> {
+    let key = match env::var(env_var) {
+        Ok(k) if !k.trim().is_empty() => k,
+        _ => fs::read_to_string(key_file)
+            .map_err(|e| format!("\nload_api_key() :: ERROR -> {} is not set and {} could not be read: {}", env_var, key_file, e))?,
+    };
+    let key = key.lines().next().unwrap_or_default().trim().to_string();
+    if key.is_empty() {
+        return Err(format!("\nload_api_key() :: ERROR -> No Alpha Vantage API key in {} or {}", env_var, key_file).into());
+    }
+    Ok(key)
+}
+
+/* Blocking Alpha Vantage client
+full_output requests outputsize=full (the whole daily history, or the trailing month of an intraday series) instead of the
+latest 100 bars, too few for the 120 day volatility cone and GARCH fits, but premium-only; AvClient::new() leaves it off
+Requests are spaced so that at most max_requests go out per window (the free tier allows 5 per minute; retries count too).
+"Note" throttle responses, transport errors and HTTP 5xx/429 are retried up to max_retries times, waiting backoff * 2^attempt;
+"Information" (quota or premium) and "Error Message" responses fail at once
+*/
+pub struct AvClient {
+    pub base_url: String,
+    pub api_key: String,
+    pub full_output: bool,
+    pub max_requests: usize,
+    pub window: Duration,
+    pub max_retries: u32,
+    pub backoff: Duration,
+    http: reqwest::blocking::Client,
+    sent: Mutex<VecDeque<Instant>>,
+}
+
+impl AvClient {
+    pub fn new(api_key: &str) -> Self {
+        AvClient {
+            base_url: AVURL.to_string(),
+            api_key: api_key.to_string(),
+            full_output: false,
+            max_requests: AVMAXREQUESTS,
+            window: Duration::from_secs(60),
+            max_retries: 3,
+            backoff: Duration::from_secs(15),
+            http: reqwest::blocking::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()
+                .expect("AvClient::new() :: could not build the HTTP client"),
+            sent: Mutex::new(VecDeque::new()),
+        }
+    }
+    pub fn from_env_or_file() -> Result<Self, Box<dyn Error>> {
+        Ok(AvClient::new(&load_api_key(AVKEYENV, AVKEYFILE)?))
+    }
+    // Blocks until another request fits in the rate limit window, then records it
+    fn wait_for_slot(&self) {
+        let mut sent = self.sent.lock().unwrap_or_else(|e| e.into_inner());
+        while sent.front().is_some_and(|t| t.elapsed() >= self.window) {
+            sent.pop_front();
+        }
+        if sent.len() >= self.max_requests.max(1) {
+            if let Some(oldest) = sent.pop_front() {
+                thread::sleep(self.window.saturating_sub(oldest.elapsed()));
+            }
+        }
+        sent.push_back(Instant::now());
+    }
+    fn request(&self, params: &[(&str, &str)]) -> Result<Value, AvError> {
+        let url = reqwest::Url::parse_with_params(&self.base_url, params).map_err(|e| AvError::Http(e.to_string()))?;
+        let mut attempt = 0;
+        loop {
+            self.wait_for_slot();
+            let result = match self.http.get(url.clone()).send() {
+                Ok(resp) if resp.status().is_server_error() || resp.status().as_u16() == 429 => Err(AvError::Http(format!("HTTP {}", resp.status()))),
+                Ok(resp) if !resp.status().is_success() => return Err(AvError::Http(format!("HTTP {}", resp.status()))),
+                Ok(resp) => match resp.json::<Value>() {
+                    Ok(doc) => match av_response_error(&doc) {
+                        Some(AvError::Throttled(msg)) => Err(AvError::Throttled(msg)),
+                        Some(err) => return Err(err),
+                        None => Ok(doc),
+                    },
+                    Err(e) => return Err(AvError::Parse(e.to_string())),
+                },
+                Err(e) => Err(AvError::Http(e.to_string())),
+            };
+            match result {
+                Ok(doc) => return Ok(doc),
+                Err(e) if attempt >= self.max_retries => return Err(e),
+                Err(e) => {
+                    let wait = self.backoff * 2u32.pow(attempt);
+                    eprintln!("\nAvClient::request() :: Retrying {} in {:?} (attempt {} of {}): {}", params[0].1, wait, attempt + 1, self.max_retries, e);
+                    thread::sleep(wait);
+                    attempt += 1;
+                },
+            }
+        }
+    }
+    pub fn fetch(&self, symbol: &str, series: AvSeries) -> Result<AvSeriesData, AvError> {
+        let outputsize = if self.full_output { "full" } else { "compact" };
+        let mut params = vec![("function", series.function()), ("symbol", symbol), ("outputsize", outputsize), ("apikey", self.api_key.as_str())];
+        if let AvSeries::Intraday(interval) = series {
+            params.push(("interval", interval.as_str()));
+        }
+        let doc = self.request(&params)?;
+        AvSeriesData::from_json(symbol, series, &doc)
+    }
+}
+
+/* Calls the Alpha Vantage API to fetch OHLCV data (key from ALPHAVANTAGE_API_KEY or dummy_key.txt) and saves it with tseries_to_csv()
+full_output = outputsize=full rather than compact (the latest 100 bars). Intraday series are aggregated into daily bars. The series is back-adjusted for the splits and dividends reported by
+the adjusted endpoints merged with `actions` (which win on the same ex-date)
+*/
+pub fn get_underlying_av(ticker: &str, series: AvSeries, full_output: bool, actions: &CorporateActions, csv_name: &str) -> Result<(), Box<dyn Error>> {
+    let mut client = AvClient::from_env_or_file()?;
+    client.full_output = full_output;
+    let data = client.fetch(ticker, series)?;
+    let mut ts = data.to_tseries();
+    CorporateActions::from_av(&data).merge(actions).apply(&mut ts);
+    if ts.is_empty() {
+        return Err(format!("\nget_underlying_av() :: ERROR -> Alpha Vantage returned no {} bars for {}", series.function(), ticker).into());
+    }
+    tseries_to_csv(&ts, csv_name)?;
+    println!("\nget_underlying_av() :: Successfully saved {} bars of {} for {} as {}", ts.len(), series.function(), ticker, csv_name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    // Ten fixed bars; closes follow 100, 102, 101, 103, 106, 105, 107, 110, 108, 111
+    fn fixture() -> TimeSeries {
+        let rows = [
+            ("2024-01-02", 99.0, 101.0, 98.0, 100.0, 1000.0),
+            ("2024-01-03", 100.5, 103.0, 100.0, 102.0, 1200.0),
+            ("2024-01-04", 102.0, 102.5, 100.0, 101.0, 900.0),
+            ("2024-01-05", 101.0, 104.0, 100.5, 103.0, 1100.0),
+            ("2024-01-08", 103.5, 107.0, 104.5, 106.0, 1500.0),
+            ("2024-01-09", 106.0, 106.5, 104.0, 105.0, 800.0),
+            ("2024-01-10", 105.0, 108.0, 104.5, 107.0, 1300.0),
+            ("2024-01-11", 107.5, 111.0, 107.0, 110.0, 1700.0),
+            ("2024-01-12", 110.0, 110.5, 107.5, 108.0, 1000.0),
+            ("2024-01-16", 108.0, 112.0, 107.0, 111.0, 1400.0),
+        ];
+        let bars = rows
+            .iter()
+            .map(|&(d, open, high, low, close, volume)| Bar::new(NaiveDate::parse_from_str(d, DATEFMT).unwrap(), open, high, low, close, volume))
+            .collect();
+        TimeSeries::new("TEST", bars)
+    }
+
+    #[test]
+    fn daily_returns_are_log_returns() {
+        let r = daily_returns(&fixture());
+        assert_eq!(r.len(), 9);
+        assert!((r[0] - (102.0f64 / 100.0).ln()).abs() < 1e-15);
+        assert!((r.iter().sum::<f64>() - (111.0f64 / 100.0).ln()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn realized_vol_matches_hand_calculation() {
+        let ts = fixture();
+        let r = daily_returns(&ts);
+        let recent = &r[r.len() - 5..];
+        let m = recent.iter().sum::<f64>() / 5.0;
+        let expected = (recent.iter().map(|x| (x - m).powi(2)).sum::<f64>() / 4.0 * 252.0).sqrt();
+        assert!((rolling_realized_vol(&ts, 5)[9] - expected).abs() < 1e-12);
+        assert!(rolling_realized_vol(&ts, 10).iter().all(|v| v.is_nan()));
+    }
+
+    #[test]
+    fn rolling_indicators_only_use_bars_up_to_each_row() {
+        let ts = fixture();
+        let (atrs, vols) = (rolling_avg_true_range(&ts, 3), rolling_realized_vol(&ts, 5));
+        for i in 0..ts.len() {
+            let prefix = TimeSeries::new("TEST", ts.bars[..=i].to_vec());
+            let same = |a: f64, b: f64| (a.is_nan() && b.is_nan()) || (a - b).abs() < 1e-12;
+            assert!(same(atrs[i], rolling_avg_true_range(&prefix, 3)[i]) && same(vols[i], rolling_realized_vol(&prefix, 5)[i]), "bar {}", i);
+        }
+        assert!(atrs[1].is_nan() && atrs[2].is_finite() && vols[4].is_nan() && vols[5].is_finite());
+    }
+
+    #[test]
+    fn weekly_and_monthly_bars_annualize_with_their_own_periods() {
+        let daily = fixture();
+        assert!(daily.is_daily() && daily.periods_per_year() == TRADING_DAYS);
+        for (days, periods) in [(7, 52.0), (30, 12.0)] {
+            let bars = daily.bars.iter().enumerate().map(|(i, b)| Bar { date: b.date + chrono::Days::new(days * i as u64), ..b.clone() }).collect();
+            let ts = TimeSeries::new("TEST", bars);
+            assert!(!ts.is_daily() && ts.periods_per_year() == periods);
+            assert!((rolling_realized_vol(&ts, 5)[9] - rolling_realized_vol(&daily, 5)[9] * (periods / TRADING_DAYS).sqrt()).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn true_range_uses_previous_close() {
+        let tr = calculate_true_range(&fixture());
+        assert_eq!(tr[0], 3.0);
+        assert_eq!(tr[2], 2.5);
+        // 2024-01-08 gaps up: high 107 - previous close 103 exceeds high - low
+        assert_eq!(tr[4], 4.0);
+        assert_eq!(tr[5], 2.5);
+    }
+
+    #[test]
+    fn avg_true_range_is_wilder_smoothed() {
+        let ts = fixture();
+        let tr = calculate_true_range(&ts);
+        let seed = tr[..8].iter().sum::<f64>() / 8.0;
+        let expected = ((seed * 7.0 + tr[8]) / 8.0 * 7.0 + tr[9]) / 8.0;
+        let atrs = rolling_avg_true_range(&ts, 8);
+        assert!(atrs[6].is_nan() && (atrs[7] - seed).abs() < 1e-12 && (atrs[9] - expected).abs() < 1e-12);
+        assert!((rolling_avg_true_range(&ts, 10)[9] - tr.iter().sum::<f64>() / 10.0).abs() < 1e-12);
+        assert!(rolling_avg_true_range(&ts, 11).iter().all(|v| v.is_nan()));
+    }
+
+    #[test]
+    fn factorials_and_binomials() {
+        assert_eq!(ln_factorial(0), 0.0);
+        assert!((ln_factorial(5) - 120f64.ln()).abs() < 1e-12);
+        // Stirling branch against the exact sum
+        let exact: f64 = (2..=30).map(|i| (i as f64).ln()).sum();
+        assert!((ln_factorial(30) - exact).abs() < 1e-10);
+        assert_eq!(binomial_coefficient(5, 2), 10.0);
+        assert_eq!(binomial_coefficient(30, 15), 155117520.0);
+        assert_eq!(binomial_coefficient(3, 4), 0.0);
+    }
+
+    #[test]
+    fn finite_differences_of_polynomials() {
+        let quadratic: Vec<f64> = (0..6).map(|x| 3.0 * (x * x) as f64 + 2.0 * x as f64 + 1.0).collect();
+        assert_eq!(back_finite_diff(&quadratic, 2), 6.0);
+        assert_eq!(back_finite_diff(&quadratic, 3), 0.0);
+        assert_eq!(back_finite_diff(&quadratic, 1), quadratic[5] - quadratic[4]);
+        assert!(back_finite_diff(&quadratic[..2], 2).is_nan());
+    }
+
+    #[test]
+    fn mean_and_linear_regression() {
+        assert_eq!(mean(&[1.0, 2.0, 3.0, 6.0]), 3.0);
+        assert!(mean(&[]).is_nan());
+        let line: Vec<f64> = (0..10).map(|x| 2.5 * x as f64 - 4.0).collect();
+        assert!((linear_regression(&line) - line[9]).abs() < 1e-12);
+        // Slope 1.2 through the mean point (1.5, 3)
+        let noisy = [1.0, 3.0, 3.0, 5.0];
+        assert!((linear_regression(&noisy) - 4.8).abs() < 1e-12);
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let mut ts = fixture();
+        for bar in &mut ts.bars[..5] {
+            bar.price_factor = 0.5;
+            bar.volume_factor = 2.0;
+        }
+        let dir = std::env::temp_dir().join(format!("markets_avantage_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let csv_name = dir.join("TEST_av_2024-01-16_16-00-00.csv");
+        let csv_name = csv_name.to_str().unwrap();
+        tseries_to_csv(&ts, csv_name).unwrap();
+        let header = fs::read_to_string(csv_name).unwrap().lines().next().unwrap().to_string();
+        assert!(header.starts_with("Date,Close,Open,Low,High,Volume,Change,%Change,Range,AvgTrueRange,RealizedVol,FiniteDiff,LinearReg,ChangeLinearReg,FiniteDiffLinearReg,AdjClose,AdjOpen,AdjLow,AdjHigh,AdjVolume,PriceFactor,VolumeFactor,ParkinsonVol,GarmanKlassVol,RogersSatchellVol,YangZhangVol,EwmaVol,SMA20,"));
+        assert!(header.ends_with(",DC20Upper,DC20Lower,DC20Mid"));
+        assert_eq!(tseries_from_csv(csv_name).unwrap(), ts);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn av_fixture(name: &str) -> String {
+        fs::read_to_string(format!("fixtures/av/{}", name)).unwrap()
+    }
+
+    #[test]
+    fn parses_alpha_vantage_daily_json() {
+        let doc: Value = serde_json::from_str(&av_fixture("IBM_daily.json")).unwrap();
+        let ts = AvSeriesData::from_json("IBM", AvSeries::Daily, &doc).unwrap().to_tseries();
+        assert_eq!(ts.len(), 5);
+        assert!(ts.bars.windows(2).all(|w| w[0].date < w[1].date));
+        let last = ts.bars.last().unwrap();
+        assert_eq!(last.date, NaiveDate::from_ymd_opt(2024, 6, 7).unwrap());
+        assert_eq!((last.open, last.high, last.low, last.close, last.volume), (169.55, 171.43, 168.9, 170.01, 3419046.0));
+    }
+
+    /* Serves the given (status, body) responses in order from a local HTTP server on its own thread
+    Returns the base url and the request urls received so far
+    */
+    fn mock_server(responses: Vec<(u16, String)>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}/query", server.server_addr().to_ip().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&received);
+        thread::spawn(move || {
+            for (status, body) in responses {
+                let request = match server.recv() {
+                    Ok(r) => r,
+                    Err(_) => return,
+                };
+                log.lock().unwrap().push(request.url().to_string());
+                let header = tiny_http::Header::from_bytes("Content-Type", "application/json").unwrap();
+                let _ = request.respond(tiny_http::Response::from_string(body).with_status_code(status).with_header(header));
+            }
+        });
+        (base_url, received)
+    }
+
+    fn test_client(base_url: &str) -> AvClient {
+        let mut client = AvClient::new("demo");
+        client.base_url = base_url.to_string();
+        client.backoff = Duration::from_millis(1);
+        client.window = Duration::from_millis(1);
+        client
+    }
+
+    #[test]
+    fn client_fetches_daily_adjusted() {
+        let (url, received) = mock_server(vec![(200, av_fixture("IBM_daily_adjusted.json"))]);
+        let mut client = test_client(&url);
+        client.full_output = true;
+        let data = client.fetch("IBM", AvSeries::DailyAdjusted).unwrap();
+        let query = received.lock().unwrap()[0].clone();
+        assert!(query.contains("function=TIME_SERIES_DAILY_ADJUSTED") && query.contains("symbol=IBM") && query.contains("apikey=demo"));
+        assert!(query.contains("outputsize=full"), "{}", query);
+        assert_eq!(data.bars.len(), 3);
+        let ex_div = &data.bars[1];
+        assert_eq!(ex_div.timestamp.date(), NaiveDate::from_ymd_opt(2024, 5, 9).unwrap());
+        assert_eq!((ex_div.dividend, ex_div.split_coefficient, ex_div.volume), (1.67, 1.0, 4266597.0));
+        assert_eq!(data.bars[0].adjusted_close, Some(168.23));
+        assert_eq!(data.to_tseries().bars[0].close, 169.9);
+    }
+
+    #[test]
+    fn client_fetches_intraday_weekly_and_monthly() {
+        let (url, received) = mock_server(vec![
+            (200, av_fixture("IBM_intraday_5min.json")),
+            (200, av_fixture("IBM_weekly_adjusted.json")),
+            (200, av_fixture("IBM_monthly_adjusted.json")),
+        ]);
+        let client = test_client(&url);
+        let intraday = client.fetch("IBM", AvSeries::Intraday(AvInterval::Min5)).unwrap();
+        assert!(received.lock().unwrap()[0].contains("interval=5min") && received.lock().unwrap()[0].contains("outputsize=compact"));
+        assert_eq!(intraday.bars.len(), 3);
+        assert_eq!(intraday.bars[2].timestamp, NaiveDate::from_ymd_opt(2024, 6, 7).unwrap().and_hms_opt(19, 55, 0).unwrap());
+        assert_eq!(intraday.bars[2].adjusted_close, None);
+        let daily = intraday.to_tseries();
+        assert_eq!(daily.len(), 2);
+        assert_eq!(daily.bars[1], Bar::new(NaiveDate::from_ymd_opt(2024, 6, 7).unwrap(), 170.0, 170.2, 169.9, 170.05, 200.0));
+        let weekly = client.fetch("IBM", AvSeries::Weekly).unwrap();
+        assert_eq!(weekly.bars.len(), 2);
+        assert_eq!(weekly.bars[1].volume, 15734233.0);
+        let monthly = client.fetch("IBM", AvSeries::Monthly).unwrap();
+        assert_eq!(monthly.bars.len(), 3);
+        assert_eq!(monthly.bars[1].dividend, 1.67);
+        let functions: Vec<bool> = received.lock().unwrap().iter().zip(["TIME_SERIES_INTRADAY", "TIME_SERIES_WEEKLY_ADJUSTED", "TIME_SERIES_MONTHLY_ADJUSTED"])
+            .map(|(q, f)| q.contains(&format!("function={}&", f)))
+            .collect();
+        assert_eq!(functions, vec![true, true, true]);
+    }
+
+    #[test]
+    fn client_retries_throttle_notes() {
+        let (url, received) = mock_server(vec![
+            (200, av_fixture("note.json")),
+            (503, String::new()),
+            (200, av_fixture("IBM_daily.json")),
+        ]);
+        let data = test_client(&url).fetch("IBM", AvSeries::Daily).unwrap();
+        assert_eq!(data.bars.len(), 5);
+        assert_eq!(received.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn client_gives_up_after_max_retries() {
+        let (url, received) = mock_server(vec![(200, av_fixture("note.json")); 4]);
+        let mut client = test_client(&url);
+        client.max_retries = 2;
+        match client.fetch("IBM", AvSeries::Daily) {
+            Err(AvError::Throttled(msg)) => assert!(msg.contains("5 calls per minute")),
+            other => panic!("expected a throttle error, got {:?}", other),
+        }
+        assert_eq!(received.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn client_does_not_retry_quota_or_premium_responses() {
+        for (fixture, text) in [("information.json", "25 requests per day"), ("premium.json", "premium endpoint")] {
+            let (url, received) = mock_server(vec![(200, av_fixture(fixture)), (200, av_fixture("IBM_daily_adjusted.json"))]);
+            match test_client(&url).fetch("IBM", AvSeries::DailyAdjusted) {
+                Err(AvError::Refused(msg)) => assert!(msg.contains(text), "{}", msg),
+                other => panic!("expected a refused request, got {:?}", other),
+            }
+            assert_eq!(received.lock().unwrap().len(), 1);
+        }
+    }
+
+    #[test]
+    fn client_does_not_retry_api_errors() {
+        let (url, received) = mock_server(vec![(200, av_fixture("error_message.json")), (200, av_fixture("IBM_daily.json"))]);
+        assert!(matches!(test_client(&url).fetch("NOPE", AvSeries::DailyAdjusted), Err(AvError::Api(_))));
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn client_spaces_requests_within_the_rate_limit() {
+        let (url, _) = mock_server(vec![(200, av_fixture("IBM_daily.json")); 3]);
+        let mut client = test_client(&url);
+        client.max_requests = 2;
+        client.window = Duration::from_millis(300);
+        let start = Instant::now();
+        for _ in 0..3 {
+            client.fetch("IBM", AvSeries::Daily).unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(300));
+    }
+
+    #[test]
+    fn api_key_from_env_or_file() {
+        let dir = std::env::temp_dir().join(format!("markets_avkey_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let key_file = dir.join("key.txt");
+        let key_file = key_file.to_str().unwrap();
+        fs::write(key_file, "FILEKEY\n").unwrap();
+        let env_var = "MARKETS_TEST_AV_KEY_UNSET";
+        assert_eq!(load_api_key(env_var, key_file).unwrap(), "FILEKEY");
+        std::env::set_var("MARKETS_TEST_AV_KEY_SET", " ENVKEY ");
+        assert_eq!(load_api_key("MARKETS_TEST_AV_KEY_SET", key_file).unwrap(), "ENVKEY");
+        fs::write(key_file, "  \n").unwrap();
+        assert!(load_api_key(env_var, key_file).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
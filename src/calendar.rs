@@ -0,0 +1,212 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::America::New_York;
+use std::str::FromStr;
+
+const SESSION_HOURS: f64 = 6.5;
+const TRADING_DAYS: f64 = 252.0;
+
+/* Day-count conventions for time to expiry
+Act365 = calendar time to the 16:00 America/New_York expiry cutoff / 365 days
+Trading252 = NYSE trading days after the valuation date up to and including expiry, plus the unelapsed fraction of the
+valuation date's session when it is a trading day, / 252
+BusinessHours = remaining NYSE session hours (09:30-16:00 America/New_York) / (252 * 6.5), i.e. variance time
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DayCount {
+    #[default]
+    Act365,
+    Trading252,
+    BusinessHours,
+}
+
+impl FromStr for DayCount {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "act365" | "act/365" => Ok(DayCount::Act365),
+            "trading252" | "bus/252" => Ok(DayCount::Trading252),
+            "hours" | "businesshours" => Ok(DayCount::BusinessHours),
+            _ => Err(format!("\nDayCount::from_str() :: ERROR -> Unknown day-count convention '{}'; expected one of act365, trading252, hours", s)),
+        }
+    }
+}
+
+fn nth_weekday(year: i32, month: u32, weekday: Weekday, n: u8) -> NaiveDate {
+    NaiveDate::from_weekday_of_month_opt(year, month, weekday, n).expect("nth_weekday() :: invalid date")
+}
+
+fn last_weekday(year: i32, month: u32, weekday: Weekday) -> NaiveDate {
+    NaiveDate::from_weekday_of_month_opt(year, month, weekday, 5).unwrap_or_else(|| nth_weekday(year, month, weekday, 4))
+}
+
+// Anonymous Gregorian algorithm for Easter Sunday
+fn easter_sunday(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32).expect("easter_sunday() :: invalid date")
+}
+
+// Fixed-date holidays falling on Saturday are observed Friday and on Sunday are observed Monday
+fn observed(date: NaiveDate) -> NaiveDate {
+    match date.weekday() {
+        Weekday::Sat => date - Duration::days(1),
+        Weekday::Sun => date + Duration::days(1),
+        _ => date,
+    }
+}
+
+// Regular NYSE full-day holidays for a year (unscheduled closures are not included)
+pub fn nyse_holidays(year: i32) -> Vec<NaiveDate> {
+    let fixed = |month: u32, day: u32| NaiveDate::from_ymd_opt(year, month, day).expect("nyse_holidays() :: invalid date");
+    let mut holidays = Vec::new();
+    // New Year's Day is not observed on the preceding Friday when it falls on a Saturday
+    let new_year = fixed(1, 1);
+    if new_year.weekday() != Weekday::Sat {
+        holidays.push(observed(new_year));
+    }
+    holidays.push(nth_weekday(year, 1, Weekday::Mon, 3));
+    holidays.push(nth_weekday(year, 2, Weekday::Mon, 3));
+    holidays.push(easter_sunday(year) - Duration::days(2));
+    holidays.push(last_weekday(year, 5, Weekday::Mon));
+    if year >= 2022 {
+        holidays.push(observed(fixed(6, 19)));
+    }
+    holidays.push(observed(fixed(7, 4)));
+    holidays.push(nth_weekday(year, 9, Weekday::Mon, 1));
+    holidays.push(nth_weekday(year, 11, Weekday::Thu, 4));
+    holidays.push(observed(fixed(12, 25)));
+    holidays
+}
+
+pub fn is_trading_day(date: NaiveDate) -> bool {
+    !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !nyse_holidays(date.year()).contains(&date)
+}
+
+fn new_york_time(date: NaiveDate, hour: u32, minute: u32) -> DateTime<Utc> {
+    let naive = date.and_time(NaiveTime::from_hms_opt(hour, minute, 0).expect("new_york_time() :: invalid time"));
+    New_York
+        .from_local_datetime(&naive)
+        .earliest()
+        .expect("new_york_time() :: nonexistent New York local time")
+        .with_timezone(&Utc)
+}
+
+// 16:00 America/New_York on the expiry date
+pub fn expiry_cutoff(expiry: NaiveDate) -> DateTime<Utc> {
+    new_york_time(expiry, 16, 0)
+}
+
+// The America/New_York calendar date of an instant
+pub fn market_date(valuation: DateTime<Utc>) -> NaiveDate {
+    valuation.with_timezone(&New_York).date_naive()
+}
+
+// Hours of the NYSE session on a date that remain after the valuation instant
+fn session_hours_after(date: NaiveDate, valuation: DateTime<Utc>) -> f64 {
+    if !is_trading_day(date) {
+        return 0.0;
+    }
+    let open = new_york_time(date, 9, 30).max(valuation);
+    let close = new_york_time(date, 16, 0);
+    ((close - open).num_seconds() as f64 / 3600.0).clamp(0.0, SESSION_HOURS)
+}
+
+// Time from the valuation instant to the expiry cutoff in years under a day-count convention (never negative)
+pub fn year_fraction(valuation: DateTime<Utc>, expiry: NaiveDate, convention: DayCount) -> f64 {
+    let cutoff = expiry_cutoff(expiry);
+    if cutoff <= valuation {
+        return 0.0;
+    }
+    match convention {
+        DayCount::Act365 => (cutoff - valuation).num_seconds() as f64 / (365.0 * 86400.0),
+        DayCount::Trading252 => {
+            let start = market_date(valuation);
+            let days = start
+                .iter_days()
+                .skip(1)
+                .take_while(|d| *d <= expiry)
+                .filter(|d| is_trading_day(*d))
+                .count();
+            (days as f64 + session_hours_after(start, valuation) / SESSION_HOURS) / TRADING_DAYS
+        },
+        DayCount::BusinessHours => {
+            let start = market_date(valuation);
+            let hours: f64 = start
+                .iter_days()
+                .take_while(|d| *d <= expiry)
+                .map(|d| session_hours_after(d, valuation))
+                .sum();
+            hours / (TRADING_DAYS * SESSION_HOURS)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn instant(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn nyse_holidays_follow_the_observance_rules() {
+        // Good Friday from the Easter computus
+        assert!(nyse_holidays(2024).contains(&date(2024, 3, 29)) && nyse_holidays(2025).contains(&date(2025, 4, 18)));
+        // Juneteenth from 2022, observed on the Friday when it falls on a Saturday
+        assert!(!nyse_holidays(2021).contains(&date(2021, 6, 18)) && !nyse_holidays(2021).contains(&date(2021, 6, 21)));
+        assert!(nyse_holidays(2022).contains(&date(2022, 6, 20)) && nyse_holidays(2026).contains(&date(2026, 6, 19)));
+        assert!(nyse_holidays(2027).contains(&date(2027, 6, 18)));
+        // New Year's Day on a Saturday is not observed on the Friday (the last session of the prior year)
+        assert!(!nyse_holidays(2021).contains(&date(2021, 12, 31)) && !nyse_holidays(2022).contains(&date(2021, 12, 31)));
+        assert!(is_trading_day(date(2021, 12, 31)) && nyse_holidays(2022).len() == 9);
+        assert!(nyse_holidays(2023).contains(&date(2023, 1, 2)));
+        // Christmas on a Saturday is observed Friday the 24th, on a Sunday Monday the 26th
+        assert!(nyse_holidays(2021).contains(&date(2021, 12, 24)) && nyse_holidays(2022).contains(&date(2022, 12, 26)));
+        assert!(is_trading_day(date(2024, 12, 24)) && is_trading_day(date(2024, 12, 26)) && !is_trading_day(date(2024, 12, 25)));
+        assert_eq!(nyse_holidays(2024).len(), 10);
+    }
+
+    #[test]
+    fn year_fraction_under_each_day_count() {
+        // Friday 2024-06-21 10:00 New York, six hours before the expiry cutoff
+        let friday = instant("2024-06-21T14:00:00Z");
+        let expiry = date(2024, 6, 21);
+        assert!((year_fraction(friday, expiry, DayCount::Act365) - 6.0 / 8760.0).abs() < 1e-12);
+        assert!((year_fraction(friday, expiry, DayCount::Trading252) - 6.0 / 6.5 / 252.0).abs() < 1e-12);
+        assert!((year_fraction(friday, expiry, DayCount::BusinessHours) - 6.0 / 1638.0).abs() < 1e-12);
+        for convention in [DayCount::Act365, DayCount::Trading252, DayCount::BusinessHours] {
+            assert_eq!(year_fraction(instant("2024-06-21T20:00:00Z"), expiry, convention), 0.0);
+            assert_eq!(year_fraction(instant("2024-06-24T14:00:00Z"), expiry, convention), 0.0);
+        }
+        // Over the weekend to the next Friday: 5 sessions plus 6/6.5 of this Friday's
+        let next = date(2024, 6, 28);
+        assert!((year_fraction(friday, next, DayCount::Act365) - (7.0 * 24.0 + 6.0) / 8760.0).abs() < 1e-12);
+        assert!((year_fraction(friday, next, DayCount::Trading252) - (5.0 + 6.0 / 6.5) / 252.0).abs() < 1e-12);
+        assert!((year_fraction(friday, next, DayCount::BusinessHours) - (6.0 + 5.0 * 6.5) / 1638.0).abs() < 1e-12);
+        // Before the open the valuation date counts as a full session
+        let saturday = instant("2024-06-22T12:00:00Z");
+        let premarket = instant("2024-06-24T12:00:00Z");
+        assert_eq!(year_fraction(saturday, next, DayCount::Trading252), year_fraction(premarket, next, DayCount::Trading252));
+        assert_eq!(year_fraction(premarket, next, DayCount::Trading252), 5.0 / 252.0);
+        assert_eq!("bus/252".parse::<DayCount>(), Ok(DayCount::Trading252));
+        assert!("act360".parse::<DayCount>().is_err());
+    }
+}
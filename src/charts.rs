@@ -0,0 +1,484 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{Datelike, NaiveDate};
+use plotters::coord::Shift;
+use plotters::prelude::*;
+use std::env;
+use std::str::FromStr;
+
+pub const CHARTBACKENDENV: &str = "CHART_BACKEND";
+pub const CHARTFORMATSENV: &str = "CHART_FORMATS";
+const CHARTSIZE: (u32, u32) = (1024, 768);
+const FONT: &str = "sans-serif";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChartFormat {
+    Png,
+    Svg,
+}
+
+impl ChartFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ChartFormat::Png => "png",
+            ChartFormat::Svg => "svg",
+        }
+    }
+}
+
+impl FromStr for ChartFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "png" => Ok(ChartFormat::Png),
+            "svg" => Ok(ChartFormat::Svg),
+            _ => Err(format!("\nChartFormat::from_str() :: ERROR -> Unknown chart format '{}'; expected png or svg", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeriesStyle {
+    Line,
+    Dashed,
+    LinePoints,
+}
+
+// One named series of (x, y) points; non-finite points are skipped when drawn
+#[derive(Debug, Clone)]
+pub struct Series {
+    pub title: String,
+    pub points: Vec<(f64, f64)>,
+    pub style: SeriesStyle,
+}
+
+// Date x values are days since the common era (see date_to_x()) and are labelled as dates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XAxis {
+    Number,
+    Date,
+}
+
+#[derive(Debug, Clone)]
+pub struct LineChart {
+    pub title: String,
+    pub x_label: String,
+    pub y_label: String,
+    pub x_axis: XAxis,
+    pub log_y: bool,
+    pub series: Vec<Series>,
+}
+
+/* A 3D scatter of (x, y, z) points coloured by z, e.g. an option chain value over strike (x) and years to expiration (y)
+The value axis z is drawn vertically
+*/
+#[derive(Debug, Clone)]
+pub struct ScatterSurface {
+    pub title: String,
+    pub x_label: String,
+    pub y_label: String,
+    pub z_label: String,
+    pub points: Vec<(f64, f64, f64)>,
+}
+
+/* A chart renderer; every chart is written to <stem>.<extension> for each of the backend's formats and the written
+paths are returned. Backends hold no shared scratch files, so charts can be rendered from several threads at once
+*/
+pub trait ChartBackend: Send + Sync {
+    fn name(&self) -> String;
+    fn line_chart(&self, chart: &LineChart, stem: &str) -> Result<Vec<String>>;
+    fn surface_chart(&self, chart: &ScatterSurface, stem: &str) -> Result<Vec<String>>;
+}
+
+pub fn date_to_x(date: NaiveDate) -> f64 {
+    f64::from(date.num_days_from_ce())
+}
+
+pub fn x_to_date(x: f64) -> NaiveDate {
+    NaiveDate::from_num_days_from_ce_opt(x.round() as i32).unwrap_or_default()
+}
+
+// gnuplot's default "rgbformulae 7,5,15" palette (black - blue - red - yellow) at t in [0, 1]
+pub fn palette(t: f64) -> RGBColor {
+    let t = if t.is_finite() { t.clamp(0.0, 1.0) } else { 0.0 };
+    let channel = |v: f64| (255.0 * v.clamp(0.0, 1.0)).round() as u8;
+    RGBColor(channel(t.sqrt()), channel(t.powi(3)), channel((2.0 * std::f64::consts::PI * t).sin()))
+}
+
+// Compact tick label: plain decimals for ordinary magnitudes, scientific notation otherwise
+fn tick_label(v: f64) -> String {
+    if v != 0.0 && (v.abs() >= 1e5 || v.abs() < 1e-3) {
+        format!("{:.2e}", v)
+    } else {
+        let s = format!("{:.4}", v);
+        s.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+// (min, max) of finite values, widened when degenerate; None without any finite value
+fn padded_range(values: impl Iterator<Item = f64>) -> Option<(f64, f64)> {
+    let (lo, hi) = values.filter(|v| v.is_finite()).fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| (lo.min(v), hi.max(v)));
+    if !lo.is_finite() {
+        return None;
+    }
+    let pad = if hi > lo { 0.05 * (hi - lo) } else { 0.5 * lo.abs().max(1.0) };
+    Some((lo - pad, hi + pad))
+}
+
+fn plot_err<E: std::fmt::Display>(e: E) -> anyhow::Error {
+    anyhow!("{}", e)
+}
+
+fn draw_line_chart<DB: DrawingBackend>(root: DrawingArea<DB, Shift>, chart: &LineChart) -> Result<()> {
+    let series: Vec<(&Series, Vec<(f64, f64)>)> = chart
+        .series
+        .iter()
+        .map(|s| {
+            let points = s
+                .points
+                .iter()
+                .filter(|(x, y)| x.is_finite() && y.is_finite() && (!chart.log_y || *y > 0.0))
+                .map(|(x, y)| (*x, if chart.log_y { y.log10() } else { *y }))
+                .collect();
+            (s, points)
+        })
+        .collect();
+    let all = || series.iter().flat_map(|(_, p)| p.iter());
+    let (x_range, y_range) = match (padded_range(all().map(|p| p.0)), padded_range(all().map(|p| p.1))) {
+        (Some(x), Some(y)) => (x, y),
+        _ => return Err(anyhow!("\ndraw_line_chart() :: ERROR -> '{}' has no finite points to plot", chart.title)),
+    };
+    root.fill(&WHITE).map_err(plot_err)?;
+    let mut ctx = ChartBuilder::on(&root)
+        .caption(&chart.title, (FONT, 24))
+        .margin(15)
+        .x_label_area_size(45)
+        .y_label_area_size(80)
+        .build_cartesian_2d(x_range.0..x_range.1, y_range.0..y_range.1)
+        .map_err(plot_err)?;
+    let x_format = |x: &f64| match chart.x_axis {
+        XAxis::Date => x_to_date(*x).format("%m/%d").to_string(),
+        XAxis::Number => tick_label(*x),
+    };
+    let y_format = |y: &f64| if chart.log_y { tick_label(10f64.powf(*y)) } else { tick_label(*y) };
+    ctx.configure_mesh()
+        .x_desc(chart.x_label.as_str())
+        .y_desc(chart.y_label.as_str())
+        .x_label_formatter(&x_format)
+        .y_label_formatter(&y_format)
+        .light_line_style(BLACK.mix(0.05))
+        .draw()
+        .map_err(plot_err)?;
+    for (i, (s, points)) in series.into_iter().enumerate() {
+        let color = Palette99::pick(i).to_rgba();
+        let legend = move |(x, y): (i32, i32)| PathElement::new(vec![(x, y), (x + 20, y)], color.stroke_width(2));
+        match s.style {
+            SeriesStyle::Line | SeriesStyle::LinePoints => {
+                ctx.draw_series(LineSeries::new(points.clone(), color.stroke_width(2))).map_err(plot_err)?.label(s.title.as_str()).legend(legend);
+            },
+            SeriesStyle::Dashed => {
+                ctx.draw_series(DashedLineSeries::new(points.clone(), 8, 5, color.stroke_width(2))).map_err(plot_err)?.label(s.title.as_str()).legend(legend);
+            },
+        }
+        if s.style == SeriesStyle::LinePoints {
+            ctx.draw_series(points.iter().map(|p| Circle::new(*p, 3, color.filled()))).map_err(plot_err)?;
+        }
+    }
+    ctx.configure_series_labels()
+        .position(SeriesLabelPosition::UpperLeft)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()
+        .map_err(plot_err)?;
+    root.present().map_err(plot_err)?;
+    Ok(())
+}
+
+fn draw_surface_chart<DB: DrawingBackend>(root: DrawingArea<DB, Shift>, chart: &ScatterSurface) -> Result<()> {
+    let points: Vec<(f64, f64, f64)> = chart.points.iter().filter(|(x, y, z)| x.is_finite() && y.is_finite() && z.is_finite()).copied().collect();
+    let ranges = (padded_range(points.iter().map(|p| p.0)), padded_range(points.iter().map(|p| p.1)), padded_range(points.iter().map(|p| p.2)));
+    let (x_range, y_range, z_range) = match ranges {
+        (Some(x), Some(y), Some(z)) => (x, y, z),
+        _ => return Err(anyhow!("\ndraw_surface_chart() :: ERROR -> '{}' has no finite points to plot", chart.title)),
+    };
+    root.fill(&WHITE).map_err(plot_err)?;
+    let (_, height) = root.dim_in_pixel();
+    let (main, footer) = root.split_vertically(height as i32 - 30);
+    let mut ctx = ChartBuilder::on(&main)
+        .caption(&chart.title, (FONT, 22))
+        .margin(20)
+        .build_cartesian_3d(x_range.0..x_range.1, z_range.0..z_range.1, y_range.0..y_range.1)
+        .map_err(plot_err)?;
+    ctx.with_projection(|mut pb| {
+        pb.pitch = 0.35;
+        pb.yaw = 0.6;
+        pb.scale = 0.85;
+        pb.into_matrix()
+    });
+    ctx.configure_axes()
+        .light_grid_style(BLACK.mix(0.08))
+        .max_light_lines(3)
+        .x_formatter(&|x| tick_label(*x))
+        .y_formatter(&|y| tick_label(*y))
+        .z_formatter(&|z| tick_label(*z))
+        .draw()
+        .map_err(plot_err)?;
+    let span = (z_range.1 - z_range.0).max(f64::MIN_POSITIVE);
+    ctx.draw_series(points.iter().map(|&(x, y, z)| Circle::new((x, z, y), 3, palette((z - z_range.0) / span).filled())))
+        .map_err(plot_err)?;
+    let caption = format!("x = {}, depth = {}, height and colour = {} (dark = low, yellow = high)", chart.x_label, chart.y_label, chart.z_label);
+    footer.draw(&Text::new(caption, (15, 5), (FONT, 15))).map_err(plot_err)?;
+    root.present().map_err(plot_err)?;
+    Ok(())
+}
+
+// Pure-Rust renderer built on plotters (the default backend)
+pub struct PlottersBackend {
+    pub formats: Vec<ChartFormat>,
+    pub size: (u32, u32),
+}
+
+impl Default for PlottersBackend {
+    fn default() -> Self {
+        PlottersBackend { formats: vec![ChartFormat::Png], size: CHARTSIZE }
+    }
+}
+
+impl ChartBackend for PlottersBackend {
+    fn name(&self) -> String {
+        "plotters".to_string()
+    }
+    fn line_chart(&self, chart: &LineChart, stem: &str) -> Result<Vec<String>> {
+        let mut written = Vec::new();
+        for format in &self.formats {
+            let path = format!("{}.{}", stem, format.extension());
+            match format {
+                ChartFormat::Png => draw_line_chart(BitMapBackend::new(&path, self.size).into_drawing_area(), chart),
+                ChartFormat::Svg => draw_line_chart(SVGBackend::new(&path, self.size).into_drawing_area(), chart),
+            }
+            .with_context(|| format!("\nPlottersBackend::line_chart() :: ERROR -> Failed to render {}", path))?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+    fn surface_chart(&self, chart: &ScatterSurface, stem: &str) -> Result<Vec<String>> {
+        let mut written = Vec::new();
+        for format in &self.formats {
+            let path = format!("{}.{}", stem, format.extension());
+            match format {
+                ChartFormat::Png => draw_surface_chart(BitMapBackend::new(&path, self.size).into_drawing_area(), chart),
+                ChartFormat::Svg => draw_surface_chart(SVGBackend::new(&path, self.size).into_drawing_area(), chart),
+            }
+            .with_context(|| format!("\nPlottersBackend::surface_chart() :: ERROR -> Failed to render {}", path))?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+}
+
+/* gnuplot renderer (cargo feature "gnuplot"); scripts are piped to a gnuplot subprocess with the data inline as
+datablocks, so no temporary data files are shared between charts
+*/
+#[cfg(feature = "gnuplot")]
+pub struct GnuplotBackend {
+    pub formats: Vec<ChartFormat>,
+}
+
+#[cfg(feature = "gnuplot")]
+fn gnuplot_terminal(format: ChartFormat) -> &'static str {
+    match format {
+        ChartFormat::Png => "png size 1024,768",
+        ChartFormat::Svg => "svg size 1024,768",
+    }
+}
+
+#[cfg(feature = "gnuplot")]
+pub fn gnuplot_line_script(chart: &LineChart, format: ChartFormat, output: &str) -> String {
+    use std::fmt::Write;
+    let mut script = String::new();
+    for (i, s) in chart.series.iter().enumerate() {
+        let _ = writeln!(script, "$d{} << EOD", i);
+        for (x, y) in s.points.iter().filter(|(x, y)| x.is_finite() && y.is_finite()) {
+            match chart.x_axis {
+                XAxis::Date => writeln!(script, "{} {}", x_to_date(*x).format("%Y-%m-%d"), y),
+                XAxis::Number => writeln!(script, "{} {}", x, y),
+            }
+            .unwrap_or_default();
+        }
+        let _ = writeln!(script, "EOD");
+    }
+    let _ = writeln!(script, "set terminal {}\nset output '{}'", gnuplot_terminal(format), output);
+    if chart.x_axis == XAxis::Date {
+        let _ = writeln!(script, "set xdata time\nset timefmt '%Y-%m-%d'\nset format x \"%m/%d\"");
+    }
+    let _ = writeln!(script, "set xlabel \"{}\"\nset ylabel \"{}\"\nset title \"{}\"\nset grid", chart.x_label, chart.y_label, chart.title);
+    if chart.log_y {
+        let _ = writeln!(script, "set logscale y");
+    }
+    let plots: Vec<String> = chart
+        .series
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            let style = match s.style {
+                SeriesStyle::Line => "lines",
+                SeriesStyle::Dashed => "lines dt 2",
+                SeriesStyle::LinePoints => "linespoints pt 7",
+            };
+            format!("$d{} using 1:2 with {} title '{}'", i, style, s.title.replace('\'', "''"))
+        })
+        .collect();
+    let _ = writeln!(script, "plot {}", plots.join(", "));
+    script
+}
+
+#[cfg(feature = "gnuplot")]
+pub fn gnuplot_surface_script(chart: &ScatterSurface, format: ChartFormat, output: &str) -> String {
+    use std::fmt::Write;
+    let mut script = String::from("$d << EOD\n");
+    for (x, y, z) in chart.points.iter().filter(|(x, y, z)| x.is_finite() && y.is_finite() && z.is_finite()) {
+        let _ = writeln!(script, "{} {} {}", x, y, z);
+    }
+    let _ = writeln!(
+        script,
+        "EOD\nset terminal {}\nset output '{}'\nset xlabel \"{}\"\nset ylabel \"{}\"\nset zlabel \"{}\"\nset title \"{}\"\nset view 60.0,0.0,1.0\nset palette rgb 7,5,15\nsplot $d using 1:2:3 with points palette notitle",
+        gnuplot_terminal(format), output, chart.x_label, chart.y_label, chart.z_label, chart.title
+    );
+    script
+}
+
+#[cfg(feature = "gnuplot")]
+fn run_gnuplot(script: &str) -> Result<()> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    let mut cmd_gnuplot = Command::new("gnuplot")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .context("\nrun_gnuplot() :: ERROR -> Failed to execute gnuplot chart generation command")?;
+    let stdin = cmd_gnuplot.stdin.as_mut().context("\nrun_gnuplot() :: ERROR -> Failed to open stdin for gnuplot script")?;
+    writeln!(stdin, "{}", script).context("\nrun_gnuplot() :: ERROR -> Failed to write gnuplot script to stdin")?;
+    let status = cmd_gnuplot.wait().context("\nrun_gnuplot() :: ERROR -> Failed to wait for gnuplot chart generation process")?;
+    if !status.success() {
+        return Err(anyhow!("\nrun_gnuplot() :: ERROR -> gnuplot exited with {}", status));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "gnuplot")]
+impl ChartBackend for GnuplotBackend {
+    fn name(&self) -> String {
+        "gnuplot".to_string()
+    }
+    fn line_chart(&self, chart: &LineChart, stem: &str) -> Result<Vec<String>> {
+        let mut written = Vec::new();
+        for format in &self.formats {
+            let path = format!("{}.{}", stem, format.extension());
+            run_gnuplot(&gnuplot_line_script(chart, *format, &path))?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+    fn surface_chart(&self, chart: &ScatterSurface, stem: &str) -> Result<Vec<String>> {
+        let mut written = Vec::new();
+        for format in &self.formats {
+            let path = format!("{}.{}", stem, format.extension());
+            run_gnuplot(&gnuplot_surface_script(chart, *format, &path))?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+}
+
+/* Chart backend selected by the CHART_BACKEND (plotters or gnuplot; default plotters) and CHART_FORMATS
+(comma-separated png / svg; default png) environment variables; gnuplot requires building with --features gnuplot
+*/
+pub fn backend_from_env() -> Result<Box<dyn ChartBackend>> {
+    let formats = match env::var(CHARTFORMATSENV) {
+        Ok(list) => list.split(',').map(|f| f.parse::<ChartFormat>()).collect::<Result<Vec<_>, _>>().map_err(|e| anyhow!(e))?,
+        Err(_) => vec![ChartFormat::Png],
+    };
+    if formats.is_empty() {
+        return Err(anyhow!("\nbackend_from_env() :: ERROR -> {} lists no chart formats", CHARTFORMATSENV));
+    }
+    match env::var(CHARTBACKENDENV).unwrap_or_else(|_| "plotters".to_string()).to_lowercase().as_str() {
+        "plotters" => Ok(Box::new(PlottersBackend { formats, size: CHARTSIZE })),
+        #[cfg(feature = "gnuplot")]
+        "gnuplot" => Ok(Box::new(GnuplotBackend { formats })),
+        #[cfg(not(feature = "gnuplot"))]
+        "gnuplot" => Err(anyhow!("\nbackend_from_env() :: ERROR -> The gnuplot chart backend is not compiled in; rebuild with 'cargo build --features gnuplot'")),
+        other => Err(anyhow!("\nbackend_from_env() :: ERROR -> Unknown chart backend '{}'; expected plotters or gnuplot", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn line_chart() -> LineChart {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let points: Vec<(f64, f64)> = (0..60).map(|i| (date_to_x(start) + i as f64, 100.0 + (i as f64 / 5.0).sin() * 10.0)).collect();
+        let mut gappy = points.clone();
+        gappy[..20].iter_mut().for_each(|p| p.1 = f64::NAN);
+        LineChart {
+            title: "TEST Close".to_string(),
+            x_label: "Date".to_string(),
+            y_label: "Close".to_string(),
+            x_axis: XAxis::Date,
+            log_y: true,
+            series: vec![
+                Series { title: "Close".to_string(), points, style: SeriesStyle::Line },
+                Series { title: "SMA20".to_string(), points: gappy, style: SeriesStyle::Dashed },
+            ],
+        }
+    }
+
+    fn surface() -> ScatterSurface {
+        let points = (0..10).flat_map(|i| (1..5).map(move |j| (90.0 + 2.0 * i as f64, 0.1 * j as f64, 0.2 + 0.01 * (i as f64 - 5.0).powi(2)))).collect();
+        ScatterSurface { title: "TEST Call Options iv Surface".to_string(), x_label: "Strike".to_string(), y_label: "Years To Expiration".to_string(), z_label: "iv".to_string(), points }
+    }
+
+    #[test]
+    fn plotters_renders_png_and_svg() {
+        let dir = env::temp_dir().join(format!("markets_charts_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let backend = PlottersBackend { formats: vec![ChartFormat::Png, ChartFormat::Svg], size: (640, 480) };
+        let line = backend.line_chart(&line_chart(), dir.join("line").to_str().unwrap()).unwrap();
+        let surf = backend.surface_chart(&surface(), dir.join("surface").to_str().unwrap()).unwrap();
+        for path in line.iter().chain(&surf) {
+            let bytes = fs::read(path).unwrap();
+            if path.ends_with(".png") {
+                assert_eq!(&bytes[..4], b"\x89PNG");
+            } else {
+                assert!(String::from_utf8(bytes).unwrap().contains("<svg"));
+            }
+        }
+        let empty = LineChart { series: vec![Series { title: "NaN".to_string(), points: vec![(1.0, f64::NAN)], style: SeriesStyle::Line }], ..line_chart() };
+        assert!(backend.line_chart(&empty, dir.join("empty").to_str().unwrap()).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn palette_dates_and_labels() {
+        assert_eq!(palette(0.0), RGBColor(0, 0, 0));
+        assert_eq!(palette(1.0), RGBColor(255, 255, 0));
+        let d = NaiveDate::from_ymd_opt(2024, 6, 3).unwrap();
+        assert_eq!(x_to_date(date_to_x(d)), d);
+        assert_eq!(tick_label(0.25), "0.25");
+        assert_eq!(tick_label(2.5e6), "2.50e6");
+        assert_eq!("SVG".parse::<ChartFormat>().unwrap(), ChartFormat::Svg);
+    }
+
+    #[cfg(feature = "gnuplot")]
+    #[test]
+    fn gnuplot_scripts_inline_their_data() {
+        let script = gnuplot_line_script(&line_chart(), ChartFormat::Svg, "out.svg");
+        assert!(script.starts_with("$d0 << EOD\n2024-01-02 100"));
+        assert!(script.contains("set terminal svg") && script.contains("set logscale y"));
+        assert!(script.contains("plot $d0 using 1:2 with lines title 'Close', $d1 using 1:2 with lines dt 2 title 'SMA20'"));
+        let script = gnuplot_surface_script(&surface(), ChartFormat::Png, "out.png");
+        assert!(script.contains("splot $d using 1:2:3 with points palette"));
+        assert!(!script.contains("dat_out"));
+    }
+}
@@ -0,0 +1,311 @@
+use super::avantage::AvSeries;
+use super::calendar::{expiry_cutoff, DayCount};
+use super::options::{ForwardBasis, PriceBasis};
+use super::pricing::model_from_str;
+use super::rates::RateCurve;
+use super::sources::SOURCECONFIG;
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use regex::Regex;
+use std::path::PathBuf;
+
+pub const EXIT_USAGE: i32 = 2;
+const EXIT_CODES_HELP: &str = "Exit codes:
+  0   every stage succeeded for every ticker
+  1   output directories could not be created
+  2   invalid arguments or settings
+  10  fetching fundamentals failed     11  fetching the time series failed
+  12  fetching the option chain failed 13  analysis failed
+  14  charting failed                  15  report generation failed
+  16  implied volatility, Greeks, probabilities or parity could not be computed
+The code of the first failing stage is returned; later stages and tickers still run where their inputs exist";
+
+/* Pipeline stages that can fail independently; each has its own process exit code (see EXIT_CODES_HELP)
+A failed stage does not stop the stages after it, but those that need its output fail in turn
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Fundamentals,
+    TimeSeries,
+    Chain,
+    Analyze,
+    Plot,
+    Report,
+    Quotes,
+}
+
+impl Stage {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Stage::Fundamentals => 10,
+            Stage::TimeSeries => 11,
+            Stage::Chain => 12,
+            Stage::Analyze => 13,
+            Stage::Plot => 14,
+            Stage::Report => 15,
+            Stage::Quotes => 16,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Stage::Fundamentals => "fetch fundamentals",
+            Stage::TimeSeries => "fetch time series",
+            Stage::Chain => "fetch option chain",
+            Stage::Analyze => "analyze",
+            Stage::Plot => "plot",
+            Stage::Report => "report",
+            Stage::Quotes => "iv/greeks/prob/parity",
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+#[command(name = "markets", version, about = "Looking at many markets in many ways", after_help = EXIT_CODES_HELP)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+    #[command(flatten)]
+    pub common: CommonArgs,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    #[command(about = "Fetch, analyze, plot and report (every stage)")]
+    Run(TickerArgs),
+    #[command(about = "Fetch fundamentals, the time series and/or the option chain (all three when none is selected)")]
+    Fetch(FetchArgs),
+    #[command(about = "Exercise boundaries, put-call parity forwards, dealer exposure, smile metrics, volatility surfaces, risk-neutral densities, GARCH forecasts and implied vs realized volatility from fetched data")]
+    Analyze(TickerArgs),
+    #[command(about = "Render the time series, indicator, volatility, density, exposure and option surface charts from fetched and analyzed data")]
+    Plot(TickerArgs),
+    #[command(about = "Write the HTML and PDF reports from fetched data and rendered charts")]
+    Report(TickerArgs),
+    #[command(about = "Print the ATM implied volatility term structure, per-strike implied volatilities of one expiry, or the fitted surface at one strike")]
+    Iv(IvArgs),
+    #[command(about = "Print implied volatility and Greeks of the contracts of one expiry")]
+    Greeks(GreeksArgs),
+    #[command(about = "Print risk-neutral density moments and the probabilities of finishing below/above price levels per expiry")]
+    Prob(ProbArgs),
+    #[command(about = "Print the put-call parity implied forward, rate and borrow per expiry and the strikes violating parity")]
+    Parity(ParityArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct TickerArgs {
+    #[arg(required = true, value_parser = parse_ticker, help = "Tickers/symbols, e.g. AAPL BRK.B GOOGL ^SPX")]
+    pub tickers: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct FetchArgs {
+    #[command(flatten)]
+    pub tickers: TickerArgs,
+    #[arg(long, help = "Fetch finviz fundamentals")]
+    pub fundamentals: bool,
+    #[arg(long, help = "Fetch the Alpha Vantage time series")]
+    pub timeseries: bool,
+    #[arg(long, help = "Fetch the option chain from --source")]
+    pub chain: bool,
+}
+
+impl FetchArgs {
+    // (fundamentals, timeseries, chain) to fetch; selecting none fetches all three
+    pub fn selected(&self) -> (bool, bool, bool) {
+        if self.fundamentals || self.timeseries || self.chain {
+            (self.fundamentals, self.timeseries, self.chain)
+        } else {
+            (true, true, true)
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct IvArgs {
+    #[command(flatten)]
+    pub tickers: TickerArgs,
+    #[arg(long, value_parser = parse_date, help = "Expiry date (YYYY-MM-DD) to list per-strike implied volatilities for")]
+    pub expiry: Option<NaiveDate>,
+    #[arg(long, help = "Print the fitted SVI and SSVI surface implied volatility at this strike (at --yte or --expiry)")]
+    pub strike: Option<f64>,
+    #[arg(long, requires = "strike", help = "Years to expiry of the --strike surface query")]
+    pub yte: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Side {
+    Calls,
+    Puts,
+    Both,
+}
+
+#[derive(Debug, Args)]
+pub struct GreeksArgs {
+    #[command(flatten)]
+    pub tickers: TickerArgs,
+    #[arg(long, value_parser = parse_date, help = "Expiry date (YYYY-MM-DD); defaults to the nearest expiry")]
+    pub expiry: Option<NaiveDate>,
+    #[arg(long, help = "Only the contracts at the listed strike nearest this price")]
+    pub strike: Option<f64>,
+    #[arg(long, value_enum, default_value = "both")]
+    pub side: Side,
+}
+
+#[derive(Debug, Args)]
+pub struct ProbArgs {
+    #[command(flatten)]
+    pub tickers: TickerArgs,
+    #[arg(long = "level", help = "Price level to print the probabilities of finishing below/above (repeatable); defaults to spot")]
+    pub levels: Vec<f64>,
+    #[arg(long, value_parser = parse_date, help = "Expiry date (YYYY-MM-DD); defaults to every fitted expiry")]
+    pub expiry: Option<NaiveDate>,
+}
+
+#[derive(Debug, Args)]
+pub struct ParityArgs {
+    #[command(flatten)]
+    pub tickers: TickerArgs,
+    #[arg(long, value_parser = parse_date, help = "Expiry date (YYYY-MM-DD); defaults to every expiry")]
+    pub expiry: Option<NaiveDate>,
+    #[arg(long, help = "Print every call/put pair, not only those whose parity deviation exceeds the bid-ask spread")]
+    pub all: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct CommonArgs {
+    #[arg(long, global = true, default_value = ".", help = "Directory that csv_out, img_out, pdf_out and html_out are created in")]
+    pub out_dir: PathBuf,
+    #[arg(long, global = true, env = "CHAIN_SOURCE", default_value = SOURCECONFIG, help = "Option chain source config file")]
+    pub source: String,
+    #[arg(long, global = true, env = "AV_SERIES", default_value = "daily", value_parser = parse_av_series, help = "Alpha Vantage series: daily, daily_adjusted, weekly, monthly or 1min/5min/15min/30min/60min (daily_adjusted is premium-only)")]
+    pub av_series: AvSeries,
+    #[arg(long, global = true, env = "AV_FULL", help = "Request the full Alpha Vantage history (outputsize=full, premium-only) instead of the latest 100 bars")]
+    pub av_full: bool,
+    #[arg(long, global = true, value_parser = parse_valuation, help = "Valuation instant: YYYY-MM-DD (16:00 New York) or RFC 3339; defaults to a stored chain's quote time (now when fetching)")]
+    pub valuation: Option<DateTime<Utc>>,
+    #[arg(long, global = true, value_parser = parse_rate, help = "Risk-free rate: flat decimal rate (0.045, not 4.5) or TENOR,RATE csv file of percents; defaults to a stored chain's rate (the fed funds rate when fetching)")]
+    pub rate: Option<RateCurve>,
+    #[arg(long, global = true, default_value = "mid", value_parser = parse_basis, help = "Option price basis: bid, ask, mid or last")]
+    pub basis: PriceBasis,
+    #[arg(long, global = true, default_value = "bs", value_parser = parse_model, help = "Pricing model: bs, crr, trinomial or baw")]
+    pub model: String,
+    #[arg(long, global = true, default_value = "act365", value_parser = parse_day_count, help = "Day count for time to expiry: act365, trading252 or hours")]
+    pub day_count: DayCount,
+    #[arg(long, global = true, default_value = "spot", value_parser = parse_forward, help = "Price each expiry off the quoted spot or its put-call parity implied forward: spot or implied")]
+    pub forward: ForwardBasis,
+    #[arg(long, global = true, value_parser = parse_run, help = "Use the data of the run stamped YYYY-MM-DD_HH-MM-SS instead of each ticker's latest files")]
+    pub run: Option<String>,
+}
+
+/* Upper-cased ticker/symbol: letters and digits with an optional leading ^ (indices) and one . or - class suffix
+e.g. AAPL, GOOGL, BRK.B, BF-B, ^SPX; underscores and slashes are rejected as they would break output file names
+*/
+pub fn parse_ticker(s: &str) -> Result<String, String> {
+    let ticker = s.trim().to_uppercase();
+    let pattern = Regex::new(r"^\^?[A-Z0-9]{1,10}([.\-][A-Z0-9]{1,4})?$").map_err(|e| e.to_string())?;
+    if pattern.is_match(&ticker) {
+        Ok(ticker)
+    } else {
+        Err(format!("\nparse_ticker() :: ERROR -> '{}' is not a ticker/symbol (letters and digits, optional leading ^ and one . or - class suffix)", s))
+    }
+}
+
+pub fn parse_date(s: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").map_err(|e| format!("\nparse_date() :: ERROR -> '{}' is not a YYYY-MM-DD date: {}", s, e))
+}
+
+pub fn parse_valuation(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d") {
+        return Ok(expiry_cutoff(date));
+    }
+    DateTime::parse_from_rfc3339(s.trim())
+        .map(|dt| dt.with_timezone(&Utc))
+        .map_err(|e| format!("\nparse_valuation() :: ERROR -> '{}' is neither a YYYY-MM-DD date nor an RFC 3339 instant: {}", s, e))
+}
+
+fn parse_rate(s: &str) -> Result<RateCurve, String> {
+    s.parse::<RateCurve>().map_err(|e| format!("\nparse_rate() :: ERROR -> Could not load risk-free rate curve from '{}': {}", s, e))
+}
+
+fn parse_basis(s: &str) -> Result<PriceBasis, String> {
+    s.parse::<PriceBasis>()
+}
+
+fn parse_forward(s: &str) -> Result<ForwardBasis, String> {
+    s.parse::<ForwardBasis>()
+}
+
+fn parse_day_count(s: &str) -> Result<DayCount, String> {
+    s.parse::<DayCount>()
+}
+
+fn parse_av_series(s: &str) -> Result<AvSeries, String> {
+    s.parse::<AvSeries>()
+}
+
+// Validated model name; the model itself is built per ticker with model_from_str()
+fn parse_model(s: &str) -> Result<String, String> {
+    model_from_str(s).map(|_| s.to_lowercase()).map_err(|e| e.to_string())
+}
+
+pub fn parse_run(s: &str) -> Result<String, String> {
+    chrono::NaiveDateTime::parse_from_str(s, "%Y-%m-%d_%H-%M-%S")
+        .map(|_| s.to_string())
+        .map_err(|_| format!("\nparse_run() :: ERROR -> '{}' is not a run stamp like 2024-06-03_16-05-00", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tickers_accept_classes_indices_and_long_symbols() {
+        for (input, ticker) in [("aapl", "AAPL"), ("GOOGL", "GOOGL"), ("brk.b", "BRK.B"), ("BF-B", "BF-B"), ("^spx", "^SPX")] {
+            assert_eq!(parse_ticker(input).unwrap(), ticker);
+        }
+        for bad in ["", "AA_PL", "AAPL.", "^", "BRK.B.C", "A APL", "BF/B"] {
+            assert!(parse_ticker(bad).is_err(), "{} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn subcommands_and_global_flags_parse() {
+        let cli = Cli::try_parse_from(["markets", "fetch", "--chain", "aapl", "brk.b", "--out-dir", "/tmp/m", "--valuation", "2024-06-03", "--rate", "0.045"]).unwrap();
+        match &cli.command {
+            Command::Fetch(args) => {
+                assert_eq!(args.tickers.tickers, ["AAPL", "BRK.B"]);
+                assert_eq!(args.selected(), (false, false, true));
+            },
+            other => panic!("expected fetch, got {:?}", other),
+        }
+        assert_eq!(cli.common.out_dir, PathBuf::from("/tmp/m"));
+        assert_eq!(cli.common.valuation.unwrap().to_rfc3339(), "2024-06-03T20:00:00+00:00");
+        assert_eq!(cli.common.rate.unwrap().rate(1.0), 0.045);
+        assert_eq!((cli.common.basis, cli.common.model.as_str(), cli.common.day_count, cli.common.forward), (PriceBasis::Mid, "bs", DayCount::Act365, ForwardBasis::Spot));
+        let cli = Cli::try_parse_from(["markets", "greeks", "SPY", "--expiry", "2024-06-21", "--side", "puts", "--model", "BAW"]).unwrap();
+        match cli.command {
+            Command::Greeks(args) => assert_eq!((args.expiry, args.side), (NaiveDate::from_ymd_opt(2024, 6, 21), Side::Puts)),
+            other => panic!("expected greeks, got {:?}", other),
+        }
+        assert_eq!(cli.common.model, "baw");
+        let cli = Cli::try_parse_from(["markets", "iv", "SPY", "--strike", "530", "--yte", "0.25"]).unwrap();
+        match cli.command {
+            Command::Iv(args) => assert_eq!((args.strike, args.yte, args.expiry), (Some(530.0), Some(0.25), None)),
+            other => panic!("expected iv, got {:?}", other),
+        }
+        let cli = Cli::try_parse_from(["markets", "parity", "SPY", "--all", "--forward", "Implied"]).unwrap();
+        match cli.command {
+            Command::Parity(args) => assert!(args.all && args.expiry.is_none()),
+            other => panic!("expected parity, got {:?}", other),
+        }
+        assert_eq!(cli.common.forward, ForwardBasis::Implied);
+        let cli = Cli::try_parse_from(["markets", "prob", "SPY", "--level", "500", "--level", "550.5", "--expiry", "2024-06-21"]).unwrap();
+        match cli.command {
+            Command::Prob(args) => assert_eq!((args.levels, args.expiry), (vec![500.0, 550.5], NaiveDate::from_ymd_opt(2024, 6, 21))),
+            other => panic!("expected prob, got {:?}", other),
+        }
+        for bad in [vec!["markets", "run"], vec!["markets", "run", "AA_PL"], vec!["markets", "iv", "SPY", "--model", "heston"], vec!["markets", "plot", "SPY", "--run", "yesterday"], vec!["markets", "iv", "SPY", "--yte", "0.5"]] {
+            assert!(Cli::try_parse_from(&bad).is_err(), "{:?} should be rejected", bad);
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use super::finmath::{black_scholes, inv_cnd};
+use super::surface::{SurfaceModel, VolSurface};
+use super::utils::csv_value;
+use csv::Writer;
+use std::collections::HashMap;
+use std::error::Error;
+
+/* Strike grid of each density: DENSITY_POINTS strikes between the DENSITY_TAIL and 1 - DENSITY_TAIL quantiles of ln(K / F)
+under a normal with the ATM total variance (about 6 standard deviations each side)
+*/
+const DENSITY_POINTS: usize = 401;
+const DENSITY_TAIL: f64 = 1e-9;
+// Quantiles written by densities_to_csv()
+pub const DENSITY_QUANTILES: [f64; 5] = [0.05, 0.25, 0.5, 0.75, 0.95];
+
+/* Risk-neutral density of the underlying at one expiry (Breeden-Litzenberger)
+The undiscounted call price C(K) = F N(d1) - K N(d2) of the fitted implied volatility is differentiated twice in strike on
+a uniform grid, p(K) = d2C/dK2; negative values (butterfly arbitrage in the fit) are floored at zero and counted
+mass = probability captured by the grid before pdf and cdf are normalized to integrate to one
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct RiskNeutralDensity {
+    pub date: String,
+    pub yte: f64,
+    pub forward: f64,
+    pub strikes: Vec<f64>,
+    pub pdf: Vec<f64>,
+    pub cdf: Vec<f64>,
+    pub mass: f64,
+    pub negative: usize,
+}
+
+/* Moments of the terminal price implied by a density
+expected_move = E|S_T - F|, the undiscounted at-the-forward straddle; skewness and excess kurtosis are standardized
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DensityMoments {
+    pub mean: f64,
+    pub std_dev: f64,
+    pub skewness: f64,
+    pub excess_kurtosis: f64,
+    pub expected_move: f64,
+}
+
+// Trapezoid rule integral of values over the strike grid
+fn integrate(strikes: &[f64], values: &[f64]) -> f64 {
+    strikes.windows(2).zip(values.windows(2)).map(|(k, v)| 0.5 * (k[1] - k[0]) * (v[0] + v[1])).sum()
+}
+
+impl RiskNeutralDensity {
+    // Risk-neutral probability of finishing below a price, interpolated linearly in the cdf
+    pub fn prob_below(&self, level: f64) -> f64 {
+        let (first, last) = (self.strikes[0], self.strikes[self.strikes.len() - 1]);
+        if level <= first {
+            return 0.0;
+        }
+        if level >= last {
+            return 1.0;
+        }
+        let j = self.strikes.partition_point(|k| *k < level);
+        let x = (level - self.strikes[j - 1]) / (self.strikes[j] - self.strikes[j - 1]);
+        self.cdf[j - 1] + x * (self.cdf[j] - self.cdf[j - 1])
+    }
+    pub fn prob_above(&self, level: f64) -> f64 {
+        1.0 - self.prob_below(level)
+    }
+    // Price the cdf reaches p at, interpolated linearly between grid strikes
+    pub fn quantile(&self, p: f64) -> f64 {
+        let j = self.cdf.partition_point(|c| *c < p).clamp(1, self.cdf.len() - 1);
+        let (c0, c1) = (self.cdf[j - 1], self.cdf[j]);
+        let x = if c1 > c0 { ((p - c0) / (c1 - c0)).clamp(0.0, 1.0) } else { 0.0 };
+        self.strikes[j - 1] + x * (self.strikes[j] - self.strikes[j - 1])
+    }
+    pub fn moments(&self) -> DensityMoments {
+        let weighted = |f: &dyn Fn(f64) -> f64| integrate(&self.strikes, &self.strikes.iter().zip(&self.pdf).map(|(k, p)| f(*k) * p).collect::<Vec<f64>>());
+        let mean = weighted(&|k| k);
+        let variance = weighted(&|k| (k - mean).powi(2));
+        let std_dev = variance.sqrt();
+        DensityMoments {
+            mean,
+            std_dev,
+            skewness: weighted(&|k| (k - mean).powi(3)) / (variance * std_dev),
+            excess_kurtosis: weighted(&|k| (k - mean).powi(4)) / (variance * variance) - 3.0,
+            expected_move: weighted(&|k| (k - self.forward).abs()),
+        }
+    }
+}
+
+/* Breeden-Litzenberger density of one fitted slice of the surface (see RiskNeutralDensity)
+Fails when the surface has no usable total variance at the money for the model (e.g. SSVI without an SSVI fit)
+*/
+pub fn slice_density(surface: &VolSurface, slice: usize, model: SurfaceModel) -> Result<RiskNeutralDensity, String> {
+    let s = surface.slices.get(slice).ok_or_else(|| format!("\nslice_density() :: ERROR -> The surface has no slice {}", slice))?;
+    let atm_sd = surface.total_variance(0.0, s.yte, model).sqrt();
+    if !atm_sd.is_finite() || atm_sd <= 0.0 {
+        return Err(format!("\nslice_density() :: ERROR -> The {} {} slice has no positive ATM total variance", s.date, model.label()));
+    }
+    let sds = -inv_cnd(DENSITY_TAIL);
+    let (low, high) = (s.forward * (-sds * atm_sd).exp(), s.forward * (sds * atm_sd).exp());
+    let h = (high - low) / (DENSITY_POINTS - 1) as f64;
+    let strikes: Vec<f64> = (0..DENSITY_POINTS).map(|i| low + h * i as f64).collect();
+    let calls: Vec<f64> = strikes
+        .iter()
+        .map(|&k| {
+            let iv = (surface.total_variance((k / s.forward).ln(), s.yte, model) / s.yte).sqrt();
+            black_scholes(iv, s.forward, k, s.yte, 0.0, 0.0, true)
+        })
+        .collect();
+    let mut pdf = vec![0.0; DENSITY_POINTS];
+    let mut negative = 0;
+    for i in 1..DENSITY_POINTS - 1 {
+        let p = (calls[i + 1] - 2.0 * calls[i] + calls[i - 1]) / (h * h);
+        if p.is_nan() || p < 0.0 {
+            negative += 1;
+        } else {
+            pdf[i] = p;
+        }
+    }
+    let mass = integrate(&strikes, &pdf);
+    if !mass.is_finite() || mass <= 0.0 {
+        return Err(format!("\nslice_density() :: ERROR -> The {} {} call price curve has no positive density", s.date, model.label()));
+    }
+    pdf.iter_mut().for_each(|p| *p /= mass);
+    let mut cdf = vec![0.0; DENSITY_POINTS];
+    for i in 1..DENSITY_POINTS {
+        cdf[i] = (cdf[i - 1] + 0.5 * h * (pdf[i - 1] + pdf[i])).min(1.0);
+    }
+    Ok(RiskNeutralDensity { date: s.date.clone(), yte: s.yte, forward: s.forward, strikes, pdf, cdf, mass, negative })
+}
+
+// Densities of every slice in yte order; slices whose density cannot be extracted are skipped
+pub fn surface_densities(surface: &VolSurface, model: SurfaceModel) -> Vec<RiskNeutralDensity> {
+    (0..surface.slices.len())
+        .filter_map(|i| match slice_density(surface, i, model) {
+            Ok(density) => Some(density),
+            Err(e) => {
+                eprintln!("{}\n\nsurface_densities() :: Skipping expiry {}", e, surface.slices[i].date);
+                None
+            },
+        })
+        .collect()
+}
+
+/* Saves the densities as a long-format curve csv and a per-expiry summary csv
+curve csv = Expiry, Yte, Strike, Pdf, Cdf
+stats csv = Expiry, Yte, Forward, Mean, StdDev, Skewness, ExcessKurtosis, ExpectedMove, ExpectedMovePct (of spot),
+ProbAboveSpot, the DENSITY_QUANTILES prices (Q05 ... Q95), Mass and NegativePoints
+*/
+pub fn densities_to_csv(densities: &[RiskNeutralDensity], spot: f64, curve_csv_name: &str, stats_csv_name: &str) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_path(curve_csv_name)?;
+    wtr.write_record(["Expiry", "Yte", "Strike", "Pdf", "Cdf"])?;
+    for d in densities {
+        for ((k, p), c) in d.strikes.iter().zip(&d.pdf).zip(&d.cdf) {
+            wtr.write_record([d.date.clone(), csv_value(d.yte), csv_value(*k), csv_value(*p), csv_value(*c)])?;
+        }
+    }
+    wtr.flush()?;
+    let mut wtr = Writer::from_path(stats_csv_name)?;
+    let mut header: Vec<String> = ["Expiry", "Yte", "Forward", "Mean", "StdDev", "Skewness", "ExcessKurtosis", "ExpectedMove", "ExpectedMovePct", "ProbAboveSpot"].iter().map(|h| h.to_string()).collect();
+    header.extend(DENSITY_QUANTILES.iter().map(|q| format!("Q{:02.0}", 100.0 * q)));
+    header.extend(["Mass".to_string(), "NegativePoints".to_string()]);
+    wtr.write_record(&header)?;
+    for d in densities {
+        let m = d.moments();
+        let mut record = vec![d.date.clone()];
+        record.extend([d.yte, d.forward, m.mean, m.std_dev, m.skewness, m.excess_kurtosis, m.expected_move, 100.0 * m.expected_move / spot, d.prob_above(spot)].map(csv_value));
+        record.extend(DENSITY_QUANTILES.iter().map(|q| csv_value(d.quantile(*q))));
+        record.extend([csv_value(d.mass), d.negative.to_string()]);
+        wtr.write_record(&record)?;
+    }
+    wtr.flush()?;
+    println!("\ndensities_to_csv() :: Successfully created {} and {} for {} expiries", curve_csv_name, stats_csv_name, densities.len());
+    Ok(())
+}
+
+// (expiry, [(strike, pdf)]) of one density read back from a curve csv
+pub type DensityCurve = (String, Vec<(f64, f64)>);
+
+// Curves of a densities_to_csv() curve csv in file order
+pub fn density_curves_from_csv(curve_csv_name: &str) -> Result<Vec<DensityCurve>, Box<dyn Error>> {
+    let mut rdr = csv::Reader::from_path(curve_csv_name)?;
+    let mut curves: Vec<DensityCurve> = Vec::new();
+    for result in rdr.deserialize::<HashMap<String, String>>() {
+        let row = result?;
+        let value = |key: &str| row.get(key).and_then(|v| v.trim().parse::<f64>().ok()).unwrap_or(f64::NAN);
+        let expiry = row.get("Expiry").cloned().unwrap_or_default();
+        match curves.last_mut() {
+            Some((date, points)) if *date == expiry => points.push((value("Strike"), value("Pdf"))),
+            _ => curves.push((expiry, vec![(value("Strike"), value("Pdf"))])),
+        }
+    }
+    Ok(curves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::finmath::cnd;
+    use crate::surface::{SviParams, SviSlice};
+    use std::env;
+    use std::fs;
+
+    fn surface(params: SviParams) -> VolSurface {
+        let slice = SviSlice { date: "June 21 2024".to_string(), yte: 1.0, forward: 100.0, params, rmse: 0.0, theta: params.total_variance(0.0), ssvi_rmse: f64::NAN, points: Vec::new() };
+        VolSurface { spot: 100.0, slices: vec![slice], ssvi: None }
+    }
+
+    #[test]
+    fn flat_smile_gives_the_lognormal_density() {
+        // b = 0 is a flat 20% smile, so S_T is lognormal with mean F and log standard deviation 0.2
+        let density = slice_density(&surface(SviParams { a: 0.04, b: 0.0, rho: 0.0, m: 0.0, sigma: 0.1 }), 0, SurfaceModel::Svi).unwrap();
+        let m = density.moments();
+        let e = 0.04f64.exp();
+        assert!((density.mass - 1.0).abs() < 1e-4 && density.negative == 0);
+        assert!((m.mean - 100.0).abs() < 0.01, "{:?}", m);
+        assert!((m.std_dev - 100.0 * (e - 1.0).sqrt()).abs() < 0.05);
+        assert!((m.skewness - (e + 2.0) * (e - 1.0).sqrt()).abs() < 0.01);
+        assert!((m.excess_kurtosis - (e.powi(4) + 2.0 * e.powi(3) + 3.0 * e.powi(2) - 6.0)).abs() < 0.05);
+        assert!((m.expected_move - 200.0 * (2.0 * cnd(0.1) - 1.0)).abs() < 0.01);
+        assert!((density.prob_below(100.0) - cnd(0.1)).abs() < 1e-3);
+        assert!((density.quantile(0.5) - 100.0 * (-0.02f64).exp()).abs() < 0.05);
+        assert_eq!((density.prob_below(1.0), density.prob_above(1000.0)), (0.0, 0.0));
+    }
+
+    #[test]
+    fn put_skew_fattens_the_left_tail_and_round_trips() {
+        let skewed = surface(SviParams { a: 0.02, b: 0.1, rho: -0.6, m: 0.05, sigma: 0.2 });
+        let density = slice_density(&skewed, 0, SurfaceModel::Svi).unwrap();
+        assert!(density.moments().skewness < 0.0 && density.negative == 0);
+        assert!(slice_density(&skewed, 0, SurfaceModel::Ssvi).is_err());
+        let dir = env::temp_dir().join(format!("markets_density_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (curve_csv, stats_csv) = (dir.join("TEST_rnd_2024-06-03_10-00-00.csv"), dir.join("TEST_rndstats_2024-06-03_10-00-00.csv"));
+        densities_to_csv(&surface_densities(&skewed, SurfaceModel::Svi), 100.0, curve_csv.to_str().unwrap(), stats_csv.to_str().unwrap()).unwrap();
+        let curves = density_curves_from_csv(curve_csv.to_str().unwrap()).unwrap();
+        assert_eq!((curves.len(), curves[0].1.len()), (1, DENSITY_POINTS));
+        assert!(fs::read_to_string(&stats_csv).unwrap().lines().next().unwrap().contains("Q05,Q25,Q50,Q75,Q95"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,194 @@
+use chrono::{Months, NaiveDate};
+use csv::Reader;
+use super::rates::RateCurve;
+use std::error::Error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dividend {
+    pub ex_date: NaiveDate,
+    pub amount: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DividendSchedule {
+    pub dividends: Vec<Dividend>,
+}
+
+impl DividendSchedule {
+    pub fn new(mut dividends: Vec<Dividend>) -> Self {
+        dividends.sort_by_key(|d| d.ex_date);
+        DividendSchedule { dividends }
+    }
+    /* Projects a schedule of equal cash dividends from a trailing-twelve-month total
+    ttm = the trailing twelve month dividend per share
+    last_ex_date = the most recent (or next) known ex-dividend date; rolled forward one period at a time
+    frequency = number of payments per year (4 for quarterly)
+    */
+    pub fn project_from_ttm(ttm: f64, last_ex_date: NaiveDate, frequency: u32, valuation: NaiveDate, horizon_years: f64) -> Self {
+        let mut dividends = Vec::new();
+        if ttm <= 0.0 || frequency == 0 || 12 % frequency != 0 {
+            return DividendSchedule { dividends };
+        }
+        let period = Months::new(12 / frequency);
+        let horizon_days = (horizon_years * 365.0).ceil() as i64;
+        let mut ex_date = last_ex_date;
+        while ex_date <= valuation {
+            ex_date = match ex_date.checked_add_months(period) {
+                Some(d) => d,
+                None => return DividendSchedule { dividends },
+            };
+        }
+        while (ex_date - valuation).num_days() <= horizon_days {
+            dividends.push(Dividend { ex_date, amount: ttm / frequency as f64 });
+            ex_date = match ex_date.checked_add_months(period) {
+                Some(d) => d,
+                None => break,
+            };
+        }
+        DividendSchedule { dividends }
+    }
+    // Loads a known schedule from a csv of EX_DATE (YYYY-MM-DD),AMOUNT rows
+    pub fn from_csv(csv_name: &str) -> Result<Self, Box<dyn Error>> {
+        let mut rdr = Reader::from_path(csv_name)?;
+        let mut dividends = Vec::new();
+        for result in rdr.records() {
+            let record = result?;
+            let line = record.position().map_or(0, |p| p.line());
+            let field = |i: usize, name: &str| record.get(i).ok_or_else(|| format!("\nDividendSchedule::from_csv() :: ERROR -> Line {} of {} has no {} column", line, csv_name, name));
+            let ex_date = NaiveDate::parse_from_str(field(0, "EX_DATE")?.trim(), "%Y-%m-%d")?;
+            let amount = field(1, "AMOUNT")?.trim().parse::<f64>()?;
+            dividends.push(Dividend { ex_date, amount });
+        }
+        Ok(DividendSchedule::new(dividends))
+    }
+    /* Seeds a quarterly projected schedule from the "Dividend TTM" (and, when present, "Dividend Ex-Date")
+    rows of a csv written by finviz::fetch_finviz_info(); without an ex-date the first payment is assumed
+    one quarter after the valuation date
+    */
+    pub fn from_finviz_csv(fv_csv_name: &str, valuation: NaiveDate, horizon_years: f64) -> Result<Self, Box<dyn Error>> {
+        let mut rdr = Reader::from_path(fv_csv_name)?;
+        let mut ttm: Option<f64> = None;
+        let mut ex_date: Option<NaiveDate> = None;
+        for result in rdr.records() {
+            let record = result?;
+            let (label, value) = match (record.get(0), record.get(1)) {
+                (Some(label), Some(value)) => (label.trim(), value.trim()),
+                _ => return Err(format!("\nDividendSchedule::from_finviz_csv() :: ERROR -> Line {} of {} is not a label,value pair", record.position().map_or(0, |p| p.line()), fv_csv_name).into()),
+            };
+            match label {
+                "Dividend TTM" => {
+                    ttm = value.split_whitespace().next().and_then(|v| v.parse::<f64>().ok());
+                },
+                "Dividend Ex-Date" => {
+                    ex_date = NaiveDate::parse_from_str(value, "%b %d, %Y").ok();
+                },
+                _ => {},
+            }
+        }
+        let ttm = ttm.ok_or("\nDividendSchedule::from_finviz_csv() :: ERROR -> No numeric 'Dividend TTM' value found")?;
+        let last_ex_date = ex_date.unwrap_or(valuation);
+        Ok(DividendSchedule::project_from_ttm(ttm, last_ex_date, 4, valuation, horizon_years))
+    }
+    pub fn is_empty(&self) -> bool {
+        self.dividends.is_empty()
+    }
+    /* Present value of the dividends going ex after the valuation date and on or before the expiry date
+    Ex-dates are compared as dates so the result does not depend on the DayCount of the expiry's yte; each dividend is
+    discounted over its calendar time to the ex-date (days / 365)
+    */
+    pub fn present_value(&self, valuation: NaiveDate, expiry: NaiveDate, curve: &RateCurve) -> f64 {
+        self.dividends
+            .iter()
+            .filter(|d| d.ex_date > valuation && d.ex_date <= expiry)
+            .map(|d| {
+                let ti = (d.ex_date - valuation).num_days() as f64 / 365.0;
+                d.amount * (-curve.rate(ti) * ti).exp()
+            })
+            .sum()
+    }
+    // Escrowed-dividend spot: the underlying price net of the present value of dividends going ex before expiry
+    pub fn escrowed_spot(&self, s: f64, valuation: NaiveDate, expiry: NaiveDate, curve: &RateCurve) -> f64 {
+        s - self.present_value(valuation, expiry, curve)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calendar::DayCount;
+    use crate::options::test_fixtures::{bs_context, test_chain};
+    use crate::options::{OptionExpiry, PricingContext};
+    use chrono::{DateTime, Utc};
+    use std::fs;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn ex_dates(schedule: &DividendSchedule) -> Vec<NaiveDate> {
+        schedule.dividends.iter().map(|d| d.ex_date).collect()
+    }
+
+    #[test]
+    fn projects_and_loads_schedules() {
+        // The last known ex-date rolls forward past the valuation date, then a year of quarterly payments follows
+        let rolled = DividendSchedule::project_from_ttm(4.0, date(2024, 2, 9), 4, date(2024, 6, 3), 1.0);
+        assert_eq!(ex_dates(&rolled), [date(2024, 8, 9), date(2024, 11, 9), date(2025, 2, 9), date(2025, 5, 9)]);
+        assert!(rolled.dividends.iter().all(|d| d.amount == 1.0));
+        let next = DividendSchedule::project_from_ttm(2.4, date(2024, 6, 10), 12, date(2024, 6, 3), 0.25);
+        assert_eq!(ex_dates(&next), [date(2024, 6, 10), date(2024, 7, 10), date(2024, 8, 10)]);
+        assert!((next.dividends[0].amount - 0.2).abs() < 1e-15);
+        for (ttm, frequency) in [(0.0, 4), (4.0, 0), (4.0, 5)] {
+            assert!(DividendSchedule::project_from_ttm(ttm, date(2024, 2, 9), frequency, date(2024, 6, 3), 1.0).is_empty());
+        }
+        let dir = std::env::temp_dir().join(format!("markets_dividends_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let fv_csv = dir.join("TEST_fv_2024-06-03_16-00-00.csv");
+        let fv_csv = fv_csv.to_str().unwrap();
+        fs::write(fv_csv, "Label,TEST\nDividend Ex-Date,\"May 09, 2024\"\nDividend TTM,4.00 (0.77%)\nP/E,20.5\n").unwrap();
+        let finviz = DividendSchedule::from_finviz_csv(fv_csv, date(2024, 6, 3), 1.0).unwrap();
+        assert_eq!(finviz.dividends, rolled.dividends);
+        fs::write(fv_csv, "Label,TEST\nDividend TTM,4.00 (0.77%)\n").unwrap();
+        assert_eq!(ex_dates(&DividendSchedule::from_finviz_csv(fv_csv, date(2024, 6, 3), 0.5).unwrap()), [date(2024, 9, 3), date(2024, 12, 3)]);
+        fs::write(fv_csv, "Label,TEST\nDividend TTM,N/A\n").unwrap();
+        assert!(DividendSchedule::from_finviz_csv(fv_csv, date(2024, 6, 3), 1.0).is_err());
+        fs::write(fv_csv, "Label\nDividend TTM\n").unwrap();
+        let err = DividendSchedule::from_finviz_csv(fv_csv, date(2024, 6, 3), 1.0).unwrap_err().to_string();
+        assert!(err.contains("Line 2") && err.contains("label,value pair"), "{}", err);
+        let csv_name = dir.join("dividends.csv");
+        fs::write(&csv_name, "EX_DATE,AMOUNT\n2024-11-08,0.25\n2024-08-09, 0.24\n").unwrap();
+        let known = DividendSchedule::from_csv(csv_name.to_str().unwrap()).unwrap();
+        assert_eq!(ex_dates(&known), [date(2024, 8, 9), date(2024, 11, 8)]);
+        fs::write(&csv_name, "EX_DATE\n2024-11-08\n").unwrap();
+        let err = DividendSchedule::from_csv(csv_name.to_str().unwrap()).unwrap_err().to_string();
+        assert!(err.contains("Line 2") && err.contains("no AMOUNT column"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn present_value_escrows_dividends_going_ex_through_expiry() {
+        let schedule = DividendSchedule::new(vec![
+            Dividend { ex_date: date(2025, 1, 6), amount: 0.5 },
+            Dividend { ex_date: date(2024, 12, 20), amount: 9.0 },
+            Dividend { ex_date: date(2025, 1, 2), amount: 1.0 },
+            Dividend { ex_date: date(2025, 1, 3), amount: 2.0 },
+        ]);
+        let curve = RateCurve::Flat(0.05);
+        let (valuation, expiry) = (date(2024, 12, 20), date(2025, 1, 3));
+        let expected = (-0.05 * 13.0 / 365.0f64).exp() + 2.0 * (-0.05 * 14.0 / 365.0f64).exp();
+        assert!((schedule.present_value(valuation, expiry, &curve) - expected).abs() < 1e-12);
+        assert!((schedule.escrowed_spot(100.0, valuation, expiry, &curve) - (100.0 - expected)).abs() < 1e-12);
+        assert_eq!(schedule.present_value(valuation, valuation, &curve), 0.0);
+        // Trading252 counts 8 sessions to the Jan 3 expiry (11.6 calendar days), which must not drop the Jan 2 and 3 dividends
+        let valued_at: DateTime<Utc> = DateTime::parse_from_rfc3339("2024-12-20T21:00:00Z").unwrap().with_timezone(&Utc);
+        let mut chain = test_chain(vec![OptionExpiry { date: "January 03 2025".to_string(), yte: 0.0, calls: Vec::new(), puts: Vec::new() }], 100.0, 0.05);
+        let mut ctx = PricingContext { dividends: schedule, valuation: valued_at, day_count: DayCount::Trading252, ..bs_context(0.05) };
+        chain.revalue(valued_at, DayCount::Trading252);
+        assert!((chain.expiries[0].yte - 8.0 / 252.0).abs() < 1e-12);
+        let trading = chain.quoted_inputs(&chain.expiries[0], &ctx);
+        ctx.day_count = DayCount::Act365;
+        chain.revalue(valued_at, DayCount::Act365);
+        assert_eq!(trading, chain.quoted_inputs(&chain.expiries[0], &ctx));
+        assert!((trading.0 - (100.0 - expected)).abs() < 1e-12 && trading.1 == 0.0);
+    }
+}
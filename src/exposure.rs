@@ -0,0 +1,339 @@
+use super::options::{Option, OptionChain, PricingContext};
+use super::utils::csv_value;
+use csv::Writer;
+use std::collections::HashMap;
+use std::error::Error;
+
+// Shares per listed equity option contract
+const CONTRACT_SIZE: f64 = 100.0;
+// Spot grid the net gamma exposure is repriced over: (low, high) multiples of spot and the number of points
+const EXPOSURE_GRID: (f64, f64, usize) = (0.8, 1.2, 81);
+// Expiry label of the profile aggregating every expiry
+pub const TOTAL_LABEL: &str = "Total";
+
+/* One open contract with the inputs it was priced under
+spot is the pricing spot (escrowed spot or discounted implied forward) and carry = quoted spot - spot, the
+adjustment held fixed in dollars when the underlying moves
+*/
+struct Position {
+    contract: Option,
+    iv: f64,
+    spot: f64,
+    carry: f64,
+    rate: f64,
+    div: f64,
+}
+
+impl Position {
+    // +1 for calls and -1 for puts: dealers are assumed long the calls and short the puts customers hold
+    fn sign(&self) -> f64 {
+        if self.contract.is_call { 1.0 } else { -1.0 }
+    }
+    // Signed dollar gamma OI * gamma * 100 * S^2 with the underlying at S, repriced at S - carry at unchanged implied volatility
+    fn gamma_exposure(&self, s: f64) -> f64 {
+        self.sign() * self.contract.open_int * self.contract.greeks(s - self.carry, self.rate, self.div, self.iv).gamma * CONTRACT_SIZE * s * s
+    }
+}
+
+// Dealer exposure at one strike (see ExposureProfile); put_gamma is negative
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StrikeExposure {
+    pub strike: f64,
+    pub call_oi: f64,
+    pub put_oi: f64,
+    pub call_gamma: f64,
+    pub put_gamma: f64,
+    pub vanna: f64,
+    pub charm: f64,
+}
+
+impl StrikeExposure {
+    pub fn net_gamma(&self) -> f64 {
+        self.call_gamma + self.put_gamma
+    }
+}
+
+/* Dealer exposure of one expiry, or of every expiry under TOTAL_LABEL (yte NaN), with puts entering negatively
+gamma = OI * gamma * 100 * spot^2 (dollar gamma; x 0.01 is the dealer delta change per 1% move in spot)
+vanna = OI * vanna * 100 * spot, the dollar delta change per 1.00 change in implied volatility
+charm = OI * charm * 100 * spot, the dollar delta change per year of calendar time
+grid = (spot, net gamma exposure) over EXPOSURE_GRID with every contract repriced at that spot less its carry (see Position)
+gamma_flip = the spot closest to the current one where the repriced net gamma exposure changes sign (NaN when it never does)
+call_wall / put_wall = the strikes with the largest call / put gamma exposure (NaN without open calls / puts)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExposureProfile {
+    pub label: String,
+    pub yte: f64,
+    pub spot: f64,
+    pub strikes: Vec<StrikeExposure>,
+    pub grid: Vec<(f64, f64)>,
+    pub gamma_flip: f64,
+    pub call_wall: f64,
+    pub put_wall: f64,
+}
+
+impl ExposureProfile {
+    pub fn call_gamma(&self) -> f64 {
+        self.strikes.iter().map(|s| s.call_gamma).sum()
+    }
+    pub fn put_gamma(&self) -> f64 {
+        self.strikes.iter().map(|s| s.put_gamma).sum()
+    }
+    pub fn net_gamma(&self) -> f64 {
+        self.call_gamma() + self.put_gamma()
+    }
+    pub fn vanna(&self) -> f64 {
+        self.strikes.iter().map(|s| s.vanna).sum()
+    }
+    pub fn charm(&self) -> f64 {
+        self.strikes.iter().map(|s| s.charm).sum()
+    }
+}
+
+// Zero crossing of a (spot, exposure) grid nearest spot, interpolated linearly between grid points
+fn gamma_flip(grid: &[(f64, f64)], spot: f64) -> f64 {
+    grid.windows(2)
+        .filter(|w| w[0].1.signum() != w[1].1.signum() && w[0].1 != 0.0)
+        .map(|w| w[0].0 - w[0].1 * (w[1].0 - w[0].0) / (w[1].1 - w[0].1))
+        .min_by(|a, b| (a - spot).abs().total_cmp(&(b - spot).abs()))
+        .unwrap_or(f64::NAN)
+}
+
+fn profile(label: String, yte: f64, spot: f64, positions: &[&Position]) -> ExposureProfile {
+    let mut strikes: Vec<StrikeExposure> = Vec::new();
+    for p in positions {
+        let g = p.contract.greeks(p.spot, p.rate, p.div, p.iv);
+        let scale = p.sign() * p.contract.open_int * CONTRACT_SIZE * spot;
+        let idx = match strikes.iter().position(|s| s.strike == p.contract.strike) {
+            Some(idx) => idx,
+            None => {
+                strikes.push(StrikeExposure { strike: p.contract.strike, ..StrikeExposure::default() });
+                strikes.len() - 1
+            },
+        };
+        let entry = &mut strikes[idx];
+        if p.contract.is_call {
+            entry.call_oi += p.contract.open_int;
+            entry.call_gamma += scale * g.gamma * spot;
+        } else {
+            entry.put_oi += p.contract.open_int;
+            entry.put_gamma += scale * g.gamma * spot;
+        }
+        entry.vanna += scale * g.vanna;
+        entry.charm += scale * g.charm;
+    }
+    strikes.sort_by(|a, b| a.strike.total_cmp(&b.strike));
+    let (low, high, n) = EXPOSURE_GRID;
+    let grid: Vec<(f64, f64)> = (0..n)
+        .map(|i| {
+            let s = spot * (low + (high - low) * i as f64 / (n - 1) as f64);
+            (s, positions.iter().map(|p| p.gamma_exposure(s)).sum())
+        })
+        .collect();
+    let wall = |exposure: &dyn Fn(&StrikeExposure) -> f64| {
+        strikes.iter().filter(|s| exposure(s) > 0.0).max_by(|a, b| exposure(a).total_cmp(&exposure(b))).map_or(f64::NAN, |s| s.strike)
+    };
+    let (call_wall, put_wall) = (wall(&|s| s.call_gamma), wall(&|s| -s.put_gamma));
+    ExposureProfile { label, yte, spot, gamma_flip: gamma_flip(&grid, spot), strikes, grid, call_wall, put_wall }
+}
+
+/* Exposure profiles of the chain's open interest priced under ctx: the TOTAL_LABEL profile first, then each unexpired expiry
+Contracts without open interest or a converged implied volatility are left out; fails when none remain
+*/
+pub fn exposure_profiles(chain: &OptionChain, ctx: &PricingContext) -> Result<Vec<ExposureProfile>, String> {
+    let mut positions: Vec<(usize, Position)> = Vec::new();
+    let mut skipped = 0;
+    for (i, expiry) in chain.expiries.iter().enumerate().filter(|(_, e)| e.yte > 0.0) {
+        let rate = expiry.rate(&ctx.curve);
+        let (spot, div) = chain.pricing_inputs(expiry, ctx);
+        for opt in expiry.calls.iter().chain(&expiry.puts).filter(|o| o.open_int > 0.0) {
+            let iv = opt.get_imp_vol(ctx.model.as_ref(), spot, rate, div, ctx.basis);
+            if iv.converged() && iv.iv > 0.0 {
+                positions.push((i, Position { contract: opt.clone(), iv: iv.iv, spot, carry: chain.current_price - spot, rate, div }));
+            } else {
+                skipped += 1;
+            }
+        }
+    }
+    if positions.is_empty() {
+        return Err(format!("\nexposure_profiles() :: ERROR -> {} has no unexpired open interest with a converged {} implied volatility", chain.ticker, ctx.basis.label()));
+    }
+    if skipped > 0 {
+        eprintln!("\nexposure_profiles() :: Left out {} {} contracts with open interest whose implied volatility did not converge", skipped, chain.ticker);
+    }
+    let all: Vec<&Position> = positions.iter().map(|(_, p)| p).collect();
+    let mut profiles = vec![profile(TOTAL_LABEL.to_string(), f64::NAN, chain.current_price, &all)];
+    for (i, expiry) in chain.expiries.iter().enumerate() {
+        let open: Vec<&Position> = positions.iter().filter(|(j, _)| *j == i).map(|(_, p)| p).collect();
+        if !open.is_empty() {
+            profiles.push(profile(expiry.date.clone(), expiry.yte, chain.current_price, &open));
+        }
+    }
+    Ok(profiles)
+}
+
+/* Saves the profiles as three csv files keyed by Expiry (TOTAL_LABEL for the aggregate)
+strike csv = Expiry, Strike, CallOpenInt, PutOpenInt, CallGex, PutGex, NetGex, Vanna, Charm
+stats csv = Expiry, Yte, Spot, CallGex, PutGex, NetGex, Vanna, Charm, GammaFlip, CallWall, PutWall
+grid csv = Expiry, Spot, NetGex
+*/
+pub fn exposure_to_csv(profiles: &[ExposureProfile], strike_csv_name: &str, stats_csv_name: &str, grid_csv_name: &str) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_path(strike_csv_name)?;
+    wtr.write_record(["Expiry", "Strike", "CallOpenInt", "PutOpenInt", "CallGex", "PutGex", "NetGex", "Vanna", "Charm"])?;
+    for p in profiles {
+        for s in &p.strikes {
+            let mut record = vec![p.label.clone()];
+            record.extend([s.strike, s.call_oi, s.put_oi, s.call_gamma, s.put_gamma, s.net_gamma(), s.vanna, s.charm].map(csv_value));
+            wtr.write_record(&record)?;
+        }
+    }
+    wtr.flush()?;
+    let mut wtr = Writer::from_path(stats_csv_name)?;
+    wtr.write_record(["Expiry", "Yte", "Spot", "CallGex", "PutGex", "NetGex", "Vanna", "Charm", "GammaFlip", "CallWall", "PutWall"])?;
+    for p in profiles {
+        let mut record = vec![p.label.clone()];
+        record.extend([p.yte, p.spot, p.call_gamma(), p.put_gamma(), p.net_gamma(), p.vanna(), p.charm(), p.gamma_flip, p.call_wall, p.put_wall].map(csv_value));
+        wtr.write_record(&record)?;
+    }
+    wtr.flush()?;
+    let mut wtr = Writer::from_path(grid_csv_name)?;
+    wtr.write_record(["Expiry", "Spot", "NetGex"])?;
+    for p in profiles {
+        for (spot, gex) in &p.grid {
+            wtr.write_record([p.label.clone(), csv_value(*spot), csv_value(*gex)])?;
+        }
+    }
+    wtr.flush()?;
+    println!("\nexposure_to_csv() :: Successfully created {}, {} and {} for {} profiles", strike_csv_name, stats_csv_name, grid_csv_name, profiles.len());
+    Ok(())
+}
+
+// Loads exposure_to_csv() files back into profiles in stats csv order
+pub fn exposure_from_csv(strike_csv_name: &str, stats_csv_name: &str, grid_csv_name: &str) -> Result<Vec<ExposureProfile>, Box<dyn Error>> {
+    let rows = |csv_name: &str| -> Result<Vec<HashMap<String, String>>, Box<dyn Error>> {
+        let mut rdr = csv::Reader::from_path(csv_name)?;
+        Ok(rdr.deserialize::<HashMap<String, String>>().collect::<Result<Vec<_>, _>>()?)
+    };
+    let value = |row: &HashMap<String, String>, key: &str| row.get(key).and_then(|v| v.trim().parse::<f64>().ok()).unwrap_or(f64::NAN);
+    let label = |row: &HashMap<String, String>| row.get("Expiry").cloned().unwrap_or_default();
+    let mut profiles: Vec<ExposureProfile> = rows(stats_csv_name)?
+        .iter()
+        .map(|row| ExposureProfile {
+            label: label(row),
+            yte: value(row, "Yte"),
+            spot: value(row, "Spot"),
+            strikes: Vec::new(),
+            grid: Vec::new(),
+            gamma_flip: value(row, "GammaFlip"),
+            call_wall: value(row, "CallWall"),
+            put_wall: value(row, "PutWall"),
+        })
+        .collect();
+    for row in rows(strike_csv_name)? {
+        if let Some(p) = profiles.iter_mut().find(|p| p.label == label(&row)) {
+            p.strikes.push(StrikeExposure {
+                strike: value(&row, "Strike"),
+                call_oi: value(&row, "CallOpenInt"),
+                put_oi: value(&row, "PutOpenInt"),
+                call_gamma: value(&row, "CallGex"),
+                put_gamma: value(&row, "PutGex"),
+                vanna: value(&row, "Vanna"),
+                charm: value(&row, "Charm"),
+            });
+        }
+    }
+    for row in rows(grid_csv_name)? {
+        if let Some(p) = profiles.iter_mut().find(|p| p.label == label(&row)) {
+            p.grid.push((value(&row, "Spot"), value(&row, "NetGex")));
+        }
+    }
+    Ok(profiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::test_fixtures::{bs_context, bs_expiry, test_chain};
+    use crate::dividends::{Dividend, DividendSchedule};
+    use crate::options::OptionExpiry;
+    use chrono::{DateTime, NaiveDate, Utc};
+    use std::{env, fs};
+
+    const RATE: f64 = 0.03;
+
+    // Black-Scholes quotes at 25% volatility with open interest oi(strike, is_call)
+    fn expiry(date: &str, yte: f64, oi: &dyn Fn(f64, bool) -> f64) -> OptionExpiry {
+        let strikes: Vec<f64> = (0..9).map(|i| 80.0 + 5.0 * i as f64).collect();
+        let mut expiry = bs_expiry(date, yte, RATE, 0.0, &strikes, 0.0, |_| 0.25);
+        for opt in expiry.calls.iter_mut().chain(expiry.puts.iter_mut()) {
+            opt.open_int = oi(opt.strike, opt.is_call);
+        }
+        expiry
+    }
+
+    #[test]
+    fn gamma_exposure_walls_and_flip() {
+        // Heavy put open interest below spot and call open interest above it: short gamma below ~100, long gamma above
+        let oi = |strike: f64, is_call: bool| match (is_call, strike) {
+            (true, k) if k >= 105.0 => if k == 110.0 { 5000.0 } else { 1000.0 },
+            (false, k) if k <= 95.0 => if k == 90.0 { 6000.0 } else { 1000.0 },
+            _ => 0.0,
+        };
+        let chain = test_chain(vec![expiry("June 21 2024", 0.1, &oi), expiry("December 20 2024", 0.5, &|_, _| 0.0)], 100.0, RATE);
+        let ctx = bs_context(RATE);
+        let profiles = exposure_profiles(&chain, &ctx).unwrap();
+        let labels: Vec<&str> = profiles.iter().map(|p| p.label.as_str()).collect();
+        assert_eq!(labels, [TOTAL_LABEL, "June 21 2024"]);
+        let total = &profiles[0];
+        assert_eq!((total.call_wall, total.put_wall), (110.0, 90.0));
+        assert!(total.put_gamma() < 0.0 && total.call_gamma() > 0.0);
+        let g = chain.expiries[0].calls[6].greeks(100.0, RATE, 0.0, 0.25).gamma;
+        let wall = total.strikes.iter().find(|s| s.strike == 110.0).unwrap();
+        assert!((wall.call_gamma - 5000.0 * g * CONTRACT_SIZE * 1e4).abs() < 1e-6 * wall.call_gamma && wall.put_oi == 0.0);
+        assert!(total.gamma_flip > 90.0 && total.gamma_flip < 110.0, "gamma flip {}", total.gamma_flip);
+        let at_flip = total.grid.iter().min_by(|a, b| (a.0 - total.gamma_flip).abs().total_cmp(&(b.0 - total.gamma_flip).abs())).unwrap();
+        assert!(at_flip.1.abs() < 0.05 * total.grid.iter().map(|p| p.1.abs()).fold(0.0, f64::max));
+        let dir = env::temp_dir().join(format!("markets_exposure_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let names = ["gex", "gexstats", "gexgrid"].map(|kind| dir.join(format!("TEST_{}_2024-06-03_10-00-00.csv", kind)).to_string_lossy().to_string());
+        exposure_to_csv(&profiles, &names[0], &names[1], &names[2]).unwrap();
+        let loaded = exposure_from_csv(&names[0], &names[1], &names[2]).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!((loaded[0].strikes.len(), loaded[0].grid.len(), loaded[0].call_wall), (total.strikes.len(), EXPOSURE_GRID.2, 110.0));
+        assert!((loaded[1].net_gamma() - profiles[1].net_gamma()).abs() < 1e-6 * profiles[1].net_gamma().abs() && loaded[0].yte.is_nan());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn grid_reprices_escrowed_positions_on_the_quoted_spot_axis() {
+        // A $5 dividend going ex before expiry: positions price off the escrowed spot but the grid runs over the quoted one
+        let oi = |strike: f64, _: bool| if (90.0..=110.0).contains(&strike) { 1000.0 } else { 0.0 };
+        let chain = test_chain(vec![expiry("June 21 2024", 0.05, &oi)], 100.0, RATE);
+        let ctx = PricingContext {
+            dividends: DividendSchedule::new(vec![Dividend { ex_date: NaiveDate::from_ymd_opt(2024, 6, 10).unwrap(), amount: 5.0 }]),
+            valuation: DateTime::parse_from_rfc3339("2024-06-03T14:00:00Z").unwrap().with_timezone(&Utc),
+            ..bs_context(RATE)
+        };
+        let (escrowed, _) = chain.pricing_inputs(&chain.expiries[0], &ctx);
+        assert!(escrowed < 95.01 && escrowed > 94.9);
+        let total = &exposure_profiles(&chain, &ctx).unwrap()[0];
+        let (low, high, n) = EXPOSURE_GRID;
+        assert!((total.grid[0].0 - 100.0 * low).abs() < 1e-9 && (total.grid[n - 1].0 - 100.0 * high).abs() < 1e-9);
+        // The unshifted grid point is the quoted spot and reproduces the strike totals
+        let (at_spot, gex) = total.grid[(n - 1) / 2];
+        assert!((at_spot - 100.0).abs() < 1e-9);
+        assert!((gex - total.net_gamma()).abs() < 1e-9 * total.net_gamma().abs(), "{} vs {}", gex, total.net_gamma());
+        // Each grid point reprices every contract at the grid spot less the same dividend carry
+        let (s, gex) = total.grid[10];
+        let expected: f64 = chain.expiries[0].calls.iter().chain(&chain.expiries[0].puts).filter(|o| o.open_int > 0.0).map(|o| {
+            let iv = o.get_imp_vol(ctx.model.as_ref(), escrowed, RATE, 0.0, ctx.basis);
+            if !iv.converged() {
+                return 0.0;
+            }
+            let (iv, sign) = (iv.iv, if o.is_call { 1.0 } else { -1.0 });
+            sign * o.open_int * o.greeks(s - (100.0 - escrowed), RATE, 0.0, iv).gamma * CONTRACT_SIZE * s * s
+        }).sum();
+        assert!((gex - expected).abs() < 1e-9 * expected.abs(), "{} vs {}", gex, expected);
+    }
+}
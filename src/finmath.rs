@@ -1,63 +1,673 @@
-const A1: f64 = 0.254829592;
-const A2: f64 = -0.284496736;
-const A3: f64 = 1.421413741;
-const A4: f64 = -1.453152027;
-const A5: f64 = 1.061405429;
-const P: f64 = 0.3275911;
-pub const FEDFUNDS: f64 = 0.0533;
-// Cumulative Normal Distribution
+const CODY_A: [f64; 5] = [2.2352520354606837, 161.02823106855587, 1067.6894854603709, 18154.98125334356, 0.06568233791820745];
+const CODY_B: [f64; 4] = [47.202581904688245, 976.0985517377767, 10260.932208618979, 45507.78933502673];
+const CODY_C: [f64; 9] = [
+    0.39894151208813466, 8.883149794388377, 93.50665613217785, 597.2702763948002, 2494.5375852903726,
+    6848.190450536283, 11602.65143764735, 9842.714838383978, 1.0765576773720192e-8,
+];
+const CODY_D: [f64; 8] = [
+    22.266688044328117, 235.387901782625, 1519.3775994075547, 6485.558298266761,
+    18615.571640885097, 34900.95272114598, 38912.00328609327, 19685.429676859992,
+];
+const CODY_P: [f64; 6] = [0.215898534057957, 0.12740116116024736, 0.022235277870649807, 0.0014216191932278934, 2.9112874951168793e-5, 0.023073441764940174];
+const CODY_Q: [f64; 5] = [1.284260096144911, 0.4682382124808651, 0.06598813786892856, 0.0037823963320275824, 7.297515550839662e-5];
+/* Cumulative Normal Distribution
+Cody (1969) rational Chebyshev approximations of erf/erfc on |x| <= 0.674, |x| <= sqrt(32) and the tails
+(the same scheme as R's pnorm); exp(-x^2/2) is split as exp(-xsq^2/2) * exp(-del/2) to keep full
+relative precision far into the lower tail
+*/
 pub fn cnd(x: f64) -> f64 {
-    let sign = if x < 0.0 { -1.0 } else { 1.0 };
-    let x = x.abs();
-    let t = 1.0 / (1.0 + P * x);
-    let y = (((((A5 * t + A4) * t) + A3) * t + A2) * t + A1) * t;
-    0.5 * (1.0 + sign * y)
+    let y = x.abs();
+    if y <= 0.67448975 {
+        let (mut xnum, mut xden) = (0.0, 0.0);
+        if y > f64::EPSILON * 0.5 {
+            let xsq = x * x;
+            xnum = CODY_A[4] * xsq;
+            xden = xsq;
+            for i in 0..3 {
+                xnum = (xnum + CODY_A[i]) * xsq;
+                xden = (xden + CODY_B[i]) * xsq;
+            }
+        }
+        return 0.5 + x * (xnum + CODY_A[3]) / (xden + CODY_B[3]);
+    }
+    let tail = if y <= 32f64.sqrt() {
+        let mut xnum = CODY_C[8] * y;
+        let mut xden = y;
+        for i in 0..7 {
+            xnum = (xnum + CODY_C[i]) * y;
+            xden = (xden + CODY_D[i]) * y;
+        }
+        (xnum + CODY_C[7]) / (xden + CODY_D[7])
+    } else {
+        let xsq = 1.0 / (x * x);
+        let mut xnum = CODY_P[5] * xsq;
+        let mut xden = xsq;
+        for i in 0..4 {
+            xnum = (xnum + CODY_P[i]) * xsq;
+            xden = (xden + CODY_Q[i]) * xsq;
+        }
+        let temp = xsq * (xnum + CODY_P[4]) / (xden + CODY_Q[4]);
+        (1.0 / (2.0 * std::f64::consts::PI).sqrt() - temp) / y
+    };
+    let xsq = (y * 16.0).trunc() / 16.0;
+    let del = (y - xsq) * (y + xsq);
+    let lower = (-xsq * xsq * 0.5).exp() * (-del * 0.5).exp() * tail;
+    if x > 0.0 {
+        1.0 - lower
+    } else {
+        lower
+    }
 }
 // Normal Probability Density Function
 pub fn npd(x: f64) -> f64 {
-    let exp_arg = -x * x / 2.0;
-    let exponential_term = exp_arg.exp();
-    let sqrt_two_pi = (2.0 * std::f64::consts::PI).sqrt();
-    exponential_term / sqrt_two_pi * (A1 * exp_arg + A2 * exp_arg.powi(2) + A3 * exp_arg.powi(3) + A4 * exp_arg.powi(4) + A5 * exp_arg.powi(5)) / (1.0 + P * x * x)
+    (-0.5 * x * x).exp() / (2.0 * std::f64::consts::PI).sqrt()
 }
-// Brent's Root Finding Method
-pub fn brentq<F>(f: F, mut a: f64, mut b: f64, tol: f64) -> Result<f64, String>
+/* Inverse Cumulative Normal Distribution (normal quantile function)
+Wichura (1988) algorithm AS241 PPND16, accurate to about 1 part in 10^16; p outside (0, 1) returns -inf/inf/NaN
+*/
+pub fn inv_cnd(p: f64) -> f64 {
+    if p.is_nan() || !(0.0..=1.0).contains(&p) {
+        return f64::NAN;
+    }
+    if p == 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if p == 1.0 {
+        return f64::INFINITY;
+    }
+    let q = p - 0.5;
+    if q.abs() <= 0.425 {
+        let r = 0.180625 - q * q;
+        return q * (((((((r * 2509.0809287301227 + 33430.57558358813) * r + 67265.7709270087) * r
+            + 45921.95393154987) * r + 13731.69376550946) * r + 1971.5909503065513) * r
+            + 133.14166789178438) * r + 3.3871328727963665)
+            / (((((((r * 5226.495278852546 + 28729.085735721943) * r + 39307.89580009271) * r
+            + 21213.794301586597) * r + 5394.196021424751) * r + 687.1870074920579) * r
+            + 42.31333070160091) * r + 1.0);
+    }
+    let mut r = if q < 0.0 { p } else { 1.0 - p };
+    r = (-r.ln()).sqrt();
+    let val = if r <= 5.0 {
+        r -= 1.6;
+        (((((((r * 0.0007745450142783414 + 0.022723844989269184) * r + 0.2417807251774506) * r
+            + 1.2704582524523684) * r + 3.6478483247632045) * r + 5.769497221460691) * r
+            + 4.630337846156546) * r + 1.4234371107496835)
+            / (((((((r * 1.0507500716444169e-9 + 0.0005475938084995345) * r + 0.015198666563616457) * r
+            + 0.14810397642748008) * r + 0.6897673349851) * r + 1.6763848301838038) * r
+            + 2.053191626637759) * r + 1.0)
+    } else {
+        r -= 5.0;
+        (((((((r * 2.0103343992922881e-7 + 2.7115555687434876e-5) * r + 0.0012426609473880784) * r
+            + 0.026532189526576124) * r + 0.29656057182850487) * r + 1.7848265399172913) * r
+            + 5.463784911164114) * r + 6.657904643501103)
+            / (((((((r * 2.0442631033899397e-15 + 1.421511758316446e-7) * r + 1.8463183175100548e-5) * r
+            + 0.0007868691311456133) * r + 0.014875361290850615) * r + 0.1369298809227358) * r
+            + 0.599832206555888) * r + 1.0)
+    };
+    if q < 0.0 {
+        -val
+    } else {
+        val
+    }
+}
+pub const IV_LOWER: f64 = 1e-6;
+pub const IV_UPPER: f64 = 15.0;
+const IV_TOL: f64 = 1e-10;
+const MAX_ITER: usize = 100;
+const NEWTON_MAX_ITER: usize = 50;
+const MIN_VEGA: f64 = 1e-10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolverStatus {
+    Converged,
+    MaxIterations,
+    NotBracketed,
+    InvalidInput,
+    BelowIntrinsic,
+    AboveUpperBound,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RootResult {
+    pub root: f64,
+    pub iterations: usize,
+    pub status: SolverStatus,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ImpVol {
+    pub iv: f64,
+    pub iterations: usize,
+    pub status: SolverStatus,
+}
+
+impl ImpVol {
+    pub fn converged(&self) -> bool {
+        self.status == SolverStatus::Converged
+    }
+}
+/* Brent's Root Finding Method (inverse quadratic interpolation + secant + bisection fallback)
+Follows the layout of scipy's brentq.c so f is evaluated exactly once per iteration;
+xblk is the contrapoint keeping the root bracketed and spre/scur are the previous two steps
+*/
+pub fn brentq<F>(f: F, a: f64, b: f64, tol: f64) -> RootResult
 where
     F: Fn(f64) -> f64,
 {
-    if f(a) * f(b) > 0.0 {
-        return Err(format!("\nbrentq(): root is not bracketed in the interval [{}, {}]", a, b));
-    }
-    while (b - a).abs() > tol {
-        let c = (a + b) / 2.0;
-        if f(c) == 0.0 {
-            return Ok(c);
-        } else if f(c) * f(a) < 0.0 {
-            b = c;
+    let (mut xpre, mut xcur) = (a, b);
+    let (mut fpre, mut fcur) = (f(xpre), f(xcur));
+    if fpre == 0.0 {
+        return RootResult { root: xpre, iterations: 0, status: SolverStatus::Converged };
+    }
+    if fcur == 0.0 {
+        return RootResult { root: xcur, iterations: 0, status: SolverStatus::Converged };
+    }
+    if fpre * fcur > 0.0 || (fpre * fcur).is_nan() {
+        return RootResult { root: f64::NAN, iterations: 0, status: SolverStatus::NotBracketed };
+    }
+    let (mut xblk, mut fblk) = (0.0, 0.0);
+    let (mut spre, mut scur) = (0.0, 0.0);
+    for i in 1..=MAX_ITER {
+        if fpre * fcur < 0.0 {
+            xblk = xpre;
+            fblk = fpre;
+            spre = xcur - xpre;
+            scur = spre;
+        }
+        if fblk.abs() < fcur.abs() {
+            xpre = xcur;
+            xcur = xblk;
+            xblk = xpre;
+            fpre = fcur;
+            fcur = fblk;
+            fblk = fpre;
+        }
+        let delta = (tol + 4.0 * f64::EPSILON * xcur.abs()) / 2.0;
+        let sbis = (xblk - xcur) / 2.0;
+        if fcur == 0.0 || sbis.abs() < delta {
+            return RootResult { root: xcur, iterations: i, status: SolverStatus::Converged };
+        }
+        if spre.abs() > delta && fcur.abs() < fpre.abs() {
+            let stry = if xpre == xblk {
+                // Secant step
+                -fcur * (xcur - xpre) / (fcur - fpre)
+            } else {
+                // Inverse quadratic interpolation step
+                let dpre = (fpre - fcur) / (xpre - xcur);
+                let dblk = (fblk - fcur) / (xblk - xcur);
+                -fcur * (fblk * dblk - fpre * dpre) / (dblk * dpre * (fblk - fpre))
+            };
+            if 2.0 * stry.abs() < spre.abs().min(3.0 * sbis.abs() - delta) {
+                spre = scur;
+                scur = stry;
+            } else {
+                spre = sbis;
+                scur = sbis;
+            }
+        } else {
+            spre = sbis;
+            scur = sbis;
+        }
+        xpre = xcur;
+        fpre = fcur;
+        if scur.abs() > delta {
+            xcur += scur;
         } else {
-            a = c;
+            xcur += if sbis > 0.0 { delta } else { -delta };
         }
+        fcur = f(xcur);
     }
-    Ok((a + b) / 2.0)
+    RootResult { root: xcur, iterations: MAX_ITER, status: SolverStatus::MaxIterations }
 }
 // Black-Scholes Helper for d1
-pub fn d_one(iv: f64, s: f64, k: f64, t: f64, q: f64) -> f64 {
-    (s.ln() / k + (FEDFUNDS - q + 0.5 * iv * iv) * t) / (iv * t.sqrt())
+pub fn d_one(iv: f64, s: f64, k: f64, t: f64, r: f64, q: f64) -> f64 {
+    ((s / k).ln() + (r - q + 0.5 * iv * iv) * t) / (iv * t.sqrt())
 }
 /* Black-Scholes Formula For US Equity Options
 iv = the implied volatility of the underlying
 s = the price of the underlying equity
 k = the contract strike price
 t = time to expiration (in years)
+r = the risk-free rate for the contract's tenor (see rates::RateCurve)
 q = the underlying equity's dividend yield
-r = the effective federal funds rate
 */
-pub fn black_scholes(iv: f64, s: f64, k: f64, t: f64, q: f64, is_call: bool) -> f64 {
-    let d1 = d_one(iv, s, k, t, q);
+pub fn black_scholes(iv: f64, s: f64, k: f64, t: f64, r: f64, q: f64, is_call: bool) -> f64 {
+    let d1 = d_one(iv, s, k, t, r, q);
     let d2 = d1 - iv * t.sqrt();
     if is_call {
-        (s * (-q * t).exp() * cnd(d1)) - (k * (-FEDFUNDS * t).exp() * cnd(d2))
+        (s * (-q * t).exp() * cnd(d1)) - (k * (-r * t).exp() * cnd(d2))
+    } else {
+        (k * (-r * t).exp() * cnd(-d2)) - (s * (-q * t).exp() * cnd(-d1))
+    }
+}
+// Black-Scholes Vega (sensitivity of price to iv) used to guide the Newton-Raphson solver
+pub fn bs_vega(iv: f64, s: f64, k: f64, t: f64, r: f64, q: f64) -> f64 {
+    let d1 = d_one(iv, s, k, t, r, q);
+    s * (-q * t).exp() * npd(d1) * t.sqrt()
+}
+/* Rational initial guess for the implied volatility solver
+Corrado-Miller quadratic approximation on the discounted spot/strike (exact at-the-money
+where it reduces to Brenner-Subrahmanyam); puts are mapped to calls through put-call parity
+*/
+fn initial_vol_guess(price: f64, s: f64, k: f64, t: f64, r: f64, q: f64, is_call: bool) -> f64 {
+    let fs = s * (-q * t).exp();
+    let dk = k * (-r * t).exp();
+    let call = if is_call { price } else { price + fs - dk };
+    let half = call - (fs - dk) / 2.0;
+    let disc = half * half - (fs - dk).powi(2) / std::f64::consts::PI;
+    let scale = (2.0 * std::f64::consts::PI / t).sqrt();
+    let guess = if disc >= 0.0 {
+        scale * (half + disc.sqrt()) / (fs + dk)
+    } else {
+        scale * call / fs
+    };
+    if guess.is_finite() && guess > IV_LOWER && guess < IV_UPPER {
+        guess
+    } else {
+        0.5
+    }
+}
+/* No-Arbitrage Price Bounds (lower, upper) for a European option
+call: max(s*e^(-qt) - k*e^(-rt), 0) <= c <= s*e^(-qt)
+put: max(k*e^(-rt) - s*e^(-qt), 0) <= p <= k*e^(-rt)
+*/
+pub fn arbitrage_bounds(s: f64, k: f64, t: f64, r: f64, q: f64, is_call: bool) -> (f64, f64) {
+    let fs = s * (-q * t).exp();
+    let dk = k * (-r * t).exp();
+    if is_call {
+        ((fs - dk).max(0.0), fs)
     } else {
-        (k * (-FEDFUNDS * t).exp() * cnd(-d2)) - (s * (-q * t).exp() * cnd(-d1))
+        ((dk - fs).max(0.0), dk)
+    }
+}
+/* Implied Volatility Solver
+Quotes outside arbitrage_bounds() are flagged without attempting a solve; otherwise
+vega-guided Newton-Raphson from a rational initial guess (price error relative below a price of 1, so deep
+out-of-the-money quotes are not matched by any volatility), and whenever a step leaves [IV_LOWER, IV_UPPER],
+vega vanishes, or Newton stalls, falls back to brentq() on that bracket
+*/
+pub fn implied_vol(price: f64, s: f64, k: f64, t: f64, r: f64, q: f64, is_call: bool) -> ImpVol {
+    if !(price > 0.0 && s > 0.0 && k > 0.0 && t > 0.0) {
+        return ImpVol { iv: f64::NAN, iterations: 0, status: SolverStatus::InvalidInput };
+    }
+    let (lower, upper) = arbitrage_bounds(s, k, t, r, q, is_call);
+    if price < lower {
+        return ImpVol { iv: f64::NAN, iterations: 0, status: SolverStatus::BelowIntrinsic };
     }
-}
\ No newline at end of file
+    if price >= upper {
+        return ImpVol { iv: f64::NAN, iterations: 0, status: SolverStatus::AboveUpperBound };
+    }
+    let mut iv = initial_vol_guess(price, s, k, t, r, q, is_call);
+    let mut iterations = 0;
+    while iterations < NEWTON_MAX_ITER {
+        iterations += 1;
+        let diff = black_scholes(iv, s, k, t, r, q, is_call) - price;
+        if diff.abs() < IV_TOL * price.min(1.0) {
+            return ImpVol { iv, iterations, status: SolverStatus::Converged };
+        }
+        let vega = bs_vega(iv, s, k, t, r, q);
+        if vega.is_nan() || vega <= MIN_VEGA {
+            break;
+        }
+        let next = iv - diff / vega;
+        if !(IV_LOWER..IV_UPPER).contains(&next) {
+            break;
+        }
+        if (next - iv).abs() < IV_TOL {
+            return ImpVol { iv: next, iterations, status: SolverStatus::Converged };
+        }
+        iv = next;
+    }
+    let f = |x: f64| black_scholes(x, s, k, t, r, q, is_call) - price;
+    let bracketed = brentq(f, IV_LOWER, IV_UPPER, IV_TOL);
+    ImpVol {
+        iv: bracketed.root,
+        iterations: iterations + bracketed.iterations,
+        status: bracketed.status,
+    }
+}
+
+const LANCZOS_G: f64 = 7.0;
+const LANCZOS_COEF: [f64; 9] = [
+    0.999_999_999_999_809_9, 676.520_368_121_885_1, -1_259.139_216_722_402_8, 771.323_428_777_653_1,
+    -176.615_029_162_140_6, 12.507_343_278_686_905, -0.138_571_095_265_720_12, 9.984_369_578_019_572e-6,
+    1.505_632_735_149_311_6e-7,
+];
+/* Natural log of the Gamma function for x > 0
+Lanczos approximation (g = 7, 9 terms, ~15 significant digits) with the reflection formula below 0.5
+*/
+pub fn ln_gamma(x: f64) -> f64 {
+    if x < 0.5 {
+        return (std::f64::consts::PI / (std::f64::consts::PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let series = LANCZOS_COEF[1..].iter().enumerate().fold(LANCZOS_COEF[0], |acc, (i, c)| acc + c / (x + i as f64 + 1.0));
+    let t = x + LANCZOS_G + 0.5;
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+const NM_MAX_ITER: usize = 20_000;
+
+#[derive(Debug, Clone)]
+pub struct MinResult {
+    pub x: Vec<f64>,
+    pub f: f64,
+    pub iterations: usize,
+    pub status: SolverStatus,
+}
+
+/* Nelder-Mead downhill simplex minimization of f over R^n
+x0 = starting point; step = initial simplex edge along each axis; tol = stop once the simplex's function values
+span less than tol (relative to |f| when |f| > 1); non-finite function values are treated as +infinity
+Standard coefficients (reflection 1, expansion 2, contraction 0.5, shrink 0.5), restarted once from the best vertex
+*/
+pub fn nelder_mead<F>(f: F, x0: &[f64], step: f64, tol: f64) -> MinResult
+where
+    F: Fn(&[f64]) -> f64,
+{
+    let eval = |x: &[f64]| {
+        let v = f(x);
+        if v.is_nan() { f64::INFINITY } else { v }
+    };
+    let n = x0.len();
+    let mut start = x0.to_vec();
+    let mut total = 0;
+    let mut converged = false;
+    for _ in 0..2 {
+        let mut simplex: Vec<Vec<f64>> = vec![start.clone()];
+        for i in 0..n {
+            let mut vertex = start.clone();
+            vertex[i] += if vertex[i].abs() > 1e-8 { step * vertex[i].abs().max(1.0) } else { step };
+            simplex.push(vertex);
+        }
+        let mut values: Vec<f64> = simplex.iter().map(|x| eval(x)).collect();
+        converged = false;
+        while total < NM_MAX_ITER {
+            total += 1;
+            let mut order: Vec<usize> = (0..=n).collect();
+            order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+            simplex = order.iter().map(|&i| simplex[i].clone()).collect();
+            values = order.iter().map(|&i| values[i]).collect();
+            let (best, worst) = (values[0], values[n]);
+            if (worst - best).abs() <= tol * best.abs().max(1.0) {
+                converged = true;
+                break;
+            }
+            let centroid: Vec<f64> = (0..n).map(|j| simplex[..n].iter().map(|v| v[j]).sum::<f64>() / n as f64).collect();
+            let along = |t: f64| -> Vec<f64> { (0..n).map(|j| centroid[j] + t * (simplex[n][j] - centroid[j])).collect() };
+            let reflected = along(-1.0);
+            let fr = eval(&reflected);
+            if fr < best {
+                let expanded = along(-2.0);
+                let fe = eval(&expanded);
+                if fe < fr {
+                    simplex[n] = expanded;
+                    values[n] = fe;
+                } else {
+                    simplex[n] = reflected;
+                    values[n] = fr;
+                }
+                continue;
+            }
+            if fr < values[n - 1] {
+                simplex[n] = reflected;
+                values[n] = fr;
+                continue;
+            }
+            let (contracted, fc) = if fr < worst {
+                let c = along(-0.5);
+                let fc = eval(&c);
+                (c, fc)
+            } else {
+                let c = along(0.5);
+                let fc = eval(&c);
+                (c, fc)
+            };
+            if fc < worst.min(fr) {
+                simplex[n] = contracted;
+                values[n] = fc;
+                continue;
+            }
+            for i in 1..=n {
+                simplex[i] = (0..n).map(|j| simplex[0][j] + 0.5 * (simplex[i][j] - simplex[0][j])).collect();
+                values[i] = eval(&simplex[i]);
+            }
+        }
+        let best = (0..=n).min_by(|&a, &b| values[a].total_cmp(&values[b])).unwrap_or(0);
+        start = simplex[best].clone();
+        if !converged {
+            break;
+        }
+    }
+    let f_best = eval(&start);
+    MinResult { x: start, f: f_best, iterations: total, status: if converged { SolverStatus::Converged } else { SolverStatus::MaxIterations } }
+}
+
+// Inverse of a square matrix by Gauss-Jordan elimination with partial pivoting; None when (numerically) singular
+pub fn invert_matrix(m: &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+    let n = m.len();
+    let mut a: Vec<Vec<f64>> = m.iter().enumerate().map(|(i, row)| {
+        let mut r = row.clone();
+        r.extend((0..n).map(|j| if i == j { 1.0 } else { 0.0 }));
+        r
+    }).collect();
+    let scale = m.iter().flatten().fold(0.0f64, |acc, x| acc.max(x.abs()));
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&x, &y| a[x][col].abs().total_cmp(&a[y][col].abs()))?;
+        if a[pivot][col].is_nan() || a[pivot][col].abs() <= 1e-14 * scale {
+            return None;
+        }
+        a.swap(col, pivot);
+        let p = a[col][col];
+        a[col].iter_mut().for_each(|x| *x /= p);
+        for row in 0..n {
+            if row != col {
+                let factor = a[row][col];
+                if factor != 0.0 {
+                    let pivot_row = a[col].clone();
+                    a[row].iter_mut().zip(pivot_row).for_each(|(x, p)| *x -= factor * p);
+                }
+            }
+        }
+    }
+    Some(a.into_iter().map(|row| row[n..].to_vec()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_rel(name: &str, actual: f64, expected: f64, rel_tol: f64) {
+        let err = if expected == 0.0 { actual.abs() } else { ((actual - expected) / expected).abs() };
+        assert!(err <= rel_tol, "{}: got {:e}, expected {:e} (relative error {:e})", name, actual, expected, err);
+    }
+
+    #[test]
+    fn cnd_matches_reference_values() {
+        let reference = [
+            (0.0, 0.5),
+            (1.0, 0.8413447460685429),
+            (-1.0, 0.15865525393145707),
+            (1.959963984540054, 0.975),
+            (-5.0, 2.866515718791939e-7),
+            (-10.0, 7.619853024160527e-24),
+            (-20.0, 2.753624118606234e-89),
+            (-30.0, 4.906713927148187e-198),
+        ];
+        for (x, expected) in reference {
+            assert_rel(&format!("cnd({})", x), cnd(x), expected, 1e-12);
+        }
+        assert_eq!(cnd(-40.0), 0.0);
+        assert_eq!(cnd(40.0), 1.0);
+    }
+
+    #[test]
+    fn cnd_is_symmetric() {
+        for i in -80..=80 {
+            let x = i as f64 * 0.1;
+            assert!((cnd(x) + cnd(-x) - 1.0).abs() < 1e-15, "cnd symmetry failed at {}", x);
+        }
+    }
+
+    #[test]
+    fn npd_is_normal_density() {
+        assert_rel("npd(0)", npd(0.0), 0.3989422804014327, 1e-15);
+        assert_rel("npd(1)", npd(1.0), 0.24197072451914337, 1e-15);
+        assert_rel("npd(-10)", npd(-10.0), 7.69459862670642e-23, 1e-13);
+    }
+
+    #[test]
+    fn inv_cnd_matches_reference_values() {
+        let reference = [
+            (0.5, 0.0),
+            (0.975, 1.959963984540054),
+            (0.025, -1.959963984540054),
+            (0.8413447460685429, 1.0),
+            (1e-10, -6.361340902404056),
+            (2.866515718791939e-7, -5.0),
+            (7.619853024160527e-24, -10.0),
+        ];
+        for (p, expected) in reference {
+            assert_rel(&format!("inv_cnd({:e})", p), inv_cnd(p), expected, 1e-12);
+        }
+        assert_eq!(inv_cnd(0.0), f64::NEG_INFINITY);
+        assert_eq!(inv_cnd(1.0), f64::INFINITY);
+        assert!(inv_cnd(1.5).is_nan());
+    }
+
+    #[test]
+    fn inv_cnd_round_trips_through_cnd() {
+        for exponent in 1..=300 {
+            let p = 10f64.powi(-exponent);
+            assert_rel(&format!("cnd(inv_cnd({:e}))", p), cnd(inv_cnd(p)), p, 1e-11);
+        }
+    }
+
+    #[test]
+    fn ln_gamma_matches_factorials_and_half_integers() {
+        for n in 1..=20u32 {
+            let factorial: f64 = (1..n).map(f64::from).product();
+            assert_rel(&format!("ln_gamma({})", n), ln_gamma(f64::from(n)), factorial.ln(), 1e-12);
+        }
+        assert_rel("ln_gamma(0.5)", ln_gamma(0.5), std::f64::consts::PI.sqrt().ln(), 1e-12);
+        assert_rel("ln_gamma(0.1)", ln_gamma(0.1), 2.252712651734206, 1e-12);
+    }
+
+    #[test]
+    fn nelder_mead_minimizes_rosenbrock() {
+        let rosenbrock = |x: &[f64]| (1.0 - x[0]).powi(2) + 100.0 * (x[1] - x[0] * x[0]).powi(2);
+        let result = nelder_mead(rosenbrock, &[-1.2, 1.0], 0.1, 1e-14);
+        assert_eq!(result.status, SolverStatus::Converged);
+        assert!((result.x[0] - 1.0).abs() < 1e-5 && (result.x[1] - 1.0).abs() < 1e-5, "{:?}", result.x);
+    }
+
+    #[test]
+    fn invert_matrix_inverts_and_rejects_singular_matrices() {
+        let inv = invert_matrix(&[vec![4.0, 7.0], vec![2.0, 6.0]]).unwrap();
+        let expected = [[0.6, -0.7], [-0.2, 0.4]];
+        for (row, exp) in inv.iter().zip(expected) {
+            for (x, e) in row.iter().zip(exp) {
+                assert!((x - e).abs() < 1e-12);
+            }
+        }
+        assert!(invert_matrix(&[vec![1.0, 2.0], vec![2.0, 4.0]]).is_none());
+    }
+
+    #[test]
+    fn brentq_finds_roots_and_reports_status() {
+        let sqrt2 = brentq(|x| x * x - 2.0, 0.0, 2.0, 1e-14);
+        assert_eq!(sqrt2.status, SolverStatus::Converged);
+        assert_rel("sqrt(2)", sqrt2.root, std::f64::consts::SQRT_2, 1e-14);
+        assert!(sqrt2.iterations > 0 && sqrt2.iterations < 15, "{} iterations", sqrt2.iterations);
+        let dottie = brentq(|x: f64| x.cos() - x, 0.0, 1.0, 1e-14);
+        assert_rel("cos(x) = x", dottie.root, 0.7390851332151607, 1e-14);
+        for (a, b) in [(1.0, 3.0), (-1.0, 1.0)] {
+            let endpoint = brentq(|x| x - 1.0, a, b, 1e-14);
+            assert_eq!((endpoint.root, endpoint.iterations, endpoint.status), (1.0, 0, SolverStatus::Converged));
+        }
+        let unbracketed = brentq(|x| x * x + 1.0, -1.0, 1.0, 1e-14);
+        assert!(unbracketed.root.is_nan() && unbracketed.status == SolverStatus::NotBracketed);
+        assert_eq!(brentq(|_| f64::NAN, 0.0, 1.0, 1e-14).status, SolverStatus::NotBracketed);
+        // A sign change at 0 with no zero keeps halving toward 0 (where delta vanishes) past MAX_ITER
+        let step = brentq(|x| if x > 0.0 { 1.0 } else { -1.0 }, -1.0, 2.0, 0.0);
+        assert_eq!((step.iterations, step.status), (MAX_ITER, SolverStatus::MaxIterations));
+        assert!(step.root.abs() < 1e-20);
+    }
+
+    #[test]
+    fn implied_vol_flags_invalid_inputs() {
+        for (price, s, k, t) in [(1.0, 100.0, 100.0, 0.0), (0.0, 100.0, 100.0, 0.5), (1.0, 0.0, 100.0, 0.5), (f64::NAN, 100.0, 100.0, 0.5)] {
+            let invalid = implied_vol(price, s, k, t, 0.05, 0.0, true);
+            assert!(invalid.iv.is_nan() && invalid.iterations == 0 && invalid.status == SolverStatus::InvalidInput);
+        }
+    }
+
+    #[test]
+    fn implied_vol_round_trips_across_moneyness_and_maturity() {
+        let (s, r, q) = (100.0, 0.05, 0.02);
+        for t in [7.0 / 365.0, 0.1, 0.5, 1.0, 5.0] {
+            for k in [60.0, 80.0, 95.0, 100.0, 105.0, 120.0, 150.0] {
+                for sigma in [0.05, 0.2, 0.5, 1.0, 3.0] {
+                    // Out-of-the-money side; in-the-money time value can sit below the price's precision
+                    let is_call = k >= s;
+                    let price = black_scholes(sigma, s, k, t, r, q, is_call);
+                    if price < 1e-200 {
+                        continue;
+                    }
+                    let solved = implied_vol(price, s, k, t, r, q, is_call);
+                    assert!(solved.converged() && solved.iterations > 0, "k {} t {} sigma {}: {:?}", k, t, sigma, solved);
+                    assert_rel(&format!("iv k {} t {}", k, t), solved.iv, sigma, 1e-6);
+                }
+            }
+        }
+        let atm = black_scholes(0.25, s, s, 0.5, r, q, true);
+        assert_rel("initial_vol_guess ATM", initial_vol_guess(atm, s, s, 0.5, r, q, true), 0.25, 0.05);
+        assert_eq!(initial_vol_guess(1e-30, s, 300.0, 0.01, r, q, true), 0.5);
+        let newton = implied_vol(atm, s, s, 0.5, r, q, true);
+        assert!(newton.converged() && newton.iterations <= 5, "{:?}", newton);
+    }
+
+    #[test]
+    fn implied_vol_falls_back_to_brent_for_deep_otm_short_dated_quotes() {
+        let (s, r, q) = (100.0, 0.05, 0.02);
+        for (k, t, sigma, is_call) in [(150.0, 2.0 / 365.0, 0.3, true), (200.0, 7.0 / 365.0, 0.4, true), (60.0, 1.0 / 365.0, 0.8, false)] {
+            let price = black_scholes(sigma, s, k, t, r, q, is_call);
+            let guess = initial_vol_guess(price, s, k, t, r, q, is_call);
+            // Vega at the initial guess vanishes, so Newton hands the quote to brentq()
+            assert!(bs_vega(guess, s, k, t, r, q) <= MIN_VEGA, "k {} t {}: vega {:e}", k, t, bs_vega(guess, s, k, t, r, q));
+            let solved = implied_vol(price, s, k, t, r, q, is_call);
+            assert!(solved.converged() && solved.iterations > 1, "k {} t {}: {:?}", k, t, solved);
+            assert_rel(&format!("iv k {} t {}", k, t), solved.iv, sigma, 1e-6);
+        }
+        let invalid = implied_vol(1.0, s, 100.0, 0.0, r, q, true);
+        assert!(invalid.iv.is_nan() && invalid.iterations == 0 && invalid.status == SolverStatus::InvalidInput);
+    }
+
+    #[test]
+    fn quotes_outside_arbitrage_bounds_are_flagged_without_solving() {
+        let (s, k, t, r, q): (f64, f64, f64, f64, f64) = (100.0, 90.0, 0.5, 0.05, 0.02);
+        let (fs, dk) = (s * (-q * t).exp(), k * (-r * t).exp());
+        assert_eq!(arbitrage_bounds(s, k, t, r, q, true), (fs - dk, fs));
+        assert_eq!(arbitrage_bounds(s, k, t, r, q, false), (0.0, dk));
+        assert_eq!(arbitrage_bounds(s, 120.0, t, r, q, true).0, 0.0);
+        assert_eq!(arbitrage_bounds(s, 120.0, t, r, q, false).0, 120.0 * (-r * t).exp() - fs);
+        for sigma in [0.01, 0.3, 5.0] {
+            for is_call in [true, false] {
+                let (lower, upper) = arbitrage_bounds(s, k, t, r, q, is_call);
+                let price = black_scholes(sigma, s, k, t, r, q, is_call);
+                assert!(price >= lower - 1e-12 && price < upper, "sigma {} call {}: {} outside [{}, {}]", sigma, is_call, price, lower, upper);
+            }
+        }
+        let (call_lower, call_upper) = arbitrage_bounds(s, k, t, r, q, true);
+        for (price, status) in [(call_lower - 0.01, SolverStatus::BelowIntrinsic), (call_upper, SolverStatus::AboveUpperBound), (call_upper + 5.0, SolverStatus::AboveUpperBound)] {
+            let flagged = implied_vol(price, s, k, t, r, q, true);
+            assert!(flagged.iv.is_nan() && flagged.iterations == 0 && flagged.status == status, "{}: {:?}", price, flagged);
+            assert!(!flagged.converged());
+        }
+        let put = implied_vol(dk + 0.01, s, k, t, r, q, false);
+        assert_eq!((put.iterations, put.status), (0, SolverStatus::AboveUpperBound));
+        let deep = arbitrage_bounds(s, 150.0, t, r, q, false).0;
+        assert_eq!(implied_vol(deep - 0.01, s, 150.0, t, r, q, false).status, SolverStatus::BelowIntrinsic);
+        assert!(implied_vol(deep + 0.01, s, 150.0, t, r, q, false).converged());
+    }
+}
@@ -132,16 +132,10 @@ pub fn fetch_finviz_info(ticker: &str, csv_name: &str) -> Result<(), Box<dyn Std
                     }
                     all_data.insert(ticker.to_string(), combined_data);
                 },
-                Err(e) => {
-                    eprintln!("\nfetch_finviz_info() :: ERROR -> Failed to parse finviz.com HTML table for {}: {}", ticker, e);
-                    return Ok(());
-                },
+                Err(e) => return Err(format!("\nfetch_finviz_info() :: ERROR -> Failed to parse finviz.com HTML table for {}: {}", ticker, e).into()),
             }
         },
-        Err(e) => {
-            eprintln!("\nfetch_finviz_info() :: ERROR -> Failed to fetch finviz.com HTML for {}:\n\n{}\n", ticker, e);
-            return Ok(());
-        },
+        Err(e) => return Err(format!("\nfetch_finviz_info() :: ERROR -> Failed to fetch finviz.com HTML for {}:\n\n{}\n", ticker, e).into()),
     }
     let mut writer = Writer::from_writer(File::create(csv_name)?);
     let mut headers = vec!["Label".to_string()];
@@ -0,0 +1,495 @@
+use super::avantage::{mean, TRADING_DAYS};
+use super::finmath::{invert_matrix, ln_gamma, nelder_mead, SolverStatus};
+use super::options::AtmIv;
+use super::utils::csv_value;
+use csv::Writer;
+use std::error::Error;
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+// Returns are fitted in percent so the variance parameters are of order one
+const RETURN_SCALE: f64 = 100.0;
+const MIN_OBSERVATIONS: usize = 30;
+const MLE_TOL: f64 = 1e-12;
+const MAX_PERSISTENCE: f64 = 0.9999;
+
+/* Conditional variance recursions, with e = demeaned return, z = e / sqrt(h)
+Garch = h[t] = omega + alpha * e[t-1]^2 + beta * h[t-1]
+Gjr = h[t] = omega + (alpha + gamma * 1{e[t-1] < 0}) * e[t-1]^2 + beta * h[t-1] (Glosten-Jagannathan-Runkle, gamma >= 0)
+Egarch = ln h[t] = omega + alpha * (|z[t-1]| - E|z|) + gamma * z[t-1] + beta * ln h[t-1] (Nelson)
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GarchKind {
+    Garch,
+    Gjr,
+    Egarch,
+}
+
+impl GarchKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            GarchKind::Garch => "GARCH",
+            GarchKind::Gjr => "GJR-GARCH",
+            GarchKind::Egarch => "EGARCH",
+        }
+    }
+}
+
+impl FromStr for GarchKind {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('-', "").as_str() {
+            "garch" => Ok(GarchKind::Garch),
+            "gjr" | "gjrgarch" => Ok(GarchKind::Gjr),
+            "egarch" => Ok(GarchKind::Egarch),
+            _ => Err(format!("\nGarchKind::from_str() :: ERROR -> Unknown GARCH model '{}'; expected garch, gjr or egarch", s)),
+        }
+    }
+}
+
+// Distribution of the standardized innovations z (StudentT is scaled to unit variance)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Innovation {
+    Normal,
+    StudentT,
+}
+
+impl Innovation {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Innovation::Normal => "normal",
+            Innovation::StudentT => "t",
+        }
+    }
+}
+
+/* Model parameters in percent-return units
+gamma = asymmetry (0 for Garch); nu = Student-t degrees of freedom (NaN for Normal innovations)
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GarchParams {
+    pub omega: f64,
+    pub alpha: f64,
+    pub gamma: f64,
+    pub beta: f64,
+    pub nu: f64,
+}
+
+impl GarchParams {
+    // Natural parameter vector in the order the likelihood and Hessian use
+    fn to_vec(self, kind: GarchKind, innovation: Innovation) -> Vec<f64> {
+        let mut v = match kind {
+            GarchKind::Garch => vec![self.omega, self.alpha, self.beta],
+            GarchKind::Gjr | GarchKind::Egarch => vec![self.omega, self.alpha, self.gamma, self.beta],
+        };
+        if innovation == Innovation::StudentT {
+            v.push(self.nu);
+        }
+        v
+    }
+    fn from_vec(v: &[f64], kind: GarchKind, innovation: Innovation) -> Self {
+        let (gamma, beta) = match kind {
+            GarchKind::Garch => (0.0, v[2]),
+            GarchKind::Gjr | GarchKind::Egarch => (v[2], v[3]),
+        };
+        let nu = if innovation == Innovation::StudentT { v[v.len() - 1] } else { f64::NAN };
+        GarchParams { omega: v[0], alpha: v[1], gamma, beta, nu }
+    }
+    // Rate at which the expected variance reverts (alpha + beta, alpha + gamma / 2 + beta, or beta for Egarch)
+    pub fn persistence(&self, kind: GarchKind) -> f64 {
+        match kind {
+            GarchKind::Garch => self.alpha + self.beta,
+            GarchKind::Gjr => self.alpha + 0.5 * self.gamma + self.beta,
+            GarchKind::Egarch => self.beta,
+        }
+    }
+}
+
+// E|z| of the standardized innovation
+fn expected_abs_z(innovation: Innovation, nu: f64) -> f64 {
+    match innovation {
+        Innovation::Normal => (2.0 / PI).sqrt(),
+        Innovation::StudentT => 2.0 * (nu - 2.0).sqrt() * (ln_gamma((nu + 1.0) / 2.0) - ln_gamma(nu / 2.0)).exp() / ((nu - 1.0) * PI.sqrt()),
+    }
+}
+
+// Whether parameters give a positive, covariance-stationary variance process
+fn valid(p: &GarchParams, kind: GarchKind, innovation: Innovation) -> bool {
+    let finite = [p.omega, p.alpha, p.gamma, p.beta].iter().all(|x| x.is_finite());
+    let nu_ok = innovation == Innovation::Normal || (p.nu.is_finite() && p.nu > 2.0);
+    let process_ok = match kind {
+        GarchKind::Garch => p.omega > 0.0 && p.alpha >= 0.0 && p.beta >= 0.0 && p.persistence(kind) < 1.0,
+        GarchKind::Gjr => p.omega > 0.0 && p.alpha >= 0.0 && p.alpha + p.gamma >= 0.0 && p.beta >= 0.0 && p.persistence(kind) < 1.0,
+        GarchKind::Egarch => p.beta.abs() < 1.0,
+    };
+    finite && nu_ok && process_ok
+}
+
+/* Conditional variances h[0..=n] of the demeaned percent returns e; h[0] starts at the sample variance and
+h[n] is the one-step-ahead variance after the last return
+*/
+fn conditional_variances(e: &[f64], p: &GarchParams, kind: GarchKind, innovation: Innovation) -> Vec<f64> {
+    let var0 = e.iter().map(|x| x * x).sum::<f64>() / e.len() as f64;
+    let mut h = Vec::with_capacity(e.len() + 1);
+    h.push(var0);
+    let e_abs = expected_abs_z(innovation, p.nu);
+    for (t, &et) in e.iter().enumerate() {
+        let ht = h[t];
+        let next = match kind {
+            GarchKind::Garch => p.omega + p.alpha * et * et + p.beta * ht,
+            GarchKind::Gjr => p.omega + (p.alpha + if et < 0.0 { p.gamma } else { 0.0 }) * et * et + p.beta * ht,
+            GarchKind::Egarch => {
+                let z = et / ht.sqrt();
+                (p.omega + p.alpha * (z.abs() - e_abs) + p.gamma * z + p.beta * ht.ln()).exp()
+            },
+        };
+        h.push(next);
+    }
+    h
+}
+
+// Negative log-likelihood of the demeaned percent returns; +infinity outside the valid parameter region
+fn neg_log_likelihood(e: &[f64], p: &GarchParams, kind: GarchKind, innovation: Innovation) -> f64 {
+    if !valid(p, kind, innovation) {
+        return f64::INFINITY;
+    }
+    let h = conditional_variances(e, p, kind, innovation);
+    let mut nll = 0.0;
+    match innovation {
+        Innovation::Normal => {
+            for (et, ht) in e.iter().zip(&h) {
+                nll += 0.5 * ((2.0 * PI).ln() + ht.ln() + et * et / ht);
+            }
+        },
+        Innovation::StudentT => {
+            let nu = p.nu;
+            let constant = ln_gamma((nu + 1.0) / 2.0) - ln_gamma(nu / 2.0) - 0.5 * (PI * (nu - 2.0)).ln();
+            for (et, ht) in e.iter().zip(&h) {
+                nll -= constant - 0.5 * ht.ln() - 0.5 * (nu + 1.0) * (1.0 + et * et / (ht * (nu - 2.0))).ln();
+            }
+        },
+    }
+    if nll.is_finite() { nll } else { f64::INFINITY }
+}
+
+fn logistic(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+fn logit(p: f64) -> f64 {
+    (p / (1.0 - p)).ln()
+}
+
+/* Maps unconstrained optimizer coordinates onto valid parameters
+Garch/Gjr: omega = exp(u0), persistence = MAX_PERSISTENCE * logistic(u1) split across alpha, gamma / 2 and beta by logistic/softmax weights
+Egarch: omega, alpha, gamma free and beta = MAX_PERSISTENCE * tanh(u3); Student-t nu = 2 + exp(u_last)
+*/
+fn transform(u: &[f64], kind: GarchKind, innovation: Innovation) -> GarchParams {
+    let nu = if innovation == Innovation::StudentT { 2.0 + u[u.len() - 1].exp() } else { f64::NAN };
+    match kind {
+        GarchKind::Garch => {
+            let s = MAX_PERSISTENCE * logistic(u[1]);
+            let w = logistic(u[2]);
+            GarchParams { omega: u[0].exp(), alpha: w * s, gamma: 0.0, beta: (1.0 - w) * s, nu }
+        },
+        GarchKind::Gjr => {
+            let s = MAX_PERSISTENCE * logistic(u[1]);
+            let (ea, eg) = (u[2].exp(), u[3].exp());
+            let total = 1.0 + ea + eg;
+            GarchParams { omega: u[0].exp(), alpha: s * ea / total, gamma: 2.0 * s * eg / total, beta: s / total, nu }
+        },
+        GarchKind::Egarch => GarchParams { omega: u[0], alpha: u[1], gamma: u[2], beta: MAX_PERSISTENCE * u[3].tanh(), nu },
+    }
+}
+
+// Optimizer starting point: persistence 0.95 with the long-run variance at the sample variance
+fn initial_coordinates(var: f64, kind: GarchKind, innovation: Innovation) -> Vec<f64> {
+    let mut u = match kind {
+        GarchKind::Garch => vec![(0.05 * var).ln(), logit(0.95 / MAX_PERSISTENCE), logit(0.05 / 0.95)],
+        GarchKind::Gjr => vec![(0.05 * var).ln(), logit(0.95 / MAX_PERSISTENCE), (0.03f64 / 0.9).ln(), (0.05f64 / 0.9).ln()],
+        GarchKind::Egarch => vec![0.05 * var.ln(), 0.1, 0.0, (0.95 / MAX_PERSISTENCE).atanh()],
+    };
+    if innovation == Innovation::StudentT {
+        u.push(6f64.ln());
+    }
+    u
+}
+
+/* A fitted GARCH-family model
+params / std_errors = estimates and asymptotic standard errors (inverse numerical Hessian of the negative log-likelihood;
+NaN when it is not positive definite, e.g. with an estimate on the boundary) in percent-return units
+mean = sample mean of the daily log returns removed before fitting; next_variance = one-day-ahead variance in percent^2
+*/
+#[derive(Debug, Clone)]
+pub struct GarchFit {
+    pub kind: GarchKind,
+    pub innovation: Innovation,
+    pub params: GarchParams,
+    pub std_errors: GarchParams,
+    pub log_likelihood: f64,
+    pub observations: usize,
+    pub mean: f64,
+    pub next_variance: f64,
+    pub status: SolverStatus,
+}
+
+impl GarchFit {
+    pub fn label(&self) -> String {
+        format!("{}-{}", self.kind.label(), self.innovation.label())
+    }
+    // Akaike information criterion (lower is better)
+    pub fn aic(&self) -> f64 {
+        2.0 * self.params.to_vec(self.kind, self.innovation).len() as f64 - 2.0 * self.log_likelihood
+    }
+    /* Expected daily variance of decimal log returns for each of the next `horizon` trading days
+    Garch/Gjr use the closed-form mean reversion h[k] = lr + p^(k-1) * (h[1] - lr) with lr = omega / (1 - p);
+    Egarch iterates E[ln h[k]] = omega + beta * E[ln h[k-1]] (exp of the expected log-variance, a slight underestimate by Jensen)
+    */
+    pub fn variance_forecast(&self, horizon: usize) -> Vec<f64> {
+        let p = self.params;
+        let scale = RETURN_SCALE * RETURN_SCALE;
+        let mut out = Vec::with_capacity(horizon);
+        match self.kind {
+            GarchKind::Garch | GarchKind::Gjr => {
+                let persistence = p.persistence(self.kind);
+                let long_run = p.omega / (1.0 - persistence);
+                let mut decay = 1.0;
+                for _ in 0..horizon {
+                    out.push((long_run + decay * (self.next_variance - long_run)) / scale);
+                    decay *= persistence;
+                }
+            },
+            GarchKind::Egarch => {
+                let mut ln_h = self.next_variance.ln();
+                for _ in 0..horizon {
+                    out.push(ln_h.exp() / scale);
+                    ln_h = p.omega + p.beta * ln_h;
+                }
+            },
+        }
+        out
+    }
+    // Annualized volatility implied by the average forecast variance over the next `days` trading days (at least one)
+    pub fn term_vol(&self, days: f64) -> f64 {
+        let horizon = (days.round() as usize).max(1);
+        let forecast = self.variance_forecast(horizon);
+        (forecast.iter().sum::<f64>() / horizon as f64 * TRADING_DAYS).sqrt()
+    }
+    // One standard deviation of tomorrow's log return (decimal)
+    pub fn next_day_vol(&self) -> f64 {
+        self.next_variance.sqrt() / RETURN_SCALE
+    }
+}
+
+/* Fits a GARCH-family model to daily log returns by maximum likelihood
+The returns are demeaned and scaled to percent, the likelihood is maximized with Nelder-Mead over unconstrained
+coordinates (see transform()) and standard errors come from the inverse central-difference Hessian at the optimum
+*/
+pub fn fit_garch(returns: &[f64], kind: GarchKind, innovation: Innovation) -> Result<GarchFit, String> {
+    if returns.len() < MIN_OBSERVATIONS || returns.iter().any(|r| !r.is_finite()) {
+        return Err(format!("\nfit_garch() :: ERROR -> {} needs at least {} finite returns; got {}", kind.label(), MIN_OBSERVATIONS, returns.len()));
+    }
+    let avg = mean(returns);
+    let e: Vec<f64> = returns.iter().map(|r| (r - avg) * RETURN_SCALE).collect();
+    let var = e.iter().map(|x| x * x).sum::<f64>() / e.len() as f64;
+    if var <= 0.0 {
+        return Err("\nfit_garch() :: ERROR -> Returns have zero variance".to_string());
+    }
+    let objective = |u: &[f64]| neg_log_likelihood(&e, &transform(u, kind, innovation), kind, innovation);
+    let result = nelder_mead(objective, &initial_coordinates(var, kind, innovation), 0.5, MLE_TOL);
+    let params = transform(&result.x, kind, innovation);
+    if !result.f.is_finite() {
+        return Err(format!("\nfit_garch() :: ERROR -> {} likelihood maximization failed ({:?} after {} iterations)", kind.label(), result.status, result.iterations));
+    }
+    if result.status != SolverStatus::Converged {
+        eprintln!("\nfit_garch() :: {}-{} likelihood maximization stopped after {} iterations without converging; keeping the best estimate", kind.label(), innovation.label(), result.iterations);
+    }
+    let theta = params.to_vec(kind, innovation);
+    let nll = |v: &[f64]| neg_log_likelihood(&e, &GarchParams::from_vec(v, kind, innovation), kind, innovation);
+    let std_errors = GarchParams::from_vec(&standard_errors(nll, &theta), kind, innovation);
+    let h = conditional_variances(&e, &params, kind, innovation);
+    Ok(GarchFit {
+        kind,
+        innovation,
+        params,
+        std_errors: if kind == GarchKind::Garch { GarchParams { gamma: f64::NAN, ..std_errors } } else { std_errors },
+        log_likelihood: -result.f,
+        observations: e.len(),
+        mean: avg,
+        next_variance: h[e.len()],
+        status: result.status,
+    })
+}
+
+// Square roots of the diagonal of the inverse central-difference Hessian of f at theta (NaN where unavailable)
+fn standard_errors<F: Fn(&[f64]) -> f64>(f: F, theta: &[f64]) -> Vec<f64> {
+    let n = theta.len();
+    let steps: Vec<f64> = theta.iter().map(|x| 1e-4 * x.abs().max(1e-2)).collect();
+    let at = |di: (usize, f64), dj: (usize, f64)| {
+        let mut x = theta.to_vec();
+        x[di.0] += di.1;
+        x[dj.0] += dj.1;
+        f(&x)
+    };
+    let mut hessian = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in i..n {
+            let (hi, hj) = (steps[i], steps[j]);
+            let value = (at((i, hi), (j, hj)) - at((i, hi), (j, -hj)) - at((i, -hi), (j, hj)) + at((i, -hi), (j, -hj))) / (4.0 * hi * hj);
+            hessian[i][j] = value;
+            hessian[j][i] = value;
+        }
+    }
+    if hessian.iter().flatten().any(|x| !x.is_finite()) {
+        return vec![f64::NAN; n];
+    }
+    match invert_matrix(&hessian) {
+        Some(inv) => (0..n).map(|i| if inv[i][i] > 0.0 { inv[i][i].sqrt() } else { f64::NAN }).collect(),
+        None => vec![f64::NAN; n],
+    }
+}
+
+/* Saves fitted GARCH models as a parameter table and a term structure against the chain's ATM implied volatility
+params csv = Model, Omega, Alpha, Gamma, Beta, Nu with their standard errors, Persistence, LogLikelihood, AIC, NextDayVol (annualized) and the optimizer Status
+term csv = Expiry, Yte, Days (yte * TRADING_DAYS), AtmIV and one annualized forecast volatility column per model
+*/
+pub fn garch_to_csv(fits: &[GarchFit], atm: &[AtmIv], params_csv_name: &str, term_csv_name: &str) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_path(params_csv_name)?;
+    wtr.write_record(["Model", "Omega", "OmegaSE", "Alpha", "AlphaSE", "Gamma", "GammaSE", "Beta", "BetaSE", "Nu", "NuSE", "Persistence", "LogLikelihood", "AIC", "NextDayVol", "Status"])?;
+    for fit in fits {
+        let (p, se) = (fit.params, fit.std_errors);
+        wtr.write_record([
+            fit.label(),
+            csv_value(p.omega),
+            csv_value(se.omega),
+            csv_value(p.alpha),
+            csv_value(se.alpha),
+            csv_value(p.gamma),
+            csv_value(se.gamma),
+            csv_value(p.beta),
+            csv_value(se.beta),
+            csv_value(p.nu),
+            csv_value(se.nu),
+            csv_value(p.persistence(fit.kind)),
+            csv_value(fit.log_likelihood),
+            csv_value(fit.aic()),
+            csv_value(fit.next_day_vol() * TRADING_DAYS.sqrt()),
+            format!("{:?}", fit.status),
+        ])?;
+    }
+    wtr.flush()?;
+    let mut wtr = Writer::from_path(term_csv_name)?;
+    let mut header = vec!["Expiry".to_string(), "Yte".to_string(), "Days".to_string(), "AtmIV".to_string()];
+    header.extend(fits.iter().map(|f| f.label()));
+    wtr.write_record(&header)?;
+    for point in atm {
+        let days = point.yte * TRADING_DAYS;
+        let mut record = vec![point.date.clone(), csv_value(point.yte), csv_value(days), csv_value(point.iv)];
+        record.extend(fits.iter().map(|f| csv_value(f.term_vol(days))));
+        wtr.write_record(&record)?;
+    }
+    wtr.flush()?;
+    println!("\ngarch_to_csv() :: Successfully created {} and {} for {} models", params_csv_name, term_csv_name, fits.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn normal(rng: &mut StdRng) -> f64 {
+        let (u1, u2): (f64, f64) = (rng.gen_range(f64::EPSILON..1.0), rng.gen());
+        (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+    }
+
+    // Unit-variance innovation; Student-t draws use an integer nu as a ratio of normals to a chi-square
+    fn innovation_draw(rng: &mut StdRng, innovation: Innovation, nu: f64) -> f64 {
+        match innovation {
+            Innovation::Normal => normal(rng),
+            Innovation::StudentT => {
+                let chi2: f64 = (0..nu as usize).map(|_| normal(rng).powi(2)).sum();
+                normal(rng) / (chi2 / nu).sqrt() * ((nu - 2.0) / nu).sqrt()
+            },
+        }
+    }
+
+    // Decimal daily returns of a simulated path (after a burn-in) from percent-unit parameters
+    fn simulate(kind: GarchKind, innovation: Innovation, p: GarchParams, n: usize, seed: u64) -> Vec<f64> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let e_abs = expected_abs_z(innovation, p.nu);
+        let mut h = match kind {
+            GarchKind::Egarch => (p.omega / (1.0 - p.beta)).exp(),
+            _ => p.omega / (1.0 - p.persistence(kind)),
+        };
+        let mut returns = Vec::with_capacity(n);
+        for t in 0..n + 500 {
+            let z = innovation_draw(&mut rng, innovation, p.nu);
+            let e = h.sqrt() * z;
+            if t >= 500 {
+                returns.push(e / RETURN_SCALE);
+            }
+            h = match kind {
+                GarchKind::Garch => p.omega + p.alpha * e * e + p.beta * h,
+                GarchKind::Gjr => p.omega + (p.alpha + if e < 0.0 { p.gamma } else { 0.0 }) * e * e + p.beta * h,
+                GarchKind::Egarch => (p.omega + p.alpha * (z.abs() - e_abs) + p.gamma * z + p.beta * h.ln()).exp(),
+            };
+        }
+        returns
+    }
+
+    fn assert_recovers(fit: &GarchFit, truth: GarchParams, tol: [f64; 5]) {
+        let est = fit.params.to_vec(fit.kind, fit.innovation);
+        let se = fit.std_errors;
+        let truth_v = truth.to_vec(fit.kind, fit.innovation);
+        let tol_v = GarchParams { omega: tol[0], alpha: tol[1], gamma: tol[2], beta: tol[3], nu: tol[4] }.to_vec(fit.kind, fit.innovation);
+        for ((e, t), tol) in est.iter().zip(&truth_v).zip(&tol_v) {
+            assert!((e - t).abs() < *tol, "{} estimate {:?} vs truth {:?}", fit.label(), fit.params, truth);
+        }
+        for s in se.to_vec(fit.kind, fit.innovation).iter().filter(|s| !s.is_nan()) {
+            assert!(*s > 0.0);
+        }
+    }
+
+    #[test]
+    fn garch_normal_recovers_simulated_parameters() {
+        let truth = GarchParams { omega: 0.05, alpha: 0.08, gamma: 0.0, beta: 0.9, nu: f64::NAN };
+        let returns = simulate(GarchKind::Garch, Innovation::Normal, truth, 4000, 42);
+        let fit = fit_garch(&returns, GarchKind::Garch, Innovation::Normal).unwrap();
+        assert_eq!(fit.status, SolverStatus::Converged);
+        assert_recovers(&fit, truth, [0.03, 0.025, 0.0, 0.03, 0.0]);
+        // Every standard error is available in the interior and the truth lies within four of them
+        let se = fit.std_errors;
+        assert!(se.omega.is_finite() && se.alpha.is_finite() && se.beta.is_finite() && se.gamma.is_nan());
+        assert!((fit.params.beta - truth.beta).abs() < 4.0 * se.beta);
+        // The term structure reverts from the next-day volatility toward the long-run level
+        let long_run = (truth.omega / (1.0 - truth.alpha - truth.beta) * TRADING_DAYS).sqrt() / RETURN_SCALE;
+        let (near, far) = (fit.term_vol(1.0), fit.term_vol(5000.0));
+        assert!((near - fit.next_day_vol() * TRADING_DAYS.sqrt()).abs() < 1e-12);
+        assert!((far - long_run).abs() < 0.03, "long run {} vs {}", far, long_run);
+    }
+
+    #[test]
+    fn gjr_student_t_recovers_simulated_parameters() {
+        let truth = GarchParams { omega: 0.04, alpha: 0.03, gamma: 0.1, beta: 0.88, nu: 8.0 };
+        let returns = simulate(GarchKind::Gjr, Innovation::StudentT, truth, 4000, 7);
+        let fit = fit_garch(&returns, GarchKind::Gjr, Innovation::StudentT).unwrap();
+        assert_recovers(&fit, truth, [0.03, 0.03, 0.05, 0.04, 3.0]);
+        // The heavier-tailed model should beat a misspecified normal GARCH
+        let normal = fit_garch(&returns, GarchKind::Garch, Innovation::Normal).unwrap();
+        assert!(fit.aic() < normal.aic());
+    }
+
+    #[test]
+    fn egarch_recovers_simulated_parameters() {
+        let truth = GarchParams { omega: 0.01, alpha: 0.12, gamma: -0.06, beta: 0.96, nu: f64::NAN };
+        let returns = simulate(GarchKind::Egarch, Innovation::Normal, truth, 4000, 3);
+        let fit = fit_garch(&returns, GarchKind::Egarch, Innovation::Normal).unwrap();
+        assert_recovers(&fit, truth, [0.02, 0.04, 0.03, 0.02, 0.0]);
+        let forecast = fit.variance_forecast(10);
+        assert_eq!(forecast.len(), 10);
+        assert!((forecast[0] - fit.next_variance / (RETURN_SCALE * RETURN_SCALE)).abs() < 1e-15);
+        assert!(fit_garch(&returns[..10], GarchKind::Egarch, Innovation::Normal).is_err());
+    }
+}
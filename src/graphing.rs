@@ -1,130 +1,396 @@
 use anyhow::{Context, Result};
-use super::options::chain_from_csv;
-use super::finmath::{d_one, FEDFUNDS};
-use std::process::{Command, Stdio};
-use std::io::{Write, BufWriter};
-use std::fs::File;
+use super::charts::{date_to_x, ChartBackend, LineChart, ScatterSurface, Series, SeriesStyle, XAxis};
+use super::indicators::indicator_from_name;
+use super::options::{chain_from_csv, PricingContext, EXPDATEFMT};
+use super::surface::{surface_from_csv, SurfaceModel};
+use super::density::density_curves_from_csv;
+use super::exposure::{exposure_from_csv, ExposureProfile, StrikeExposure, TOTAL_LABEL};
+use chrono::NaiveDate;
+use std::collections::HashMap;
+use std::path::Path;
 
-const CDATNAME: &str = "dat_out/ctemp.dat";
-const PDATNAME: &str = "dat_out/ptemp.dat";
 pub const IMGDIR: &str = "img_out/";
 
-pub fn generate_tseries_plot(ts_csv_name: &str, field: usize) -> Result<()> {
-    let data_label = match field {
-        0 => "Close",
-        1 => "Open",
-        2 => "Low",
-        3 => "High",
-        4 => "Volume",
-        5 => "Change",
-        6 => "%Change",
-        7 => "Range",
-        8 => "AvgTrueRange",
-        9 => "RealizedVol",
-        10 => "FiniteDiff",
-        _ => "Close",
+// Time series csv columns that can be charted with generate_tseries_plot(); indicator columns are charted by indicator name
+pub const TSERIES_PLOT_COLUMNS: [&str; 21] = ["Close", "Open", "Low", "High", "Volume", "Change", "%Change", "Range", "AvgTrueRange", "RealizedVol", "FiniteDiff", "AdjClose", "AdjOpen", "AdjLow", "AdjHigh", "AdjVolume", "ParkinsonVol", "GarmanKlassVol", "RogersSatchellVol", "YangZhangVol", "EwmaVol"];
+
+// Chart file name labels of the option chain surface fields, indexed by generate_surface_plot()'s field; call and put charts prefix them with c and p
+pub const SURFACE_PLOT_LABELS: [&str; 27] = ["last", "change", "volume", "bid", "ask", "oi", "strike", "yte", "iv", "delta", "elasticity", "vega", "theta", "rho", "epsilon", "gamma", "vanna", "charm", "vomma", "veta", "speed", "zomma", "color", "ultima", "bidiv", "askiv", "ivband"];
+
+// Chart file name labels of the fitted volatility surface charts drawn by generate_vol_surface_plots()
+pub const VOL_SURFACE_PLOT_LABELS: [&str; 4] = ["svisurface", "svismiles", "ssvisurface", "ssvismiles"];
+// Chart file name labels of plot_volatility_smiles() and generate_term_structure_plot()
+pub const SMILE_PLOT_LABELS: [&str; 2] = ["volsmiles", "termstructure"];
+// Chart file name label of every expiry's density on one chart; each expiry's own chart appends its YYYYMMDD date
+pub const DENSITY_PLOT_LABEL: &str = "rnd";
+// Chart file name labels of the aggregate exposure charts of generate_exposure_plots(); each expiry's gamma exposure chart is gex<YYYYMMDD>
+pub const EXPOSURE_PLOT_LABELS: [&str; 4] = ["gex", "gexprofile", "vex", "cex"];
+// Moneyness (strike / spot) range and grid sizes of the fitted surface charts
+const SURFACE_MONEYNESS: (f64, f64) = (0.7, 1.3);
+const SURFACE_GRID: (usize, usize) = (41, 30);
+
+// Chart file name label of a TSERIES_PLOT_COLUMNS column
+pub fn tseries_plot_label(column: &str) -> String {
+    match column {
+        "%Change" => "perchange".to_string(),
+        "AvgTrueRange" => "atr".to_string(),
+        "RealizedVol" => "rvol".to_string(),
+        "FiniteDiff" => "bfd".to_string(),
+        _ => column.to_lowercase(),
+    }
+}
+
+/* Output path without extension for a chart of a csv named <TICKER>_<kind>_<date>_<time>.csv: <img_dir>/<TICKER>_<label>_<date>_<time>
+Returns (ticker, stem)
+*/
+fn chart_stem(csv_name: &str, label: &str, img_dir: &str) -> Result<(String, String)> {
+    let stem = Path::new(csv_name).file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let info_parts: Vec<&str> = stem.split('_').collect();
+    if info_parts.len() < 4 {
+        anyhow::bail!("\nchart_stem() :: ERROR -> {} is not named <TICKER>_<kind>_<date>_<time>.csv", csv_name);
+    }
+    let name = format!("{}_{}_{}_{}", info_parts[0], label, info_parts[2], info_parts[3]);
+    Ok((info_parts[0].to_string(), Path::new(img_dir).join(name).to_string_lossy().to_string()))
+}
+
+type TseriesColumns = (Vec<f64>, HashMap<String, Vec<f64>>);
+
+// Date x values (charts::date_to_x()) and every column of a time series csv by header name; blank or non-numeric cells read as NaN
+fn read_tseries_csv(ts_csv_name: &str) -> Result<TseriesColumns> {
+    let mut rdr = csv::Reader::from_path(ts_csv_name).with_context(|| format!("\nread_tseries_csv() :: ERROR -> Failed to open {}", ts_csv_name))?;
+    let headers: Vec<String> = rdr.headers()?.iter().map(|h| h.to_string()).collect();
+    let mut dates = Vec::new();
+    let mut columns: HashMap<String, Vec<f64>> = headers.iter().map(|h| (h.clone(), Vec::new())).collect();
+    for result in rdr.records() {
+        let record = result?;
+        let date = NaiveDate::parse_from_str(record.get(0).unwrap_or_default().trim(), "%Y-%m-%d")
+            .with_context(|| format!("\nread_tseries_csv() :: ERROR -> Invalid date in {}", ts_csv_name))?;
+        dates.push(date_to_x(date));
+        for (header, cell) in headers.iter().zip(record.iter()) {
+            if let Some(column) = columns.get_mut(header) {
+                column.push(cell.trim().parse::<f64>().unwrap_or(f64::NAN));
+            }
+        }
+    }
+    Ok((dates, columns))
+}
+
+/* Renders a dated line chart of time series csv columns
+lines = (column, title, style) drawn in order; png_label = chart name between the ticker and the csv timestamp in the file name
+*/
+fn plot_tseries_columns(ts_csv_name: &str, img_dir: &str, title: &str, png_label: &str, lines: &[(String, String, SeriesStyle)], logscale: bool, backend: &dyn ChartBackend) -> Result<Vec<String>> {
+    let (ticker, stem) = chart_stem(ts_csv_name, png_label, img_dir)?;
+    let (dates, columns) = read_tseries_csv(ts_csv_name)?;
+    let mut series = Vec::new();
+    for (column, line_title, style) in lines {
+        let values = columns.get(column).with_context(|| format!("\nplot_tseries_columns() :: ERROR -> {} has no {} column", ts_csv_name, column))?;
+        series.push(Series { title: line_title.clone(), points: dates.iter().copied().zip(values.iter().copied()).collect(), style: *style });
+    }
+    let chart = LineChart {
+        title: format!("{} {}", ticker, title),
+        x_label: "Date".to_string(),
+        y_label: title.to_string(),
+        x_axis: XAxis::Date,
+        log_y: logscale,
+        series,
     };
-    let name_parts: Vec<&str> = ts_csv_name.split('/').collect();
-    let info_parts = name_parts[1].split('_').collect::<Vec<&str>>();
-    let ticker = info_parts[0];
-    let binding = data_label.to_lowercase();
-    let png_name_label = match data_label {
-        "%Change" => "perchange",
-        "AvgTrueRange" => "atr",
-        "RealizedVol" => "rvol",
-        "FiniteDiff" => "bfd",
-        _ => binding.as_str(),
+    backend.line_chart(&chart, &stem)
+}
+
+/* Lines of a time series chart as (csv column, legend title) pairs and whether it uses a log scale
+Shared by the PNG charts and the HTML report so both draw the same columns
+*/
+pub type ChartLines = (Vec<(String, String)>, bool);
+
+// One TSERIES_PLOT_COLUMNS column with its rolling linear regression where one is written
+pub fn tseries_chart_lines(column: &str) -> ChartLines {
+    let mut lines = vec![(column.to_string(), column.to_string())];
+    // Indicators are computed on the split/dividend adjusted series, so the regression overlays the adjusted prices
+    let regression = match column {
+        "AdjClose" | "AdjOpen" | "AdjLow" | "AdjHigh" => Some("LinearReg"),
+        "Change" => Some("ChangeLinearReg"),
+        "FiniteDiff" => Some("FiniteDiffLinearReg"),
+        _ => None,
     };
-    let png_name = format!("{}{}_{}_{}_{}.png", IMGDIR, ticker, png_name_label, info_parts[2], info_parts[3].replace(".csv", ""));
-    let mut gnuplot_script = format!(
-        r#"
-        set terminal png
-        set output '{}'
-        set datafile separator ','
-        set xdata time
-        set timefmt '%Y-%m-%d'
-        set format x "%m/%d"
-        set xlabel "Date"
-        set ylabel "{}"
-        set title "{} {}"
-        set grid
-        set logscale y
-        set key autotitle columnheader
-        plot '{}' using "Date":"{}" with lines title '{}'"#,
-        png_name, data_label, ticker, data_label, ts_csv_name, data_label, data_label
-    );
-    if field == 0 || field == 1 || field == 2 || field == 3 {
-        gnuplot_script.push_str(&format!(
-            ", '{}' using \"Date\":\"LinearReg\" with lines title 'Linear Regression'",
-            ts_csv_name
-        ));
-    } else if field == 5 {
-        gnuplot_script.push_str(&format!(
-            ", '{}' using \"Date\":\"ChangeLinearReg\" with lines title 'Linear Regression'",
-            ts_csv_name
-        ));
-    } else if field == 10 {
-        gnuplot_script.push_str(&format!(
-            ", '{}' using \"Date\":\"FiniteDiffLinearReg\" with lines title 'Linear Regression'",
-            ts_csv_name
-        ));
+    if let Some(reg) = regression {
+        lines.push((reg.to_string(), "Linear Regression".to_string()));
+    }
+    // Differences can be negative, so only levels get a log scale
+    (lines, !matches!(column, "Change" | "%Change" | "FiniteDiff"))
+}
+
+// Every column of a registered indicator (e.g. "BB20", "MACD12_26_9"), over AdjClose on a log scale when it is on the price scale
+pub fn indicator_chart_lines(indicator: &str) -> Result<ChartLines> {
+    let ind = indicator_from_name(indicator).map_err(|e| anyhow::anyhow!(e))?;
+    let mut lines = Vec::new();
+    if ind.overlay() {
+        lines.push(("AdjClose".to_string(), "AdjClose".to_string()));
+    }
+    for column in ind.columns() {
+        lines.push((column.clone(), column));
+    }
+    Ok((lines, ind.overlay()))
+}
+
+fn styled(lines: Vec<(String, String)>) -> Vec<(String, String, SeriesStyle)> {
+    lines.into_iter().map(|(column, title)| (column, title, SeriesStyle::Line)).collect()
+}
+
+// Charts one time series csv column by name (see TSERIES_PLOT_COLUMNS and tseries_chart_lines())
+pub fn generate_tseries_plot(ts_csv_name: &str, img_dir: &str, column: &str, backend: &dyn ChartBackend) -> Result<()> {
+    let (lines, logscale) = tseries_chart_lines(column);
+    for path in plot_tseries_columns(ts_csv_name, img_dir, column, &tseries_plot_label(column), &styled(lines), logscale, backend)? {
+        println!("\ngenerate_tseries_plot() :: Successfully generated {}", path);
     }
-    let mut cmd_gnuplot = Command::new("gnuplot")
-        .stdin(Stdio::piped())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .spawn()
-        .context("\ngenerate_tseries_plot() :: ERROR -> Failed to execute cmd_gnuplot chart generation command")?;
-    let stdin = cmd_gnuplot.stdin.as_mut().context("\ngenerate_tseries_plot() :: ERROR -> Failed to open stdin for gnuplot_script")?;
-    writeln!(stdin, "{}", gnuplot_script).context("\ngenerate_tseries_plot() :: ERROR -> Failed to write gnuplot_script to stdin for chart")?;
-    cmd_gnuplot.wait().context("\ngenerate_tseries_plot() :: ERROR -> Failed to wait for gnuplot chart generation process")?;
-    println!("\ngenerate_surface_plot() :: Successfully generated {}", png_name);
     Ok(())
 }
 
-pub fn generate_surface_plot(chain_csv_name: &str, field: usize) -> Result<()> {
-    let chain = chain_from_csv(chain_csv_name)
-        .map_err(|e| anyhow::anyhow!("\ngenerate_surface_plot() :: ERROR -> Failed to load option chain with chain_from_csv: {}", e))?;
-    let name_parts: Vec<&str> = chain_csv_name.split('/').collect();
-    let info_parts: Vec<&str> = name_parts[1].split('_').collect();
-    let cdatfile = File::create(CDATNAME).context("\ngenerate_surface_plot() :: ERROR -> Failed to create cdatfile")?;
-    let pdatfile = File::create(PDATNAME).context("\ngenerate_surface_plot() :: ERROR -> Failed to create pdatfile")?;
-    let mut cwriter = BufWriter::new(cdatfile);
-    let mut pwriter = BufWriter::new(pdatfile);
-    let data_label = match field {
-        0 => "last",
-        1 => "change",
-        2 => "volume",
-        3 => "bid",
-        4 => "ask",
-        5 => "oi",
-        6 => "strike",
-        7 => "yte",
-        8 => "iv",
-        9 => "delta",
-        10 => "elasticity",
-        11 => "vega",
-        12 => "theta",
-        13 => "rho",
-        14 => "epsilon",
-        15 => "gamma",
-        16 => "vanna",
-        17 => "charm",
-        18 => "vomma",
-        19 => "veta",
-        20 => "speed",
-        21 => "zomma",
-        22 => "color",
-        23 => "ultima",
-        _ => "last",
+// Charts every column of a registered indicator (see indicator_chart_lines())
+pub fn generate_indicator_plot(ts_csv_name: &str, img_dir: &str, indicator: &str, backend: &dyn ChartBackend) -> Result<()> {
+    let (lines, logscale) = indicator_chart_lines(indicator)?;
+    let paths = plot_tseries_columns(ts_csv_name, img_dir, indicator, &indicator.to_lowercase(), &styled(lines), logscale, backend)
+        .context("\ngenerate_indicator_plot() :: ERROR -> Indicators must be written by tseries_to_csv() before plotting")?;
+    for path in paths {
+        println!("\ngenerate_indicator_plot() :: Successfully generated {}", path);
+    }
+    Ok(())
+}
+
+/* Charts a volatility cone against the ATM implied volatility term structure from a csv written by volatility::iv_rv_to_csv()
+Cone percentiles and the current realized volatility are drawn against their window, ATM IVs against their expiry, both in trading days
+*/
+pub fn generate_iv_rv_plot(iv_rv_csv_name: &str, img_dir: &str, backend: &dyn ChartBackend) -> Result<()> {
+    let (ticker, stem) = chart_stem(iv_rv_csv_name, "ivrv", img_dir)?;
+    let mut rdr = csv::Reader::from_path(iv_rv_csv_name).with_context(|| format!("\ngenerate_iv_rv_plot() :: ERROR -> Failed to open {}", iv_rv_csv_name))?;
+    let titles = [("Min", "RV Min", SeriesStyle::Dashed), ("P25", "RV 25th Percentile", SeriesStyle::Dashed), ("Median", "RV Median", SeriesStyle::Line), ("P75", "RV 75th Percentile", SeriesStyle::Dashed), ("Max", "RV Max", SeriesStyle::Dashed), ("Current", "Current RV", SeriesStyle::LinePoints)];
+    let mut cone: Vec<Series> = titles.iter().map(|(_, title, style)| Series { title: title.to_string(), points: Vec::new(), style: *style }).collect();
+    let mut atm = Series { title: "ATM IV".to_string(), points: Vec::new(), style: SeriesStyle::LinePoints };
+    for result in rdr.deserialize::<HashMap<String, String>>() {
+        let row = result?;
+        let value = |key: &str| row.get(key).and_then(|v| v.trim().parse::<f64>().ok()).unwrap_or(f64::NAN);
+        let days = value("Days");
+        if row.get("Source").map(|s| s.as_str()) == Some("AtmIV") {
+            atm.points.push((days, value("Current")));
+        } else {
+            for (series, (key, _, _)) in cone.iter_mut().zip(titles.iter()) {
+                series.points.push((days, value(key)));
+            }
+        }
+    }
+    cone.push(atm);
+    let chart = LineChart {
+        title: format!("{} Implied vs Realized Volatility", ticker),
+        x_label: "Trading Days".to_string(),
+        y_label: "Annualized Volatility".to_string(),
+        x_axis: XAxis::Number,
+        log_y: false,
+        series: cone,
     };
-    let call_png_name = format!("{}{}_c{}_{}_{}.png", IMGDIR, &chain.ticker, data_label, info_parts[2], info_parts[3].replace(".csv", ""));
-    let put_png_name = format!("{}{}_p{}_{}_{}.png", IMGDIR, &chain.ticker, data_label, info_parts[2], info_parts[3].replace(".csv", ""));
+    for path in backend.line_chart(&chart, &stem)? {
+        println!("\ngenerate_iv_rv_plot() :: Successfully generated {}", path);
+    }
+    Ok(())
+}
+
+/* Charts the SVI (and SSVI, when fitted) surfaces of a csv written by surface::surface_to_csv()
+<model>surface = fitted implied volatility over a strike x yte grid spanning SURFACE_MONEYNESS and the fitted expiries
+<model>smiles = each fitted expiry's implied volatility smile against log-moneyness ln(K / F)
+*/
+pub fn generate_vol_surface_plots(svi_csv_name: &str, img_dir: &str, backend: &dyn ChartBackend) -> Result<()> {
+    let surface = surface_from_csv(svi_csv_name).map_err(|e| anyhow::anyhow!("\ngenerate_vol_surface_plots() :: ERROR -> Failed to load {}: {}", svi_csv_name, e))?;
+    let models = if surface.ssvi.is_some() { vec![SurfaceModel::Svi, SurfaceModel::Ssvi] } else { vec![SurfaceModel::Svi] };
+    let (first, last) = (surface.slices[0].yte, surface.slices[surface.slices.len() - 1].yte);
+    let (strikes, ytes) = SURFACE_GRID;
+    let (low, high) = SURFACE_MONEYNESS;
+    for model in models {
+        let label = model.label().to_lowercase();
+        let (ticker, stem) = chart_stem(svi_csv_name, &format!("{}surface", label), img_dir)?;
+        let mut points = Vec::new();
+        for j in 0..ytes {
+            let yte = first + (last - first) * j as f64 / (ytes - 1) as f64;
+            for i in 0..strikes {
+                let strike = surface.spot * (low + (high - low) * i as f64 / (strikes - 1) as f64);
+                points.push((strike, yte, surface.iv(strike, yte, model)));
+            }
+        }
+        let chart = ScatterSurface {
+            title: format!("{} {} Implied Volatility Surface", ticker, model.label()),
+            x_label: "Contract Strike Price ($)".to_string(),
+            y_label: "Years To Expiration".to_string(),
+            z_label: "iv".to_string(),
+            points,
+        };
+        for path in backend.surface_chart(&chart, &stem)? {
+            println!("\ngenerate_vol_surface_plots() :: Successfully generated {}", path);
+        }
+        let (_, stem) = chart_stem(svi_csv_name, &format!("{}smiles", label), img_dir)?;
+        let series = surface
+            .slices
+            .iter()
+            .map(|slice| {
+                let points = (0..strikes)
+                    .map(|i| {
+                        let k = (low + (high - low) * i as f64 / (strikes - 1) as f64).ln();
+                        (k, surface.iv(slice.forward * k.exp(), slice.yte, model))
+                    })
+                    .collect();
+                Series { title: slice.date.clone(), points, style: SeriesStyle::Line }
+            })
+            .collect();
+        let chart = LineChart {
+            title: format!("{} {} Volatility Smiles", ticker, model.label()),
+            x_label: "Log-Moneyness ln(K / F)".to_string(),
+            y_label: "Implied Volatility".to_string(),
+            x_axis: XAxis::Number,
+            log_y: false,
+            series,
+        };
+        for path in backend.line_chart(&chart, &stem)? {
+            println!("\ngenerate_vol_surface_plots() :: Successfully generated {}", path);
+        }
+    }
+    Ok(())
+}
+
+/* Charts the risk-neutral densities of a csv written by density::densities_to_csv()
+rnd = every expiry's density on one chart; rnd<YYYYMMDD> = one chart per expiry
+*/
+pub fn generate_density_plots(rnd_csv_name: &str, img_dir: &str, backend: &dyn ChartBackend) -> Result<()> {
+    let curves = density_curves_from_csv(rnd_csv_name).map_err(|e| anyhow::anyhow!("\ngenerate_density_plots() :: ERROR -> Failed to load {}: {}", rnd_csv_name, e))?;
+    let (ticker, stem) = chart_stem(rnd_csv_name, DENSITY_PLOT_LABEL, img_dir)?;
+    let chart = |title: String, series: Vec<Series>| LineChart {
+        title,
+        x_label: "Underlying Price At Expiration ($)".to_string(),
+        y_label: "Risk-Neutral Probability Density".to_string(),
+        x_axis: XAxis::Number,
+        log_y: false,
+        series,
+    };
+    let all: Vec<Series> = curves.iter().map(|(date, points)| Series { title: date.clone(), points: points.clone(), style: SeriesStyle::Line }).collect();
+    let mut paths = backend.line_chart(&chart(format!("{} Risk-Neutral Densities", ticker), all), &stem)?;
+    for (i, (date, points)) in curves.into_iter().enumerate() {
+        let label = match NaiveDate::parse_from_str(&date, EXPDATEFMT) {
+            Ok(d) => format!("{}{}", DENSITY_PLOT_LABEL, d.format("%Y%m%d")),
+            Err(_) => format!("{}{}", DENSITY_PLOT_LABEL, i),
+        };
+        let (_, stem) = chart_stem(rnd_csv_name, &label, img_dir)?;
+        let series = vec![Series { title: "Breeden-Litzenberger Density".to_string(), points, style: SeriesStyle::Line }];
+        paths.extend(backend.line_chart(&chart(format!("{} Risk-Neutral Density Expiring {}", ticker, date), series), &stem)?);
+    }
+    for path in paths {
+        println!("\ngenerate_density_plots() :: Successfully generated {}", path);
+    }
+    Ok(())
+}
+
+/* Dealer exposure charts of exposure_to_csv() files:
+gex = call, put and net gamma exposure by strike of every expiry together with the spot, gamma flip and walls (gex<YYYYMMDD> per expiry)
+gexprofile = net gamma exposure repriced across spot, in total and per expiry; vex / cex = total vanna / charm exposure by strike
+*/
+pub fn generate_exposure_plots(strike_csv_name: &str, stats_csv_name: &str, grid_csv_name: &str, img_dir: &str, backend: &dyn ChartBackend) -> Result<()> {
+    let profiles = exposure_from_csv(strike_csv_name, stats_csv_name, grid_csv_name)
+        .map_err(|e| anyhow::anyhow!("\ngenerate_exposure_plots() :: ERROR -> Failed to load {}, {} and {}: {}", strike_csv_name, stats_csv_name, grid_csv_name, e))?;
+    let total = profiles.iter().find(|p| p.label == TOTAL_LABEL).with_context(|| format!("\ngenerate_exposure_plots() :: ERROR -> {} has no {} profile", stats_csv_name, TOTAL_LABEL))?;
+    // Vertical dashed marker at x spanning the finite y range of the series
+    let marker = |title: String, x: f64, series: &[Series]| {
+        let ys = series.iter().flat_map(|s| s.points.iter().map(|p| p.1)).filter(|y| y.is_finite());
+        let (low, high) = ys.fold((0.0f64, 0.0f64), |(low, high), y| (low.min(y), high.max(y)));
+        Series { title, points: vec![(x, low), (x, high)], style: SeriesStyle::Dashed }
+    };
+    let with_markers = |mut series: Vec<Series>, p: &ExposureProfile| {
+        let mut markers = vec![marker(format!("Spot {:.2}", p.spot), p.spot, &series)];
+        if p.gamma_flip.is_finite() {
+            markers.push(marker(format!("Gamma Flip {:.2}", p.gamma_flip), p.gamma_flip, &series));
+        }
+        series.extend(markers);
+        series
+    };
+    let chart = |title: String, x_label: &str, y_label: &str, series: Vec<Series>| LineChart {
+        title,
+        x_label: x_label.to_string(),
+        y_label: y_label.to_string(),
+        x_axis: XAxis::Number,
+        log_y: false,
+        series,
+    };
+    let by_strike = |title: &str, value: &dyn Fn(&StrikeExposure) -> f64, p: &ExposureProfile, style: SeriesStyle| Series {
+        title: title.to_string(),
+        points: p.strikes.iter().map(|s| (s.strike, value(s))).collect(),
+        style,
+    };
+    let mut paths = Vec::new();
+    for (i, p) in profiles.iter().enumerate() {
+        let (label, title) = if p.label == TOTAL_LABEL {
+            (EXPOSURE_PLOT_LABELS[0].to_string(), "Dealer Gamma Exposure, All Expiries".to_string())
+        } else {
+            let label = match NaiveDate::parse_from_str(&p.label, EXPDATEFMT) {
+                Ok(d) => format!("{}{}", EXPOSURE_PLOT_LABELS[0], d.format("%Y%m%d")),
+                Err(_) => format!("{}{}", EXPOSURE_PLOT_LABELS[0], i),
+            };
+            (label, format!("Dealer Gamma Exposure Expiring {}", p.label))
+        };
+        let series = vec![
+            by_strike(&format!("Call GEX (wall {:.2})", p.call_wall), &|s| s.call_gamma, p, SeriesStyle::LinePoints),
+            by_strike(&format!("Put GEX (wall {:.2})", p.put_wall), &|s| s.put_gamma, p, SeriesStyle::LinePoints),
+            by_strike("Net GEX", &|s| s.net_gamma(), p, SeriesStyle::Line),
+        ];
+        let (ticker, stem) = chart_stem(strike_csv_name, &label, img_dir)?;
+        paths.extend(backend.line_chart(&chart(format!("{} {}", ticker, title), "Strike ($)", "Gamma Exposure (OI x Gamma x 100 x Spot^2)", with_markers(series, p)), &stem)?);
+    }
+    let (ticker, stem) = chart_stem(strike_csv_name, EXPOSURE_PLOT_LABELS[1], img_dir)?;
+    let grids: Vec<Series> = profiles
+        .iter()
+        .map(|p| Series { title: p.label.clone(), points: p.grid.clone(), style: if p.label == TOTAL_LABEL { SeriesStyle::Line } else { SeriesStyle::Dashed } })
+        .collect();
+    paths.extend(backend.line_chart(&chart(format!("{} Net Gamma Exposure Across Spot", ticker), "Underlying Price ($)", "Net Gamma Exposure", with_markers(grids, total)), &stem)?);
+    let greek_chart = |label: &str, name: &str, value: &dyn Fn(&StrikeExposure) -> f64| -> Result<Vec<String>> {
+        let (_, stem) = chart_stem(strike_csv_name, label, img_dir)?;
+        let series = vec![by_strike(&format!("Net {} Exposure", name), value, total, SeriesStyle::LinePoints)];
+        backend.line_chart(&chart(format!("{} Dealer {} Exposure, All Expiries", ticker, name), "Strike ($)", &format!("{} Exposure (OI x {} x 100 x Spot)", name, name), with_markers(series, total)), &stem)
+    };
+    paths.extend(greek_chart(EXPOSURE_PLOT_LABELS[2], "Vanna", &|s| s.vanna)?);
+    paths.extend(greek_chart(EXPOSURE_PLOT_LABELS[3], "Charm", &|s| s.charm)?);
+    for path in paths {
+        println!("\ngenerate_exposure_plots() :: Successfully generated {}", path);
+    }
+    Ok(())
+}
+
+pub fn generate_surface_plot(chain_csv_name: &str, img_dir: &str, field: usize, ctx: &PricingContext, backend: &dyn ChartBackend) -> Result<()> {
+    let mut chain = chain_from_csv(chain_csv_name)
+        .map_err(|e| anyhow::anyhow!("\ngenerate_surface_plot() :: ERROR -> Failed to load option chain with chain_from_csv: {}", e))?;
+    chain.revalue(ctx.valuation, ctx.day_count);
+    let (model, basis) = (ctx.model.as_ref(), ctx.basis);
+    let mut cpoints: Vec<(f64, f64, f64)> = Vec::new();
+    let mut ppoints: Vec<(f64, f64, f64)> = Vec::new();
+    let data_label = SURFACE_PLOT_LABELS.get(field).copied().unwrap_or(SURFACE_PLOT_LABELS[0]);
+    let (_, call_stem) = chart_stem(chain_csv_name, &format!("c{}", data_label), img_dir)?;
+    let (_, put_stem) = chart_stem(chain_csv_name, &format!("p{}", data_label), img_dir)?;
     for expiry in &chain.expiries {
+        let rate = expiry.rate(&ctx.curve);
+        let (spot, div) = chain.pricing_inputs(expiry, ctx);
         for call in expiry.calls.iter() {
-            let civ = call.get_imp_vol(chain.current_price, chain.div_yield);
-            let cd1 = d_one(civ, chain.current_price, call.strike, call.yte, chain.div_yield);
-            let cd2 = cd1 - (civ * call.yte.sqrt());
+            if field >= 24 {
+                let cband = call.get_iv_band(model, spot, rate, div);
+                let cdata = match field {
+                    24 => cband.bid.iv,
+                    25 => cband.ask.iv,
+                    _ => cband.width(),
+                };
+                if !cdata.is_finite() {
+                    eprintln!("\ngenerate_surface_plot() :: Skipping {} call strike {}; bid iv solver returned {:?} and ask iv solver returned {:?}", expiry.date, call.strike, cband.bid.status, cband.ask.status);
+                    continue;
+                }
+                cpoints.push((call.strike, call.yte, cdata));
+                continue;
+            }
+            let civ_result = call.get_imp_vol(model, spot, rate, div, basis);
+            if field >= 8 && !civ_result.converged() {
+                eprintln!("\ngenerate_surface_plot() :: Skipping {} call strike {}; {} {} implied volatility solver returned {:?} after {} iterations", expiry.date, call.strike, basis.label(), model.name(), civ_result.status, civ_result.iterations);
+                continue;
+            }
+            let civ = civ_result.iv;
+            let cgreeks = call.greeks(spot, rate, div, civ);
             let cdata = match field {
                 0 => call.last,
                 1 => call.change,
@@ -135,48 +401,47 @@ pub fn generate_surface_plot(chain_csv_name: &str, field: usize) -> Result<()> {
                 6 => call.strike,
                 7 => call.yte,
                 8 => civ,
-                9 => call.get_delta(chain.div_yield, cd1),
-                10 => {
-                    let cdelta = call.get_delta(chain.div_yield, cd1);
-                    call.get_elasticity(chain.current_price, cdelta)
-                },
-                11 => call.get_vega(cd2),
-                12 => call.get_theta(civ, chain.current_price, chain.div_yield, cd1, cd2, FEDFUNDS),
-                13 => call.get_rho(cd2, FEDFUNDS),
-                14 => call.get_epsilon(chain.current_price, chain.div_yield, cd1),
-                15 => call.get_gamma(civ, chain.current_price, cd2, FEDFUNDS),
-                16 => {
-                    let cvega: f64 = call.get_vega(cd2);
-                    call.get_vanna(civ, cvega, chain.current_price, cd1)
-                },
-                17 => call.get_charm(civ, chain.div_yield, cd1, cd2, FEDFUNDS),
-                18 => {
-                    let cvega: f64 = call.get_vega(cd2);
-                    call.get_vomma(civ, cvega, cd1, cd2)
-                },
-                19 => call.get_veta(civ, chain.current_price, chain.div_yield, cd1, cd2, FEDFUNDS),
-                20 => {
-                    let cgamma: f64 = call.get_gamma(civ, chain.current_price, cd2, FEDFUNDS);
-                    call.get_speed(civ, cgamma, cd1, cd2)
-                },
-                21 => {
-                    let cgamma: f64 = call.get_gamma(civ, chain.current_price, cd2, FEDFUNDS); 
-                    call.get_zomma(civ, cgamma, cd1, cd2)
-                },
-                22 => call.get_color(civ, chain.current_price, chain.div_yield, cd1, cd2, FEDFUNDS),
-                23 => {
-                    let cvega: f64 = call.get_vega(cd2);
-                    call.get_ultima(civ, cvega, cd1, cd2)
-                },
+                9 => cgreeks.delta,
+                10 => cgreeks.elasticity,
+                11 => cgreeks.vega,
+                12 => cgreeks.theta,
+                13 => cgreeks.rho,
+                14 => cgreeks.epsilon,
+                15 => cgreeks.gamma,
+                16 => cgreeks.vanna,
+                17 => cgreeks.charm,
+                18 => cgreeks.vomma,
+                19 => cgreeks.veta,
+                20 => cgreeks.speed,
+                21 => cgreeks.zomma,
+                22 => cgreeks.color,
+                23 => cgreeks.ultima,
                 _ => call.last,
             };
-            writeln!(cwriter, "{} {} {}", call.strike, call.yte, cdata)?;
+            cpoints.push((call.strike, call.yte, cdata));
         }
-        writeln!(cwriter, "")?;
         for put in expiry.puts.iter() {
-            let piv = put.get_imp_vol(chain.current_price, chain.div_yield);
-            let pd1 = d_one(piv, chain.current_price, put.strike, put.yte, chain.div_yield);
-            let pd2 = pd1 - (piv * put.yte.sqrt());
+            if field >= 24 {
+                let pband = put.get_iv_band(model, spot, rate, div);
+                let pdata = match field {
+                    24 => pband.bid.iv,
+                    25 => pband.ask.iv,
+                    _ => pband.width(),
+                };
+                if !pdata.is_finite() {
+                    eprintln!("\ngenerate_surface_plot() :: Skipping {} put strike {}; bid iv solver returned {:?} and ask iv solver returned {:?}", expiry.date, put.strike, pband.bid.status, pband.ask.status);
+                    continue;
+                }
+                ppoints.push((put.strike, put.yte, pdata));
+                continue;
+            }
+            let piv_result = put.get_imp_vol(model, spot, rate, div, basis);
+            if field >= 8 && !piv_result.converged() {
+                eprintln!("\ngenerate_surface_plot() :: Skipping {} put strike {}; {} {} implied volatility solver returned {:?} after {} iterations", expiry.date, put.strike, basis.label(), model.name(), piv_result.status, piv_result.iterations);
+                continue;
+            }
+            let piv = piv_result.iv;
+            let pgreeks = put.greeks(spot, rate, div, piv);
             let pdata = match field {
                 0 => put.last,
                 1 => put.change,
@@ -187,159 +452,101 @@ pub fn generate_surface_plot(chain_csv_name: &str, field: usize) -> Result<()> {
                 6 => put.strike,
                 7 => put.yte,
                 8 => piv,
-                9 => put.get_delta(chain.div_yield, pd1),
-                10 => {
-                    let pdelta = put.get_delta(chain.div_yield, pd1);
-                    put.get_elasticity(chain.current_price, pdelta)
-                },
-                11 => put.get_vega(pd2),
-                12 => put.get_theta(piv, chain.current_price, chain.div_yield, pd1, pd2, FEDFUNDS),
-                13 => put.get_rho(pd2, FEDFUNDS),
-                14 => put.get_epsilon(chain.current_price, chain.div_yield, pd1),
-                15 => put.get_gamma(piv, chain.current_price, pd2, FEDFUNDS),
-                16 => {
-                    let pvega: f64 = put.get_vega(pd2);
-                    put.get_vanna(piv, pvega, chain.current_price, pd1)
-                },
-                17 => put.get_charm(piv, chain.div_yield, pd1, pd2, FEDFUNDS),
-                18 => {
-                    let pvega: f64 = put.get_vega(pd2);
-                    put.get_vomma(piv, pvega, pd1, pd2)
-                },
-                19 => put.get_veta(piv, chain.current_price, chain.div_yield, pd1, pd2, FEDFUNDS),
-                20 => {
-                    let pgamma: f64 = put.get_gamma(piv, chain.current_price, pd2, FEDFUNDS);
-                    put.get_speed(piv, pgamma, pd1, pd2)
-                },
-                21 => {
-                    let pgamma: f64 = put.get_gamma(piv, chain.current_price, pd2, FEDFUNDS); 
-                    put.get_zomma(piv, pgamma, pd1, pd2)
-                },
-                22 => put.get_color(piv, chain.current_price, chain.div_yield, pd1, pd2, FEDFUNDS),
-                23 => {
-                    let pvega: f64 = put.get_vega(pd2);
-                    put.get_ultima(piv, pvega, pd1, pd2)
-                },
+                9 => pgreeks.delta,
+                10 => pgreeks.elasticity,
+                11 => pgreeks.vega,
+                12 => pgreeks.theta,
+                13 => pgreeks.rho,
+                14 => pgreeks.epsilon,
+                15 => pgreeks.gamma,
+                16 => pgreeks.vanna,
+                17 => pgreeks.charm,
+                18 => pgreeks.vomma,
+                19 => pgreeks.veta,
+                20 => pgreeks.speed,
+                21 => pgreeks.zomma,
+                22 => pgreeks.color,
+                23 => pgreeks.ultima,
                 _ => put.last,
             };
-            writeln!(pwriter, "{} {} {}", put.strike, put.yte, pdata)?;
+            ppoints.push((put.strike, put.yte, pdata));
+        }
+    }
+    for (is_call, points, stem) in [(true, cpoints, call_stem), (false, ppoints, put_stem)] {
+        let side = if is_call { "Call" } else { "Put" };
+        let chart = ScatterSurface {
+            title: format!("{} {} Options {} Surface ({} prices, {} model)", &chain.ticker, side, data_label, basis.label(), model.name()),
+            x_label: "Contract Strike Price ($)".to_string(),
+            y_label: "Years To Expiration".to_string(),
+            z_label: data_label.to_string(),
+            points,
+        };
+        for path in backend.surface_chart(&chart, &stem)? {
+            println!("\ngenerate_surface_plot() :: Successfully generated {}", path);
         }
-        writeln!(pwriter, "")?;
     }
-    cwriter.flush()?;
-    pwriter.flush()?;
-    let gnuplot_cscript = format!(
-        r#"
-        set terminal png
-        set output '{}'
-        set xlabel "Contract Strike Price ($)"
-        set ylabel "Years To Expiration"
-        set zlabel "{}"
-        set title "{} Call Options {} Surface"
-        set view 60.0,0.0,1.0
-        set palette rgb 7,5,15
-        splot '{}' using 1:2:3 with points palette title "Calls"
-    "#, call_png_name, data_label, &chain.ticker, data_label, CDATNAME
-    );
-    let mut cmd_call = Command::new("gnuplot")
-        .stdin(Stdio::piped())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .spawn()
-        .context("\ngenerate_surface_plot() :: ERROR -> Failed to execute cmd_call gnuplot surface generation command")?;
-    let stdin = cmd_call.stdin.as_mut().context("\ngenerate_surface_plot() :: ERROR -> Failed to open stdin for gnuplot_cscript")?;
-    writeln!(stdin, "{}", gnuplot_cscript).context("\ngenerate_surface_plot() :: ERROR -> Failed to write gnuplot_cscript to stdin for call surface")?;
-    cmd_call.wait().context("\ngenerate_surface_plot() :: ERROR -> Failed to wait for gnuplot call surface generation process")?;    
-    println!("\ngenerate_surface_plot() :: Successfully generated {}", call_png_name);
-    let gnuplot_pscript = format!(
-        r#"
-        set terminal png
-        set output '{}'
-        set xlabel "Contract Strike Price ($)"
-        set ylabel "Years To Expiration"
-        set zlabel "{}"
-        set title "{} Put Options {} Surface"
-        set view 60.0,0.0,1.0
-        set palette rgb 7,5,15
-        splot '{}' using 1:2:3 with points palette title "Puts"
-    "#, put_png_name, data_label, &chain.ticker, data_label, PDATNAME
-    );
-    let mut cmd_put = Command::new("gnuplot")
-        .stdin(Stdio::piped())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .spawn()
-        .context("\ngenerate_surface_plot() :: ERROR -> Failed to execute cmd_put gnuplot surface generation command")?;
-    let stdin = cmd_put.stdin.as_mut().context("\ngenerate_surface_plot() :: ERROR -> Failed to open stdin for gnuplot_pscript")?;
-    writeln!(stdin, "{}", gnuplot_pscript).context("\ngenerate_surface_plot() :: ERROR -> Failed to write gnuplot_pscript to stdin for put surface")?;
-    cmd_put.wait().context("\ngenerate_surface_plot() :: ERROR -> Failed to wait for gnuplot put surface generation process")?;    
-    println!("\ngenerate_surface_plot() :: Successfully generated {}", put_png_name);
     Ok(())
 }
 
-/*pub fn plot_volatility_smiles(chain_csv_name: &str) -> Result<()> {
-    let chain = chain_from_csv(chain_csv_name)
+// Charts the out-of-the-money implied volatility smile (OptionExpiry::otm_smile()) of every unexpired expiry on one chart
+pub fn plot_volatility_smiles(chain_csv_name: &str, img_dir: &str, ctx: &PricingContext, backend: &dyn ChartBackend) -> Result<()> {
+    let mut chain = chain_from_csv(chain_csv_name)
         .map_err(|e| anyhow::anyhow!("\nplot_volatility_smiles() :: ERROR -> Failed to load option chain with chain_from_csv: {}", e))?;
-    let name_parts: Vec<&str> = chain_csv_name.split('/').collect();
-    let info_parts: Vec<&str> = name_parts[1].split('_').collect();
-    let cdatfile = File::create(CDATNAME).context("\nplot_volatility_smiles() :: ERROR -> Failed to create cdatfile")?;
-    let pdatfile = File::create(PDATNAME).context("\nplot_volatility_smiles() :: ERROR -> Failed to create pdatfile")?;
-    let mut cwriter = BufWriter::new(cdatfile);
-    let mut pwriter = BufWriter::new(pdatfile);
-    let exp_date = &chain.expiries[0].date;
-    for call in &chain.expiries[0].calls {
-        let civ = call.get_imp_vol(chain.current_price, chain.div_yield);
-        writeln!(cwriter, "{} {}", call.strike, civ)?;
+    chain.revalue(ctx.valuation, ctx.day_count);
+    let (_, stem) = chart_stem(chain_csv_name, "volsmiles", img_dir)?;
+    let series: Vec<Series> = chain
+        .expiries
+        .iter()
+        .filter(|expiry| expiry.yte > 0.0)
+        .map(|expiry| {
+            let (spot, div) = chain.pricing_inputs(expiry, ctx);
+            let points = expiry.otm_smile(spot, expiry.rate(&ctx.curve), div, ctx.model.as_ref(), ctx.basis);
+            Series { title: expiry.date.clone(), points, style: SeriesStyle::LinePoints }
+        })
+        .filter(|series| !series.points.is_empty())
+        .collect();
+    if series.is_empty() {
+        anyhow::bail!("\nplot_volatility_smiles() :: ERROR -> No {} expiry in {} has a converged out-of-the-money implied volatility", chain.ticker, chain_csv_name);
     }
-    cwriter.flush()?;
-    for put in &chain.expiries[0].puts {
-        let piv = put.get_imp_vol(chain.current_price, chain.div_yield);
-        writeln!(pwriter, "{} {}", put.strike, piv)?;
+    let chart = LineChart {
+        title: format!("{} Volatility Smiles ({} prices, {} model)", chain.ticker, ctx.basis.label(), ctx.model.name()),
+        x_label: "Contract Strike Price ($)".to_string(),
+        y_label: "Implied Volatility".to_string(),
+        x_axis: XAxis::Number,
+        log_y: false,
+        series,
+    };
+    for path in backend.line_chart(&chart, &stem)? {
+        println!("\nplot_volatility_smiles() :: Successfully generated {}", path);
     }
-    pwriter.flush()?;
-    let call_png_name = format!("{}{}_volsmile_{}_{}.png", IMGDIR, &chain.ticker, info_parts[2], info_parts[3].replace(".csv", ""));
-    let put_png_name = format!("{}{}_volsmile_{}_{}.png", IMGDIR, &chain.ticker, info_parts[2], info_parts[3].replace(".csv", ""));
-    let gnuplot_cscript = format!(
-        r#"
-        set terminal png
-        set output '{}'
-        set xlabel "Strike Price ($)"
-        set ylabel "Implied Volatility"
-        set title "{} Calls Volatility Smile (Expiring {})"
-        set grid
-        plot '{}' using 1:2 with lines title 'Implied Volatility'
-        "#, call_png_name, &chain.ticker, exp_date, CDATNAME
-    );
-    let mut cmd_call = Command::new("gnuplot")
-        .stdin(Stdio::piped())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .spawn()
-        .context("\nplot_volatility_smiles() :: ERROR -> Failed to execute gnuplot call volatility smile command")?;
-    let stdin = cmd_call.stdin.as_mut().context("\nplot_volatility_smiles() :: ERROR -> Failed to open stdin for gnuplot call volatility smile script")?;
-    writeln!(stdin, "{}", gnuplot_cscript).context("\nplot_volatility_smiles() :: ERROR -> Failed to write gnuplot call volatility smile script to stdin")?;
-    cmd_call.wait().context("\nplot_volatility_smiles() :: ERROR -> Failed to wait for gnuplot call volatility smile process")?;
-    println!("\nplot_volatility_simles() :: Successfully generated {}", call_png_name);
-    let gnuplot_pscript = format!(
-        r#"
-        set terminal png
-        set output '{}'
-        set xlabel "Strike Price ($)"
-        set ylabel "Implied Volatility"
-        set title "{} Puts Voliatility Smile (Expiring {})"
-        set grid
-        plot '{}' using 1:2 with lines title 'Implied Volatilty'
-        "#, put_png_name, &chain.ticker, exp_date, PDATNAME
-    );
-    let mut cmd_put = Command::new("gnuplot")
-        .stdin(Stdio::piped())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::inherit())
-        .spawn()
-        .context("\nplot_volatility_smiles() :: ERROR -> Failed to execute gnuplot put volatility smile command")?;
-    let stdin = cmd_put.stdin.as_mut().context("\nplot_volatility_smiles() :: ERROR -> Failed to open stdin for gnuplot put volatility smile script")?;
-    writeln!(stdin, "{}", gnuplot_pscript).context("\nplot_volatility_smiles() :: ERROR -> Failed to write gnuplot put volatility smile script to stdin")?;
-    cmd_call.wait().context("\nplot_volatility_smiles() :: ERROR -> Failed to wait for gnuplot put volatility smile process")?;
-    println!("\nplot_volatility_simles() :: Successfully generated {}", put_png_name);
     Ok(())
-}*/
\ No newline at end of file
+}
+
+// Charts the ATM and 25-delta implied volatility term structures of a csv written by options::smile_metrics_to_csv()
+pub fn generate_term_structure_plot(smile_csv_name: &str, img_dir: &str, backend: &dyn ChartBackend) -> Result<()> {
+    let (ticker, stem) = chart_stem(smile_csv_name, "termstructure", img_dir)?;
+    let mut rdr = csv::Reader::from_path(smile_csv_name).with_context(|| format!("\ngenerate_term_structure_plot() :: ERROR -> Failed to open {}", smile_csv_name))?;
+    let titles = [("AtmIV", "ATM IV", SeriesStyle::LinePoints), ("Call25dIV", "25 Delta Call IV", SeriesStyle::Dashed), ("Put25dIV", "25 Delta Put IV", SeriesStyle::Dashed)];
+    let mut series: Vec<Series> = titles.iter().map(|(_, title, style)| Series { title: title.to_string(), points: Vec::new(), style: *style }).collect();
+    let mut shape = String::new();
+    for result in rdr.deserialize::<HashMap<String, String>>() {
+        let row = result?;
+        let value = |key: &str| row.get(key).and_then(|v| v.trim().parse::<f64>().ok()).unwrap_or(f64::NAN);
+        for (line, (key, _, _)) in series.iter_mut().zip(titles.iter()) {
+            line.points.push((value("Yte"), value(key)));
+        }
+        shape = row.get("TermStructure").cloned().unwrap_or_default();
+    }
+    let chart = LineChart {
+        title: format!("{} Implied Volatility Term Structure ({})", ticker, shape),
+        x_label: "Years To Expiration".to_string(),
+        y_label: "Implied Volatility".to_string(),
+        x_axis: XAxis::Number,
+        log_y: false,
+        series,
+    };
+    for path in backend.line_chart(&chart, &stem)? {
+        println!("\ngenerate_term_structure_plot() :: Successfully generated {}", path);
+    }
+    Ok(())
+}
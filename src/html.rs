@@ -0,0 +1,528 @@
+use anyhow::{Context, Result};
+use super::graphing::{indicator_chart_lines, tseries_chart_lines, TSERIES_PLOT_COLUMNS};
+use super::indicators::DEFAULT_INDICATORS;
+use super::options::{Option, OptionChain, PricingContext};
+use super::report::read_fundamentals;
+use serde_json::{json, Value};
+use std::fs;
+
+pub const HTMLDIR: &str = "html_out/";
+
+// Per-contract columns of the chain table; any of them can also be drawn as a strike x expiry surface
+pub const CHAIN_COLUMNS: [&str; 24] = ["Strike", "Bid", "Ask", "Last", "Change", "Volume", "OpenInt", "YTE", "IV", "Delta", "Elasticity", "Gamma", "Vega", "Theta", "Rho", "Epsilon", "Vanna", "Charm", "Vomma", "Veta", "Speed", "Zomma", "Color", "Ultima"];
+
+// CHAIN_COLUMNS values of one contract; IV and the Greeks are NaN (null in the report) when the solver does not converge
+pub fn contract_row(opt: &Option, ctx: &PricingContext, spot: f64, rate: f64, div: f64) -> Vec<f64> {
+    let mut row = vec![opt.strike, opt.bid, opt.ask, opt.last, opt.change, opt.vol, opt.open_int, opt.yte];
+    let iv = opt.get_imp_vol(ctx.model.as_ref(), spot, rate, div, ctx.basis);
+    if iv.converged() {
+        let g = opt.greeks(spot, rate, div, iv.iv);
+        row.extend([iv.iv, g.delta, g.elasticity, g.gamma, g.vega, g.theta, g.rho, g.epsilon, g.vanna, g.charm, g.vomma, g.veta, g.speed, g.zomma, g.color, g.ultima]);
+    } else {
+        row.extend([f64::NAN; 16]);
+    }
+    row
+}
+
+// The chain's expiries with IV and Greeks of every contract solved under ctx, in CHAIN_COLUMNS order
+pub fn chain_json(chain: &OptionChain, ctx: &PricingContext) -> Value {
+    let expiries: Vec<Value> = chain
+        .expiries
+        .iter()
+        .map(|expiry| {
+            let rate = expiry.rate(&ctx.curve);
+            let (spot, div) = chain.pricing_inputs(expiry, ctx);
+            let calls: Vec<Vec<f64>> = expiry.calls.iter().map(|c| contract_row(c, ctx, spot, rate, div)).collect();
+            let puts: Vec<Vec<f64>> = expiry.puts.iter().map(|p| contract_row(p, ctx, spot, rate, div)).collect();
+            json!({ "date": expiry.date, "yte": expiry.yte, "calls": calls, "puts": puts })
+        })
+        .collect();
+    json!({
+        "columns": CHAIN_COLUMNS,
+        "spot": chain.current_price,
+        "basis": ctx.basis.label(),
+        "model": ctx.model.name(),
+        "expiries": expiries,
+    })
+}
+
+/* Every numeric column of a time series csv by header name with its dates, plus the chart presets drawn by graphing.rs
+Blank or non-numeric cells are written as null
+*/
+pub fn tseries_json(ts_csv_name: &str) -> Result<Value> {
+    let mut rdr = csv::Reader::from_path(ts_csv_name).with_context(|| format!("\ntseries_json() :: ERROR -> Failed to open {}", ts_csv_name))?;
+    let headers: Vec<String> = rdr.headers()?.iter().map(|h| h.to_string()).collect();
+    let mut dates = Vec::new();
+    let mut columns: Vec<Vec<f64>> = vec![Vec::new(); headers.len()];
+    for result in rdr.records() {
+        let record = result?;
+        dates.push(record.get(0).unwrap_or_default().trim().to_string());
+        for (column, cell) in columns.iter_mut().zip(record.iter()).skip(1) {
+            column.push(cell.trim().parse::<f64>().unwrap_or(f64::NAN));
+        }
+    }
+    let columns: serde_json::Map<String, Value> = headers.into_iter().zip(columns).skip(1).map(|(h, c)| (h, json!(c))).collect();
+    let mut presets = Vec::new();
+    for column in TSERIES_PLOT_COLUMNS {
+        let (lines, log) = tseries_chart_lines(column);
+        presets.push(json!({ "title": column, "lines": lines, "log": log }));
+    }
+    for indicator in DEFAULT_INDICATORS {
+        let (lines, log) = indicator_chart_lines(indicator)?;
+        presets.push(json!({ "title": indicator, "lines": lines, "log": log }));
+    }
+    presets.retain(|p| p["lines"].as_array().is_some_and(|lines| lines.iter().all(|l| columns.contains_key(l[0].as_str().unwrap_or_default()))));
+    Ok(json!({ "dates": dates, "columns": columns, "presets": presets }))
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/* Writes one self-contained HTML report (no external scripts, styles or images) of a ticker's run
+cover_lines = run details listed under the title; sections whose inputs cannot be read are left out with an error message
+The page holds a sortable, filterable fundamentals table, zoomable time series charts, rotatable 3D surfaces of any chain column
+and a sortable per-expiry chain table with IV and Greeks
+*/
+pub fn generate_html_report(ticker: &str, cover_lines: &[String], fv_csv_name: &str, ts_csv_name: &str, chain: std::option::Option<&OptionChain>, ctx: &PricingContext, html_name: &str) -> Result<()> {
+    let fundamentals = match read_fundamentals(fv_csv_name) {
+        Ok(rows) => json!(rows),
+        Err(e) => {
+            eprintln!("{}\n\ngenerate_html_report() :: Continuing without the fundamentals table", e);
+            Value::Null
+        },
+    };
+    let tseries = tseries_json(ts_csv_name).unwrap_or_else(|e| {
+        eprintln!("{}\n\ngenerate_html_report() :: Continuing without the time series charts", e);
+        Value::Null
+    });
+    let chain = chain.filter(|c| !c.expiries.is_empty()).map_or(Value::Null, |c| chain_json(c, ctx));
+    let data = json!({ "fundamentals": fundamentals, "tseries": tseries, "chain": chain });
+    // serde_json writes NaN as null; "</" is escaped so the data cannot close its script element
+    let data = serde_json::to_string(&data)?.replace("</", "<\\/");
+    let title = escape_html(&format!("{} Market Report", ticker));
+    let cover: String = cover_lines.iter().map(|l| format!("<p>{}</p>", escape_html(l))).collect();
+    let html = format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<header>\n<h1>{title}</h1>\n{cover}\n<nav><a href=\"#fundamentals\">Fundamentals</a><a href=\"#tseries\">Time Series</a><a href=\"#surfaces\">Option Surfaces</a><a href=\"#chain\">Option Chain</a></nav>\n</header>\n{BODY}\n<script>const DATA = {data};</script>\n<script>{SCRIPT}</script>\n</body>\n</html>\n"
+    );
+    fs::write(html_name, html).with_context(|| format!("\ngenerate_html_report() :: ERROR -> Failed to write {}", html_name))?;
+    Ok(())
+}
+
+const STYLE: &str = r#"
+body { font-family: sans-serif; margin: 0 2em 2em; color: #222; }
+header { border-bottom: 1px solid #ccc; margin-bottom: 1em; }
+header p { margin: 0.2em 0; color: #555; }
+nav a { margin-right: 1.5em; }
+nav { margin: 0.8em 0; }
+section { margin-bottom: 2.5em; }
+.controls { margin: 0.5em 0; display: flex; gap: 1em; align-items: center; flex-wrap: wrap; }
+.hint { color: #777; font-size: 0.85em; }
+.missing { color: #a00; }
+canvas { border: 1px solid #ddd; max-width: 100%; cursor: crosshair; }
+.scroll { max-height: 40em; overflow: auto; }
+table { border-collapse: collapse; font-size: 0.85em; }
+th, td { padding: 0.2em 0.6em; border-bottom: 1px solid #eee; text-align: right; white-space: nowrap; }
+th { background: #eee; cursor: pointer; position: sticky; top: 0; user-select: none; }
+td:first-child, th:first-child { text-align: left; }
+"#;
+
+const BODY: &str = r#"<section id="fundamentals">
+<h2>Fundamentals</h2>
+<div class="controls"><input id="fund-filter" type="search" placeholder="Filter labels"></div>
+<div class="scroll"><table id="fund-table"></table></div>
+</section>
+<section id="tseries">
+<h2>Time Series</h2>
+<div class="controls"><select id="ts-select"></select><label><input id="ts-log" type="checkbox"> Log scale</label><button id="ts-reset">Reset zoom</button></div>
+<canvas id="ts-canvas" width="1100" height="500"></canvas>
+<p class="hint">Scroll to zoom, drag to pan, double-click to reset.</p>
+</section>
+<section id="surfaces">
+<h2>Option Surfaces</h2>
+<div class="controls"><select id="sf-field"></select><select id="sf-side"><option>Calls</option><option>Puts</option></select><button id="sf-reset">Reset view</button></div>
+<canvas id="sf-canvas" width="900" height="650"></canvas>
+<p class="hint">Drag to rotate, scroll to zoom. Contracts whose implied volatility did not converge are left out.</p>
+</section>
+<section id="chain">
+<h2>Option Chain</h2>
+<div class="controls"><select id="ch-expiry"></select><select id="ch-side"><option>Calls and Puts</option><option>Calls</option><option>Puts</option></select></div>
+<div class="scroll"><table id="ch-table"></table></div>
+</section>"#;
+
+const SCRIPT: &str = r#"
+(function () {
+  'use strict';
+  const COLORS = ['#1f77b4', '#d62728', '#2ca02c', '#ff7f0e', '#9467bd', '#8c564b', '#e377c2', '#17becf'];
+  const $ = id => document.getElementById(id);
+  const finite = v => typeof v === 'number' && isFinite(v);
+
+  function fmt(v) {
+    if (!finite(v)) return '';
+    if (v !== 0 && (Math.abs(v) >= 1e6 || Math.abs(v) < 1e-3)) return v.toExponential(3);
+    return String(+v.toFixed(4));
+  }
+
+  // Numeric sort key of a cell: numbers as is, finviz style text such as "1.5B", "12.3%" or "1,024" parsed, otherwise null
+  function numeric(v) {
+    if (typeof v === 'number') return finite(v) ? v : null;
+    const m = String(v).trim().replace(/,/g, '').match(/^(-?\d+(?:\.\d+)?)\s*([KMBT%]?)$/i);
+    if (!m) return null;
+    return parseFloat(m[1]) * ({ k: 1e3, m: 1e6, b: 1e9, t: 1e12 }[m[2].toLowerCase()] || 1);
+  }
+
+  function compare(a, b) {
+    const na = numeric(a), nb = numeric(b);
+    if (na !== null && nb !== null) return na - nb;
+    if (na !== null) return -1;
+    if (nb !== null) return 1;
+    const sa = a === null ? '' : String(a), sb = b === null ? '' : String(b);
+    if (!sa || !sb) return sa ? -1 : sb ? 1 : 0;
+    return sa.localeCompare(sb);
+  }
+
+  // Table sorted by clicking a header (again to reverse); filter(row) hides rows
+  function sortableTable(table, headers, rows, filter) {
+    let sortCol = -1, asc = true;
+    function draw() {
+      table.innerHTML = '';
+      const head = table.createTHead().insertRow();
+      headers.forEach((h, i) => {
+        const th = document.createElement('th');
+        th.textContent = h + (i === sortCol ? (asc ? ' ▲' : ' ▼') : '');
+        th.onclick = () => {
+          asc = sortCol === i ? !asc : true;
+          sortCol = i;
+          rows.sort((a, b) => (asc ? 1 : -1) * compare(a[i], b[i]));
+          draw();
+        };
+        head.appendChild(th);
+      });
+      const body = table.createTBody();
+      rows.forEach(r => {
+        if (filter && !filter(r)) return;
+        const tr = body.insertRow();
+        r.forEach(c => { tr.insertCell().textContent = typeof c === 'number' || c === null ? fmt(c) : c; });
+      });
+    }
+    draw();
+    return draw;
+  }
+
+  function missing(section, text) {
+    const p = document.createElement('p');
+    p.className = 'missing';
+    p.textContent = text;
+    const s = $(section);
+    while (s.children.length > 1) s.removeChild(s.lastChild);
+    s.appendChild(p);
+  }
+
+  function niceTicks(lo, hi, n) {
+    const span = hi - lo;
+    if (!(span > 0)) return [lo];
+    const step0 = span / n, mag = Math.pow(10, Math.floor(Math.log10(step0)));
+    const step = [1, 2, 5, 10].map(m => m * mag).find(s => s >= step0);
+    const ticks = [];
+    for (let t = Math.ceil(lo / step) * step; t <= hi + 1e-9 * span; t += step) ticks.push(t);
+    return ticks;
+  }
+
+  // Zoomable and pannable dated line chart on a canvas
+  function lineChart(canvas) {
+    const ctx = canvas.getContext('2d');
+    const pad = { l: 80, r: 20, t: 20, b: 40 };
+    let series = [], log = false, full = null, view = null, drag = null;
+    const w = () => canvas.width - pad.l - pad.r, h = () => canvas.height - pad.t - pad.b;
+    const tx = x => pad.l + (x - view[0]) / (view[1] - view[0]) * w();
+
+    function yRange() {
+      let lo = Infinity, hi = -Infinity;
+      series.forEach(s => s.points.forEach(([x, y]) => {
+        if (x < view[0] || x > view[1]) return;
+        const v = log ? Math.log10(y) : y;
+        if (isFinite(v)) { lo = Math.min(lo, v); hi = Math.max(hi, v); }
+      }));
+      if (!isFinite(lo)) return [0, 1];
+      const p = hi > lo ? 0.05 * (hi - lo) : 0.5 * Math.max(Math.abs(lo), 1);
+      return [lo - p, hi + p];
+    }
+
+    function draw() {
+      ctx.clearRect(0, 0, canvas.width, canvas.height);
+      if (!view) return;
+      const [ylo, yhi] = yRange();
+      const ty = y => pad.t + (1 - ((log ? Math.log10(y) : y) - ylo) / (yhi - ylo)) * h();
+      ctx.font = '12px sans-serif';
+      ctx.strokeStyle = '#eee';
+      ctx.fillStyle = '#444';
+      ctx.textAlign = 'right';
+      niceTicks(ylo, yhi, 8).forEach(t => {
+        const y = pad.t + (1 - (t - ylo) / (yhi - ylo)) * h();
+        ctx.beginPath(); ctx.moveTo(pad.l, y); ctx.lineTo(pad.l + w(), y); ctx.stroke();
+        ctx.fillText(fmt(log ? Math.pow(10, t) : t), pad.l - 6, y + 4);
+      });
+      ctx.textAlign = 'center';
+      niceTicks(view[0], view[1], 8).forEach(t => {
+        const x = tx(t);
+        ctx.beginPath(); ctx.moveTo(x, pad.t); ctx.lineTo(x, pad.t + h()); ctx.stroke();
+        ctx.fillText(new Date(t).toISOString().slice(0, 10), x, pad.t + h() + 18);
+      });
+      ctx.save();
+      ctx.beginPath(); ctx.rect(pad.l, pad.t, w(), h()); ctx.clip();
+      series.forEach((s, i) => {
+        ctx.strokeStyle = COLORS[i % COLORS.length];
+        ctx.lineWidth = 1.5;
+        ctx.beginPath();
+        let pen = false;
+        s.points.forEach(([x, y]) => {
+          const v = ty(y);
+          if (!isFinite(v)) { pen = false; return; }
+          if (pen) ctx.lineTo(tx(x), v); else ctx.moveTo(tx(x), v);
+          pen = true;
+        });
+        ctx.stroke();
+      });
+      ctx.restore();
+      ctx.strokeStyle = '#999';
+      ctx.strokeRect(pad.l, pad.t, w(), h());
+      ctx.textAlign = 'left';
+      series.forEach((s, i) => {
+        ctx.fillStyle = COLORS[i % COLORS.length];
+        ctx.fillRect(pad.l + 10, pad.t + 10 + 16 * i, 14, 3);
+        ctx.fillStyle = '#222';
+        ctx.fillText(s.title, pad.l + 30, pad.t + 15 + 16 * i);
+      });
+    }
+
+    function clampView(lo, hi) {
+      const span = Math.min(hi - lo, full[1] - full[0]);
+      lo = Math.max(full[0], Math.min(lo, full[1] - span));
+      view = [lo, lo + span];
+    }
+
+    canvas.addEventListener('wheel', e => {
+      if (!view) return;
+      e.preventDefault();
+      const x = view[0] + (e.offsetX * canvas.width / canvas.clientWidth - pad.l) / w() * (view[1] - view[0]);
+      const k = e.deltaY < 0 ? 0.8 : 1.25;
+      clampView(x - (x - view[0]) * k, x + (view[1] - x) * k);
+      draw();
+    }, { passive: false });
+    canvas.addEventListener('mousedown', e => { drag = { x: e.offsetX, view: view && view.slice() }; });
+    window.addEventListener('mouseup', () => { drag = null; });
+    canvas.addEventListener('mousemove', e => {
+      if (!drag || !drag.view) return;
+      const dx = (e.offsetX - drag.x) * canvas.width / canvas.clientWidth / w() * (drag.view[1] - drag.view[0]);
+      clampView(drag.view[0] - dx, drag.view[1] - dx);
+      draw();
+    });
+    canvas.addEventListener('dblclick', () => { if (full) { view = full.slice(); draw(); } });
+
+    return {
+      set(newSeries, newLog) {
+        series = newSeries;
+        log = newLog;
+        const xs = series.flatMap(s => s.points.map(p => p[0]));
+        full = xs.length ? [Math.min(...xs), Math.max(...xs)] : null;
+        if (full && full[1] === full[0]) full[1] += 864e5;
+        view = full && full.slice();
+        draw();
+      },
+      setLog(newLog) { log = newLog; draw(); },
+      reset() { if (full) { view = full.slice(); draw(); } },
+    };
+  }
+
+  // gnuplot's default "rgbformulae 7,5,15" palette, as used by the PNG surfaces
+  function palette(t) {
+    const c = v => Math.round(255 * Math.min(1, Math.max(0, v)));
+    return `rgb(${c(Math.sqrt(t))},${c(t * t * t)},${c(Math.sin(2 * Math.PI * t))})`;
+  }
+
+  // Rotatable 3D scatter of (x, y, z) points coloured by z
+  function surfaceChart(canvas) {
+    const ctx = canvas.getContext('2d');
+    let pts = [], labels = ['', '', ''], ranges = null, az = -0.8, el = 0.5, zoom = 1, drag = null;
+    const norm = (v, [lo, hi]) => hi > lo ? 2 * (v - lo) / (hi - lo) - 1 : 0;
+
+    function project([x, y, z]) {
+      const ca = Math.cos(az), sa = Math.sin(az), ce = Math.cos(el), se = Math.sin(el);
+      const X = x * ca - y * sa, D = x * sa + y * ca;
+      const s = 0.3 * Math.min(canvas.width, canvas.height) * zoom;
+      return { x: canvas.width / 2 + s * X, y: canvas.height / 2 - s * (z * ce - D * se), depth: z * se + D * ce };
+    }
+
+    function draw() {
+      ctx.clearRect(0, 0, canvas.width, canvas.height);
+      ctx.font = '12px sans-serif';
+      if (!ranges) { ctx.fillText('No points to plot', 20, 30); return; }
+      const corners = [];
+      for (let i = 0; i < 8; i++) corners.push([i & 1 ? 1 : -1, i & 2 ? 1 : -1, i & 4 ? 1 : -1]);
+      ctx.strokeStyle = '#ccc';
+      for (let i = 0; i < 8; i++) for (let b = 1; b < 8; b <<= 1) {
+        if (i & b) continue;
+        const p = project(corners[i]), q = project(corners[i | b]);
+        ctx.beginPath(); ctx.moveTo(p.x, p.y); ctx.lineTo(q.x, q.y); ctx.stroke();
+      }
+      ctx.fillStyle = '#222';
+      [[0, [1.25, -1, -1]], [1, [-1, 1.25, -1]], [2, [-1, -1, 1.2]]].forEach(([axis, at]) => {
+        const p = project(at), [lo, hi] = ranges[axis];
+        ctx.fillText(`${labels[axis]} (${fmt(lo)} to ${fmt(hi)})`, p.x, p.y);
+      });
+      const projected = pts.map(p => ({ ...project([norm(p[0], ranges[0]), norm(p[1], ranges[1]), norm(p[2], ranges[2])]), t: (norm(p[2], ranges[2]) + 1) / 2 }));
+      projected.sort((a, b) => a.depth - b.depth);
+      projected.forEach(p => {
+        ctx.fillStyle = palette(p.t);
+        ctx.beginPath(); ctx.arc(p.x, p.y, 3, 0, 2 * Math.PI); ctx.fill();
+      });
+      for (let i = 0; i <= 50; i++) {
+        ctx.fillStyle = palette(1 - i / 50);
+        ctx.fillRect(canvas.width - 40, 40 + 5 * i, 15, 5);
+      }
+      ctx.fillStyle = '#222';
+      ctx.textAlign = 'right';
+      ctx.fillText(fmt(ranges[2][1]), canvas.width - 45, 48);
+      ctx.fillText(fmt(ranges[2][0]), canvas.width - 45, 295);
+      ctx.textAlign = 'left';
+    }
+
+    canvas.addEventListener('mousedown', e => { drag = { x: e.offsetX, y: e.offsetY, az, el }; });
+    window.addEventListener('mouseup', () => { drag = null; });
+    canvas.addEventListener('mousemove', e => {
+      if (!drag) return;
+      az = drag.az + (e.offsetX - drag.x) * 0.01;
+      el = Math.max(-1.5, Math.min(1.5, drag.el + (e.offsetY - drag.y) * 0.01));
+      draw();
+    });
+    canvas.addEventListener('wheel', e => {
+      e.preventDefault();
+      zoom = Math.max(0.3, Math.min(5, zoom * (e.deltaY < 0 ? 1.1 : 0.9)));
+      draw();
+    }, { passive: false });
+
+    return {
+      set(points, axisLabels) {
+        pts = points.filter(p => p.every(finite));
+        labels = axisLabels;
+        ranges = pts.length ? [0, 1, 2].map(i => [Math.min(...pts.map(p => p[i])), Math.max(...pts.map(p => p[i]))]) : null;
+        draw();
+      },
+      reset() { az = -0.8; el = 0.5; zoom = 1; draw(); },
+    };
+  }
+
+  function fundamentals() {
+    if (!DATA.fundamentals) return missing('fundamentals', 'Fundamentals are not available for this run.');
+    const rows = DATA.fundamentals.map(r => r.slice());
+    const filter = $('fund-filter');
+    const draw = sortableTable($('fund-table'), ['Label', 'Value'], rows, r => r[0].toLowerCase().includes(filter.value.toLowerCase()));
+    filter.addEventListener('input', draw);
+  }
+
+  function tseries() {
+    const ts = DATA.tseries;
+    if (!ts || !ts.presets.length) return missing('tseries', 'Time series data is not available for this run.');
+    const xs = ts.dates.map(d => Date.parse(d));
+    const chart = lineChart($('ts-canvas'));
+    const select = $('ts-select'), log = $('ts-log');
+    ts.presets.forEach((p, i) => select.add(new Option(p.title, i)));
+    const show = () => {
+      const preset = ts.presets[select.value];
+      log.checked = preset.log;
+      chart.set(preset.lines.map(([column, title]) => ({ title, points: xs.map((x, i) => [x, ts.columns[column][i]]) })), preset.log);
+    };
+    select.addEventListener('change', show);
+    log.addEventListener('change', () => chart.setLog(log.checked));
+    $('ts-reset').addEventListener('click', () => chart.reset());
+    show();
+  }
+
+  function chain() {
+    const ch = DATA.chain;
+    if (!ch) {
+      missing('surfaces', 'Option chain data is not available for this run.');
+      return missing('chain', 'Option chain data is not available for this run.');
+    }
+    const col = name => ch.columns.indexOf(name);
+    const field = $('sf-field'), side = $('sf-side');
+    ch.columns.forEach(c => { if (c !== 'Strike' && c !== 'YTE') field.add(new Option(c, c)); });
+    field.value = 'IV';
+    const chart = surfaceChart($('sf-canvas'));
+    const showSurface = () => {
+      const key = side.value === 'Calls' ? 'calls' : 'puts', z = col(field.value);
+      const points = ch.expiries.flatMap(e => e[key].map(r => [r[col('Strike')], r[col('YTE')], r[z]]));
+      chart.set(points, ['Strike', 'Years To Expiration', `${side.value} ${field.value}`]);
+    };
+    field.addEventListener('change', showSurface);
+    side.addEventListener('change', showSurface);
+    $('sf-reset').addEventListener('click', () => chart.reset());
+    showSurface();
+
+    const expiry = $('ch-expiry'), chSide = $('ch-side');
+    ch.expiries.forEach((e, i) => expiry.add(new Option(`${e.date} (${fmt(e.yte)} years)`, i)));
+    const showTable = () => {
+      const e = ch.expiries[expiry.value];
+      const rows = [];
+      if (chSide.value !== 'Puts') e.calls.forEach(r => rows.push(['Call', ...r]));
+      if (chSide.value !== 'Calls') e.puts.forEach(r => rows.push(['Put', ...r]));
+      rows.sort((a, b) => a[1] - b[1]);
+      sortableTable($('ch-table'), ['Side', ...ch.columns], rows);
+    };
+    expiry.addEventListener('change', showTable);
+    chSide.addEventListener('change', showTable);
+    showTable();
+  }
+
+  fundamentals();
+  tseries();
+  chain();
+})();
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::test_fixtures::{bs_context, bs_expiry, test_chain};
+    use crate::rates::FEDFUNDS;
+    use std::env;
+
+    fn chain() -> (OptionChain, PricingContext) {
+        let mut expiry = bs_expiry("2024-12-20", 0.5, FEDFUNDS, 0.0, &[90.0, 100.0], 0.05, |_| 0.3);
+        expiry.calls.push(Option { strike: 110.0, yte: 0.5, is_call: true, ..Option::default() });
+        (test_chain(vec![expiry], 100.0, FEDFUNDS), bs_context(FEDFUNDS))
+    }
+
+    #[test]
+    fn chain_json_solves_iv_and_nulls_unconverged_contracts() {
+        let (chain, ctx) = chain();
+        let value = chain_json(&chain, &ctx);
+        let calls = value["expiries"][0]["calls"].as_array().unwrap();
+        let iv = CHAIN_COLUMNS.iter().position(|c| *c == "IV").unwrap();
+        assert!((calls[1][iv].as_f64().unwrap() - 0.3).abs() < 1e-3);
+        assert!(calls[2][iv].is_null());
+        assert_eq!(calls[0].as_array().unwrap().len(), CHAIN_COLUMNS.len());
+    }
+
+    #[test]
+    fn report_is_self_contained_and_embeds_parseable_data() {
+        let dir = env::temp_dir().join(format!("markets_html_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let fv_csv = dir.join("TEST_fv_2024-06-03_10-00-00.csv");
+        fs::write(&fv_csv, "Label,TEST\nP/E,31.2\nNote,</script><b>\n").unwrap();
+        let (chain, ctx) = chain();
+        let html_name = dir.join("TEST_2024-06-03_10-00-00.html");
+        let missing_ts = dir.join("missing.csv");
+        generate_html_report("TEST", &["Generated <now>".to_string()], fv_csv.to_str().unwrap(), missing_ts.to_str().unwrap(), Some(&chain), &ctx, html_name.to_str().unwrap()).unwrap();
+        let html = fs::read_to_string(&html_name).unwrap();
+        assert!(!html.contains("src=\"http") && !html.contains("href=\"http"));
+        assert!(html.contains("Generated &lt;now&gt;"));
+        let start = html.find("const DATA = ").unwrap() + "const DATA = ".len();
+        let end = start + html[start..].find(";</script>").unwrap();
+        let data: Value = serde_json::from_str(&html[start..end].replace("<\\/", "</")).unwrap();
+        assert_eq!(data["fundamentals"][0], json!(["Note", "</script><b>"]));
+        assert!(data["tseries"].is_null());
+        assert_eq!(data["chain"]["expiries"][0]["puts"].as_array().unwrap().len(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
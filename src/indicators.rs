@@ -0,0 +1,565 @@
+use super::avantage::{Bar, TimeSeries};
+use std::collections::VecDeque;
+
+/* Technical indicators over OHLCV bars
+Every indicator is streaming: update() consumes the next bar and returns one value per column (NaN until enough
+bars have been seen), so the same state machine serves both whole-series csv columns and incremental updates.
+Indicators are looked up by name with indicator_from_name() ("SMA20", "MACD12_26_9", "BB20_2.5", ...)
+*/
+pub trait Indicator {
+    fn name(&self) -> String;
+    fn columns(&self) -> Vec<String>;
+    fn update(&mut self, bar: &Bar) -> Vec<f64>;
+    // Whether the indicator is on the price scale and should be drawn over the close
+    fn overlay(&self) -> bool {
+        false
+    }
+}
+
+// Indicators written to every time series csv and plotted by main.rs
+pub const DEFAULT_INDICATORS: [&str; 13] = ["SMA20", "EMA20", "WMA20", "HMA20", "RSI14", "MACD12_26_9", "BB20", "KC20", "STOCH14", "ADX14", "OBV", "VWAP20", "DC20"];
+
+// Fixed-length window of the latest values
+#[derive(Debug, Clone)]
+pub struct Window {
+    len: usize,
+    values: VecDeque<f64>,
+}
+
+impl Window {
+    fn new(len: usize) -> Self {
+        Window { len: len.max(1), values: VecDeque::with_capacity(len.max(1) + 1) }
+    }
+    fn push(&mut self, x: f64) {
+        self.values.push_back(x);
+        if self.values.len() > self.len {
+            self.values.pop_front();
+        }
+    }
+    fn full(&self) -> bool {
+        self.values.len() == self.len
+    }
+    fn max(&self) -> f64 {
+        self.values.iter().copied().fold(f64::NEG_INFINITY, f64::max)
+    }
+    fn min(&self) -> f64 {
+        self.values.iter().copied().fold(f64::INFINITY, f64::min)
+    }
+}
+
+/* Streaming smoothers of a scalar series, the building blocks of the bar indicators
+Sma = simple moving average
+Ema = exponential moving average (alpha = 2 / (n + 1)) seeded with the first n-value SMA
+Wma = linearly weighted moving average (weights 1..n, newest heaviest)
+Hma = Hull moving average WMA(sqrt n) of 2 * WMA(n / 2) - WMA(n)
+Wilder = Wilder's smoothing (alpha = 1 / n) seeded with the first n-value SMA
+NaN inputs are ignored, so smoothers can be chained behind ones that are still warming up
+*/
+#[derive(Debug, Clone)]
+pub enum Smoother {
+    Sma(Window),
+    Ema { n: usize, alpha: f64, seed: Window, value: f64 },
+    Wma(Window),
+    Hma { half: Box<Smoother>, full: Box<Smoother>, hull: Box<Smoother> },
+}
+
+impl Smoother {
+    pub fn sma(n: usize) -> Self {
+        Smoother::Sma(Window::new(n))
+    }
+    pub fn ema(n: usize) -> Self {
+        Smoother::Ema { n: n.max(1), alpha: 2.0 / (n.max(1) as f64 + 1.0), seed: Window::new(n), value: f64::NAN }
+    }
+    pub fn wilder(n: usize) -> Self {
+        Smoother::Ema { n: n.max(1), alpha: 1.0 / n.max(1) as f64, seed: Window::new(n), value: f64::NAN }
+    }
+    pub fn wma(n: usize) -> Self {
+        Smoother::Wma(Window::new(n))
+    }
+    pub fn hma(n: usize) -> Self {
+        Smoother::Hma {
+            half: Box::new(Smoother::wma((n / 2).max(1))),
+            full: Box::new(Smoother::wma(n)),
+            hull: Box::new(Smoother::wma(((n as f64).sqrt().round() as usize).max(1))),
+        }
+    }
+    pub fn update(&mut self, x: f64) -> f64 {
+        if x.is_nan() {
+            return f64::NAN;
+        }
+        match self {
+            Smoother::Sma(w) => {
+                w.push(x);
+                if w.full() {
+                    w.values.iter().sum::<f64>() / w.len as f64
+                } else {
+                    f64::NAN
+                }
+            },
+            Smoother::Ema { n, alpha, seed, value } => {
+                if value.is_nan() {
+                    seed.push(x);
+                    if seed.values.len() == *n {
+                        *value = seed.values.iter().sum::<f64>() / *n as f64;
+                    }
+                } else {
+                    *value += *alpha * (x - *value);
+                }
+                *value
+            },
+            Smoother::Wma(w) => {
+                w.push(x);
+                if !w.full() {
+                    return f64::NAN;
+                }
+                let denom = (w.len * (w.len + 1)) as f64 / 2.0;
+                w.values.iter().enumerate().map(|(i, v)| (i + 1) as f64 * v).sum::<f64>() / denom
+            },
+            Smoother::Hma { half, full, hull } => {
+                let (h, f) = (half.update(x), full.update(x));
+                hull.update(2.0 * h - f)
+            },
+        }
+    }
+}
+
+// Simple, exponential, weighted or Hull moving average of the close
+pub struct MovingAverage {
+    label: &'static str,
+    n: usize,
+    smoother: Smoother,
+}
+
+impl Indicator for MovingAverage {
+    fn name(&self) -> String {
+        format!("{}{}", self.label, self.n)
+    }
+    fn columns(&self) -> Vec<String> {
+        vec![self.name()]
+    }
+    fn update(&mut self, bar: &Bar) -> Vec<f64> {
+        vec![self.smoother.update(bar.close)]
+    }
+    fn overlay(&self) -> bool {
+        true
+    }
+}
+
+// Wilder relative strength index of the close
+pub struct Rsi {
+    n: usize,
+    prev_close: f64,
+    gains: Smoother,
+    losses: Smoother,
+}
+
+impl Indicator for Rsi {
+    fn name(&self) -> String {
+        format!("RSI{}", self.n)
+    }
+    fn columns(&self) -> Vec<String> {
+        vec![self.name()]
+    }
+    fn update(&mut self, bar: &Bar) -> Vec<f64> {
+        let change = bar.close - self.prev_close;
+        self.prev_close = bar.close;
+        let (gain, loss) = (self.gains.update(change.max(0.0)), self.losses.update((-change).max(0.0)));
+        let rsi = if loss == 0.0 { 100.0 } else { 100.0 - 100.0 / (1.0 + gain / loss) };
+        vec![rsi]
+    }
+}
+
+// MACD line (fast EMA - slow EMA), its signal EMA and the histogram
+pub struct Macd {
+    params: (usize, usize, usize),
+    fast: Smoother,
+    slow: Smoother,
+    signal: Smoother,
+}
+
+impl Indicator for Macd {
+    fn name(&self) -> String {
+        format!("MACD{}_{}_{}", self.params.0, self.params.1, self.params.2)
+    }
+    fn columns(&self) -> Vec<String> {
+        let name = self.name();
+        vec![name.clone(), format!("{}Signal", name), format!("{}Hist", name)]
+    }
+    fn update(&mut self, bar: &Bar) -> Vec<f64> {
+        let macd = self.fast.update(bar.close) - self.slow.update(bar.close);
+        let signal = self.signal.update(macd);
+        vec![macd, signal, macd - signal]
+    }
+}
+
+// Bollinger Bands: n-bar SMA of the close +/- k population standard deviations
+pub struct Bollinger {
+    n: usize,
+    k: f64,
+    window: Window,
+}
+
+impl Indicator for Bollinger {
+    fn name(&self) -> String {
+        if self.k == 2.0 {
+            format!("BB{}", self.n)
+        } else {
+            format!("BB{}_{}", self.n, self.k)
+        }
+    }
+    fn columns(&self) -> Vec<String> {
+        let name = self.name();
+        vec![format!("{}Mid", name), format!("{}Upper", name), format!("{}Lower", name)]
+    }
+    fn update(&mut self, bar: &Bar) -> Vec<f64> {
+        self.window.push(bar.close);
+        if !self.window.full() {
+            return vec![f64::NAN; 3];
+        }
+        let n = self.n as f64;
+        let mid = self.window.values.iter().sum::<f64>() / n;
+        let sd = (self.window.values.iter().map(|x| (x - mid).powi(2)).sum::<f64>() / n).sqrt();
+        vec![mid, mid + self.k * sd, mid - self.k * sd]
+    }
+    fn overlay(&self) -> bool {
+        true
+    }
+}
+
+// Streaming Wilder average true range
+struct Atr {
+    prev_close: f64,
+    smoother: Smoother,
+}
+
+impl Atr {
+    fn new(n: usize) -> Self {
+        Atr { prev_close: f64::NAN, smoother: Smoother::wilder(n) }
+    }
+    fn true_range(&mut self, bar: &Bar) -> f64 {
+        let range = bar.high - bar.low;
+        let tr = if self.prev_close.is_nan() {
+            range
+        } else {
+            range.max((bar.high - self.prev_close).abs()).max((bar.low - self.prev_close).abs())
+        };
+        self.prev_close = bar.close;
+        tr
+    }
+    fn update(&mut self, bar: &Bar) -> f64 {
+        let tr = self.true_range(bar);
+        self.smoother.update(tr)
+    }
+}
+
+// Keltner Channels: n-bar EMA of the close +/- k n-bar Wilder ATRs
+pub struct Keltner {
+    n: usize,
+    k: f64,
+    ema: Smoother,
+    atr: Atr,
+}
+
+impl Indicator for Keltner {
+    fn name(&self) -> String {
+        if self.k == 2.0 {
+            format!("KC{}", self.n)
+        } else {
+            format!("KC{}_{}", self.n, self.k)
+        }
+    }
+    fn columns(&self) -> Vec<String> {
+        let name = self.name();
+        vec![format!("{}Mid", name), format!("{}Upper", name), format!("{}Lower", name)]
+    }
+    fn update(&mut self, bar: &Bar) -> Vec<f64> {
+        let (mid, atr) = (self.ema.update(bar.close), self.atr.update(bar));
+        vec![mid, mid + self.k * atr, mid - self.k * atr]
+    }
+    fn overlay(&self) -> bool {
+        true
+    }
+}
+
+// Stochastic oscillator: %K = position of the close in the n-bar high-low range, %D = d-bar SMA of %K
+pub struct Stochastic {
+    n: usize,
+    highs: Window,
+    lows: Window,
+    d: Smoother,
+}
+
+impl Indicator for Stochastic {
+    fn name(&self) -> String {
+        format!("STOCH{}", self.n)
+    }
+    fn columns(&self) -> Vec<String> {
+        let name = self.name();
+        vec![format!("{}K", name), format!("{}D", name)]
+    }
+    fn update(&mut self, bar: &Bar) -> Vec<f64> {
+        self.highs.push(bar.high);
+        self.lows.push(bar.low);
+        if !self.highs.full() {
+            return vec![f64::NAN; 2];
+        }
+        let (hh, ll) = (self.highs.max(), self.lows.min());
+        let k = if hh > ll { 100.0 * (bar.close - ll) / (hh - ll) } else { 50.0 };
+        vec![k, self.d.update(k)]
+    }
+}
+
+// Wilder average directional index with the +DI / -DI lines
+pub struct Adx {
+    n: usize,
+    prev: Option<Bar>,
+    atr: Atr,
+    plus_dm: Smoother,
+    minus_dm: Smoother,
+    adx: Smoother,
+}
+
+impl Indicator for Adx {
+    fn name(&self) -> String {
+        format!("ADX{}", self.n)
+    }
+    fn columns(&self) -> Vec<String> {
+        let name = self.name();
+        vec![name.clone(), format!("{}PlusDI", name), format!("{}MinusDI", name)]
+    }
+    fn update(&mut self, bar: &Bar) -> Vec<f64> {
+        let prev = match self.prev.replace(bar.clone()) {
+            Some(p) => p,
+            None => {
+                self.atr.true_range(bar);
+                return vec![f64::NAN; 3];
+            },
+        };
+        let (up, down) = (bar.high - prev.high, prev.low - bar.low);
+        let plus = if up > down && up > 0.0 { up } else { 0.0 };
+        let minus = if down > up && down > 0.0 { down } else { 0.0 };
+        let atr = self.atr.update(bar);
+        let (plus_di, minus_di) = (100.0 * self.plus_dm.update(plus) / atr, 100.0 * self.minus_dm.update(minus) / atr);
+        let di_sum = plus_di + minus_di;
+        let dx = if di_sum > 0.0 { 100.0 * (plus_di - minus_di).abs() / di_sum } else if di_sum == 0.0 { 0.0 } else { f64::NAN };
+        vec![self.adx.update(dx), plus_di, minus_di]
+    }
+}
+
+// On-balance volume: cumulative volume signed by the direction of the close
+pub struct Obv {
+    prev_close: f64,
+    total: f64,
+}
+
+impl Indicator for Obv {
+    fn name(&self) -> String {
+        "OBV".to_string()
+    }
+    fn columns(&self) -> Vec<String> {
+        vec![self.name()]
+    }
+    fn update(&mut self, bar: &Bar) -> Vec<f64> {
+        if bar.close > self.prev_close {
+            self.total += bar.volume;
+        } else if bar.close < self.prev_close {
+            self.total -= bar.volume;
+        }
+        self.prev_close = bar.close;
+        vec![self.total]
+    }
+}
+
+// Rolling n-bar volume weighted average of the typical price (high + low + close) / 3
+pub struct Vwap {
+    n: usize,
+    pv: Window,
+    volume: Window,
+}
+
+impl Indicator for Vwap {
+    fn name(&self) -> String {
+        format!("VWAP{}", self.n)
+    }
+    fn columns(&self) -> Vec<String> {
+        vec![self.name()]
+    }
+    fn update(&mut self, bar: &Bar) -> Vec<f64> {
+        self.pv.push((bar.high + bar.low + bar.close) / 3.0 * bar.volume);
+        self.volume.push(bar.volume);
+        let volume: f64 = self.volume.values.iter().sum();
+        if !self.pv.full() || volume <= 0.0 {
+            return vec![f64::NAN];
+        }
+        vec![self.pv.values.iter().sum::<f64>() / volume]
+    }
+    fn overlay(&self) -> bool {
+        true
+    }
+}
+
+// Donchian Channels: n-bar highest high, lowest low and their midpoint
+pub struct Donchian {
+    n: usize,
+    highs: Window,
+    lows: Window,
+}
+
+impl Indicator for Donchian {
+    fn name(&self) -> String {
+        format!("DC{}", self.n)
+    }
+    fn columns(&self) -> Vec<String> {
+        let name = self.name();
+        vec![format!("{}Upper", name), format!("{}Lower", name), format!("{}Mid", name)]
+    }
+    fn update(&mut self, bar: &Bar) -> Vec<f64> {
+        self.highs.push(bar.high);
+        self.lows.push(bar.low);
+        if !self.highs.full() {
+            return vec![f64::NAN; 3];
+        }
+        let (upper, lower) = (self.highs.max(), self.lows.min());
+        vec![upper, lower, (upper + lower) / 2.0]
+    }
+    fn overlay(&self) -> bool {
+        true
+    }
+}
+
+/* Builds an indicator from its name: a type prefix followed by '_'-separated parameters
+SMA<n>, EMA<n>, WMA<n>, HMA<n>, RSI<n>, MACD<fast>_<slow>_<signal>, BB<n>[_<k>], KC<n>[_<k>], STOCH<n>[_<d>],
+ADX<n>, OBV, VWAP<n>, DC<n> (case-insensitive; omitted parameters take the usual defaults)
+*/
+pub fn indicator_from_name(name: &str) -> Result<Box<dyn Indicator>, String> {
+    let upper = name.trim().to_uppercase();
+    let split = upper.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(upper.len());
+    let (kind, rest) = upper.split_at(split);
+    let params: Vec<f64> = if rest.is_empty() {
+        Vec::new()
+    } else {
+        rest.split('_')
+            .map(|p| p.parse::<f64>().map_err(|_| format!("\nindicator_from_name() :: ERROR -> Invalid parameter '{}' in indicator '{}'", p, name)))
+            .collect::<Result<_, _>>()?
+    };
+    if params.iter().any(|p| !(p.is_finite() && *p > 0.0)) {
+        return Err(format!("\nindicator_from_name() :: ERROR -> Indicator parameters must be positive in '{}'", name));
+    }
+    let n = |i: usize, default: usize| params.get(i).map_or(default, |p| *p as usize).max(1);
+    let x = |i: usize, default: f64| params.get(i).copied().unwrap_or(default);
+    let indicator: Box<dyn Indicator> = match kind {
+        "SMA" => Box::new(MovingAverage { label: "SMA", n: n(0, 20), smoother: Smoother::sma(n(0, 20)) }),
+        "EMA" => Box::new(MovingAverage { label: "EMA", n: n(0, 20), smoother: Smoother::ema(n(0, 20)) }),
+        "WMA" => Box::new(MovingAverage { label: "WMA", n: n(0, 20), smoother: Smoother::wma(n(0, 20)) }),
+        "HMA" => Box::new(MovingAverage { label: "HMA", n: n(0, 20), smoother: Smoother::hma(n(0, 20)) }),
+        "RSI" => Box::new(Rsi { n: n(0, 14), prev_close: f64::NAN, gains: Smoother::wilder(n(0, 14)), losses: Smoother::wilder(n(0, 14)) }),
+        "MACD" => {
+            let (fast, slow, signal) = (n(0, 12), n(1, 26), n(2, 9));
+            Box::new(Macd { params: (fast, slow, signal), fast: Smoother::ema(fast), slow: Smoother::ema(slow), signal: Smoother::ema(signal) })
+        },
+        "BB" => Box::new(Bollinger { n: n(0, 20), k: x(1, 2.0), window: Window::new(n(0, 20)) }),
+        "KC" => Box::new(Keltner { n: n(0, 20), k: x(1, 2.0), ema: Smoother::ema(n(0, 20)), atr: Atr::new(n(0, 20)) }),
+        "STOCH" => Box::new(Stochastic { n: n(0, 14), highs: Window::new(n(0, 14)), lows: Window::new(n(0, 14)), d: Smoother::sma(n(1, 3)) }),
+        "ADX" => Box::new(Adx { n: n(0, 14), prev: None, atr: Atr::new(n(0, 14)), plus_dm: Smoother::wilder(n(0, 14)), minus_dm: Smoother::wilder(n(0, 14)), adx: Smoother::wilder(n(0, 14)) }),
+        "OBV" => Box::new(Obv { prev_close: f64::NAN, total: 0.0 }),
+        "VWAP" => Box::new(Vwap { n: n(0, 20), pv: Window::new(n(0, 20)), volume: Window::new(n(0, 20)) }),
+        "DC" => Box::new(Donchian { n: n(0, 20), highs: Window::new(n(0, 20)), lows: Window::new(n(0, 20)) }),
+        _ => return Err(format!("\nindicator_from_name() :: ERROR -> Unknown indicator '{}'; expected one of SMA, EMA, WMA, HMA, RSI, MACD, BB, KC, STOCH, ADX, OBV, VWAP, DC followed by its parameters", name)),
+    };
+    Ok(indicator)
+}
+
+// Runs indicators over a whole series, returning (column name, values aligned with the bars) for every column
+pub fn compute_columns(ts: &TimeSeries, indicators: &mut [Box<dyn Indicator>]) -> Vec<(String, Vec<f64>)> {
+    let mut columns: Vec<(String, Vec<f64>)> = indicators
+        .iter()
+        .flat_map(|ind| ind.columns())
+        .map(|c| (c, Vec::with_capacity(ts.len())))
+        .collect();
+    for bar in &ts.bars {
+        let values = indicators.iter_mut().flat_map(|ind| ind.update(bar));
+        for (column, value) in columns.iter_mut().zip(values) {
+            column.1.push(value);
+        }
+    }
+    columns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, NaiveDate};
+
+    fn series(closes: &[f64]) -> TimeSeries {
+        let start = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        let bars = closes
+            .iter()
+            .enumerate()
+            .map(|(i, c)| Bar::new(start + Duration::days(i as i64), *c, c + 1.0, c - 1.0, *c, 100.0 * (i + 1) as f64))
+            .collect();
+        TimeSeries::new("TEST", bars)
+    }
+
+    fn column(ts: &TimeSeries, name: &str) -> Vec<Vec<f64>> {
+        let mut indicators = vec![indicator_from_name(name).unwrap()];
+        let columns = compute_columns(ts, &mut indicators);
+        columns.into_iter().map(|(_, v)| v).collect()
+    }
+
+    #[test]
+    fn moving_averages_match_hand_calculations() {
+        let ts = series(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let sma = &column(&ts, "sma3")[0];
+        assert!(sma[1].is_nan());
+        assert_eq!(&sma[2..], &[2.0, 3.0, 4.0, 5.0]);
+        let wma = &column(&ts, "wma3")[0];
+        assert_eq!(wma[2], (1.0 + 4.0 + 9.0) / 6.0);
+        // EMA seeded with SMA(3) = 2 then alpha = 0.5
+        let ema = &column(&ts, "ema3")[0];
+        assert_eq!(&ema[2..], &[2.0, 3.0, 4.0, 5.0]);
+        // The Hull average of a straight line tracks it with no lag once warm
+        let hma = &column(&series(&(0..30).map(|i| i as f64).collect::<Vec<_>>()), "hma9")[0];
+        assert!((hma[29] - 29.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn oscillators_saturate_on_monotone_series() {
+        let rising = series(&(0..40).map(|i| 100.0 + i as f64).collect::<Vec<_>>());
+        assert_eq!(column(&rising, "rsi14")[0][39], 100.0);
+        let stoch = column(&rising, "stoch14");
+        // Close is 1 below the high of the latest bar in a 15-point range
+        assert!((stoch[0][39] - 100.0 * 14.0 / 15.0).abs() < 1e-12);
+        let adx = column(&rising, "adx14");
+        assert!(adx[0][39] > 99.0 && adx[2][39] == 0.0);
+        let macd = column(&rising, "macd12_26_9");
+        assert!((macd[0][39] - 7.0).abs() < 1e-9 && macd[2][39].abs() < 1e-9);
+    }
+
+    #[test]
+    fn bands_and_volume_indicators() {
+        let ts = series(&[10.0, 11.0, 10.0, 11.0]);
+        let bb = column(&ts, "bb4_1");
+        assert_eq!((bb[0][3], bb[1][3], bb[2][3]), (10.5, 11.0, 10.0));
+        let dc = column(&ts, "dc2");
+        assert_eq!((dc[0][3], dc[1][3], dc[2][3]), (12.0, 9.0, 10.5));
+        assert_eq!(column(&ts, "obv")[0], vec![0.0, 200.0, -100.0, 300.0]);
+        let vwap = column(&ts, "vwap2")[0][3];
+        assert!((vwap - (10.0 * 300.0 + 11.0 * 400.0) / 700.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn registry_names_are_canonical_and_unique() {
+        let mut seen = Vec::new();
+        for name in DEFAULT_INDICATORS {
+            let ind = indicator_from_name(&name.to_lowercase()).unwrap();
+            assert_eq!(ind.name(), name);
+            seen.extend(ind.columns());
+        }
+        let count = seen.len();
+        seen.sort();
+        seen.dedup();
+        assert_eq!(seen.len(), count);
+        assert!(indicator_from_name("FOO10").is_err());
+        assert!(indicator_from_name("SMA0").is_err());
+    }
+}
@@ -1,81 +1,674 @@
-use chrono::Local;
+use chrono::{Local, NaiveDate, Utc};
+use clap::Parser;
+mod cli;
+use cli::{parse_run, Cli, Command, CommonArgs, IvArgs, ParityArgs, ProbArgs, Side, Stage, EXIT_USAGE};
 mod finviz;
 use finviz::{fetch_finviz_info};
+mod adjust;
+use adjust::CorporateActions;
 mod avantage;
-use avantage::{get_underlying_av};
+use avantage::{daily_returns, get_underlying_av, tseries_from_csv};
+mod indicators;
+use indicators::DEFAULT_INDICATORS;
+mod volatility;
+use volatility::{iv_rv_to_csv, vol_cone, RvEstimator, CONE_WINDOWS};
+mod garch;
+use garch::{fit_garch, garch_to_csv, GarchKind, Innovation};
 mod finmath;
+mod calendar;
+use calendar::market_date;
+mod rates;
+use rates::RateCurve;
+mod dividends;
+use dividends::DividendSchedule;
+mod pricing;
+use pricing::model_from_str;
 mod options;
-use options::{fetch_option_chain, chain_from_csv/*, get_atm_straddle, get_atm_credit_spread, get_atm_debit_spread*/};
+mod parity;
+use parity::{chain_parity, parity_to_csv};
+mod surface;
+mod density;
+mod exposure;
+use exposure::{exposure_profiles, exposure_to_csv};
+use density::{densities_to_csv, surface_densities, RiskNeutralDensity, DENSITY_QUANTILES};
+use surface::{fit_surface, surface_to_csv, SurfaceModel};
+use options::{chain_from_csv, chain_to_csv, migrate_chain_dir, exercise_boundaries_to_csv, smile_metrics_to_csv, OptionChain, PricingContext, EXPDATEFMT/*, get_atm_straddle, get_atm_credit_spread, get_atm_debit_spread*/};
+mod sources;
+use sources::source_from_config;
+mod charts;
+use charts::{backend_from_env, ChartBackend};
 mod graphing;
-use graphing::{generate_tseries_plot, generate_surface_plot/*, plot_volatility_smiles*/};
+use graphing::{generate_tseries_plot, generate_density_plots, generate_exposure_plots, generate_indicator_plot, generate_iv_rv_plot, generate_surface_plot, generate_term_structure_plot, generate_vol_surface_plots, plot_volatility_smiles, IMGDIR, SURFACE_PLOT_LABELS, TSERIES_PLOT_COLUMNS};
+mod report;
+use report::{generate_pdf_report, report_sections, PDFDIR};
+mod html;
+use html::{contract_row, generate_html_report, CHAIN_COLUMNS, HTMLDIR};
 mod utils;
-use utils::{clear_directory_or_create, create_directory_if_dne};
-use std::process::{Command, exit};
-use std::env;
-use std::str;
+use utils::{create_directory_if_dne, remove_run_charts};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::exit;
+use std::thread;
 
 const CSVDIR: &str = "csv_out/";
+const DIVHORIZON: f64 = 3.0;
+const GREEKS_COLUMNS: [&str; 11] = ["Strike", "Bid", "Ask", "IV", "Delta", "Gamma", "Vega", "Theta", "Rho", "Vanna", "Charm"];
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("\nmain() :: ERROR -> Please enter only one financial ticker/symbol (4 alphabetical characters) as a command line input argument after 'cargo run'\n\tExample: 'cargo run AAPL'\n");
-        exit(1);
+// csv_out, img_out, pdf_out and html_out under --out-dir, each with a trailing separator
+struct OutputDirs {
+    csv: String,
+    img: String,
+    pdf: String,
+    html: String,
+}
+
+impl OutputDirs {
+    fn new(out_dir: &Path) -> Self {
+        let dir = |name: &str| format!("{}/", out_dir.join(name.trim_end_matches('/')).to_string_lossy());
+        OutputDirs { csv: dir(CSVDIR), img: dir(IMGDIR), pdf: dir(PDFDIR), html: dir(HTMLDIR) }
+    }
+
+    fn create(&self) -> io::Result<()> {
+        for dir in [&self.csv, &self.img, &self.pdf, &self.html] {
+            create_directory_if_dne(dir)?;
+        }
+        Ok(())
+    }
+
+    // <csv dir><TICKER>_<kind>_<stamp>.csv
+    fn csv_name(&self, ticker: &str, kind: &str, stamp: &str) -> String {
+        format!("{}{}_{}_{}.csv", self.csv, ticker, kind, stamp)
+    }
+
+    // The <kind> csv of the run stamp when one is given, otherwise the ticker's latest <kind> csv
+    fn input_csv(&self, ticker: &str, kind: &str, run: Option<&str>) -> Result<String, String> {
+        if let Some(stamp) = run {
+            let csv_name = self.csv_name(ticker, kind, stamp);
+            return if Path::new(&csv_name).exists() {
+                Ok(csv_name)
+            } else {
+                Err(format!("\ninput_csv() :: ERROR -> {} does not exist", csv_name))
+            };
+        }
+        let prefix = format!("{}_{}_", ticker, kind);
+        let entries = fs::read_dir(&self.csv).map_err(|e| format!("\ninput_csv() :: ERROR -> Could not read {}: {}", self.csv, e))?;
+        entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter_map(|name| name.strip_prefix(&prefix)?.strip_suffix(".csv").map(|stamp| stamp.to_string()))
+            .filter(|stamp| parse_run(stamp).is_ok())
+            .max()
+            .map(|stamp| self.csv_name(ticker, kind, &stamp))
+            .ok_or_else(|| format!("\ninput_csv() :: ERROR -> No {} {} csv in {}; fetch or analyze {} first", ticker, kind, self.csv, ticker))
+    }
+}
+
+// The <date>_<time> run stamp of a csv named <TICKER>_<kind>_<date>_<time>.csv
+fn csv_stamp(csv_name: &str) -> String {
+    let stem = Path::new(csv_name).file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    stem.splitn(3, '_').nth(2).unwrap_or_default().to_string()
+}
+
+// Failed stages in the order they failed; the first one decides the exit code
+#[derive(Default)]
+struct Failures(Vec<(String, Stage)>);
+
+impl Failures {
+    fn record(&mut self, ticker: &str, stage: Stage, result: Result<(), String>) -> bool {
+        match result {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("{}\n\nmain() :: {} {} failed", e, ticker, stage.label());
+                self.0.push((ticker.to_string(), stage));
+                false
+            },
+        }
     }
-    let ticker = &args[1];
-    if ticker.chars().all(|c| c.is_alphabetic()) && ticker.len() <= 4 {
-        let _ = create_directory_if_dne("csv_out");
-        let _ = create_directory_if_dne("pdf_out");
-        let _ = create_directory_if_dne("img_out");
-        let _ = create_directory_if_dne("dat_out");
-        let _ = create_directory_if_dne("html_out");
-        let uticker = ticker.to_uppercase();
-        let now = Local::now();
-        let datetime_str = now.format("%Y-%m-%d_%H-%M-%S").to_string();
-        let fv_csv = format!("{}{}_fv_{}.csv", CSVDIR, uticker, datetime_str);
-        let av_csv = format!("{}{}_av_{}.csv", CSVDIR, uticker, datetime_str);
-        let oc_csv = format!("{}{}_oc_{}.csv", CSVDIR, uticker, datetime_str);
-        let _ = fetch_finviz_info(&uticker, &fv_csv);
-        let _ = get_underlying_av(&uticker, &av_csv);
-        let _ = fetch_option_chain(&uticker, &oc_csv);
-        for series_field in 0..11 {
-            let _ = generate_tseries_plot(&av_csv, series_field);
-        }
-        for plot_field in 0..24 {
-            let _ = generate_surface_plot(&oc_csv, plot_field);
-        }
-        let chain = chain_from_csv(&oc_csv).expect("\nmain() :: Failed to load option chain from csv file");
-        let contract_volume = chain.total_contract_volume();
-        let open_interest = chain.total_open_interest();
-        print!("\nmain() :: {} Total Option Contract Volume = {} and Total Open Interest = {}", uticker, contract_volume, open_interest);
-        /*let _ = plot_volatility_smiles(&oc_csv);
-        let (straddle_price, atm_call, atm_put) = get_atm_straddle(&oc_csv);
-        println!("\nmain() :: {} ATM Straddle: ${} cost (implying underlying moves {}% by nearest expiry date)\nATM Call: {:?}\nATM Put: {:?}\n", uticker, straddle_price, straddle_price*0.85, atm_call, atm_put);
-        let (ccredit, itm_call, otm_call) = get_atm_credit_spread(&oc_csv, true);
-        println!("\nmain() :: Call Credit Spread:\nCredit: ${}\nITM Call: {:?}\nOTM Call: {:?}\n", ccredit, itm_call, otm_call);
-        let (pcredit, itm_put, otm_put) = get_atm_credit_spread(&oc_csv, false);
-        println!("\nmain() :: Put Credit Spread:\nCredit: ${}\nITM Put: {:?}\nOTM Put: {:?}\n", pcredit, itm_put, otm_put);
-        let (cdebit, otm_call, itm_call) = get_atm_debit_spread(&oc_csv, true);
-        println!("\nmain() :: Call Debit Spread:\nDebit: ${}\nOTM Call: {:?}\nITM Call: {:?}\n", cdebit, otm_call, itm_call);
-        let (pdebit, otm_put, itm_put) = get_atm_debit_spread(&oc_csv, false);
-        println!("\nmain() :: Put Debit Spread:\nDebit: ${}\nOTM Put: {:?}\nITM Put: {:?}\n", pdebit, otm_put, itm_put);*/
-        let pdf_cmd = Command::new("cmd")
-            .args(["/C", "python", "scripts/main.py", &uticker, &datetime_str])
-            .output()
-            .expect("\nmain() :: ERROR -> Failed to execute pdf_cmd");
-        let stdout = str::from_utf8(&pdf_cmd.stdout).expect("\nmain() :: ERROR -> Invalif UTF-8 sequence in string");
-        let stderr = str::from_utf8(&pdf_cmd.stderr).expect("\nmain() :: ERROR -> Invalif UTF-8 sequence in string");
-        if pdf_cmd.status.success() {
-            println!("\nmain() :: Successfully executed pdf_cmd / called scripts/main.py to generate PDF:\n\n{}\n", stdout);
-            let _ = clear_directory_or_create("img_out");
-            let _ = clear_directory_or_create("dat_out");
-            let _ = clear_directory_or_create("html_out");
-        } else {
-            eprintln!("\nmain() :: ERROR -> PDF Generation failed with status: {:?}\n\n{}\n", pdf_cmd.status, stderr);
-            exit(1);
+
+    fn exit_code(&self) -> i32 {
+        for (ticker, stage) in &self.0 {
+            eprintln!("\nmain() :: FAILED -> {} {} (exit code {})", ticker, stage.label(), stage.exit_code());
         }
+        self.0.first().map_or(0, |(_, stage)| stage.exit_code())
+    }
+}
+
+/* Pricing context of a stored chain: --valuation and --rate when given, otherwise the chain's QUOTE_TIME and flat RATE
+metadata (now and the default FEDFUNDS curve without a chain)
+*/
+fn pricing_context(common: &CommonArgs, dirs: &OutputDirs, ticker: &str, chain: Option<&OptionChain>, fv_csv: Option<&str>) -> PricingContext {
+    let valuation = common.valuation.or(chain.map(|c| c.quote_time)).unwrap_or_else(Utc::now);
+    let model = model_from_str(&common.model).expect("pricing_context() :: --model is validated when the arguments are parsed");
+    let div_csv = format!("{}{}_divs.csv", dirs.csv, ticker);
+    let dividends = if Path::new(&div_csv).exists() {
+        DividendSchedule::from_csv(&div_csv)
+    } else if let Some(fv_csv) = fv_csv {
+        DividendSchedule::from_finviz_csv(fv_csv, market_date(valuation), DIVHORIZON)
     } else {
-        eprintln!("\nmain() :: ERROR -> Please enter a financial ticker/symbol that is at most 4 alphabetical characters; you entered '{}'", ticker);
+        Ok(DividendSchedule::default())
+    };
+    let dividends = match dividends {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("\nmain() :: Could not load or project a dividend schedule for {}; pricing with the continuous dividend yield instead: {}", ticker, e);
+            DividendSchedule::default()
+        }
+    };
+    let curve = match (&common.rate, chain) {
+        (Some(curve), _) => curve.clone(),
+        (None, Some(chain)) if chain.rate.is_finite() => RateCurve::Flat(chain.rate),
+        _ => RateCurve::default(),
+    };
+    PricingContext { model, basis: common.basis, curve, dividends, valuation, day_count: common.day_count, forward: common.forward }
+}
+
+/* A stored chain with its pricing_context() and every yte recomputed at the valuation instant
+Fails when every expiry has expired at that instant
+*/
+fn load_chain(common: &CommonArgs, dirs: &OutputDirs, ticker: &str, oc_csv: &str, fv_csv: Option<&str>) -> Result<(OptionChain, PricingContext), String> {
+    let mut chain = chain_from_csv(oc_csv).map_err(|e| format!("\nload_chain() :: ERROR -> Failed to load option chain from {}: {}", oc_csv, e))?;
+    let ctx = pricing_context(common, dirs, ticker, Some(&chain), fv_csv);
+    chain.revalue(ctx.valuation, ctx.day_count);
+    if !chain.expiries.iter().any(|e| e.yte > 0.0) {
+        return Err(format!("\nload_chain() :: ERROR -> Every expiry in {} has expired at the valuation instant {}", oc_csv, ctx.valuation.to_rfc3339()));
+    }
+    Ok((chain, ctx))
+}
+
+fn fetch_timeseries(common: &CommonArgs, dirs: &OutputDirs, ticker: &str, av_csv: &str) -> Result<(), String> {
+    let actions_csv = format!("{}{}_actions.csv", dirs.csv, ticker);
+    let actions = if Path::new(&actions_csv).exists() {
+        CorporateActions::from_csv(&actions_csv).unwrap_or_else(|e| {
+            eprintln!("\nmain() :: Could not load corporate actions from {}; using only those reported by Alpha Vantage: {}", actions_csv, e);
+            CorporateActions::default()
+        })
+    } else {
+        CorporateActions::default()
+    };
+    get_underlying_av(ticker, common.av_series, common.av_full, &actions, av_csv).map_err(|e| e.to_string())
+}
+
+fn fetch_chain(common: &CommonArgs, dirs: &OutputDirs, ticker: &str, oc_csv: &str) -> Result<(), String> {
+    match migrate_chain_dir(&dirs.csv) {
+        Ok(0) => {},
+        Ok(n) => println!("\nmain() :: Migrated {} version 1 option chain csv file(s) in {}", n, dirs.csv),
+        Err(e) => eprintln!("\nmain() :: ERROR -> Could not scan {} for option chain csv files to migrate: {}", dirs.csv, e),
+    }
+    let source = source_from_config(&common.source).map_err(|e| e.to_string())?;
+    let mut chain = source.fetch(ticker).map_err(|e| format!("\nmain() :: ERROR -> Could not fetch {} option chain from the {} source: {}", ticker, source.name(), e))?;
+    let curve = common.rate.clone().unwrap_or_default();
+    if !curve.is_flat() {
+        eprintln!("\nmain() :: {} chain metadata stores only the {} short-end rate of the --rate curve; pass the same --rate to later stages to price with the full curve", ticker, curve.rate(0.0));
+    }
+    chain.rate = curve.rate(0.0);
+    chain_to_csv(&chain, oc_csv).map_err(|e| format!("\nmain() :: ERROR -> Could not save option chain csv {}: {}", oc_csv, e))?;
+    println!("\nmain() :: Saved {} option chain from the {} source as {}", ticker, source.name(), oc_csv);
+    Ok(())
+}
+
+fn fetch(common: &CommonArgs, dirs: &OutputDirs, ticker: &str, stamp: &str, selected: (bool, bool, bool), failures: &mut Failures) {
+    let (fundamentals, timeseries, chain) = selected;
+    if fundamentals {
+        let fv_csv = dirs.csv_name(ticker, "fv", stamp);
+        failures.record(ticker, Stage::Fundamentals, fetch_finviz_info(ticker, &fv_csv).map_err(|e| e.to_string()));
+    }
+    if timeseries {
+        failures.record(ticker, Stage::TimeSeries, fetch_timeseries(common, dirs, ticker, &dirs.csv_name(ticker, "av", stamp)));
+    }
+    if chain {
+        failures.record(ticker, Stage::Chain, fetch_chain(common, dirs, ticker, &dirs.csv_name(ticker, "oc", stamp)));
+    }
+}
+
+/* Exercise boundaries, put-call parity forwards, dealer exposure, smile metrics, the ATM term structure and the SVI/SSVI
+volatility surface of the chain with its risk-neutral densities, and GARCH forecasts and implied vs realized volatility of the time series; outputs are stamped with the newer of the two
+inputs' run stamps
+*/
+fn analyze(common: &CommonArgs, dirs: &OutputDirs, ticker: &str, run: Option<&str>) -> Result<(), String> {
+    let av_csv = dirs.input_csv(ticker, "av", run)?;
+    let oc_csv = dirs.input_csv(ticker, "oc", run)?;
+    let stamp = csv_stamp(&av_csv).max(csv_stamp(&oc_csv));
+    let fv_csv = dirs.input_csv(ticker, "fv", run).ok();
+    let (chain, ctx) = load_chain(common, dirs, ticker, &oc_csv, fv_csv.as_deref())?;
+    let mut errors = Vec::new();
+    let eb_csv = dirs.csv_name(ticker, "eb", &stamp);
+    if let Err(e) = exercise_boundaries_to_csv(&chain, &ctx, &eb_csv) {
+        errors.push(format!("\nmain() :: ERROR -> Could not save exercise boundaries csv {}: {}", eb_csv, e));
+    }
+    let parity = chain_parity(&chain, &ctx);
+    for fit in &parity {
+        println!("\nmain() :: {} {} put-call parity forward = {:.2} (spot {:.2}), implied rate = {:.2}%, implied borrow = {:.2}%, {} of {} strikes outside the bid-ask spread", ticker, fit.date, fit.forward, fit.spot, 100.0 * fit.implied_rate, 100.0 * fit.borrow, fit.violations().count(), fit.points.len());
+    }
+    let (parity_csv, parity_dev_csv) = (dirs.csv_name(ticker, "parity", &stamp), dirs.csv_name(ticker, "paritydev", &stamp));
+    if let Err(e) = parity_to_csv(&parity, &parity_csv, &parity_dev_csv) {
+        errors.push(format!("\nmain() :: ERROR -> Could not save put-call parity csv files {} and {}: {}", parity_csv, parity_dev_csv, e));
+    }
+    match exposure_profiles(&chain, &ctx) {
+        Ok(profiles) => {
+            let total = &profiles[0];
+            println!("\nmain() :: {} dealer net gamma exposure = ${:.0} (${:.0} per 1% move), vanna exposure = ${:.0}, charm exposure = ${:.0} per year", ticker, total.net_gamma(), 0.01 * total.net_gamma(), total.vanna(), total.charm());
+            println!("\nmain() :: {} gamma flip = {:.2}, call wall = {:.2}, put wall = {:.2} (spot {:.2})", ticker, total.gamma_flip, total.call_wall, total.put_wall, total.spot);
+            let (gex_csv, gex_stats_csv, gex_grid_csv) = (dirs.csv_name(ticker, "gex", &stamp), dirs.csv_name(ticker, "gexstats", &stamp), dirs.csv_name(ticker, "gexgrid", &stamp));
+            if let Err(e) = exposure_to_csv(&profiles, &gex_csv, &gex_stats_csv, &gex_grid_csv) {
+                errors.push(format!("\nmain() :: ERROR -> Could not save dealer exposure csv files {}, {} and {}: {}", gex_csv, gex_stats_csv, gex_grid_csv, e));
+            }
+        },
+        Err(e) => errors.push(e),
+    }
+    let term = chain.term_structure(&ctx);
+    if term.points.is_empty() {
+        errors.push(format!("\nmain() :: ERROR -> No ATM implied volatility of {} converged", oc_csv));
+    }
+    println!("\nmain() :: {} ATM implied volatility term structure is in {} ({:+.4} per year front to back)", ticker, term.shape.label(), term.slope);
+    let smile_csv = dirs.csv_name(ticker, "smile", &stamp);
+    if let Err(e) = smile_metrics_to_csv(&chain.smile_metrics(&ctx), &term, &smile_csv) {
+        errors.push(format!("\nmain() :: ERROR -> Could not save smile metrics csv {}: {}", smile_csv, e));
+    }
+    let svi_csv = dirs.csv_name(ticker, "svi", &stamp);
+    match fit_surface(&chain, &ctx) {
+        Ok(surface) => {
+            for model in [SurfaceModel::Svi, SurfaceModel::Ssvi] {
+                let arbitrage = surface.arbitrage(model);
+                let (butterfly, calendar) = (arbitrage.iter().filter(|a| a.butterfly > 0).count(), arbitrage.iter().filter(|a| a.calendar > 0).count());
+                if surface.ssvi.is_some() || model == SurfaceModel::Svi {
+                    println!("\nmain() :: {} {} surface over {} expiries: {} with butterfly arbitrage, {} with calendar arbitrage", ticker, model.label(), surface.slices.len(), butterfly, calendar);
+                }
+            }
+            if let Err(e) = surface_to_csv(&surface, &svi_csv) {
+                errors.push(format!("\nmain() :: ERROR -> Could not save volatility surface csv {}: {}", svi_csv, e));
+            }
+            let densities = surface_densities(&surface, SurfaceModel::Svi);
+            for d in &densities {
+                let m = d.moments();
+                println!("\nmain() :: {} {} risk-neutral expected move = ${:.2} ({:.2}%), P(above spot) = {:.1}%, skewness = {:.3}, excess kurtosis = {:.3}", ticker, d.date, m.expected_move, 100.0 * m.expected_move / surface.spot, 100.0 * d.prob_above(surface.spot), m.skewness, m.excess_kurtosis);
+            }
+            let (rnd_csv, rnd_stats_csv) = (dirs.csv_name(ticker, "rnd", &stamp), dirs.csv_name(ticker, "rndstats", &stamp));
+            if densities.is_empty() {
+                errors.push(format!("\nmain() :: ERROR -> No risk-neutral density could be extracted from the {} volatility surface", ticker));
+            } else if let Err(e) = densities_to_csv(&densities, surface.spot, &rnd_csv, &rnd_stats_csv) {
+                errors.push(format!("\nmain() :: ERROR -> Could not save risk-neutral density csv files {} and {}: {}", rnd_csv, rnd_stats_csv, e));
+            }
+        },
+        Err(e) => errors.push(e),
+    }
+    let ts = tseries_from_csv(&av_csv).map_err(|e| format!("\nmain() :: ERROR -> Could not load {} for realized volatility: {}", av_csv, e))?;
+    let adj = ts.adjusted();
+    let atm = chain.atm_iv_term_structure(&ctx);
+    if !adj.is_daily() {
+        errors.push(format!("\nmain() :: ERROR -> GARCH fits and the volatility cone need daily bars to compare with implied volatility; {} holds {} bars a year (fetch it with --av-series daily, daily_adjusted or an intraday interval)", av_csv, adj.periods_per_year()));
+    } else {
+        let returns = daily_returns(&adj);
+        let mut fits = Vec::new();
+        for kind in [GarchKind::Garch, GarchKind::Gjr, GarchKind::Egarch] {
+            for innovation in [Innovation::Normal, Innovation::StudentT] {
+                match fit_garch(&returns, kind, innovation) {
+                    Ok(fit) => {
+                        let last_close = adj.bars.last().map_or(f64::NAN, |b| b.close);
+                        println!("\nmain() :: {} {} on {} returns (mean {:.6}): next-day 1 sd move = {:.2}% (${:.2})", ticker, fit.label(), fit.observations, fit.mean, 100.0 * fit.next_day_vol(), last_close * fit.next_day_vol());
+                        fits.push(fit);
+                    },
+                    Err(e) => eprintln!("{}", e),
+                }
+            }
+        }
+        if fits.is_empty() {
+            errors.push(format!("\nmain() :: ERROR -> No GARCH model could be fit to the {} returns in {}", ticker, av_csv));
+        }
+        let garch_csv = dirs.csv_name(ticker, "garch", &stamp);
+        let garch_term_csv = dirs.csv_name(ticker, "garchterm", &stamp);
+        if let Err(e) = garch_to_csv(&fits, &atm, &garch_csv, &garch_term_csv) {
+            errors.push(format!("\nmain() :: ERROR -> Could not save GARCH csv files {} and {}: {}", garch_csv, garch_term_csv, e));
+        }
+        let cones = vol_cone(&adj, RvEstimator::YangZhang, &CONE_WINDOWS);
+        let iv_rv_csv = dirs.csv_name(ticker, "ivrv", &stamp);
+        if let Err(e) = iv_rv_to_csv(&cones, RvEstimator::YangZhang, &atm, &iv_rv_csv) {
+            errors.push(format!("\nmain() :: ERROR -> Could not save implied vs realized volatility csv {}: {}", iv_rv_csv, e));
+        }
+    }
+    print!("\nmain() :: {} Total Option Contract Volume = {} and Total Open Interest = {}", ticker, chain.total_contract_volume(), chain.total_open_interest());
+    /*let (straddle_price, atm_call, atm_put) = get_atm_straddle(&oc_csv);
+    println!("\nmain() :: {} ATM Straddle: ${} cost (implying underlying moves {}% by nearest expiry date)\nATM Call: {:?}\nATM Put: {:?}\n", ticker, straddle_price, straddle_price*0.85, atm_call, atm_put);
+    let (ccredit, itm_call, otm_call) = get_atm_credit_spread(&oc_csv, true);
+    println!("\nmain() :: Call Credit Spread:\nCredit: ${}\nITM Call: {:?}\nOTM Call: {:?}\n", ccredit, itm_call, otm_call);
+    let (pcredit, itm_put, otm_put) = get_atm_credit_spread(&oc_csv, false);
+    println!("\nmain() :: Put Credit Spread:\nCredit: ${}\nITM Put: {:?}\nOTM Put: {:?}\n", pcredit, itm_put, otm_put);
+    let (cdebit, otm_call, itm_call) = get_atm_debit_spread(&oc_csv, true);
+    println!("\nmain() :: Call Debit Spread:\nDebit: ${}\nOTM Call: {:?}\nITM Call: {:?}\n", cdebit, otm_call, itm_call);
+    let (pdebit, otm_put, itm_put) = get_atm_debit_spread(&oc_csv, false);
+    println!("\nmain() :: Put Debit Spread:\nDebit: ${}\nOTM Put: {:?}\nITM Put: {:?}\n", pdebit, otm_put, itm_put);*/
+    if errors.is_empty() { Ok(()) } else { Err(errors.join("\n")) }
+}
+
+// Renders every chart whose input csv exists; fails when an input is missing or any chart fails
+fn plot(common: &CommonArgs, dirs: &OutputDirs, ticker: &str, run: Option<&str>, backend: &dyn ChartBackend) -> Result<(), String> {
+    let mut errors = Vec::new();
+    let av_csv = dirs.input_csv(ticker, "av", run).map_err(|e| errors.push(e)).ok();
+    let oc_csv = dirs.input_csv(ticker, "oc", run).map_err(|e| errors.push(e)).ok();
+    let iv_rv_csv = dirs.input_csv(ticker, "ivrv", run).map_err(|e| errors.push(e)).ok();
+    let svi_csv = dirs.input_csv(ticker, "svi", run).map_err(|e| errors.push(e)).ok();
+    let smile_csv = dirs.input_csv(ticker, "smile", run).map_err(|e| errors.push(e)).ok();
+    let rnd_csv = dirs.input_csv(ticker, "rnd", run).map_err(|e| errors.push(e)).ok();
+    let gex_csvs = ["gex", "gexstats", "gexgrid"].map(|kind| dirs.input_csv(ticker, kind, run).map_err(|e| errors.push(e)).ok());
+    let fv_csv = dirs.input_csv(ticker, "fv", run).ok();
+    let ctx = match oc_csv.as_deref().map(|oc_csv| load_chain(common, dirs, ticker, oc_csv, fv_csv.as_deref())) {
+        Some(Ok((_, ctx))) => ctx,
+        Some(Err(e)) => {
+            errors.push(e);
+            pricing_context(common, dirs, ticker, None, fv_csv.as_deref())
+        },
+        None => pricing_context(common, dirs, ticker, None, fv_csv.as_deref()),
+    };
+    let img = dirs.img.as_str();
+    let chart_errors: Vec<String> = thread::scope(|scope| {
+        let mut handles = Vec::new();
+        if let Some(av_csv) = &av_csv {
+            for column in TSERIES_PLOT_COLUMNS {
+                handles.push(scope.spawn(move || generate_tseries_plot(av_csv, img, column, backend)));
+            }
+            for indicator in DEFAULT_INDICATORS {
+                handles.push(scope.spawn(move || generate_indicator_plot(av_csv, img, indicator, backend)));
+            }
+        }
+        if let Some(oc_csv) = &oc_csv {
+            let ctx = &ctx;
+            for plot_field in 0..SURFACE_PLOT_LABELS.len() {
+                handles.push(scope.spawn(move || generate_surface_plot(oc_csv, img, plot_field, ctx, backend)));
+            }
+            handles.push(scope.spawn(move || plot_volatility_smiles(oc_csv, img, ctx, backend)));
+        }
+        if let Some(iv_rv_csv) = &iv_rv_csv {
+            handles.push(scope.spawn(move || generate_iv_rv_plot(iv_rv_csv, img, backend)));
+        }
+        if let Some(smile_csv) = &smile_csv {
+            handles.push(scope.spawn(move || generate_term_structure_plot(smile_csv, img, backend)));
+        }
+        if let Some(svi_csv) = &svi_csv {
+            handles.push(scope.spawn(move || generate_vol_surface_plots(svi_csv, img, backend)));
+        }
+        if let Some(rnd_csv) = &rnd_csv {
+            handles.push(scope.spawn(move || generate_density_plots(rnd_csv, img, backend)));
+        }
+        if let [Some(gex_csv), Some(gex_stats_csv), Some(gex_grid_csv)] = &gex_csvs {
+            handles.push(scope.spawn(move || generate_exposure_plots(gex_csv, gex_stats_csv, gex_grid_csv, img, backend)));
+        }
+        handles
+            .into_iter()
+            .filter_map(|h| match h.join() {
+                Ok(Ok(())) => None,
+                Ok(Err(e)) => Some(e.to_string()),
+                Err(_) => Some("\nplot() :: ERROR -> A chart thread panicked".to_string()),
+            })
+            .collect()
+    });
+    errors.extend(chart_errors);
+    if errors.is_empty() { Ok(()) } else { Err(errors.join("\n")) }
+}
+
+// HTML and PDF reports of the data and the charts rendered from it, written as <TICKER>_<stamp>.html/.pdf
+fn report(common: &CommonArgs, dirs: &OutputDirs, ticker: &str, run: Option<&str>, stamp: &str) -> Result<(), String> {
+    let fv_csv = dirs.input_csv(ticker, "fv", run).ok();
+    let av_csv = dirs.input_csv(ticker, "av", run).ok();
+    let oc_csv = dirs.input_csv(ticker, "oc", run).ok();
+    let iv_rv_csv = dirs.input_csv(ticker, "ivrv", run).ok();
+    let svi_csv = dirs.input_csv(ticker, "svi", run).ok();
+    let smile_csv = dirs.input_csv(ticker, "smile", run).ok();
+    let rnd_csv = dirs.input_csv(ticker, "rnd", run).ok();
+    let gex_csv = dirs.input_csv(ticker, "gex", run).ok();
+    let stamps: Vec<String> = [&av_csv, &oc_csv, &iv_rv_csv, &smile_csv, &svi_csv, &rnd_csv, &gex_csv].into_iter().flatten().map(|csv| csv_stamp(csv)).collect();
+    let (chain, ctx) = match oc_csv.and_then(|oc_csv| load_chain(common, dirs, ticker, &oc_csv, fv_csv.as_deref()).map_err(|e| eprintln!("{}", e)).ok()) {
+        Some((chain, ctx)) => (Some(chain), ctx),
+        None => (None, pricing_context(common, dirs, ticker, None, fv_csv.as_deref())),
+    };
+    let mut cover_lines = vec![
+        format!("Generated {}", Local::now().format("%Y-%m-%d %H:%M:%S %Z")),
+        format!("Valued at {}", ctx.valuation.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S %Z")),
+        format!("Option prices: {} basis, {} model, {:?} day count, {} forward", ctx.basis.label(), ctx.model.name(), ctx.day_count, ctx.forward.label()),
+    ];
+    if let Some(chain) = &chain {
+        cover_lines.push(format!("Total option contract volume: {}", chain.total_contract_volume()));
+        cover_lines.push(format!("Total open interest: {}", chain.total_open_interest()));
+    }
+    let fv_name = fv_csv.unwrap_or_default();
+    let av_name = av_csv.unwrap_or_default();
+    let mut errors = Vec::new();
+    let html_name = format!("{}{}_{}.html", dirs.html, ticker, stamp);
+    match generate_html_report(ticker, &cover_lines, &fv_name, &av_name, chain.as_ref(), &ctx, &html_name) {
+        Ok(()) => println!("\nmain() :: Successfully created interactive html report as {}", html_name),
+        Err(e) => errors.push(format!("\nmain() :: ERROR -> HTML report generation failed: {}", e)),
+    }
+    let pdf_name = format!("{}{}_{}.pdf", dirs.pdf, ticker, stamp);
+    let sections = report_sections(&fv_name, &dirs.img, ticker, &stamps);
+    match generate_pdf_report(ticker, &cover_lines, &sections, &pdf_name) {
+        Ok(pages) => println!("\nmain() :: Successfully created {} page pdf report as {}", pages, pdf_name),
+        Err(e) => errors.push(format!("\nmain() :: ERROR -> PDF generation failed: {}", e)),
+    }
+    if errors.is_empty() { Ok(()) } else { Err(errors.join("\n")) }
+}
+
+fn expiry_date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date, EXPDATEFMT).ok()
+}
+
+fn format_value(v: f64) -> String {
+    if v.is_finite() { format!("{:.4}", v) } else { "-".to_string() }
+}
+
+/* ATM implied volatility of every expiry, the call and put implied volatility of every strike of one expiry, or with
+--strike the SVI and SSVI surfaces fitted to the chain at that strike and --yte (or the --expiry's yte)
+*/
+fn print_iv(common: &CommonArgs, dirs: &OutputDirs, ticker: &str, run: Option<&str>, args: &IvArgs) -> Result<(), String> {
+    let oc_csv = dirs.input_csv(ticker, "oc", run)?;
+    let fv_csv = dirs.input_csv(ticker, "fv", run).ok();
+    let (chain, ctx) = load_chain(common, dirs, ticker, &oc_csv, fv_csv.as_deref())?;
+    println!("\n{} implied volatility ({} prices, {} model) from {}", ticker, ctx.basis.label(), ctx.model.name(), oc_csv);
+    if let Some(strike) = args.strike {
+        let yte = match (args.yte, args.expiry) {
+            (Some(yte), _) => yte,
+            (None, Some(date)) => chain.expiries.iter().find(|e| expiry_date(&e.date) == Some(date)).map(|e| e.yte).ok_or_else(|| format!("\nprint_iv() :: ERROR -> {} has no {} expiry in {}", ticker, date, oc_csv))?,
+            (None, None) => return Err("\nprint_iv() :: ERROR -> A --strike surface query needs --yte or --expiry".to_string()),
+        };
+        let surface = fit_surface(&chain, &ctx)?;
+        println!("Strike {:.2} at YTE {:.4} (forward {:.2})", strike, yte, surface.forward(yte));
+        let ivs = [SurfaceModel::Svi, SurfaceModel::Ssvi].map(|model| (model, surface.iv(strike, yte, model)));
+        for (model, iv) in ivs {
+            println!("{:<6} {:>10}", model.label(), format_value(iv));
+        }
+        return if ivs.iter().any(|(_, iv)| iv.is_finite()) { Ok(()) } else { Err(format!("\nprint_iv() :: ERROR -> Neither surface has an implied volatility at strike {} and YTE {}", strike, yte)) };
+    }
+    let Some(date) = args.expiry else {
+        let atm = chain.atm_iv_term_structure(&ctx);
+        println!("{:<20} {:>10} {:>10}", "Expiry", "YTE", "ATM IV");
+        for atm in &atm {
+            println!("{:<20} {:>10.4} {:>10}", atm.date, atm.yte, format_value(atm.iv));
+        }
+        return if atm.iter().any(|a| a.iv.is_finite()) { Ok(()) } else { Err(format!("\nprint_iv() :: ERROR -> No ATM implied volatility of {} converged", oc_csv)) };
+    };
+    let expiry = chain.expiries.iter().find(|e| expiry_date(&e.date) == Some(date)).ok_or_else(|| format!("\nprint_iv() :: ERROR -> {} has no {} expiry in {}", ticker, date, oc_csv))?;
+    let rate = expiry.rate(&ctx.curve);
+    let (spot, div) = chain.pricing_inputs(expiry, &ctx);
+    let solve = |opt: &options::Option| {
+        let iv = opt.get_imp_vol(ctx.model.as_ref(), spot, rate, div, ctx.basis);
+        if iv.converged() { iv.iv } else { f64::NAN }
+    };
+    let mut strikes: Vec<f64> = expiry.calls.iter().chain(&expiry.puts).map(|o| o.strike).collect();
+    strikes.sort_by(f64::total_cmp);
+    strikes.dedup();
+    println!("{} (spot {:.2})\n{:>10} {:>10} {:>10}", expiry.date, spot, "Strike", "Call IV", "Put IV");
+    let mut converged = false;
+    for strike in strikes {
+        let call = expiry.calls.iter().find(|c| c.strike == strike).map_or(f64::NAN, solve);
+        let put = expiry.puts.iter().find(|p| p.strike == strike).map_or(f64::NAN, solve);
+        converged |= call.is_finite() || put.is_finite();
+        println!("{:>10.2} {:>10} {:>10}", strike, format_value(call), format_value(put));
+    }
+    if converged { Ok(()) } else { Err(format!("\nprint_iv() :: ERROR -> No {} implied volatility of {} converged", expiry.date, oc_csv)) }
+}
+
+/* Moments, quantiles and the probabilities of finishing below/above each --level (spot when none) of the risk-neutral
+densities of the SVI surface fitted to the chain, for every expiry or only --expiry
+*/
+fn print_prob(common: &CommonArgs, dirs: &OutputDirs, ticker: &str, run: Option<&str>, args: &ProbArgs) -> Result<(), String> {
+    let oc_csv = dirs.input_csv(ticker, "oc", run)?;
+    let fv_csv = dirs.input_csv(ticker, "fv", run).ok();
+    let (chain, ctx) = load_chain(common, dirs, ticker, &oc_csv, fv_csv.as_deref())?;
+    let surface = fit_surface(&chain, &ctx)?;
+    let densities: Vec<RiskNeutralDensity> = surface_densities(&surface, SurfaceModel::Svi).into_iter().filter(|d| args.expiry.is_none_or(|date| expiry_date(&d.date) == Some(date))).collect();
+    if densities.is_empty() {
+        return Err(format!("\nprint_prob() :: ERROR -> No {} risk-neutral density for {} in {}", ticker, args.expiry.map_or("any expiry".to_string(), |d| d.to_string()), oc_csv));
+    }
+    let levels = if args.levels.is_empty() { vec![surface.spot] } else { args.levels.clone() };
+    println!("\n{} risk-neutral densities ({} prices, SVI surface, spot {:.2}) from {}", ticker, ctx.basis.label(), surface.spot, oc_csv);
+    for d in &densities {
+        let m = d.moments();
+        println!("\n{} (YTE {:.4}, forward {:.2})", d.date, d.yte, d.forward);
+        println!("{:<16} {:>10}\n{:<16} {:>10}\n{:<16} {:>10}", "Mean", format_value(m.mean), "Std Dev", format_value(m.std_dev), "Expected Move", format_value(m.expected_move));
+        println!("{:<16} {:>10}\n{:<16} {:>10}", "Skewness", format_value(m.skewness), "Excess Kurtosis", format_value(m.excess_kurtosis));
+        println!("{:<16} {}", "Quantiles", DENSITY_QUANTILES.iter().map(|q| format!("{:.0}%={:.2}", 100.0 * q, d.quantile(*q))).collect::<Vec<String>>().join("  "));
+        println!("{:>10} {:>10} {:>10}", "Level", "P(below)", "P(above)");
+        for level in &levels {
+            println!("{:>10.2} {:>10} {:>10}", level, format_value(d.prob_below(*level)), format_value(d.prob_above(*level)));
+        }
+    }
+    Ok(())
+}
+
+/* Put-call parity forward, discount rate and carry of every expiry (or only --expiry) against the quoted spot, and the
+call/put pairs whose fitted parity value lies outside their bid-ask spread (every pair with --all)
+*/
+fn print_parity(common: &CommonArgs, dirs: &OutputDirs, ticker: &str, run: Option<&str>, args: &ParityArgs) -> Result<(), String> {
+    let oc_csv = dirs.input_csv(ticker, "oc", run)?;
+    let fv_csv = dirs.input_csv(ticker, "fv", run).ok();
+    let (chain, ctx) = load_chain(common, dirs, ticker, &oc_csv, fv_csv.as_deref())?;
+    let fits: Vec<_> = chain_parity(&chain, &ctx).into_iter().filter(|f| args.expiry.is_none_or(|date| expiry_date(&f.date) == Some(date))).collect();
+    if fits.is_empty() {
+        return Err(format!("\nprint_parity() :: ERROR -> No {} put-call parity fit for {} in {}", ticker, args.expiry.map_or("any expiry".to_string(), |d| d.to_string()), oc_csv));
+    }
+    println!("\n{} put-call parity (mid prices, spot {:.2}) from {}", ticker, chain.current_price, oc_csv);
+    println!("{:<20} {:>8} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>6}", "Expiry", "YTE", "Forward", "Discount", "Impl Rate", "Rate", "Impl Yld", "Borrow", "Viol");
+    for f in &fits {
+        println!("{:<20} {:>8.4} {:>10.2} {:>10} {:>10} {:>10} {:>10} {:>10} {:>6}", f.date, f.yte, f.forward, format_value(f.discount), format_value(f.implied_rate), format_value(f.rate), format_value(f.implied_yield), format_value(f.borrow), f.violations().count());
+    }
+    for f in &fits {
+        let points: Vec<_> = f.points.iter().filter(|p| args.all || p.violation).collect();
+        if points.is_empty() {
+            continue;
+        }
+        println!("\n{}\n{:>10} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}", f.date, "Strike", "C - P", "Bid Side", "Ask Side", "Fitted", "Deviation", "Violation");
+        for p in points {
+            println!("{:>10.2} {:>10} {:>10} {:>10} {:>10} {:>10} {:>10}", p.strike, format_value(p.synthetic), format_value(p.lower), format_value(p.upper), format_value(p.fitted), format_value(p.deviation), p.violation);
+        }
+    }
+    Ok(())
+}
+
+// Implied volatility and Greeks (GREEKS_COLUMNS) of one expiry's contracts, optionally only at the listed strike nearest a price
+fn print_greeks(common: &CommonArgs, dirs: &OutputDirs, ticker: &str, run: Option<&str>, expiry: Option<NaiveDate>, strike: Option<f64>, side: Side) -> Result<(), String> {
+    let oc_csv = dirs.input_csv(ticker, "oc", run)?;
+    let fv_csv = dirs.input_csv(ticker, "fv", run).ok();
+    let (chain, ctx) = load_chain(common, dirs, ticker, &oc_csv, fv_csv.as_deref())?;
+    let selected = match expiry {
+        Some(date) => chain.expiries.iter().find(|e| expiry_date(&e.date) == Some(date)),
+        None => chain.expiries.iter().filter(|e| e.yte > 0.0).min_by(|a, b| a.yte.total_cmp(&b.yte)),
+    };
+    let expiry = selected.ok_or_else(|| format!("\nprint_greeks() :: ERROR -> No {} expiry in {}", expiry.map_or("unexpired".to_string(), |d| d.to_string()), oc_csv))?;
+    let rate = expiry.rate(&ctx.curve);
+    let (spot, div) = chain.pricing_inputs(expiry, &ctx);
+    let nearest = strike.and_then(|k| expiry.calls.iter().chain(&expiry.puts).map(|o| o.strike).min_by(|a, b| (a - k).abs().total_cmp(&(b - k).abs())));
+    let columns: Vec<usize> = GREEKS_COLUMNS.iter().filter_map(|c| CHAIN_COLUMNS.iter().position(|h| h == c)).collect();
+    println!("\n{} {} Greeks ({} prices, {} model, spot {:.2}) from {}", ticker, expiry.date, ctx.basis.label(), ctx.model.name(), spot, oc_csv);
+    println!("{:<5}{}", "Side", GREEKS_COLUMNS.iter().map(|c| format!("{:>11}", c)).collect::<String>());
+    let iv = CHAIN_COLUMNS.iter().position(|h| *h == "IV").expect("CHAIN_COLUMNS has an IV column");
+    let sides = [("Call", &expiry.calls, side != Side::Puts), ("Put", &expiry.puts, side != Side::Calls)];
+    let mut converged = false;
+    for (label, contracts, shown) in sides {
+        for opt in contracts.iter().filter(|o| shown && nearest.is_none_or(|k| o.strike == k)) {
+            let row = contract_row(opt, &ctx, spot, rate, div);
+            converged |= row[iv].is_finite();
+            println!("{:<5}{}", label, columns.iter().map(|&i| format!("{:>11}", format_value(row[i]))).collect::<String>());
+        }
+    }
+    if converged { Ok(()) } else { Err(format!("\nprint_greeks() :: ERROR -> No {} implied volatility of {} converged", expiry.date, oc_csv)) }
+}
+
+fn chart_backend() -> Box<dyn ChartBackend> {
+    match backend_from_env() {
+        Ok(b) => {
+            println!("\nmain() :: Rendering charts with the {} backend", b.name());
+            b
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(EXIT_USAGE);
+        }
+    }
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let common = &cli.common;
+    let dirs = OutputDirs::new(&common.out_dir);
+    if let Err(e) = dirs.create() {
+        eprintln!("\nmain() :: ERROR -> Could not create the output directories under {}: {}", common.out_dir.display(), e);
         exit(1);
     }
+    let stamp = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+    let run = common.run.as_deref();
+    let mut failures = Failures::default();
+    match &cli.command {
+        Command::Run(args) => {
+            let backend = chart_backend();
+            for ticker in &args.tickers {
+                fetch(common, &dirs, ticker, &stamp, (true, true, true), &mut failures);
+                failures.record(ticker, Stage::Analyze, analyze(common, &dirs, ticker, Some(&stamp)));
+                failures.record(ticker, Stage::Plot, plot(common, &dirs, ticker, Some(&stamp), backend.as_ref()));
+                if failures.record(ticker, Stage::Report, report(common, &dirs, ticker, Some(&stamp), &stamp)) {
+                    if let Err(e) = remove_run_charts(&dirs.img, ticker, &stamp) {
+                        eprintln!("\nmain() :: ERROR -> Could not remove the {} charts embedded in its report from {}: {}", ticker, dirs.img, e);
+                    }
+                }
+            }
+        },
+        Command::Fetch(args) => {
+            for ticker in &args.tickers.tickers {
+                fetch(common, &dirs, ticker, &stamp, args.selected(), &mut failures);
+            }
+        },
+        Command::Analyze(args) => {
+            for ticker in &args.tickers {
+                failures.record(ticker, Stage::Analyze, analyze(common, &dirs, ticker, run));
+            }
+        },
+        Command::Plot(args) => {
+            let backend = chart_backend();
+            for ticker in &args.tickers {
+                failures.record(ticker, Stage::Plot, plot(common, &dirs, ticker, run, backend.as_ref()));
+            }
+        },
+        Command::Report(args) => {
+            for ticker in &args.tickers {
+                failures.record(ticker, Stage::Report, report(common, &dirs, ticker, run, run.unwrap_or(&stamp)));
+            }
+        },
+        Command::Iv(args) => {
+            for ticker in &args.tickers.tickers {
+                failures.record(ticker, Stage::Quotes, print_iv(common, &dirs, ticker, run, args));
+            }
+        },
+        Command::Greeks(args) => {
+            for ticker in &args.tickers.tickers {
+                failures.record(ticker, Stage::Quotes, print_greeks(common, &dirs, ticker, run, args.expiry, args.strike, args.side));
+            }
+        },
+        Command::Parity(args) => {
+            for ticker in &args.tickers.tickers {
+                failures.record(ticker, Stage::Quotes, print_parity(common, &dirs, ticker, run, args));
+            }
+        },
+        Command::Prob(args) => {
+            for ticker in &args.tickers.tickers {
+                failures.record(ticker, Stage::Quotes, print_prob(common, &dirs, ticker, run, args));
+            }
+        },
+    }
+    exit(failures.exit_code());
 }
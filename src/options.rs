@@ -1,15 +1,134 @@
 use anyhow::{Context, Result};
 use playwright::Playwright;
 use csv::ReaderBuilder;
-use super::finmath::{cnd, npd, brentq, black_scholes};
-use super::utils::str_to_float;
-use std::fs::File;
+use super::finmath::{cnd, npd, d_one, ImpVol};
+use super::pricing::{PricingInputs, PricingModel};
+use super::rates::{RateCurve, FEDFUNDS};
+use super::dividends::DividendSchedule;
+use super::parity::fit_parity;
+use super::calendar::{market_date, year_fraction, DayCount};
+use chrono::{DateTime, Days, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use super::avantage::tseries_from_csv;
+use super::utils::{csv_value, str_to_float};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
 use std::error::Error;
 
-const OURLP1: &str = "youll have to adapt this code to use another option chain source";
-const OURLP2: &str = "&showAll=True";
+pub const EXPDATEFMT: &str = "%B %d %Y";
+const OCSOURCE: &str = "bigcharts.marketwatch.com";
+/* Chain csv format version, written as the first metadata row
+1 = bare UNDERLYING,EXPIRATION,... rows (no spot, dividend yield, rate or quote timestamp)
+2 = '#'-prefixed KEY,VALUE metadata rows (VERSION, TICKER, SPOT, DIV_YIELD, RATE, QUOTE_TIME, SOURCE) before the header
+*/
+pub const CHAIN_CSV_VERSION: u32 = 2;
+const CSVDTFMT: &str = "%Y-%m-%d_%H-%M-%S";
+// Log-moneyness half-width around the forward that OptionExpiry::smile_metrics() fits the skew slope over
+const SKEW_BAND: f64 = 0.1;
+// Front-to-back ATM implied volatility change below which the term structure is classified Flat
+const FLAT_TERM_TOL: f64 = 0.005;
 //const HTMLDIR: &str = "html_out/";
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PriceBasis {
+    Bid,
+    Ask,
+    Mid,
+    Last,
+}
+
+impl PriceBasis {
+    pub fn label(&self) -> &'static str {
+        match self {
+            PriceBasis::Bid => "bid",
+            PriceBasis::Ask => "ask",
+            PriceBasis::Mid => "mid",
+            PriceBasis::Last => "last",
+        }
+    }
+}
+
+impl std::str::FromStr for PriceBasis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "bid" => Ok(PriceBasis::Bid),
+            "ask" => Ok(PriceBasis::Ask),
+            "mid" => Ok(PriceBasis::Mid),
+            "last" => Ok(PriceBasis::Last),
+            _ => Err(format!("\nPriceBasis::from_str() :: ERROR -> Unknown price basis '{}'; expected one of bid, ask, mid, last", s)),
+        }
+    }
+}
+
+/* What each expiry is priced off
+Spot = the quoted spot carried at the curve rate less the dividend yield (or the escrowed spot of discrete dividends)
+Implied = the expiry's own put-call parity forward (see parity.rs), falling back to Spot when it cannot be fit
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForwardBasis {
+    Spot,
+    Implied,
+}
+
+impl ForwardBasis {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ForwardBasis::Spot => "spot",
+            ForwardBasis::Implied => "implied",
+        }
+    }
+}
+
+impl std::str::FromStr for ForwardBasis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "spot" => Ok(ForwardBasis::Spot),
+            "implied" => Ok(ForwardBasis::Implied),
+            _ => Err(format!("\nForwardBasis::from_str() :: ERROR -> Unknown forward basis '{}'; expected spot or implied", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct IvBand {
+    pub bid: ImpVol,
+    pub ask: ImpVol,
+}
+
+impl IvBand {
+    pub fn width(&self) -> f64 {
+        if self.bid.converged() && self.ask.converged() {
+            self.ask.iv - self.bid.iv
+        } else {
+            f64::NAN
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Greeks {
+    pub delta: f64,
+    pub elasticity: f64,
+    pub vega: f64,
+    pub theta: f64,
+    pub rho: f64,
+    pub epsilon: f64,
+    pub gamma: f64,
+    pub vanna: f64,
+    pub charm: f64,
+    pub vomma: f64,
+    pub veta: f64,
+    pub speed: f64,
+    pub zomma: f64,
+    pub color: f64,
+    pub ultima: f64,
+}
+
 #[derive(Debug, Clone)]
 pub struct Option {
     pub last: f64,
@@ -47,179 +166,80 @@ impl Option {
             is_call: is_call,
         }
     }*/
-    pub fn get_imp_vol(&self, s: f64, q: f64) -> f64 {
-        let f = |x: f64| black_scholes(x, s, self.strike, self.yte, q, self.is_call) - self.last;
-        match brentq(f, 0.0, 15.0, 1e-6) {
-            Ok(iv) => iv,
-            Err(_) => 0.0,
-        }
-    }
-    pub fn get_delta(&self, q: f64, d1: f64) -> f64{
-        if self.is_call {
-            (-1.0 * q * self.yte).exp() * cnd(d1)
-        } else {
-            -1.0 * (-1.0 * q * self.yte) * cnd(d1)
-        }
-    }
-    pub fn get_elasticity(&self, s: f64, delta: f64) -> f64 {
-        delta * (s / self.last)
-    }
-    pub fn get_vega(&self, d2: f64) -> f64 {
-        let vega = self.strike * npd(d2) * self.yte.sqrt();
-        if vega.is_nan() {
-            0.0
-        } else {
-            vega
-        }
-    }
-    pub fn get_theta(&self, iv: f64, s: f64, q: f64, d1: f64, d2: f64, fed_funds: f64) -> f64 {
-        if self.is_call {
-            let ctheta = (-1.0 * q * self.yte).exp()
-                * ((s * npd(d1) * iv) / (2.0 * self.yte.sqrt()))
-                - fed_funds * self.strike * (-1.0 * fed_funds * self.yte).exp() * cnd(d2)
-                + q * s * (-1.0 * q * self.yte).exp() * cnd(d1);
-
-            if ctheta.is_nan() {
-                0.0
-            } else {
-                ctheta
-            }
-        } else {
-            let ptheta = (-1.0 * q * self.yte).exp()
-                * ((s * npd(d1) * iv) / (2.0 * self.yte.sqrt()))
-                + fed_funds * self.strike * (-1.0 * fed_funds * self.yte).exp() * cnd(-d2)
-                - q * s * (-1.0 * q * self.yte).exp() * cnd(-d1);
-
-            if ptheta.is_nan() {
-                0.0
-            } else {
-                ptheta
-            }
-        }
-    }
-    pub fn get_rho(&self, d2: f64, fed_funds: f64) -> f64 {
-        if self.is_call {
-            let c_rho = self.strike * self.yte * (-1.0 * fed_funds * self.yte).exp() * cnd(d2);
-            if c_rho.is_nan() {
-                0.0
-            } else {
-                c_rho
-            }
-        } else {
-            let p_rho = -1.0 * self.strike * self.yte * (-1.0 * fed_funds * self.yte).exp() * cnd(-d2);
-            if p_rho.is_nan() {
-                0.0
-            } else {
-                p_rho
-            }
-        }
-    }
-    pub fn get_epsilon(&self, s: f64, q: f64, d1: f64) -> f64 {
-        if self.is_call {
-            let c_eps = -1.0 * s * self.strike * self.yte * (-1.0 * q * self.yte).exp() * cnd(d1);
-            if c_eps.is_nan() {
-                0.0
-            } else {
-                c_eps
-            }
-        } else {
-            let p_eps = s * self.yte * (-1.0 * q * self.yte).exp() * cnd(-d1);
-            if p_eps.is_nan() {
-                0.0
-            } else {
-                p_eps
-            }
-        }
-    }
-    pub fn get_gamma(&self, iv: f64, s: f64, d2: f64, fed_funds: f64) -> f64 {
-        let gamma = self.strike * (-1.0 * fed_funds * self.yte).exp() * (npd(d2) / (s * s * iv * self.yte.sqrt()));
-        if gamma.is_nan() {
-            0.0
-        } else {
-            gamma
-        }
-    }
-    pub fn get_vanna(&self, iv: f64, vega: f64, s: f64, d1: f64) -> f64 {
-        let vanna = (vega / s) * (1.0 - (d1 / (iv * self.yte.sqrt())));
-        if vanna.is_nan() {
-            0.0
-        } else {
-            vanna
-        }
-    }
-    pub fn get_charm(&self, iv: f64, q: f64, d1: f64, d2: f64, fed_funds: f64) -> f64 {
-        if self.is_call {
-            let c_charm = (q * (-1.0 * q * self.yte).exp() * cnd(d1))
-                - ((-1.0 * q * self.yte).exp() * npd(d1) * ((2.0 * (fed_funds - q) * self.yte) - (d2 * iv * self.yte.sqrt()))
-                / (2.0 * self.yte * iv * self.yte.sqrt()));
-            if c_charm.is_nan() {
-                0.0
-            } else {
-                c_charm
-            }
-        } else {
-            let p_charm = (-1.0 * q * (-1.0 * q * self.yte).exp() * cnd(-d1))
-                - ((-1.0 * q * self.yte).exp() * npd(d1) * ((2.0 * (fed_funds - q) * self.yte) - (d2 * iv * self.yte.sqrt()))
-                / (2.0 * self.yte * iv * self.yte.sqrt()));
-            if p_charm.is_nan() {
-                0.0
-            } else {
-                p_charm
-            }
-        }
-    }
-    pub fn get_vomma(&self, iv: f64, vega: f64, d1: f64, d2: f64) -> f64 {
-        let vomma = (vega * d1 * d2) / iv;
-        if vomma.is_nan() {
-            0.0
-        } else {
-            vomma
-        }
-    }
-    pub fn get_veta(&self, iv: f64, s: f64, q: f64, d1: f64, d2: f64, fed_funds: f64) -> f64 {
-        let factor = -1.0 * s * (-1.0 * q * self.yte).exp() * npd(d1) * self.yte.sqrt();
-        let veta = factor * (q + (((fed_funds - q) * d1) / (iv * self.yte.sqrt()))
-            - ((1.0 + (d1 * d2)) / (2.0 * self.yte)));
-        if veta.is_nan() {
-            0.0
-        } else {
-            veta
+    pub fn get_price(&self, basis: PriceBasis) -> f64 {
+        match basis {
+            PriceBasis::Bid => self.bid,
+            PriceBasis::Ask => self.ask,
+            PriceBasis::Mid => {
+                if self.ask > 0.0 {
+                    (self.bid + self.ask) / 2.0
+                } else {
+                    0.0
+                }
+            },
+            PriceBasis::Last => self.last,
         }
     }
-    pub fn get_speed(&self, iv: f64, gamma: f64, s: f64, d1: f64) -> f64 {
-        let speed = (-1.0 * gamma / s) * ((d1 / (iv * self.yte.sqrt())) + 1.0);
-        if speed.is_nan() {
-            0.0
-        } else {
-            speed
-        }
+    pub fn inputs(&self, s: f64, r: f64, q: f64) -> PricingInputs {
+        PricingInputs { s, k: self.strike, t: self.yte, r, q, is_call: self.is_call }
     }
-    pub fn get_zomma(&self, iv: f64, gamma: f64, d1: f64, d2: f64) -> f64 {
-        let zomma = gamma * (((d1 * d2) - 1.0) / iv);
-        if zomma.is_nan() {
-            0.0
-        } else {
-            zomma
-        }
+    pub fn get_imp_vol(&self, model: &dyn PricingModel, s: f64, r: f64, q: f64, basis: PriceBasis) -> ImpVol {
+        model.implied_vol(self.get_price(basis), &self.inputs(s, r, q))
     }
-    pub fn get_color(&self, iv: f64, s: f64, q: f64, d1: f64, d2: f64, fed_funds: f64) -> f64 {
-        let factor1 = -1.0 * (-1.0 * q * self.yte).exp() * npd(d1) / (2.0 * s * self.yte * iv * self.yte.sqrt());
-        let factor2 = (((2.0 * (fed_funds - q) * self.yte) - (d2 * iv * self.yte.sqrt()))
-            / (iv * self.yte.sqrt())) * d1;
-        let color = factor1 * ((2.0 * q * self.yte) + 1.0 + factor2);
-        if color.is_nan() {
-            0.0
-        } else {
-            color
+    pub fn get_iv_band(&self, model: &dyn PricingModel, s: f64, r: f64, q: f64) -> IvBand {
+        IvBand {
+            bid: self.get_imp_vol(model, s, r, q, PriceBasis::Bid),
+            ask: self.get_imp_vol(model, s, r, q, PriceBasis::Ask),
         }
     }
-    pub fn get_ultima(&self, iv: f64, vega: f64, d1: f64, d2: f64) -> f64 {
-        let factor = (-1.0 * vega) / (iv * iv);
-        let ultima = factor * (((d1 * d2) * (1.0 - (d1 * d2))) + (d1 * d1) + (d2 * d2));
-        if ultima.is_nan() {
-            0.0
+    /* All Black-Scholes Greeks from a single d1/d2 evaluation
+    spot = the price of the underlying equity
+    rate = the continuously compounded risk-free rate
+    div = the underlying equity's continuous dividend yield
+    iv = the implied volatility of the contract
+    Time sensitivities (theta, charm, veta, color) are derivatives with respect to calendar time
+    (the negative of the derivative with respect to yte), per year
+    */
+    pub fn greeks(&self, spot: f64, rate: f64, div: f64, iv: f64) -> Greeks {
+        let t = self.yte;
+        let sqrt_t = t.sqrt();
+        let iv_sqrt_t = iv * sqrt_t;
+        let d1 = d_one(iv, spot, self.strike, t, rate, div);
+        let d2 = d1 - iv_sqrt_t;
+        let div_disc = (-div * t).exp();
+        let rate_disc = (-rate * t).exp();
+        let pdf_d1 = npd(d1);
+        // Signed so the call and put formulas share one expression: N(d) for calls, -N(-d) for puts
+        let (nd1, nd2) = if self.is_call {
+            (cnd(d1), cnd(d2))
         } else {
-            ultima
+            (-cnd(-d1), -cnd(-d2))
+        };
+        let price = spot * div_disc * nd1 - self.strike * rate_disc * nd2;
+        let delta = div_disc * nd1;
+        let gamma = div_disc * pdf_d1 / (spot * iv_sqrt_t);
+        let vega = spot * div_disc * pdf_d1 * sqrt_t;
+        let carry = (2.0 * (rate - div) * t - d2 * iv_sqrt_t) / (2.0 * t * iv_sqrt_t);
+        Greeks {
+            delta,
+            elasticity: delta * spot / price,
+            vega,
+            theta: -spot * div_disc * pdf_d1 * iv / (2.0 * sqrt_t)
+                - rate * self.strike * rate_disc * nd2
+                + div * spot * div_disc * nd1,
+            rho: self.strike * t * rate_disc * nd2,
+            epsilon: -spot * t * div_disc * nd1,
+            gamma,
+            vanna: -div_disc * pdf_d1 * d2 / iv,
+            charm: div * div_disc * nd1 - div_disc * pdf_d1 * carry,
+            vomma: vega * d1 * d2 / iv,
+            veta: spot * div_disc * pdf_d1 * sqrt_t
+                * (div + (rate - div) * d1 / iv_sqrt_t - (1.0 + d1 * d2) / (2.0 * t)),
+            speed: -gamma / spot * (d1 / iv_sqrt_t + 1.0),
+            zomma: gamma * (d1 * d2 - 1.0) / iv,
+            color: div_disc * pdf_d1 / (2.0 * spot * t * iv_sqrt_t)
+                * (2.0 * div * t + 1.0 + d1 * (2.0 * (rate - div) * t - d2 * iv_sqrt_t) / iv_sqrt_t),
+            ultima: -vega / (iv * iv) * (d1 * d2 * (1.0 - d1 * d2) + d1 * d1 + d2 * d2),
         }
     }
 }
@@ -248,15 +268,276 @@ pub struct OptionExpiry {
     pub puts: Vec<Option>,
 }
 
+/* Everything needed to turn a quoted chain into implied volatilities and Greeks
+model = the pricing model inverted for implied volatility
+basis = which quoted price (bid, ask, mid, last) is solved against
+curve = risk-free rate curve; each expiry is discounted at its own tenor
+dividends = discrete dividend schedule (empty to use the chain's continuous div_yield)
+valuation = the instant the chain is priced at; yte is recomputed from it under day_count
+forward = whether expiries are priced off the quoted spot or their put-call parity implied forward
+*/
+pub struct PricingContext {
+    pub model: Box<dyn PricingModel>,
+    pub basis: PriceBasis,
+    pub curve: RateCurve,
+    pub dividends: DividendSchedule,
+    pub valuation: DateTime<Utc>,
+    pub day_count: DayCount,
+    pub forward: ForwardBasis,
+}
+
+impl OptionExpiry {
+    // Risk-free rate used to discount this expiry, read off the curve at its own tenor
+    pub fn rate(&self, curve: &RateCurve) -> f64 {
+        curve.rate(self.yte)
+    }
+    /* At-the-money implied volatility: the mean of the converged call and put implied volatilities at the listed
+    strike closest to spot (NaN when neither converges)
+    */
+    pub fn atm_iv(&self, spot: f64, rate: f64, div: f64, model: &dyn PricingModel, basis: PriceBasis) -> f64 {
+        let nearest = |contracts: &[Option]| contracts.iter().min_by(|a, b| (a.strike - spot).abs().total_cmp(&(b.strike - spot).abs())).cloned();
+        let strike = match nearest(&self.calls).or_else(|| nearest(&self.puts)) {
+            Some(c) => c.strike,
+            None => return f64::NAN,
+        };
+        let ivs: Vec<f64> = self
+            .calls
+            .iter()
+            .chain(self.puts.iter())
+            .filter(|c| c.strike == strike)
+            .map(|c| c.get_imp_vol(model, spot, rate, div, basis))
+            .filter(|iv| iv.converged())
+            .map(|iv| iv.iv)
+            .collect();
+        if ivs.is_empty() {
+            f64::NAN
+        } else {
+            ivs.iter().sum::<f64>() / ivs.len() as f64
+        }
+    }
+    /* Out-of-the-money smile as (strike, implied volatility) in strike order: puts below the forward
+    spot * exp((rate - div) * yte) and calls at or above it; quotes whose implied volatility does not converge are dropped
+    */
+    pub fn otm_smile(&self, spot: f64, rate: f64, div: f64, model: &dyn PricingModel, basis: PriceBasis) -> Vec<(f64, f64)> {
+        let forward = spot * ((rate - div) * self.yte).exp();
+        let mut smile: Vec<(f64, f64)> = self
+            .puts
+            .iter()
+            .filter(|p| p.strike < forward)
+            .chain(self.calls.iter().filter(|c| c.strike >= forward))
+            .filter_map(|opt| {
+                let iv = opt.get_imp_vol(model, spot, rate, div, basis);
+                (iv.converged() && iv.iv > 0.0).then_some((opt.strike, iv.iv))
+            })
+            .collect();
+        smile.sort_by(|a, b| a.0.total_cmp(&b.0));
+        smile
+    }
+    /* Implied volatility at a Black-Scholes delta, interpolated linearly in delta between the two converged quotes
+    bracketing it on one side (calls for a positive delta, puts for a negative one); NaN when no quotes bracket it
+    */
+    pub fn iv_at_delta(&self, delta: f64, spot: f64, rate: f64, div: f64, model: &dyn PricingModel, basis: PriceBasis) -> f64 {
+        let contracts = if delta > 0.0 { &self.calls } else { &self.puts };
+        let mut points: Vec<(f64, f64)> = contracts
+            .iter()
+            .filter_map(|opt| {
+                let iv = opt.get_imp_vol(model, spot, rate, div, basis);
+                iv.converged().then(|| (opt.greeks(spot, rate, div, iv.iv).delta, iv.iv))
+            })
+            .filter(|(d, _)| d.is_finite())
+            .collect();
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        points
+            .windows(2)
+            .find(|w| w[0].0 <= delta && delta <= w[1].0)
+            .map_or(f64::NAN, |w| {
+                let ((d0, iv0), (d1, iv1)) = (w[0], w[1]);
+                if d1 > d0 { iv0 + (iv1 - iv0) * (delta - d0) / (d1 - d0) } else { iv0 }
+            })
+    }
+    /* ATM, 25-delta and skew metrics of this expiry (see SmileMetrics)
+    The skew slope is the least squares slope of the out-of-the-money smile against ln(K / F) within SKEW_BAND of the forward
+    */
+    pub fn smile_metrics(&self, spot: f64, rate: f64, div: f64, model: &dyn PricingModel, basis: PriceBasis) -> SmileMetrics {
+        let atm_iv = self.atm_iv(spot, rate, div, model, basis);
+        let call_25d = self.iv_at_delta(0.25, spot, rate, div, model, basis);
+        let put_25d = self.iv_at_delta(-0.25, spot, rate, div, model, basis);
+        let forward = spot * ((rate - div) * self.yte).exp();
+        let near: Vec<(f64, f64)> = self
+            .otm_smile(spot, rate, div, model, basis)
+            .into_iter()
+            .map(|(strike, iv)| ((strike / forward).ln(), iv))
+            .filter(|(k, _)| k.abs() <= SKEW_BAND)
+            .collect();
+        let n = near.len() as f64;
+        let (k_avg, iv_avg) = (near.iter().map(|p| p.0).sum::<f64>() / n, near.iter().map(|p| p.1).sum::<f64>() / n);
+        let sxx: f64 = near.iter().map(|(k, _)| (k - k_avg).powi(2)).sum();
+        let sxy: f64 = near.iter().map(|(k, iv)| (k - k_avg) * (iv - iv_avg)).sum();
+        SmileMetrics {
+            date: self.date.clone(),
+            yte: self.yte,
+            atm_iv,
+            call_25d,
+            put_25d,
+            risk_reversal: call_25d - put_25d,
+            butterfly: 0.5 * (call_25d + put_25d) - atm_iv,
+            skew: if near.len() >= 2 && sxx > 0.0 { sxy / sxx } else { f64::NAN },
+        }
+    }
+}
+
+// One point of the at-the-money implied volatility term structure
+#[derive(Debug, Clone, PartialEq)]
+pub struct AtmIv {
+    pub date: String,
+    pub yte: f64,
+    pub iv: f64,
+}
+
+/* Smile of one expiry
+atm_iv = OptionExpiry::atm_iv(); call_25d / put_25d = implied volatility at +0.25 call / -0.25 put delta
+risk_reversal = call_25d - put_25d; butterfly = (call_25d + put_25d) / 2 - atm_iv
+skew = slope of implied volatility per unit of log-moneyness ln(K / F) at the money (negative for a put skew)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmileMetrics {
+    pub date: String,
+    pub yte: f64,
+    pub atm_iv: f64,
+    pub call_25d: f64,
+    pub put_25d: f64,
+    pub risk_reversal: f64,
+    pub butterfly: f64,
+    pub skew: f64,
+}
+
+/* Shape of the ATM implied volatility term structure from its front to its back expiry
+Contango = back above front by more than FLAT_TERM_TOL, Backwardation = back below front, Flat otherwise
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TermShape {
+    Contango,
+    Backwardation,
+    Flat,
+}
+
+impl TermShape {
+    pub fn label(&self) -> &'static str {
+        match self {
+            TermShape::Contango => "Contango",
+            TermShape::Backwardation => "Backwardation",
+            TermShape::Flat => "Flat",
+        }
+    }
+}
+
+// ATM implied volatility of the unexpired expiries with a converged ATM IV, its shape and front-to-back slope per year
+#[derive(Debug, Clone, PartialEq)]
+pub struct TermStructure {
+    pub points: Vec<AtmIv>,
+    pub shape: TermShape,
+    pub slope: f64,
+}
+
 #[derive(Debug, Clone)]
 pub struct OptionChain {
     pub expiries: Vec<OptionExpiry>,
     pub ticker: String,
     pub current_price: f64,
     pub div_yield: f64,
+    pub rate: f64,
+    pub quote_time: DateTime<Utc>,
+    pub source: String,
 }
 
 impl OptionChain {
+    /* Spot and dividend yield of the quoted spot: the escrowed-dividend spot with q = 0 when a discrete
+    DividendSchedule is supplied, otherwise the scraped spot with the continuous div_yield
+    Dividends are escrowed up to the expiry date (yte calendar years after the valuation date when the date does not parse)
+    */
+    pub fn quoted_inputs(&self, expiry: &OptionExpiry, ctx: &PricingContext) -> (f64, f64) {
+        if ctx.dividends.is_empty() {
+            return (self.current_price, self.div_yield);
+        }
+        let valuation = market_date(ctx.valuation);
+        let exp_date = NaiveDate::parse_from_str(&expiry.date, EXPDATEFMT)
+            .unwrap_or_else(|_| valuation + Days::new((expiry.yte * 365.0).round().max(0.0) as u64));
+        (ctx.dividends.escrowed_spot(self.current_price, valuation, exp_date, &ctx.curve), 0.0)
+    }
+    /* Spot and dividend yield to price an expiry with: quoted_inputs() under ForwardBasis::Spot, or under
+    ForwardBasis::Implied the present value of the parity forward F * exp(-rate * yte) with q = 0, so the model
+    forward is F (quoted_inputs() when parity cannot be fit)
+    */
+    pub fn pricing_inputs(&self, expiry: &OptionExpiry, ctx: &PricingContext) -> (f64, f64) {
+        let (spot, div) = self.quoted_inputs(expiry, ctx);
+        match ctx.forward {
+            ForwardBasis::Spot => (spot, div),
+            ForwardBasis::Implied => {
+                let rate = expiry.rate(&ctx.curve);
+                match fit_parity(expiry, spot, rate, div) {
+                    Ok(fit) => (fit.forward * (-rate * expiry.yte).exp(), 0.0),
+                    Err(_) => (spot, div),
+                }
+            },
+        }
+    }
+    // Recomputes every expiry's (and contract's) yte for a new valuation instant and day-count convention
+    pub fn revalue(&mut self, valuation: DateTime<Utc>, day_count: DayCount) {
+        for expiry in self.expiries.iter_mut() {
+            let exp_date = match NaiveDate::parse_from_str(&expiry.date, EXPDATEFMT) {
+                Ok(d) => d,
+                Err(e) => {
+                    eprintln!("\nOptionChain::revalue() :: ERROR -> Could not parse expiration date '{}'; keeping yte = {}: {:?}", expiry.date, expiry.yte, e);
+                    continue;
+                },
+            };
+            expiry.yte = year_fraction(valuation, exp_date, day_count);
+            for opt in expiry.calls.iter_mut().chain(expiry.puts.iter_mut()) {
+                opt.yte = expiry.yte;
+            }
+        }
+    }
+    // ATM implied volatility of every expiry priced under ctx, in expiry order
+    pub fn atm_iv_term_structure(&self, ctx: &PricingContext) -> Vec<AtmIv> {
+        self.expiries
+            .iter()
+            .map(|expiry| {
+                let (spot, div) = self.pricing_inputs(expiry, ctx);
+                let iv = expiry.atm_iv(spot, expiry.rate(&ctx.curve), div, ctx.model.as_ref(), ctx.basis);
+                AtmIv { date: expiry.date.clone(), yte: expiry.yte, iv }
+            })
+            .collect()
+    }
+    // SmileMetrics of every expiry priced under ctx, in expiry order
+    pub fn smile_metrics(&self, ctx: &PricingContext) -> Vec<SmileMetrics> {
+        self.expiries
+            .iter()
+            .map(|expiry| {
+                let (spot, div) = self.pricing_inputs(expiry, ctx);
+                expiry.smile_metrics(spot, expiry.rate(&ctx.curve), div, ctx.model.as_ref(), ctx.basis)
+            })
+            .collect()
+    }
+    // The ATM term structure and its contango / backwardation classification; Flat with a NaN slope below two expiries
+    pub fn term_structure(&self, ctx: &PricingContext) -> TermStructure {
+        let mut points: Vec<AtmIv> = self.atm_iv_term_structure(ctx).into_iter().filter(|p| p.yte > 0.0 && p.iv.is_finite()).collect();
+        points.sort_by(|a, b| a.yte.total_cmp(&b.yte));
+        let (shape, slope) = match (points.first(), points.last()) {
+            (Some(front), Some(back)) if points.len() >= 2 => {
+                let change = back.iv - front.iv;
+                let shape = if change > FLAT_TERM_TOL {
+                    TermShape::Contango
+                } else if change < -FLAT_TERM_TOL {
+                    TermShape::Backwardation
+                } else {
+                    TermShape::Flat
+                };
+                (shape, change / (back.yte - front.yte))
+            },
+            _ => (TermShape::Flat, f64::NAN),
+        };
+        TermStructure { points, shape, slope }
+    }
     pub fn total_contract_volume(&self) -> f64 {
         let mut sum = 0.0;
         for expiry in self.expiries.clone() {
@@ -283,8 +564,12 @@ impl OptionChain {
     }
 }
 
+/* Scrapes a bigcharts.marketwatch.com style option chain page with a headless Chromium browser
+oc_url = the full chain page url for the ticker (see sources::BrowserSource)
+The returned chain's rate is FEDFUNDS; callers record the rate they actually price with
+*/
 #[tokio::main]
-pub async fn fetch_option_chain(ticker: &str, csv_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn fetch_option_chain(ticker: &str, oc_url: &str) -> Result<OptionChain, Box<dyn std::error::Error>> {
     let playwright = Playwright::initialize()
         .await
         .context("\nfetch_option_chain() :: ERROR -> Could not initialize Playwright")?;
@@ -294,9 +579,8 @@ pub async fn fetch_option_chain(ticker: &str, csv_name: &str) -> Result<(), Box<
         .context("\nfetch_option_chain() :: ERROR -> Could not launch Chromium")?;
     let context = browser.context_builder().build().await?;
     let page = context.new_page().await?;
-    let oc_url = format!("{}{}{}", OURLP1, ticker, OURLP2);
     println!("\nfetch_option_chain() :: Fetching HTML from bigcharts.marketwatch.com for {}", ticker);
-    page.goto_builder(&oc_url)
+    page.goto_builder(oc_url)
         .goto()
         .await
         .context("\nfetch_option_chain() :: ERROR -> Could not navigate to bigcharts.marketwatch.com")?;
@@ -341,8 +625,11 @@ pub async fn fetch_option_chain(ticker: &str, csv_name: &str) -> Result<(), Box<
     let mut chain = OptionChain {
         expiries: Vec::new(),
         ticker: ticker.to_string(),
-        current_price: current_price,
+        current_price,
         div_yield: yield_val,
+        rate: FEDFUNDS,
+        quote_time: Utc::now(),
+        source: OCSOURCE.to_string(),
     };
     let mut current_exp_date = "".to_string();
     let mut current_yte = 0.0;
@@ -352,7 +639,7 @@ pub async fn fetch_option_chain(ticker: &str, csv_name: &str) -> Result<(), Box<
         calls: Vec::new(),
         puts: Vec::new(),
     };
-    let current_time = chrono::Utc::now();
+    let current_time = chain.quote_time;
     let mut i: i128 = 0;
     for tr in rows {
         i += 1;
@@ -386,16 +673,14 @@ pub async fn fetch_option_chain(ticker: &str, csv_name: &str) -> Result<(), Box<
                 continue;
             }
             current_exp_date = format!("{} {} {}", &date_fields[1], date_fields[2].replace(",", ""), date_fields[3]);
-            let parsed_time = match chrono::NaiveDate::parse_from_str(&current_exp_date, "%B %d %Y") {
+            let parsed_time = match chrono::NaiveDate::parse_from_str(&current_exp_date, EXPDATEFMT) {
                 Ok(dt) => dt,
                 Err(e) => {
                     eprintln!("\nfetch_option_chain() :: ERROR -> A problem occurred parsing new_exp_date '{}': {:?}", current_exp_date, e);
                     continue;
                 },
             };
-            let parsed_datetime = chrono::DateTime::<chrono::Utc>::from_naive_utc_and_offset(parsed_time.into(), chrono::Utc);
-            let duration = current_time.signed_duration_since(parsed_datetime);
-            current_yte = (duration.num_hours().abs() as f64) / 24.0 / 252.0;
+            current_yte = year_fraction(current_time, parsed_time, DayCount::default());
             continue;
         }
         let td_cells_result = tr.query_selector_all("td").await;
@@ -466,52 +751,64 @@ pub async fn fetch_option_chain(ticker: &str, csv_name: &str) -> Result<(), Box<
     browser.close()
         .await
         .context("\nfetch_option_chain() :: ERROR -> Could not close playwright chromium browser")?;
-    if !chain.expiries.is_empty() {
-        let mut wtr = csv::Writer::from_path(&csv_name).context("\nfetch_option_chain() :: ERROR -> Could not open file for CSV writing")?;
-        wtr.write_record(&["UNDERLYING", "EXPIRATION", "STRIKE", "CALL(c)/PUT(p)", "LAST", "CHANGE", "VOLUME", "BID", "ASK", "OPENINT", "YTE"])?;
-        for expiry in &chain.expiries {
-            for call in &expiry.calls {
-                wtr.write_record(&[
-                    &chain.ticker,
-                    &expiry.date,
-                    &call.strike.to_string(),
-                    "c",
-                    &call.last.to_string(),
-                    &call.change.to_string(),
-                    &call.vol.to_string(),
-                    &call.bid.to_string(),
-                    &call.ask.to_string(),
-                    &call.open_int.to_string(),
-                    &call.yte.to_string(),
-                ])?;
-            }
-            for put in &expiry.puts {
-                wtr.write_record(&[
-                    &chain.ticker,
-                    &expiry.date,
-                    &put.strike.to_string(),
-                    "p",
-                    &put.last.to_string(),
-                    &put.change.to_string(),
-                    &put.vol.to_string(),
-                    &put.bid.to_string(),
-                    &put.ask.to_string(),
-                    &put.open_int.to_string(),
-                    &put.yte.to_string(),
+    if chain.expiries.is_empty() {
+        return Err("\nfetch_option_chain() :: ERROR -> Unsuccessful at parsing HTML into OptionChain struct".into());
+    }
+    Ok(chain)
+}
+
+// Saves an OptionChain in the current versioned csv format: metadata rows followed by one row per contract
+pub fn chain_to_csv(chain: &OptionChain, csv_name: &str) -> Result<(), Box<dyn Error>> {
+    let mut file = File::create(csv_name)?;
+    writeln!(file, "#VERSION,{}", CHAIN_CSV_VERSION)?;
+    writeln!(file, "#TICKER,{}", chain.ticker)?;
+    writeln!(file, "#SPOT,{}", chain.current_price)?;
+    writeln!(file, "#DIV_YIELD,{}", chain.div_yield)?;
+    writeln!(file, "#RATE,{}", chain.rate)?;
+    writeln!(file, "#QUOTE_TIME,{}", chain.quote_time.to_rfc3339())?;
+    writeln!(file, "#SOURCE,{}", chain.source)?;
+    let mut wtr = csv::Writer::from_writer(file);
+    wtr.write_record(["UNDERLYING", "EXPIRATION", "STRIKE", "CALL(c)/PUT(p)", "LAST", "CHANGE", "VOLUME", "BID", "ASK", "OPENINT", "YTE"])?;
+    for expiry in &chain.expiries {
+        for (contracts, flag) in [(&expiry.calls, "c"), (&expiry.puts, "p")] {
+            for opt in contracts {
+                wtr.write_record([
+                    chain.ticker.as_str(),
+                    expiry.date.as_str(),
+                    &opt.strike.to_string(),
+                    flag,
+                    &opt.last.to_string(),
+                    &opt.change.to_string(),
+                    &opt.vol.to_string(),
+                    &opt.bid.to_string(),
+                    &opt.ask.to_string(),
+                    &opt.open_int.to_string(),
+                    &opt.yte.to_string(),
                 ])?;
             }
         }
-        wtr.flush().context("\nfetch_option_chain() :: ERROR -> Could not flush CSV writer")?;
-        println!("\nfetch_option_chain() :: Successfully created {} with option chain data for {}", csv_name, ticker);
-    } else {
-        eprintln!("\nfetch_option_chain() :: ERROR -> Unsuccessful at parsing HTML into OptionChain struct; no csv output to be made");
     }
+    wtr.flush()?;
     Ok(())
 }
 
-pub fn chain_from_csv(csv_file: &str) -> Result<OptionChain, Box<dyn Error>> {
-    let file = File::open(csv_file)?;
-    let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(file);
+// Splits the leading '#KEY,VALUE' metadata rows of a chain csv from the contract rows
+fn split_chain_metadata(contents: &str) -> (HashMap<String, String>, &str) {
+    let mut metadata = HashMap::new();
+    let mut rest = contents;
+    while let Some(line) = rest.strip_prefix('#') {
+        let (row, remaining) = line.split_once('\n').unwrap_or((line, ""));
+        if let Some((key, value)) = row.trim_end_matches('\r').split_once(',') {
+            metadata.insert(key.trim().to_uppercase(), value.trim().to_string());
+        }
+        rest = remaining;
+    }
+    (metadata, rest)
+}
+
+// Parses the contract rows (header included) shared by every chain csv version
+fn parse_chain_rows(rows: &str) -> Result<(String, Vec<OptionExpiry>), Box<dyn Error>> {
+    let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(rows.as_bytes());
     let mut expiries: Vec<OptionExpiry> = Vec::new();
     let mut current_expiry: OptionExpiry = OptionExpiry {
         date: String::new(),
@@ -520,8 +817,6 @@ pub fn chain_from_csv(csv_file: &str) -> Result<OptionChain, Box<dyn Error>> {
         puts: Vec::new(),
     };
     let mut current_ticker: String = String::new();
-    let current_price: f64 = 0.0;
-    let div_yield: f64 = 0.0;
     for result in rdr.records() {
         let record = result?;
         if current_ticker.is_empty() {
@@ -537,8 +832,10 @@ pub fn chain_from_csv(csv_file: &str) -> Result<OptionChain, Box<dyn Error>> {
         let ask = record[8].parse::<f64>()?;
         let open_int = record[9].parse::<f64>()?;
         let yte = record[10].parse::<f64>()?;
-        if expiry_date != current_expiry.date && !current_expiry.date.is_empty() {
-            expiries.push(current_expiry.clone());
+        if expiry_date != current_expiry.date {
+            if !current_expiry.calls.is_empty() || !current_expiry.puts.is_empty() {
+                expiries.push(current_expiry.clone());
+            }
             current_expiry = OptionExpiry {
                 date: expiry_date.clone(),
                 yte,
@@ -547,15 +844,15 @@ pub fn chain_from_csv(csv_file: &str) -> Result<OptionChain, Box<dyn Error>> {
             };
         }
         let opt = Option {
-            last: last,
-            change: change,
-            vol: vol,
-            bid: bid,
-            ask: ask,
-            open_int: open_int,
-            strike: strike,
-            yte: yte,
-            is_call: is_call,
+            last,
+            change,
+            vol,
+            bid,
+            ask,
+            open_int,
+            strike,
+            yte,
+            is_call,
         };
         if is_call {
             current_expiry.calls.push(opt);
@@ -566,15 +863,168 @@ pub fn chain_from_csv(csv_file: &str) -> Result<OptionChain, Box<dyn Error>> {
     if !current_expiry.calls.is_empty() || !current_expiry.puts.is_empty() {
         expiries.push(current_expiry);
     }
+    Ok((current_ticker, expiries))
+}
+
+/* Instantiates an OptionChain from a versioned chain csv written by chain_to_csv()
+Version 1 files (no metadata rows) are rejected; convert them with migrate_chain_csv() first
+*/
+pub fn chain_from_csv(csv_file: &str) -> Result<OptionChain, Box<dyn Error>> {
+    let contents = fs::read_to_string(csv_file)?;
+    let (metadata, rows) = split_chain_metadata(&contents);
+    let version = match metadata.get("VERSION") {
+        Some(v) => v.parse::<u32>()?,
+        None => return Err(format!("\nchain_from_csv() :: ERROR -> {} has no VERSION metadata (version 1 chain csv); convert it with migrate_chain_csv()", csv_file).into()),
+    };
+    if version != CHAIN_CSV_VERSION {
+        return Err(format!("\nchain_from_csv() :: ERROR -> {} is chain csv version {}; expected version {}", csv_file, version, CHAIN_CSV_VERSION).into());
+    }
+    let field = |key: &str| metadata.get(key).ok_or_else(|| format!("\nchain_from_csv() :: ERROR -> {} is missing {} metadata", csv_file, key));
+    let (row_ticker, expiries) = parse_chain_rows(rows)?;
     let option_chain = OptionChain {
         expiries,
-        ticker: current_ticker,
-        current_price,
-        div_yield,
+        ticker: metadata.get("TICKER").cloned().unwrap_or(row_ticker),
+        current_price: field("SPOT")?.parse::<f64>()?,
+        div_yield: field("DIV_YIELD")?.parse::<f64>()?,
+        rate: field("RATE")?.parse::<f64>()?,
+        quote_time: DateTime::parse_from_rfc3339(field("QUOTE_TIME")?)?.with_timezone(&Utc),
+        source: metadata.get("SOURCE").cloned().unwrap_or_default(),
     };
     Ok(option_chain)
 }
 
+/* Rewrites a version 1 `_oc_` chain csv in the current format
+The quote timestamp is taken from the `<TICKER>_oc_<%Y-%m-%d_%H-%M-%S>` file name (local time) and the spot is backfilled
+from the close of the last bar on or before the quote's market date in the matching `_av_` time series csv; the dividend yield (unknown) is written as 0 and the rate
+as FEDFUNDS. The original file is kept alongside as `<name>.v1`. Current-version files are returned unchanged.
+*/
+pub fn migrate_chain_csv(csv_file: &str) -> Result<OptionChain, Box<dyn Error>> {
+    let contents = fs::read_to_string(csv_file)?;
+    let (metadata, rows) = split_chain_metadata(&contents);
+    if metadata.contains_key("VERSION") {
+        return chain_from_csv(csv_file);
+    }
+    let (ticker, expiries) = parse_chain_rows(rows)?;
+    let path = Path::new(csv_file);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+    let (prefix, dt_str) = stem.split_once("_oc_")
+        .ok_or_else(|| format!("\nmigrate_chain_csv() :: ERROR -> {} is not named <TICKER>_oc_<datetime>.csv", csv_file))?;
+    let naive = NaiveDateTime::parse_from_str(dt_str, CSVDTFMT)?;
+    let quote_time = Local.from_local_datetime(&naive).earliest()
+        .ok_or_else(|| format!("\nmigrate_chain_csv() :: ERROR -> {} is not a valid local time", dt_str))?
+        .with_timezone(&Utc);
+    let av_csv = path.with_file_name(format!("{}_av_{}.csv", prefix, dt_str)).to_string_lossy().to_string();
+    let ts = tseries_from_csv(&av_csv)
+        .map_err(|e| format!("\nmigrate_chain_csv() :: ERROR -> Could not backfill spot for {} from {}: {}", csv_file, av_csv, e))?;
+    let quote_date = market_date(quote_time);
+    let (close_date, spot) = match ts.bars.iter().rev().find(|bar| bar.date <= quote_date) {
+        Some(bar) => (bar.date, bar.close),
+        None => return Err(format!("\nmigrate_chain_csv() :: ERROR -> {} has no bars on or before {} to backfill spot from", av_csv, quote_date).into()),
+    };
+    println!("\nmigrate_chain_csv() :: Backfilled {} spot = {} from the {} close in {}", csv_file, spot, close_date, av_csv);
+    let chain = OptionChain {
+        expiries,
+        ticker,
+        current_price: spot,
+        div_yield: 0.0,
+        rate: FEDFUNDS,
+        quote_time,
+        source: format!("{} (migrated from version 1; spot from {})", OCSOURCE, av_csv),
+    };
+    fs::copy(csv_file, format!("{}.v1", csv_file))?;
+    chain_to_csv(&chain, csv_file)?;
+    Ok(chain)
+}
+
+// Migrates every version 1 `_oc_` chain csv in a directory, returning the number of files converted
+pub fn migrate_chain_dir(dir_name: &str) -> Result<usize, Box<dyn Error>> {
+    let mut migrated = 0;
+    for entry in fs::read_dir(dir_name)? {
+        let path = entry?.path();
+        let name = path.to_string_lossy().to_string();
+        if !name.contains("_oc_") || path.extension().and_then(|e| e.to_str()) != Some("csv") {
+            continue;
+        }
+        let (metadata, _) = split_chain_metadata(&fs::read_to_string(&path)?);
+        if metadata.contains_key("VERSION") {
+            continue;
+        }
+        match migrate_chain_csv(&name) {
+            Ok(_) => migrated += 1,
+            Err(e) => eprintln!("\nmigrate_chain_dir() :: ERROR -> Could not migrate {}: {}", name, e),
+        }
+    }
+    Ok(migrated)
+}
+
+/* Writes the early-exercise boundary of the at-the-money call and put of every expiry to csv
+Each contract's implied volatility is solved with the same model that produces the boundary;
+models without an exercise boundary (Black-Scholes, Barone-Adesi-Whaley) produce no csv
+*/
+pub fn exercise_boundaries_to_csv(chain: &OptionChain, ctx: &PricingContext, csv_name: &str) -> Result<(), Box<dyn Error>> {
+    let model = ctx.model.as_ref();
+    let mut rows: Vec<[String; 6]> = Vec::new();
+    for expiry in &chain.expiries {
+        let rate = expiry.rate(&ctx.curve);
+        let (spot, div) = chain.pricing_inputs(expiry, ctx);
+        for contracts in [&expiry.calls, &expiry.puts] {
+            let atm = match contracts.iter().min_by(|a, b| (a.strike - spot).abs().total_cmp(&(b.strike - spot).abs())) {
+                Some(opt) => opt,
+                None => continue,
+            };
+            let iv = atm.get_imp_vol(model, spot, rate, div, ctx.basis);
+            if !iv.converged() {
+                eprintln!("\nexercise_boundaries_to_csv() :: Skipping {} strike {}; implied volatility solver returned {:?}", expiry.date, atm.strike, iv.status);
+                continue;
+            }
+            let boundary = match model.exercise_boundary(iv.iv, &atm.inputs(spot, rate, div)) {
+                Some(b) => b,
+                None => {
+                    eprintln!("\nexercise_boundaries_to_csv() :: ERROR -> The {} pricing model has no early-exercise boundary; no csv output to be made", model.name());
+                    return Ok(());
+                },
+            };
+            let cp_flag = if atm.is_call { "c" } else { "p" };
+            for (time, critical) in boundary.times.iter().zip(boundary.spots.iter()) {
+                rows.push([
+                    expiry.date.clone(),
+                    cp_flag.to_string(),
+                    atm.strike.to_string(),
+                    iv.iv.to_string(),
+                    time.to_string(),
+                    critical.to_string(),
+                ]);
+            }
+        }
+    }
+    let mut wtr = csv::Writer::from_path(csv_name)?;
+    wtr.write_record(["EXPIRATION", "CALL(c)/PUT(p)", "STRIKE", "IV", "TIME", "CRITICAL_PRICE"])?;
+    for row in &rows {
+        wtr.write_record(row)?;
+    }
+    wtr.flush()?;
+    println!("\nexercise_boundaries_to_csv() :: Successfully created {} with {} early-exercise boundaries for {}", csv_name, model.name(), chain.ticker);
+    Ok(())
+}
+
+/* Saves the smile metrics of every expiry with the term structure classification
+Expiry, Yte, AtmIV, Call25dIV, Put25dIV, RiskReversal25d, Butterfly25d, Skew, then the TermStructure shape and
+TermSlope (ATM IV change per year, front to back) repeated on every row
+*/
+pub fn smile_metrics_to_csv(metrics: &[SmileMetrics], term: &TermStructure, csv_name: &str) -> Result<(), Box<dyn Error>> {
+    let mut wtr = csv::Writer::from_path(csv_name)?;
+    wtr.write_record(["Expiry", "Yte", "AtmIV", "Call25dIV", "Put25dIV", "RiskReversal25d", "Butterfly25d", "Skew", "TermStructure", "TermSlope"])?;
+    for m in metrics {
+        let mut record = vec![m.date.clone()];
+        record.extend([m.yte, m.atm_iv, m.call_25d, m.put_25d, m.risk_reversal, m.butterfly, m.skew].map(csv_value));
+        record.extend([term.shape.label().to_string(), csv_value(term.slope)]);
+        wtr.write_record(&record)?;
+    }
+    wtr.flush()?;
+    println!("\nsmile_metrics_to_csv() :: Successfully created {} for {} expiries", csv_name, metrics.len());
+    Ok(())
+}
+
 /*pub fn get_atm_options(chain_csv_name: &str, cp_flag: bool) -> (Option, Option) {
     let chain = match chain_from_csv(chain_csv_name) {
         Ok(c) => c,
@@ -622,4 +1072,228 @@ pub fn get_atm_debit_spread(chain_csv_name: &str, cp_flag: bool) -> (f64, Option
     let (otm, itm) = get_atm_options(chain_csv_name, cp_flag);
     let debit_spread_value = otm.last - itm.last;
     (debit_spread_value, otm, itm)
-}*/
\ No newline at end of file
+}*/
+// Chain fixtures shared by the tests of every module that prices an OptionChain
+#[cfg(test)]
+pub mod test_fixtures {
+    use super::*;
+    use crate::finmath::black_scholes;
+    use crate::pricing::BlackScholes;
+
+    /* One expiry of Black-Scholes quotes off spot 100: both sides of every strike priced at iv(strike) with rate and
+    dividend yield q, bid and ask half_spread either side of the model price and last at it
+    */
+    pub fn bs_expiry(date: &str, yte: f64, rate: f64, q: f64, strikes: &[f64], half_spread: f64, iv: impl Fn(f64) -> f64) -> OptionExpiry {
+        let quote = |strike: f64, is_call: bool| {
+            let price = black_scholes(iv(strike), 100.0, strike, yte, rate, q, is_call);
+            Option { strike, yte, is_call, bid: price - half_spread, ask: price + half_spread, last: price, ..Option::default() }
+        };
+        OptionExpiry { date: date.to_string(), yte, calls: strikes.iter().map(|k| quote(*k, true)).collect(), puts: strikes.iter().map(|k| quote(*k, false)).collect() }
+    }
+
+    // Black-Scholes at mid prices on a flat rate, without dividends, valued now off the quoted spot
+    pub fn bs_context(rate: f64) -> PricingContext {
+        PricingContext { model: Box::new(BlackScholes), basis: PriceBasis::Mid, curve: RateCurve::Flat(rate), dividends: DividendSchedule::default(), valuation: Utc::now(), day_count: DayCount::default(), forward: ForwardBasis::Spot }
+    }
+
+    // A "TEST" chain quoted now at spot with a flat rate and no dividend yield
+    pub fn test_chain(expiries: Vec<OptionExpiry>, spot: f64, rate: f64) -> OptionChain {
+        OptionChain { expiries, ticker: "TEST".to_string(), current_price: spot, div_yield: 0.0, rate, quote_time: Utc::now(), source: "test".to_string() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test_fixtures::{bs_context, bs_expiry, test_chain};
+    use crate::finmath::{black_scholes, SolverStatus};
+    use proptest::prelude::*;
+
+    const H: f64 = 1e-4;
+    const R: f64 = 0.0533;
+
+    fn contract(strike: f64, yte: f64, is_call: bool) -> Option {
+        Option { strike, yte, is_call, ..Option::default() }
+    }
+
+    fn price(opt: &Option, iv: f64, s: f64, t: f64, q: f64) -> f64 {
+        black_scholes(iv, s, opt.strike, t, R, q, opt.is_call)
+    }
+
+    fn greeks_at(opt: &Option, iv: f64, s: f64, t: f64, q: f64) -> Greeks {
+        contract(opt.strike, t, opt.is_call).greeks(s, R, q, iv)
+    }
+
+    fn assert_close(name: &str, analytic: f64, numeric: f64, abs_tol: f64) {
+        let tol = abs_tol + 1e-3 * numeric.abs();
+        assert!(
+            (analytic - numeric).abs() <= tol,
+            "{}: analytic {} vs finite difference {} (tol {})", name, analytic, numeric, tol
+        );
+    }
+
+    fn params() -> impl Strategy<Value = (f64, f64, f64, f64, f64, bool)> {
+        (50.0..150.0f64, 0.7..1.3f64, 0.1..2.0f64, 0.1..0.8f64, 0.0..0.05f64, any::<bool>())
+            .prop_map(|(s, m, t, iv, q, is_call)| (s, s * m, t, iv, q, is_call))
+    }
+
+    proptest! {
+        #[test]
+        fn first_order_greeks_match_black_scholes((s, k, t, iv, q, is_call) in params()) {
+            let opt = contract(k, t, is_call);
+            let g = opt.greeks(s, R, q, iv);
+            let hs = s * H;
+            let delta = (price(&opt, iv, s + hs, t, q) - price(&opt, iv, s - hs, t, q)) / (2.0 * hs);
+            let vega = (price(&opt, iv + H, s, t, q) - price(&opt, iv - H, s, t, q)) / (2.0 * H);
+            let theta = -(price(&opt, iv, s, t + H, q) - price(&opt, iv, s, t - H, q)) / (2.0 * H);
+            let epsilon = (price(&opt, iv, s, t, q + H) - price(&opt, iv, s, t, q - H)) / (2.0 * H);
+            let rho = (black_scholes(iv, s, k, t, R + H, q, is_call) - black_scholes(iv, s, k, t, R - H, q, is_call)) / (2.0 * H);
+            let bs_price = price(&opt, iv, s, t, q);
+            assert_close("delta", g.delta, delta, 1e-4);
+            assert_close("vega", g.vega, vega, 1e-3);
+            assert_close("theta", g.theta, theta, 1e-3);
+            assert_close("epsilon", g.epsilon, epsilon, 1e-3);
+            assert_close("rho", g.rho, rho, 1e-3);
+            if bs_price > 0.5 {
+                assert_close("elasticity", g.elasticity, delta * s / bs_price, 1e-2);
+            }
+        }
+
+        #[test]
+        fn second_order_greeks_match_first_order((s, k, t, iv, q, is_call) in params()) {
+            let opt = contract(k, t, is_call);
+            let g = opt.greeks(s, R, q, iv);
+            let hs = s * H;
+            let gamma = (greeks_at(&opt, iv, s + hs, t, q).delta - greeks_at(&opt, iv, s - hs, t, q).delta) / (2.0 * hs);
+            let vanna = (greeks_at(&opt, iv + H, s, t, q).delta - greeks_at(&opt, iv - H, s, t, q).delta) / (2.0 * H);
+            let charm = -(greeks_at(&opt, iv, s, t + H, q).delta - greeks_at(&opt, iv, s, t - H, q).delta) / (2.0 * H);
+            let vomma = (greeks_at(&opt, iv + H, s, t, q).vega - greeks_at(&opt, iv - H, s, t, q).vega) / (2.0 * H);
+            let veta = -(greeks_at(&opt, iv, s, t + H, q).vega - greeks_at(&opt, iv, s, t - H, q).vega) / (2.0 * H);
+            assert_close("gamma", g.gamma, gamma, 1e-6);
+            assert_close("vanna", g.vanna, vanna, 1e-5);
+            assert_close("charm", g.charm, charm, 1e-5);
+            assert_close("vomma", g.vomma, vomma, 1e-4);
+            assert_close("veta", g.veta, veta, 1e-4);
+        }
+
+        #[test]
+        fn second_order_greeks_match_black_scholes((s, k, t, iv, q, is_call) in params()) {
+            let opt = contract(k, t, is_call);
+            let g = opt.greeks(s, R, q, iv);
+            let hs = s * 1e-3;
+            let hv = 1e-3;
+            let p = |iv: f64, s: f64| price(&opt, iv, s, t, q);
+            let gamma = (p(iv, s + hs) - 2.0 * p(iv, s) + p(iv, s - hs)) / (hs * hs);
+            let vomma = (p(iv + hv, s) - 2.0 * p(iv, s) + p(iv - hv, s)) / (hv * hv);
+            let vanna = (p(iv + hv, s + hs) - p(iv + hv, s - hs) - p(iv - hv, s + hs) + p(iv - hv, s - hs)) / (4.0 * hs * hv);
+            assert_close("gamma", g.gamma, gamma, 1e-5);
+            assert_close("vomma", g.vomma, vomma, 1e-3);
+            assert_close("vanna", g.vanna, vanna, 1e-4);
+        }
+
+        #[test]
+        fn third_order_greeks_match_second_order((s, k, t, iv, q, is_call) in params()) {
+            let opt = contract(k, t, is_call);
+            let g = opt.greeks(s, R, q, iv);
+            let hs = s * H;
+            let speed = (greeks_at(&opt, iv, s + hs, t, q).gamma - greeks_at(&opt, iv, s - hs, t, q).gamma) / (2.0 * hs);
+            let zomma = (greeks_at(&opt, iv + H, s, t, q).gamma - greeks_at(&opt, iv - H, s, t, q).gamma) / (2.0 * H);
+            let color = -(greeks_at(&opt, iv, s, t + H, q).gamma - greeks_at(&opt, iv, s, t - H, q).gamma) / (2.0 * H);
+            let ultima = (greeks_at(&opt, iv + H, s, t, q).vomma - greeks_at(&opt, iv - H, s, t, q).vomma) / (2.0 * H);
+            assert_close("speed", g.speed, speed, 1e-6);
+            assert_close("zomma", g.zomma, zomma, 1e-5);
+            assert_close("color", g.color, color, 1e-5);
+            assert_close("ultima", g.ultima, ultima, 1e-2);
+        }
+    }
+
+    #[test]
+    fn put_delta_is_negative() {
+        let put = contract(100.0, 0.5, false);
+        let g = put.greeks(100.0, R, 0.01, 0.25);
+        assert!(g.delta < 0.0 && g.delta > -1.0);
+    }
+
+    #[test]
+    fn iv_band_and_mid_price_with_a_zero_bid() {
+        let model = crate::pricing::BlackScholes;
+        let ask = price(&contract(130.0, 0.25, true), 0.3, 100.0, 0.25, 0.0);
+        let opt = Option { bid: 0.0, ask, last: ask, ..contract(130.0, 0.25, true) };
+        assert_eq!((opt.get_price(PriceBasis::Bid), opt.get_price(PriceBasis::Mid), opt.get_price(PriceBasis::Ask)), (0.0, ask / 2.0, ask));
+        let band = opt.get_iv_band(&model, 100.0, R, 0.0);
+        assert!(band.bid.iv.is_nan() && band.bid.status == SolverStatus::InvalidInput, "{:?}", band.bid);
+        assert!(band.ask.converged() && (band.ask.iv - 0.3).abs() < 1e-8, "{:?}", band.ask);
+        assert!(band.width().is_nan());
+        let mid = opt.get_imp_vol(&model, 100.0, R, 0.0, PriceBasis::Mid);
+        assert!(mid.converged() && mid.iv < 0.3);
+        let unquoted = Option { bid: 0.0, ask: 0.0, ..opt };
+        assert_eq!(unquoted.get_price(PriceBasis::Mid), 0.0);
+        assert_eq!(unquoted.get_imp_vol(&model, 100.0, R, 0.0, PriceBasis::Mid).status, SolverStatus::InvalidInput);
+        let quoted = Option { bid: price(&opt, 0.28, 100.0, 0.25, 0.0), ..opt };
+        let band = quoted.get_iv_band(&model, 100.0, R, 0.0);
+        assert!((band.width() - 0.02).abs() < 1e-8, "{:?}", band);
+    }
+
+    #[test]
+    fn chain_csv_round_trips_and_migrates_version_1() {
+        let dir = std::env::temp_dir().join(format!("markets_options_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+        let quote_time = DateTime::parse_from_rfc3339("2024-06-03T14:30:00Z").unwrap().with_timezone(&Utc);
+        let strikes = [90.0, 95.0, 100.0, 105.0, 110.0];
+        let expiries = vec![bs_expiry("June 21 2024", 0.05, R, 0.0, &strikes, 0.05, |k| 0.2 + 0.001 * (100.0 - k)), bs_expiry("July 19 2024", 0.13, R, 0.0, &strikes, 0.05, |_| 0.21)];
+        let chain = OptionChain { div_yield: 0.013, quote_time, source: "test, with a comma".to_string(), ..test_chain(expiries, 100.0, R) };
+        let csv_name = path("TEST_oc_2024-06-03_10-30-00.csv");
+        chain_to_csv(&chain, &csv_name).unwrap();
+        assert_eq!(format!("{:?}", chain_from_csv(&csv_name).unwrap()), format!("{:?}", chain));
+        let contents = fs::read_to_string(&csv_name).unwrap();
+        let (_, rows) = split_chain_metadata(&contents);
+        for (version, name) in [("#VERSION,3\n", "TEST_oc_v3.csv"), ("", "TEST_oc_v1.csv")] {
+            fs::write(path(name), format!("{}{}", version, rows)).unwrap();
+            let err = chain_from_csv(&path(name)).unwrap_err().to_string();
+            assert!(err.contains(if version.is_empty() { "migrate_chain_csv" } else { "version 3" }), "{}", err);
+        }
+        // Version 1 quoted at 20:00 local on June 3; the June 5 bar postdates the quote and must not be used for spot
+        // The directory name also contains _oc_, which must not be rewritten when locating the av csv
+        fs::create_dir_all(dir.join("TEST_oc_archive")).unwrap();
+        let v1_name = path("TEST_oc_archive/TEST_oc_2024-06-03_20-00-00.csv");
+        fs::write(&v1_name, rows).unwrap();
+        let bars = "Date,Open,High,Low,Close,Volume\n2024-05-31,98,99,97,98.5,1000\n2024-06-03,99,101,98,100.25,1000\n2024-06-05,104,106,103,105,1000\n";
+        fs::write(path("TEST_oc_archive/TEST_av_2024-06-03_20-00-00.csv"), bars).unwrap();
+        let migrated = migrate_chain_csv(&v1_name).unwrap();
+        assert_eq!((migrated.current_price, migrated.div_yield, migrated.rate, migrated.expiries.len()), (100.25, 0.0, FEDFUNDS, 2));
+        assert_eq!(migrated.quote_time, Local.with_ymd_and_hms(2024, 6, 3, 20, 0, 0).unwrap().with_timezone(&Utc));
+        assert_eq!(fs::read_to_string(format!("{}.v1", v1_name)).unwrap(), rows);
+        let reloaded = chain_from_csv(&v1_name).unwrap();
+        assert_eq!(format!("{:?}", reloaded.expiries), format!("{:?}", chain.expiries));
+        assert_eq!(format!("{:?}", migrate_chain_csv(&v1_name).unwrap()), format!("{:?}", reloaded));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // Quotes priced off a smile iv(K) = atm + slope * ln(K / F) at spot 100 with strikes 60..140
+    fn skewed_expiry(date: &str, t: f64, atm: f64, slope: f64) -> OptionExpiry {
+        let forward = 100.0 * (R * t).exp();
+        let strikes: Vec<f64> = (0..=32).map(|i| 60.0 + 2.5 * i as f64).collect();
+        bs_expiry(date, t, R, 0.0, &strikes, 0.0, |k| atm + slope * (k / forward).ln())
+    }
+
+    #[test]
+    fn smile_metrics_and_term_structure() {
+        let model = crate::pricing::BlackScholes;
+        let flat = skewed_expiry("A", 0.25, 0.2, 0.0).smile_metrics(100.0, R, 0.0, &model, PriceBasis::Mid);
+        assert!((flat.atm_iv - 0.2).abs() < 1e-6 && flat.risk_reversal.abs() < 1e-6 && flat.butterfly.abs() < 1e-6 && flat.skew.abs() < 1e-6, "{:?}", flat);
+        let skewed = skewed_expiry("B", 0.5, 0.25, -0.2).smile_metrics(100.0, R, 0.0, &model, PriceBasis::Mid);
+        assert!((skewed.skew + 0.2).abs() < 1e-6, "{:?}", skewed);
+        // A put skew prices the 25 delta put above the ATM and the 25 delta call below it
+        assert!(skewed.risk_reversal < -0.02 && skewed.put_25d > skewed.atm_iv && skewed.call_25d < skewed.atm_iv);
+        let ctx = bs_context(R);
+        let mut chain = test_chain(vec![skewed_expiry("B", 0.5, 0.25, -0.2), skewed_expiry("A", 0.25, 0.2, 0.0)], 100.0, R);
+        let term = chain.term_structure(&ctx);
+        assert_eq!((term.shape, term.points[0].date.as_str()), (TermShape::Contango, "A"));
+        assert!(term.slope > 0.0 && chain.smile_metrics(&ctx).len() == 2);
+        chain.expiries[0] = skewed_expiry("B", 0.5, 0.15, 0.0);
+        assert_eq!(chain.term_structure(&ctx).shape, TermShape::Backwardation);
+        chain.expiries[0] = skewed_expiry("B", 0.5, 0.202, 0.0);
+        assert_eq!(chain.term_structure(&ctx).shape, TermShape::Flat);
+    }
+}
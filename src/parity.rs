@@ -0,0 +1,222 @@
+use super::options::{OptionChain, OptionExpiry, PriceBasis, PricingContext};
+use super::utils::csv_value;
+use csv::Writer;
+use std::error::Error;
+
+// Log-moneyness half-width around spot of the strikes the regression is fit to (deep in-the-money American quotes carry early exercise premium)
+const PARITY_BAND: f64 = 0.2;
+// Fewest call/put pairs a regression is fit to; the nearest pairs to spot are used when fewer lie inside PARITY_BAND
+const MIN_PARITY_PAIRS: usize = 3;
+
+/* One listed strike quoted on both sides
+synthetic = C - P at mid; [lower, upper] = [C_bid - P_ask, C_ask - P_bid], the range the synthetic forward can be traded in
+fitted = D (F - K) of the expiry's regression and deviation = synthetic - fitted; violation when fitted lies outside [lower, upper]
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParityPoint {
+    pub strike: f64,
+    pub call_mid: f64,
+    pub put_mid: f64,
+    pub synthetic: f64,
+    pub lower: f64,
+    pub upper: f64,
+    pub fitted: f64,
+    pub deviation: f64,
+    pub violation: bool,
+}
+
+/* Put-call parity fit of one expiry: C - P = D F - D K regressed on K by least squares
+discount = D (minus the slope), forward = F (the intercept over D), implied_rate = -ln(D) / yte
+implied_yield = rate - ln(F / spot) / yte, the carry the forward implies over the curve rate given the spot it is priced off
+borrow = implied_yield - div, the carry not explained by the dividend yield (hard-to-borrow cost when positive)
+rmse = root mean square deviation of the `fitted` pairs the regression was run on
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParityFit {
+    pub date: String,
+    pub yte: f64,
+    pub spot: f64,
+    pub forward: f64,
+    pub discount: f64,
+    pub implied_rate: f64,
+    pub rate: f64,
+    pub implied_yield: f64,
+    pub borrow: f64,
+    pub rmse: f64,
+    pub fitted: usize,
+    pub points: Vec<ParityPoint>,
+}
+
+impl ParityFit {
+    pub fn violations(&self) -> impl Iterator<Item = &ParityPoint> {
+        self.points.iter().filter(|p| p.violation)
+    }
+}
+
+// Strikes quoted with a positive mid on both the call and the put side, in strike order
+pub fn parity_points(expiry: &OptionExpiry) -> Vec<ParityPoint> {
+    let mut points: Vec<ParityPoint> = expiry
+        .calls
+        .iter()
+        .filter_map(|call| {
+            let put = expiry.puts.iter().find(|p| p.strike == call.strike)?;
+            let (call_mid, put_mid) = (call.get_price(PriceBasis::Mid), put.get_price(PriceBasis::Mid));
+            (call_mid > 0.0 && put_mid > 0.0).then_some(ParityPoint {
+                strike: call.strike,
+                call_mid,
+                put_mid,
+                synthetic: call_mid - put_mid,
+                lower: call.bid - put.ask,
+                upper: call.ask - put.bid,
+                fitted: f64::NAN,
+                deviation: f64::NAN,
+                violation: false,
+            })
+        })
+        .collect();
+    points.sort_by(|a, b| a.strike.total_cmp(&b.strike));
+    points
+}
+
+/* Fits put-call parity to one expiry (see ParityFit) priced off spot with the curve rate and dividend yield div
+Fails below MIN_PARITY_PAIRS quoted pairs, for an expired expiry, or when the regression implies no positive discount factor
+*/
+pub fn fit_parity(expiry: &OptionExpiry, spot: f64, rate: f64, div: f64) -> Result<ParityFit, String> {
+    if expiry.yte <= 0.0 {
+        return Err(format!("\nfit_parity() :: ERROR -> {} has expired (yte = {})", expiry.date, expiry.yte));
+    }
+    let mut points = parity_points(expiry);
+    if points.len() < MIN_PARITY_PAIRS {
+        return Err(format!("\nfit_parity() :: ERROR -> {} has {} call/put pairs quoted on both sides; at least {} are needed", expiry.date, points.len(), MIN_PARITY_PAIRS));
+    }
+    let mut nearest: Vec<&ParityPoint> = points.iter().collect();
+    nearest.sort_by(|a, b| (a.strike / spot).ln().abs().total_cmp(&(b.strike / spot).ln().abs()));
+    let band = nearest.iter().filter(|p| (p.strike / spot).ln().abs() <= PARITY_BAND).count().max(MIN_PARITY_PAIRS);
+    let sample: Vec<(f64, f64)> = nearest[..band].iter().map(|p| (p.strike, p.synthetic)).collect();
+    let n = sample.len() as f64;
+    let (mean_k, mean_y) = (sample.iter().map(|s| s.0).sum::<f64>() / n, sample.iter().map(|s| s.1).sum::<f64>() / n);
+    let sxx: f64 = sample.iter().map(|s| (s.0 - mean_k).powi(2)).sum();
+    let sxy: f64 = sample.iter().map(|s| (s.0 - mean_k) * (s.1 - mean_y)).sum();
+    let discount = -sxy / sxx;
+    if !discount.is_finite() || discount <= 0.0 {
+        return Err(format!("\nfit_parity() :: ERROR -> The {} call - put prices imply a discount factor of {}", expiry.date, discount));
+    }
+    let forward = (mean_y + discount * mean_k) / discount;
+    let rmse = (sample.iter().map(|(k, y)| (y - discount * (forward - k)).powi(2)).sum::<f64>() / n).sqrt();
+    for p in points.iter_mut() {
+        p.fitted = discount * (forward - p.strike);
+        p.deviation = p.synthetic - p.fitted;
+        p.violation = p.fitted < p.lower || p.fitted > p.upper;
+    }
+    let implied_yield = rate - (forward / spot).ln() / expiry.yte;
+    Ok(ParityFit {
+        date: expiry.date.clone(),
+        yte: expiry.yte,
+        spot,
+        forward,
+        discount,
+        implied_rate: -discount.ln() / expiry.yte,
+        rate,
+        implied_yield,
+        borrow: implied_yield - div,
+        rmse,
+        fitted: sample.len(),
+        points,
+    })
+}
+
+// Parity fits of every unexpired expiry of the chain off its quoted spot and dividends; expiries that cannot be fit are skipped
+pub fn chain_parity(chain: &OptionChain, ctx: &PricingContext) -> Vec<ParityFit> {
+    chain
+        .expiries
+        .iter()
+        .filter(|expiry| expiry.yte > 0.0)
+        .filter_map(|expiry| {
+            let (spot, div) = chain.quoted_inputs(expiry, ctx);
+            match fit_parity(expiry, spot, expiry.rate(&ctx.curve), div) {
+                Ok(fit) => Some(fit),
+                Err(e) => {
+                    eprintln!("{}\n\nchain_parity() :: Skipping expiry {}", e, expiry.date);
+                    None
+                },
+            }
+        })
+        .collect()
+}
+
+/* Saves the parity fits as a per-expiry summary csv and a per-strike deviation csv
+fit csv = Expiry, Yte, Spot, Forward, Discount, ImpliedRate, Rate, ImpliedYield, Borrow, Rmse, FittedPairs, Pairs, Violations
+deviation csv = Expiry, Strike, CallMid, PutMid, Synthetic, Lower, Upper, Fitted, Deviation, Violation
+*/
+pub fn parity_to_csv(fits: &[ParityFit], fit_csv_name: &str, deviation_csv_name: &str) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_path(fit_csv_name)?;
+    wtr.write_record(["Expiry", "Yte", "Spot", "Forward", "Discount", "ImpliedRate", "Rate", "ImpliedYield", "Borrow", "Rmse", "FittedPairs", "Pairs", "Violations"])?;
+    for f in fits {
+        let mut record = vec![f.date.clone()];
+        record.extend([f.yte, f.spot, f.forward, f.discount, f.implied_rate, f.rate, f.implied_yield, f.borrow, f.rmse].map(csv_value));
+        record.extend([f.fitted, f.points.len(), f.violations().count()].map(|n| n.to_string()));
+        wtr.write_record(&record)?;
+    }
+    wtr.flush()?;
+    let mut wtr = Writer::from_path(deviation_csv_name)?;
+    wtr.write_record(["Expiry", "Strike", "CallMid", "PutMid", "Synthetic", "Lower", "Upper", "Fitted", "Deviation", "Violation"])?;
+    for f in fits {
+        for p in &f.points {
+            let mut record = vec![f.date.clone()];
+            record.extend([p.strike, p.call_mid, p.put_mid, p.synthetic, p.lower, p.upper, p.fitted, p.deviation].map(csv_value));
+            record.push(p.violation.to_string());
+            wtr.write_record(&record)?;
+        }
+    }
+    wtr.flush()?;
+    println!("\nparity_to_csv() :: Successfully created {} and {} for {} expiries", fit_csv_name, deviation_csv_name, fits.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::test_fixtures::{bs_context, bs_expiry, test_chain};
+    use crate::options::ForwardBasis;
+
+    const SPOT: f64 = 100.0;
+    const RATE: f64 = 0.05;
+    const YTE: f64 = 0.5;
+
+    // European quotes 0.05 wide around Black-Scholes prices carried at RATE - q
+    fn expiry(q: f64) -> OptionExpiry {
+        let strikes: Vec<f64> = (0..13).map(|i| 70.0 + 5.0 * i as f64).collect();
+        bs_expiry("December 20 2024", YTE, RATE, q, &strikes, 0.05, |_| 0.25)
+    }
+
+    #[test]
+    fn recovers_forward_rate_and_borrow() {
+        let fit = fit_parity(&expiry(0.03), SPOT, RATE, 0.01).unwrap();
+        assert!((fit.forward - SPOT * ((RATE - 0.03) * YTE).exp()).abs() < 1e-9, "forward {}", fit.forward);
+        assert!((fit.implied_rate - RATE).abs() < 1e-9 && (fit.implied_yield - 0.03).abs() < 1e-9);
+        assert!((fit.borrow - 0.02).abs() < 1e-9 && fit.rmse < 1e-9);
+        assert_eq!((fit.fitted, fit.points.len(), fit.violations().count()), (8, 13, 0));
+        let chain = test_chain(vec![expiry(0.03)], 97.0, RATE);
+        let mut ctx = bs_context(RATE);
+        assert_eq!(chain.pricing_inputs(&chain.expiries[0], &ctx), (97.0, 0.0));
+        ctx.forward = ForwardBasis::Implied;
+        let (spot, div) = chain.pricing_inputs(&chain.expiries[0], &ctx);
+        assert!((spot - SPOT * (-0.03 * YTE).exp()).abs() < 1e-9 && div == 0.0, "implied spot {}", spot);
+    }
+
+    #[test]
+    fn flags_pairs_outside_the_bid_ask_band() {
+        let mut exp = expiry(0.0);
+        let put = exp.puts.iter_mut().find(|p| p.strike == 80.0).unwrap();
+        put.bid += 1.0;
+        put.ask += 1.0;
+        exp.calls.retain(|c| c.strike != 130.0);
+        let fit = fit_parity(&exp, SPOT, RATE, 0.0).unwrap();
+        let flagged: Vec<f64> = fit.violations().map(|p| p.strike).collect();
+        assert_eq!((flagged, fit.points.len()), (vec![80.0], 12));
+        assert!((fit.points[2].deviation + 1.0).abs() < 0.05);
+        let mut thin = expiry(0.0);
+        thin.puts.truncate(2);
+        assert!(fit_parity(&thin, SPOT, RATE, 0.0).is_err());
+    }
+}
@@ -0,0 +1,382 @@
+use super::finmath::{black_scholes, brentq, cnd, d_one, implied_vol, arbitrage_bounds, ImpVol, SolverStatus, IV_LOWER, IV_UPPER};
+use std::error::Error;
+
+const IV_TOL: f64 = 1e-8;
+pub const TREE_STEPS: usize = 200;
+
+/* Contract and market inputs shared by every pricing model
+s = the price of the underlying equity
+k = the contract strike price
+t = time to expiration (in years)
+r = the risk-free rate for the contract's tenor
+q = the underlying equity's continuous dividend yield
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct PricingInputs {
+    pub s: f64,
+    pub k: f64,
+    pub t: f64,
+    pub r: f64,
+    pub q: f64,
+    pub is_call: bool,
+}
+
+impl PricingInputs {
+    pub fn payoff(&self, spot: f64) -> f64 {
+        if self.is_call {
+            (spot - self.k).max(0.0)
+        } else {
+            (self.k - spot).max(0.0)
+        }
+    }
+}
+
+// Critical underlying price at each tree time step below (puts) or above (calls) which early exercise is optimal
+#[derive(Debug, Clone, Default)]
+pub struct ExerciseBoundary {
+    pub times: Vec<f64>,
+    pub spots: Vec<f64>,
+}
+
+pub trait PricingModel: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn price(&self, iv: f64, inputs: &PricingInputs) -> f64;
+    fn exercise_boundary(&self, _iv: f64, _inputs: &PricingInputs) -> Option<ExerciseBoundary> {
+        None
+    }
+    /* Inverts price() for the implied volatility with brentq() on [IV_LOWER, IV_UPPER]
+    Quotes below the American lower bound (the larger of immediate exercise and the European bound)
+    or at/above the upper bound (s for calls, k for puts) are flagged without attempting a solve
+    */
+    fn implied_vol(&self, price: f64, inputs: &PricingInputs) -> ImpVol {
+        let PricingInputs { s, k, t, r, q, is_call } = *inputs;
+        if !(price > 0.0 && s > 0.0 && k > 0.0 && t > 0.0) {
+            return ImpVol { iv: f64::NAN, iterations: 0, status: SolverStatus::InvalidInput };
+        }
+        let (european_lower, _) = arbitrage_bounds(s, k, t, r, q, is_call);
+        let lower = european_lower.max(inputs.payoff(s));
+        let upper = if is_call { s } else { k };
+        if price < lower {
+            return ImpVol { iv: f64::NAN, iterations: 0, status: SolverStatus::BelowIntrinsic };
+        }
+        if price >= upper {
+            return ImpVol { iv: f64::NAN, iterations: 0, status: SolverStatus::AboveUpperBound };
+        }
+        let f = |x: f64| self.price(x, inputs) - price;
+        let root = brentq(f, IV_LOWER, IV_UPPER, IV_TOL);
+        ImpVol { iv: root.root, iterations: root.iterations, status: root.status }
+    }
+}
+
+// European Black-Scholes; inverted with the Newton-Raphson solver in finmath::implied_vol()
+pub struct BlackScholes;
+
+impl PricingModel for BlackScholes {
+    fn name(&self) -> &'static str {
+        "bs"
+    }
+    fn price(&self, iv: f64, inputs: &PricingInputs) -> f64 {
+        black_scholes(iv, inputs.s, inputs.k, inputs.t, inputs.r, inputs.q, inputs.is_call)
+    }
+    fn implied_vol(&self, price: f64, inputs: &PricingInputs) -> ImpVol {
+        implied_vol(price, inputs.s, inputs.k, inputs.t, inputs.r, inputs.q, inputs.is_call)
+    }
+}
+
+// Cox-Ross-Rubinstein binomial tree with early exercise
+pub struct Binomial {
+    pub steps: usize,
+}
+
+impl Binomial {
+    pub fn price_with_boundary(&self, iv: f64, inputs: &PricingInputs) -> (f64, ExerciseBoundary) {
+        let n = self.steps.max(1);
+        let dt = inputs.t / n as f64;
+        let u = (iv * dt.sqrt()).exp();
+        let d = 1.0 / u;
+        let p = ((((inputs.r - inputs.q) * dt).exp() - d) / (u - d)).clamp(0.0, 1.0);
+        let disc = (-inputs.r * dt).exp();
+        let mut values: Vec<f64> = (0..=n)
+            .map(|j| inputs.payoff(inputs.s * u.powi(j as i32) * d.powi((n - j) as i32)))
+            .collect();
+        let mut boundary = ExerciseBoundary::default();
+        for i in (0..n).rev() {
+            let mut critical = f64::NAN;
+            for j in 0..=i {
+                let spot = inputs.s * u.powi(j as i32) * d.powi((i - j) as i32);
+                let cont = disc * (p * values[j + 1] + (1.0 - p) * values[j]);
+                let exercise = inputs.payoff(spot);
+                values[j] = if exercise > 0.0 && exercise >= cont {
+                    critical = record_critical(critical, spot, inputs.is_call);
+                    exercise
+                } else {
+                    cont
+                };
+            }
+            boundary.times.push(i as f64 * dt);
+            boundary.spots.push(critical);
+        }
+        boundary.times.reverse();
+        boundary.spots.reverse();
+        (values[0], boundary)
+    }
+}
+
+impl PricingModel for Binomial {
+    fn name(&self) -> &'static str {
+        "crr"
+    }
+    fn price(&self, iv: f64, inputs: &PricingInputs) -> f64 {
+        self.price_with_boundary(iv, inputs).0
+    }
+    fn exercise_boundary(&self, iv: f64, inputs: &PricingInputs) -> Option<ExerciseBoundary> {
+        Some(self.price_with_boundary(iv, inputs).1)
+    }
+}
+
+// Boyle trinomial tree with early exercise
+pub struct Trinomial {
+    pub steps: usize,
+}
+
+impl Trinomial {
+    pub fn price_with_boundary(&self, iv: f64, inputs: &PricingInputs) -> (f64, ExerciseBoundary) {
+        let n = self.steps.max(1);
+        let dt = inputs.t / n as f64;
+        let u = (iv * (2.0 * dt).sqrt()).exp();
+        let half_up = (iv * (dt / 2.0).sqrt()).exp();
+        let half_down = 1.0 / half_up;
+        let drift = ((inputs.r - inputs.q) * dt / 2.0).exp();
+        let pu = ((drift - half_down) / (half_up - half_down)).powi(2).clamp(0.0, 1.0);
+        let pd = ((half_up - drift) / (half_up - half_down)).powi(2).clamp(0.0, 1.0 - pu);
+        let pm = 1.0 - pu - pd;
+        let disc = (-inputs.r * dt).exp();
+        let spot_at = |level: i64| inputs.s * u.powi(level as i32);
+        let mut values: Vec<f64> = (0..=2 * n as i64).map(|j| inputs.payoff(spot_at(j - n as i64))).collect();
+        let mut boundary = ExerciseBoundary::default();
+        for i in (0..n).rev() {
+            let mut critical = f64::NAN;
+            for j in 0..=2 * i {
+                let spot = spot_at(j as i64 - i as i64);
+                let cont = disc * (pu * values[j + 2] + pm * values[j + 1] + pd * values[j]);
+                let exercise = inputs.payoff(spot);
+                values[j] = if exercise > 0.0 && exercise >= cont {
+                    critical = record_critical(critical, spot, inputs.is_call);
+                    exercise
+                } else {
+                    cont
+                };
+            }
+            boundary.times.push(i as f64 * dt);
+            boundary.spots.push(critical);
+        }
+        boundary.times.reverse();
+        boundary.spots.reverse();
+        (values[0], boundary)
+    }
+}
+
+impl PricingModel for Trinomial {
+    fn name(&self) -> &'static str {
+        "trinomial"
+    }
+    fn price(&self, iv: f64, inputs: &PricingInputs) -> f64 {
+        self.price_with_boundary(iv, inputs).0
+    }
+    fn exercise_boundary(&self, iv: f64, inputs: &PricingInputs) -> Option<ExerciseBoundary> {
+        Some(self.price_with_boundary(iv, inputs).1)
+    }
+}
+
+// Highest exercised spot for puts, lowest exercised spot for calls
+fn record_critical(critical: f64, spot: f64, is_call: bool) -> f64 {
+    if critical.is_nan() {
+        spot
+    } else if is_call {
+        critical.min(spot)
+    } else {
+        critical.max(spot)
+    }
+}
+
+/* Barone-Adesi-Whaley quadratic approximation for American options
+Calls without a dividend yield and puts without a positive rate are never exercised early and price as European
+*/
+pub struct BaroneAdesiWhaley;
+
+impl BaroneAdesiWhaley {
+    // Critical underlying price S* solving the BAW smooth-pasting condition
+    pub fn critical_price(&self, iv: f64, inputs: &PricingInputs) -> f64 {
+        let PricingInputs { k, t, r, q, is_call, .. } = *inputs;
+        let exponent = baw_exponent(iv, inputs);
+        let euro = |x: f64| black_scholes(iv, x, k, t, r, q, is_call);
+        if is_call {
+            let g = |x: f64| x - k - euro(x) - (1.0 - (-q * t).exp() * cnd(d_one(iv, x, k, t, r, q))) * x / exponent;
+            let mut upper = 2.0 * k;
+            while g(upper) < 0.0 && upper < 1e4 * k {
+                upper *= 2.0;
+            }
+            brentq(g, k, upper, 1e-10).root
+        } else {
+            let h = |x: f64| k - x - euro(x) + (1.0 - (-q * t).exp() * cnd(-d_one(iv, x, k, t, r, q))) * x / exponent;
+            brentq(h, k * 1e-8, k, 1e-10).root
+        }
+    }
+}
+
+impl PricingModel for BaroneAdesiWhaley {
+    fn name(&self) -> &'static str {
+        "baw"
+    }
+    fn price(&self, iv: f64, inputs: &PricingInputs) -> f64 {
+        let PricingInputs { s, k, t, r, q, is_call } = *inputs;
+        let euro = black_scholes(iv, s, k, t, r, q, is_call);
+        if (is_call && q <= 0.0) || (!is_call && r <= 0.0) {
+            return euro;
+        }
+        let exponent = baw_exponent(iv, inputs);
+        let critical = self.critical_price(iv, inputs);
+        if critical.is_nan() {
+            return euro;
+        }
+        let d1 = d_one(iv, critical, k, t, r, q);
+        if is_call {
+            if s >= critical {
+                return s - k;
+            }
+            let a2 = critical / exponent * (1.0 - (-q * t).exp() * cnd(d1));
+            euro + a2 * (s / critical).powf(exponent)
+        } else {
+            if s <= critical {
+                return k - s;
+            }
+            let a1 = -critical / exponent * (1.0 - (-q * t).exp() * cnd(-d1));
+            euro + a1 * (s / critical).powf(exponent)
+        }
+    }
+}
+
+// BAW characteristic root q2 (calls) or q1 (puts)
+fn baw_exponent(iv: f64, inputs: &PricingInputs) -> f64 {
+    let PricingInputs { t, r, q, is_call, .. } = *inputs;
+    let m = 2.0 * r / (iv * iv);
+    let n = 2.0 * (r - q) / (iv * iv);
+    let kt = 1.0 - (-r * t).exp();
+    let root = ((n - 1.0).powi(2) + 4.0 * m / kt).sqrt();
+    if is_call {
+        (-(n - 1.0) + root) / 2.0
+    } else {
+        (-(n - 1.0) - root) / 2.0
+    }
+}
+
+// Parses a model name: bs, crr (binomial), trinomial, baw
+pub fn model_from_str(name: &str) -> Result<Box<dyn PricingModel>, Box<dyn Error>> {
+    match name.to_lowercase().as_str() {
+        "bs" => Ok(Box::new(BlackScholes)),
+        "crr" | "binomial" => Ok(Box::new(Binomial { steps: TREE_STEPS })),
+        "trinomial" => Ok(Box::new(Trinomial { steps: TREE_STEPS })),
+        "baw" => Ok(Box::new(BaroneAdesiWhaley)),
+        _ => Err(format!("\nmodel_from_str() :: ERROR -> Unknown pricing model '{}'; expected one of bs, crr, trinomial, baw", name).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IV: f64 = 0.2;
+    const ATM_PUT: PricingInputs = PricingInputs { s: 100.0, k: 100.0, t: 1.0, r: 0.05, q: 0.0, is_call: false };
+
+    fn trees() -> [Box<dyn PricingModel>; 2] {
+        [Box::new(Binomial { steps: TREE_STEPS }), Box::new(Trinomial { steps: TREE_STEPS })]
+    }
+
+    #[test]
+    fn trees_converge_to_black_scholes_for_calls_without_dividends() {
+        for k in [80.0, 100.0, 120.0] {
+            for t in [0.25, 1.0] {
+                let inputs = PricingInputs { k, t, is_call: true, ..ATM_PUT };
+                let euro = BlackScholes.price(IV, &inputs);
+                for tree in trees() {
+                    let price = tree.price(IV, &inputs);
+                    assert!((price - euro).abs() < 0.02, "{} k {} t {}: {} vs {}", tree.name(), k, t, price, euro);
+                    assert!(tree.exercise_boundary(IV, &inputs).unwrap().spots.iter().all(|s| s.is_nan()), "{} exercised a call without dividends", tree.name());
+                }
+                assert_eq!(BaroneAdesiWhaley.price(IV, &inputs), euro);
+            }
+        }
+        let inputs = PricingInputs { is_call: true, ..ATM_PUT };
+        let euro = BlackScholes.price(IV, &inputs);
+        let coarse = (Binomial { steps: 50 }.price(IV, &inputs) - euro).abs();
+        let fine = (Binomial { steps: 800 }.price(IV, &inputs) - euro).abs();
+        assert!(fine < coarse / 4.0, "crr error {} at 800 steps vs {} at 50", fine, coarse);
+    }
+
+    #[test]
+    fn american_prices_dominate_european_and_pin_the_atm_put() {
+        let [crr, trinomial] = trees();
+        for model in [crr.as_ref(), trinomial.as_ref(), &BaroneAdesiWhaley] {
+            for is_call in [true, false] {
+                for k in [70.0, 90.0, 100.0, 110.0, 130.0] {
+                    let inputs = PricingInputs { k, q: 0.03, is_call, ..ATM_PUT };
+                    let (american, euro) = (model.price(IV, &inputs), BlackScholes.price(IV, &inputs));
+                    assert!(american >= euro - 0.01 && american >= inputs.payoff(inputs.s) - 1e-12, "{} k {} call {}: {} vs {}", model.name(), k, is_call, american, euro);
+                }
+            }
+            // Deep in the money the put is worth more exercised than its European value 200 e^(-rt) - 100
+            let deep = PricingInputs { k: 200.0, ..ATM_PUT };
+            assert!(BlackScholes.price(IV, &deep) < 91.0);
+            assert!((model.price(IV, &deep) - 100.0).abs() < 1e-9, "{} deep ITM put {}", model.name(), model.price(IV, &deep));
+        }
+        // 500 step trees; TREE_STEPS = 200 gives 6.0864 (CRR) and 6.0865 (trinomial)
+        assert!((Binomial { steps: 500 }.price(IV, &ATM_PUT) - 6.0888).abs() < 5e-5);
+        assert!((Trinomial { steps: 500 }.price(IV, &ATM_PUT) - 6.0889).abs() < 5e-5);
+        assert!((BaroneAdesiWhaley.price(IV, &ATM_PUT) - 6.0976).abs() < 5e-5);
+    }
+
+    #[test]
+    fn every_model_round_trips_implied_vol() {
+        let models: [Box<dyn PricingModel>; 6] = [
+            Box::new(BlackScholes),
+            Box::new(Binomial { steps: TREE_STEPS }),
+            Box::new(Trinomial { steps: TREE_STEPS }),
+            Box::new(BaroneAdesiWhaley),
+            model_from_str("CRR").unwrap(),
+            model_from_str("trinomial").unwrap(),
+        ];
+        for model in &models {
+            for (k, is_call) in [(90.0, false), (100.0, false), (110.0, false), (90.0, true), (110.0, true)] {
+                for sigma in [0.15, 0.4] {
+                    let inputs = PricingInputs { k, q: 0.02, is_call, ..ATM_PUT };
+                    let solved = model.implied_vol(model.price(sigma, &inputs), &inputs);
+                    assert!(solved.converged() && (solved.iv - sigma).abs() < 1e-6, "{} k {} call {} sigma {}: {:?}", model.name(), k, is_call, sigma, solved);
+                }
+            }
+            let deep = PricingInputs { k: 130.0, ..ATM_PUT };
+            assert_eq!((model.implied_vol(20.0, &deep).status, model.implied_vol(130.0, &deep).status), (SolverStatus::BelowIntrinsic, SolverStatus::AboveUpperBound));
+        }
+        assert!(model_from_str("heston").is_err());
+    }
+
+    #[test]
+    fn exercise_boundaries_are_monotone_in_time() {
+        let inputs = [ATM_PUT, PricingInputs { r: 0.02, q: 0.06, is_call: true, ..ATM_PUT }];
+        for tree in trees() {
+            // CRR alternates between the odd and even step lattices, so its boundary is monotone only up to one node spacing u
+            let spacing = if tree.name() == "crr" { (IV * (ATM_PUT.t / TREE_STEPS as f64).sqrt()).exp() * (1.0 + 1e-12) } else { 1.0 };
+            for inputs in inputs {
+                let boundary = tree.exercise_boundary(IV, &inputs).unwrap();
+                assert_eq!(boundary.times.len(), TREE_STEPS);
+                let exercised: Vec<f64> = boundary.spots.iter().copied().filter(|s| s.is_finite()).collect();
+                assert!(exercised.len() > TREE_STEPS * 9 / 10, "{} exercised at {} steps", tree.name(), exercised.len());
+                for w in exercised.windows(2) {
+                    let monotone = if inputs.is_call { w[1] <= w[0] * spacing && w[1] > inputs.k } else { w[1] * spacing >= w[0] && w[1] < inputs.k };
+                    assert!(monotone, "{} call {}: boundary {} then {}", tree.name(), inputs.is_call, w[0], w[1]);
+                }
+                let critical = BaroneAdesiWhaley.critical_price(IV, &inputs);
+                assert!((exercised[0] / critical - 1.0).abs() < 0.03, "{} boundary starts at {} vs BAW {}", tree.name(), exercised[0], critical);
+            }
+        }
+    }
+}
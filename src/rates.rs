@@ -0,0 +1,162 @@
+use csv::ReaderBuilder;
+use std::error::Error;
+use std::fs::File;
+use std::str::FromStr;
+
+pub const FEDFUNDS: f64 = 0.0533;
+// Largest flat rate accepted as a decimal; anything above is taken to be a percent typed by mistake ("5.3" for 5.3%)
+const MAX_FLAT_RATE: f64 = 1.0;
+
+/* Risk-Free Rate Curve
+Flat = one continuously compounded rate for every tenor
+PiecewiseLinear = (tenor in years, continuously compounded zero rate) pairs sorted by tenor;
+rates are linearly interpolated between tenors and held flat beyond the first/last tenor
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub enum RateCurve {
+    Flat(f64),
+    PiecewiseLinear(Vec<(f64, f64)>),
+}
+
+impl RateCurve {
+    pub fn piecewise_linear(mut points: Vec<(f64, f64)>) -> Result<RateCurve, String> {
+        if points.is_empty() {
+            return Err("\nRateCurve::piecewise_linear() :: ERROR -> No (tenor, rate) points were provided".to_string());
+        }
+        if let Some((t, r)) = points.iter().find(|(t, r)| !t.is_finite() || !r.is_finite() || *t < 0.0) {
+            return Err(format!("\nRateCurve::piecewise_linear() :: ERROR -> Invalid (tenor, rate) point ({}, {})", t, r));
+        }
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        points.dedup_by(|a, b| a.0 == b.0);
+        Ok(RateCurve::PiecewiseLinear(points))
+    }
+    /* Loads a zero curve from a csv of TENOR,RATE rows (e.g. Treasury bill yields)
+    TENOR is either years ("0.25") or a count with a unit suffix ("30D", "4 Wk", "3 Mo", "1Y")
+    RATE is a continuously compounded annual rate in percent ("5.27")
+    */
+    pub fn from_csv(csv_name: &str) -> Result<RateCurve, Box<dyn Error>> {
+        let file = File::open(csv_name)?;
+        let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(file);
+        let mut points: Vec<(f64, f64)> = Vec::new();
+        for result in rdr.records() {
+            let record = result?;
+            let line = record.position().map_or(0, |p| p.line());
+            let field = |i: usize, name: &str| record.get(i).ok_or_else(|| format!("\nRateCurve::from_csv() :: ERROR -> Line {} of {} has no {} column", line, csv_name, name));
+            let tenor = parse_tenor(field(0, "TENOR")?)?;
+            let rate = field(1, "RATE")?.trim().trim_end_matches('%').parse::<f64>()? / 100.0;
+            points.push((tenor, rate));
+        }
+        Ok(RateCurve::piecewise_linear(points)?)
+    }
+    pub fn rate(&self, t: f64) -> f64 {
+        match self {
+            RateCurve::Flat(r) => *r,
+            RateCurve::PiecewiseLinear(points) => {
+                let (first, last) = (points[0], points[points.len() - 1]);
+                if t <= first.0 {
+                    return first.1;
+                }
+                if t >= last.0 {
+                    return last.1;
+                }
+                let i = points.partition_point(|(tenor, _)| *tenor <= t);
+                let (t0, r0) = points[i - 1];
+                let (t1, r1) = points[i];
+                r0 + (r1 - r0) * (t - t0) / (t1 - t0)
+            },
+        }
+    }
+    // Same rate at every tenor, so the curve is fully described by rate(0.0)
+    pub fn is_flat(&self) -> bool {
+        match self {
+            RateCurve::Flat(_) => true,
+            RateCurve::PiecewiseLinear(points) => points.iter().all(|(_, r)| *r == points[0].1),
+        }
+    }
+}
+
+impl Default for RateCurve {
+    fn default() -> Self {
+        RateCurve::Flat(FEDFUNDS)
+    }
+}
+
+/* Parses either a flat decimal rate ("0.045") or the name of a TENOR,RATE csv file (rates in percent, see from_csv())
+Flat rates above MAX_FLAT_RATE in absolute value are rejected as percents
+*/
+impl FromStr for RateCurve {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<f64>() {
+            Ok(r) if r.is_finite() && r.abs() <= MAX_FLAT_RATE => Ok(RateCurve::Flat(r)),
+            Ok(r) => Err(format!("\nRateCurve::from_str() :: ERROR -> Flat rate {} is not a decimal rate (e.g. 0.045 for 4.5%)", r).into()),
+            Err(_) => RateCurve::from_csv(s),
+        }
+    }
+}
+
+// Converts a tenor label into years
+pub fn parse_tenor(label: &str) -> Result<f64, String> {
+    let cleaned = label.trim().to_lowercase();
+    if let Ok(years) = cleaned.parse::<f64>() {
+        return Ok(years);
+    }
+    let split = cleaned.find(|c: char| c.is_alphabetic()).unwrap_or(cleaned.len());
+    let (count, unit) = cleaned.split_at(split);
+    let count = count.trim().parse::<f64>()
+        .map_err(|_| format!("\nparse_tenor() :: ERROR -> Could not parse tenor '{}'", label))?;
+    let years_per_unit = match unit.chars().next() {
+        Some('d') => 1.0 / 365.0,
+        Some('w') => 7.0 / 365.0,
+        Some('m') => 1.0 / 12.0,
+        Some('y') => 1.0,
+        _ => return Err(format!("\nparse_tenor() :: ERROR -> Unknown tenor unit in '{}'", label)),
+    };
+    Ok(count * years_per_unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn piecewise_linear_interpolates_and_extrapolates_flat() {
+        let curve = RateCurve::piecewise_linear(vec![(1.0, 0.05), (0.25, 0.04), (0.5, 0.045), (0.5, 0.07)]).unwrap();
+        assert_eq!(curve, RateCurve::PiecewiseLinear(vec![(0.25, 0.04), (0.5, 0.045), (1.0, 0.05)]));
+        for (t, expected) in [(0.0, 0.04), (0.1, 0.04), (0.25, 0.04), (0.375, 0.0425), (0.5, 0.045), (0.75, 0.0475), (1.0, 0.05), (30.0, 0.05)] {
+            assert!((curve.rate(t) - expected).abs() < 1e-15, "rate({}) = {}", t, curve.rate(t));
+        }
+        assert!(RateCurve::piecewise_linear(Vec::new()).is_err());
+        assert!(RateCurve::piecewise_linear(vec![(-0.5, 0.04)]).is_err() && RateCurve::piecewise_linear(vec![(0.5, f64::NAN)]).is_err());
+        assert_eq!(RateCurve::default().rate(2.0), FEDFUNDS);
+        assert!(RateCurve::default().is_flat() && RateCurve::piecewise_linear(vec![(0.25, 0.04), (1.0, 0.04)]).unwrap().is_flat() && !curve.is_flat());
+    }
+
+    #[test]
+    fn parses_tenors_flat_rates_and_csv_curves() {
+        for (label, years) in [("0.25", 0.25), ("30D", 30.0 / 365.0), ("4 Wk", 28.0 / 365.0), ("3 Mo", 0.25), ("1Y", 1.0), (" 2 yr ", 2.0)] {
+            assert!((parse_tenor(label).unwrap() - years).abs() < 1e-15, "{}", label);
+        }
+        assert!(parse_tenor("3 Qtr").is_err() && parse_tenor("Mo").is_err());
+        assert_eq!("0.045".parse::<RateCurve>().unwrap(), RateCurve::Flat(0.045));
+        assert_eq!("-0.005".parse::<RateCurve>().unwrap(), RateCurve::Flat(-0.005));
+        let err = "5.3".parse::<RateCurve>().unwrap_err().to_string();
+        assert!(err.contains("not a decimal rate"), "{}", err);
+        let dir = std::env::temp_dir().join(format!("markets_rates_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let csv_name = dir.join("curve.csv");
+        fs::write(&csv_name, "Tenor,Rate\n1 Mo,5.50\n3 Mo,5.40%\n1Y,5.00\n").unwrap();
+        let curve = csv_name.to_str().unwrap().parse::<RateCurve>().unwrap();
+        for (t, expected) in [(1.0 / 12.0, 0.055), (0.25, 0.054), (1.0, 0.05), (2.0, 0.05)] {
+            assert!((curve.rate(t) - expected).abs() < 1e-15, "rate({}) = {}", t, curve.rate(t));
+        }
+        fs::write(&csv_name, "Tenor,Rate\n1 Mo,n/a\n").unwrap();
+        assert!(RateCurve::from_csv(csv_name.to_str().unwrap()).is_err());
+        fs::write(&csv_name, "Tenor\n1 Mo\n").unwrap();
+        let err = RateCurve::from_csv(csv_name.to_str().unwrap()).unwrap_err().to_string();
+        assert!(err.contains("Line 2") && err.contains("no RATE column"), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
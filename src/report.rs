@@ -0,0 +1,306 @@
+use anyhow::{anyhow, Context, Result};
+use super::graphing::{tseries_plot_label, DENSITY_PLOT_LABEL, EXPOSURE_PLOT_LABELS, SURFACE_PLOT_LABELS, SMILE_PLOT_LABELS, TSERIES_PLOT_COLUMNS, VOL_SURFACE_PLOT_LABELS};
+use super::indicators::DEFAULT_INDICATORS;
+use printpdf::image_crate::codecs::png::PngDecoder;
+use printpdf::path::PaintMode;
+use printpdf::{BuiltinFont, Color, Image, ImageTransform, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference, Rect, Rgb};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+
+pub const PDFDIR: &str = "pdf_out/";
+// A4 landscape page and layout in mm
+const PAGESIZE: (f32, f32) = (297.0, 210.0);
+const MARGIN: f32 = 15.0;
+const HEADERHEIGHT: f32 = 25.0;
+const FOOTERHEIGHT: f32 = 15.0;
+const ROWHEIGHT: f32 = 5.5;
+const TABLECOLUMNS: usize = 2;
+const LABELCHARS: usize = 55;
+const VALUECHARS: usize = 22;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PageContent {
+    Table(Vec<(String, String)>),
+    Chart(String),
+}
+
+// A titled report section; it starts on a new page, gets a table of contents entry and a bookmark
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportSection {
+    pub title: String,
+    pub pages: Vec<PageContent>,
+}
+
+// Label/value rows that fit on one table page
+fn rows_per_page() -> usize {
+    let rows = ((PAGESIZE.1 - HEADERHEIGHT - FOOTERHEIGHT - MARGIN) / ROWHEIGHT).floor() as usize;
+    TABLECOLUMNS * (rows - 1)
+}
+
+fn truncate(s: &str, max_chars: usize) -> String {
+    if s.chars().count() <= max_chars {
+        s.to_string()
+    } else {
+        format!("{}...", s.chars().take(max_chars - 3).collect::<String>())
+    }
+}
+
+// Finviz fundamentals written by fetch_finviz_info() as (label, value) rows sorted by label
+pub fn read_fundamentals(fv_csv_name: &str) -> Result<Vec<(String, String)>> {
+    let mut rdr = csv::Reader::from_path(fv_csv_name).with_context(|| format!("\nread_fundamentals() :: ERROR -> Failed to open {}", fv_csv_name))?;
+    let mut rows = Vec::new();
+    for result in rdr.records() {
+        let record = result?;
+        rows.push((record.get(0).unwrap_or_default().to_string(), record.get(1).unwrap_or_default().to_string()));
+    }
+    rows.sort();
+    Ok(rows)
+}
+
+// <label><YYYYMMDD> chart labels of per-expiry charts of a ticker found in img_dir, in date order
+fn dated_labels(img_dir: &str, ticker: &str, label: &str) -> Vec<String> {
+    let prefix = format!("{}_{}", ticker, label);
+    let mut dated: Vec<String> = fs::read_dir(img_dir)
+        .map(|entries| entries.filter_map(|entry| entry.ok()?.file_name().into_string().ok()).collect::<Vec<String>>())
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|name| {
+            let date = name.strip_prefix(&prefix)?.split('_').next()?.to_string();
+            (!date.is_empty() && date.chars().all(|c| c.is_ascii_digit())).then(|| format!("{}{}", label, date))
+        })
+        .collect();
+    dated.sort();
+    dated.dedup();
+    dated
+}
+
+/* Sections of the PNG charts in img_dir named <TICKER>_<label>_<stamp>.png by graphing.rs
+stamps = run stamps (<date>_<time>) of the charted csv files, searched in order for each chart
+Charts are ordered as main.rs generates them (per-expiry density and exposure charts by date); missing charts are skipped
+and sections without any chart are omitted
+*/
+pub fn chart_sections(img_dir: &str, ticker: &str, stamps: &[String]) -> Vec<ReportSection> {
+    let tseries: Vec<String> = TSERIES_PLOT_COLUMNS.iter().map(|c| tseries_plot_label(c)).collect();
+    let indicators: Vec<String> = DEFAULT_INDICATORS.iter().map(|i| i.to_lowercase()).collect();
+    let volatility: Vec<String> = ["ivrv"].iter().chain(SMILE_PLOT_LABELS.iter()).chain(VOL_SURFACE_PLOT_LABELS.iter()).map(|l| l.to_string()).collect();
+    let densities: Vec<String> = std::iter::once(DENSITY_PLOT_LABEL.to_string()).chain(dated_labels(img_dir, ticker, DENSITY_PLOT_LABEL)).collect();
+    let exposure: Vec<String> = EXPOSURE_PLOT_LABELS.iter().map(|l| l.to_string()).chain(dated_labels(img_dir, ticker, EXPOSURE_PLOT_LABELS[0])).collect();
+    let surfaces: Vec<String> = SURFACE_PLOT_LABELS.iter().flat_map(|l| [format!("c{}", l), format!("p{}", l)]).collect();
+    let mut sections = Vec::new();
+    for (title, labels) in [("Time Series", tseries), ("Technical Indicators", indicators), ("Volatility", volatility), ("Risk-Neutral Densities", densities), ("Dealer Exposure", exposure), ("Option Surfaces", surfaces)] {
+        let pages: Vec<PageContent> = labels
+            .iter()
+            .filter_map(|label| stamps.iter().map(|stamp| Path::new(img_dir).join(format!("{}_{}_{}.png", ticker, label, stamp))).find(|path| path.exists()))
+            .map(|path| PageContent::Chart(path.to_string_lossy().to_string()))
+            .collect();
+        if !pages.is_empty() {
+            sections.push(ReportSection { title: title.to_string(), pages });
+        }
+    }
+    sections
+}
+
+// The fundamentals table (paginated) followed by the chart sections; an unreadable fundamentals csv only drops its section
+pub fn report_sections(fv_csv_name: &str, img_dir: &str, ticker: &str, stamps: &[String]) -> Vec<ReportSection> {
+    let mut sections = Vec::new();
+    match read_fundamentals(fv_csv_name) {
+        Ok(rows) if !rows.is_empty() => {
+            let pages = rows.chunks(rows_per_page()).map(|chunk| PageContent::Table(chunk.to_vec())).collect();
+            sections.push(ReportSection { title: "Fundamentals".to_string(), pages });
+        },
+        Ok(_) => eprintln!("\nreport_sections() :: {} has no fundamentals; continuing without the fundamentals table", fv_csv_name),
+        Err(e) => eprintln!("{}\n\nreport_sections() :: Continuing without the fundamentals table", e),
+    }
+    sections.extend(chart_sections(img_dir, ticker, stamps));
+    sections
+}
+
+fn grey(level: f32) -> Color {
+    Color::Rgb(Rgb::new(level, level, level, None))
+}
+
+// Section heading and the page footer shared by every content page
+fn draw_frame(layer: &PdfLayerReference, fonts: &(IndirectFontRef, IndirectFontRef), heading: &str, footer: &str, page: usize, pages: usize) {
+    let (regular, bold) = fonts;
+    layer.use_text(heading, 18.0, Mm(MARGIN), Mm(PAGESIZE.1 - MARGIN - 5.0), bold);
+    layer.set_fill_color(grey(0.4));
+    layer.use_text(footer, 8.0, Mm(MARGIN), Mm(MARGIN - 5.0), regular);
+    layer.use_text(format!("Page {} of {}", page, pages), 8.0, Mm(PAGESIZE.0 - MARGIN - 20.0), Mm(MARGIN - 5.0), regular);
+    layer.set_fill_color(grey(0.0));
+}
+
+fn draw_table(layer: &PdfLayerReference, fonts: &(IndirectFontRef, IndirectFontRef), rows: &[(String, String)]) {
+    let (regular, bold) = fonts;
+    let per_column = rows_per_page() / TABLECOLUMNS;
+    let column_width = (PAGESIZE.0 - 2.0 * MARGIN) / TABLECOLUMNS as f32;
+    let top = PAGESIZE.1 - MARGIN - HEADERHEIGHT;
+    for (c, column) in rows.chunks(per_column).enumerate() {
+        let x = MARGIN + c as f32 * column_width;
+        layer.set_fill_color(grey(0.85));
+        layer.add_rect(Rect::new(Mm(x), Mm(top - 1.5), Mm(x + column_width - 5.0), Mm(top + ROWHEIGHT - 1.5)).with_mode(PaintMode::Fill));
+        layer.set_fill_color(grey(0.0));
+        layer.use_text("Label", 9.0, Mm(x + 1.0), Mm(top), bold);
+        layer.use_text("Value", 9.0, Mm(x + 0.72 * column_width), Mm(top), bold);
+        for (r, (label, value)) in column.iter().enumerate() {
+            let y = top - (r + 1) as f32 * ROWHEIGHT;
+            layer.use_text(truncate(label, LABELCHARS), 9.0, Mm(x + 1.0), Mm(y), regular);
+            layer.use_text(truncate(value, VALUECHARS), 9.0, Mm(x + 0.72 * column_width), Mm(y), regular);
+        }
+    }
+}
+
+// Scales a PNG chart to the content area of the page and centres it
+fn draw_chart(layer: PdfLayerReference, path: &str) -> Result<()> {
+    let file = File::open(path).with_context(|| format!("\ndraw_chart() :: ERROR -> Failed to open {}", path))?;
+    let decoder = PngDecoder::new(file).map_err(|e| anyhow!("\ndraw_chart() :: ERROR -> Failed to decode {}: {}", path, e))?;
+    let image = Image::try_from(decoder).map_err(|e| anyhow!("\ndraw_chart() :: ERROR -> Failed to read {}: {}", path, e))?;
+    let (width_px, height_px) = (image.image.width.0 as f32, image.image.height.0 as f32);
+    let (area_w, area_h) = (PAGESIZE.0 - 2.0 * MARGIN, PAGESIZE.1 - MARGIN - HEADERHEIGHT - FOOTERHEIGHT);
+    let dpi = (width_px * 25.4 / area_w).max(height_px * 25.4 / area_h);
+    let (width_mm, height_mm) = (width_px * 25.4 / dpi, height_px * 25.4 / dpi);
+    let transform = ImageTransform {
+        translate_x: Some(Mm(MARGIN + 0.5 * (area_w - width_mm))),
+        translate_y: Some(Mm(FOOTERHEIGHT + 0.5 * (area_h - height_mm))),
+        dpi: Some(dpi),
+        ..Default::default()
+    };
+    image.add_to_layer(layer, transform);
+    Ok(())
+}
+
+fn add_page(doc: &PdfDocumentReference, name: &str) -> (printpdf::PdfPageIndex, PdfLayerReference) {
+    let (page, layer) = doc.add_page(Mm(PAGESIZE.0), Mm(PAGESIZE.1), name);
+    (page, doc.get_page(page).get_layer(layer))
+}
+
+/* Writes a paginated PDF report: a cover page, a table of contents, then every section starting on a new page
+cover_lines = run details listed on the cover page; charts that cannot be read are replaced by a note on their page
+Returns the number of pages written
+*/
+pub fn generate_pdf_report(ticker: &str, cover_lines: &[String], sections: &[ReportSection], pdf_name: &str) -> Result<usize> {
+    let title = format!("{} Market Report", ticker);
+    let pages = 2 + sections.iter().map(|s| s.pages.len()).sum::<usize>();
+    let (doc, cover_page, cover_layer) = PdfDocument::new(title.as_str(), Mm(PAGESIZE.0), Mm(PAGESIZE.1), "Cover");
+    let regular = doc.add_builtin_font(BuiltinFont::Helvetica).map_err(|e| anyhow!("\ngenerate_pdf_report() :: ERROR -> {}", e))?;
+    let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold).map_err(|e| anyhow!("\ngenerate_pdf_report() :: ERROR -> {}", e))?;
+    let fonts = (regular, bold);
+    let layer = doc.get_page(cover_page).get_layer(cover_layer);
+    layer.use_text(title.as_str(), 32.0, Mm(MARGIN + 10.0), Mm(0.65 * PAGESIZE.1), &fonts.1);
+    for (i, line) in cover_lines.iter().enumerate() {
+        layer.use_text(line.as_str(), 12.0, Mm(MARGIN + 10.0), Mm(0.65 * PAGESIZE.1 - 15.0 - 7.0 * i as f32), &fonts.0);
+    }
+    doc.add_bookmark("Cover", cover_page);
+    let (toc_page, layer) = add_page(&doc, "Contents");
+    draw_frame(&layer, &fonts, "Contents", &title, 2, pages);
+    let mut start = 3;
+    for (i, section) in sections.iter().enumerate() {
+        let y = PAGESIZE.1 - MARGIN - HEADERHEIGHT - 8.0 * i as f32;
+        layer.use_text(section.title.as_str(), 12.0, Mm(MARGIN + 5.0), Mm(y), &fonts.0);
+        layer.use_text(start.to_string(), 12.0, Mm(PAGESIZE.0 - MARGIN - 20.0), Mm(y), &fonts.0);
+        start += section.pages.len();
+    }
+    doc.add_bookmark("Contents", toc_page);
+    let mut page_number = 2;
+    for section in sections {
+        for (i, content) in section.pages.iter().enumerate() {
+            page_number += 1;
+            let (page, layer) = add_page(&doc, &section.title);
+            if i == 0 {
+                doc.add_bookmark(section.title.as_str(), page);
+            }
+            let heading = if i == 0 { section.title.clone() } else { format!("{} (continued)", section.title) };
+            draw_frame(&layer, &fonts, &heading, &title, page_number, pages);
+            match content {
+                PageContent::Table(rows) => draw_table(&layer, &fonts, rows),
+                PageContent::Chart(path) => {
+                    if let Err(e) = draw_chart(layer.clone(), path) {
+                        eprintln!("{}", e);
+                        layer.use_text(format!("Could not load chart {}", path), 10.0, Mm(MARGIN), Mm(PAGESIZE.1 / 2.0), &fonts.0);
+                    }
+                },
+            }
+        }
+    }
+    let file = File::create(pdf_name).with_context(|| format!("\ngenerate_pdf_report() :: ERROR -> Failed to create {}", pdf_name))?;
+    doc.save(&mut BufWriter::new(file)).map_err(|e| anyhow!("\ngenerate_pdf_report() :: ERROR -> Failed to save {}: {}", pdf_name, e))?;
+    Ok(pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::charts::{ChartBackend, ChartFormat, LineChart, PlottersBackend, Series, SeriesStyle, XAxis};
+    use std::{env, fs};
+
+    #[test]
+    fn fundamentals_paginate_and_charts_follow_generation_order() {
+        let dir = env::temp_dir().join(format!("markets_report_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let fv_csv = dir.join("TEST_fv_2024-06-03_10-00-00.csv");
+        let mut rows = String::from("Label,TEST\n");
+        for i in 0..(rows_per_page() + 5) {
+            rows.push_str(&format!("Metric {:03},{}\n", rows_per_page() + 5 - i, i));
+        }
+        fs::write(&fv_csv, rows).unwrap();
+        for label in ["pdelta", "rsi14", "close", "cdelta"] {
+            fs::write(dir.join(format!("TEST_{}_2024-06-03_10-00-00.png", label)), b"").unwrap();
+        }
+        fs::write(dir.join("TEST_ivrv_2024-06-04_09-00-00.png"), b"").unwrap();
+        fs::write(dir.join("TEST_open_2024-06-05_10-00-00.png"), b"").unwrap();
+        for label in ["rnd20241220", "rnd", "rnd20240621", "gexprofile", "gex20240621", "gex"] {
+            fs::write(dir.join(format!("TEST_{}_2024-06-03_10-00-00.png", label)), b"").unwrap();
+        }
+        fs::write(dir.join("OTHER_high_2024-06-03_10-00-00.png"), b"").unwrap();
+        let stamps = ["2024-06-03_10-00-00".to_string(), "2024-06-04_09-00-00".to_string()];
+        let sections = report_sections(fv_csv.to_str().unwrap(), dir.to_str().unwrap(), "TEST", &stamps);
+        let titles: Vec<&str> = sections.iter().map(|s| s.title.as_str()).collect();
+        assert_eq!(titles, ["Fundamentals", "Time Series", "Technical Indicators", "Volatility", "Risk-Neutral Densities", "Dealer Exposure", "Option Surfaces"]);
+        match &sections[0].pages[..] {
+            [PageContent::Table(first), PageContent::Table(last)] => {
+                assert_eq!((first.len(), last.len()), (rows_per_page(), 5));
+                assert_eq!(first[0].0, "Metric 001");
+            },
+            pages => panic!("expected two table pages, got {:?}", pages),
+        }
+        let charts = |section: &ReportSection| -> Vec<String> {
+            section.pages.iter().map(|p| match p {
+                PageContent::Chart(path) => Path::new(path).file_name().unwrap().to_string_lossy().to_string(),
+                PageContent::Table(_) => panic!("unexpected table"),
+            }).collect()
+        };
+        assert_eq!(charts(&sections[4]), ["TEST_rnd_2024-06-03_10-00-00.png", "TEST_rnd20240621_2024-06-03_10-00-00.png", "TEST_rnd20241220_2024-06-03_10-00-00.png"]);
+        assert_eq!(charts(&sections[5]), ["TEST_gex_2024-06-03_10-00-00.png", "TEST_gexprofile_2024-06-03_10-00-00.png", "TEST_gex20240621_2024-06-03_10-00-00.png"]);
+        assert_eq!(charts(&sections[6]), ["TEST_cdelta_2024-06-03_10-00-00.png", "TEST_pdelta_2024-06-03_10-00-00.png"]);
+        assert_eq!(sections[1].pages.len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn writes_pdf_with_embedded_charts() {
+        let dir = env::temp_dir().join(format!("markets_report_pdf_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let backend = PlottersBackend { formats: vec![ChartFormat::Png], size: (640, 480) };
+        let chart = LineChart {
+            title: "TEST Close".to_string(),
+            x_label: "Day".to_string(),
+            y_label: "Close".to_string(),
+            x_axis: XAxis::Number,
+            log_y: false,
+            series: vec![Series { title: "Close".to_string(), points: (0..50).map(|i| (i as f64, 100.0 + (i as f64 / 5.0).sin())).collect(), style: SeriesStyle::Line }],
+        };
+        let png = backend.line_chart(&chart, dir.join("TEST_close_2024-06-03_10-00-00").to_str().unwrap()).unwrap().remove(0);
+        let sections = vec![
+            ReportSection { title: "Fundamentals".to_string(), pages: vec![PageContent::Table(vec![("P/E".to_string(), "31.2".to_string())])] },
+            ReportSection { title: "Time Series".to_string(), pages: vec![PageContent::Chart(png), PageContent::Chart(dir.join("missing.png").to_string_lossy().to_string())] },
+        ];
+        let pdf_name = dir.join("TEST_2024-06-03_10-00-00.pdf");
+        let pages = generate_pdf_report("TEST", &["Generated 2024-06-03 10:00:00".to_string()], &sections, pdf_name.to_str().unwrap()).unwrap();
+        assert_eq!(pages, 5);
+        let bytes = fs::read(&pdf_name).unwrap();
+        assert_eq!(&bytes[..5], b"%PDF-");
+        let doc = printpdf::lopdf::Document::load_mem(&bytes).unwrap();
+        assert_eq!(doc.get_pages().len(), 5);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,371 @@
+use super::options::{fetch_option_chain, chain_from_csv, Option as Contract, OptionChain, OptionExpiry, EXPDATEFMT};
+use super::calendar::{year_fraction, DayCount};
+use super::rates::FEDFUNDS;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+const OURLP1: &str = "youll have to adapt this code to use another option chain source";
+const OURLP2: &str = "&showAll=True";
+pub const SOURCECONFIG: &str = "chain_source.json";
+const SOURCEEXAMPLE: &str = "chain_source.example.json";
+
+// A provider of option chain snapshots for a ticker
+pub trait OptionChainSource {
+    fn name(&self) -> String;
+    fn fetch(&self, ticker: &str) -> Result<OptionChain, Box<dyn Error>>;
+}
+
+// The Playwright scraper in options::fetch_option_chain(); the chain page url is url_prefix + TICKER + url_suffix
+pub struct BrowserSource {
+    pub url_prefix: String,
+    pub url_suffix: String,
+}
+
+impl Default for BrowserSource {
+    fn default() -> Self {
+        BrowserSource { url_prefix: OURLP1.to_string(), url_suffix: OURLP2.to_string() }
+    }
+}
+
+impl OptionChainSource for BrowserSource {
+    fn name(&self) -> String {
+        "browser".to_string()
+    }
+    fn fetch(&self, ticker: &str) -> Result<OptionChain, Box<dyn Error>> {
+        fetch_option_chain(ticker, &format!("{}{}{}", self.url_prefix, ticker, self.url_suffix))
+    }
+}
+
+/* Where each value lives in a JSON option chain response, as JSON pointers (RFC 6901, e.g. "/data/options/0")
+contracts = pointer from the document root to the array of contracts ("" when the root itself is the array)
+spot, div_yield, quote_time = pointers from the document root; an empty pointer means the value is not provided
+(div_yield then defaults to 0 and quote_time to the fetch time; spot is required)
+expiration .. open_interest = pointers from each contract object; missing numeric fields read as 0
+expiration_format = chrono format of the expiration field, or "unix" for epoch seconds
+call_value / put_value = the values of the option_type field that mark a call / put (compared case-insensitively, e.g.
+"call" / "put" or "C" / "P"); contracts with a missing or other option_type are skipped
+*/
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FieldMapping {
+    pub contracts: String,
+    pub spot: String,
+    pub div_yield: String,
+    pub quote_time: String,
+    pub expiration: String,
+    pub expiration_format: String,
+    pub strike: String,
+    pub option_type: String,
+    pub call_value: String,
+    pub put_value: String,
+    pub last: String,
+    pub change: String,
+    pub volume: String,
+    pub bid: String,
+    pub ask: String,
+    pub open_interest: String,
+}
+
+impl Default for FieldMapping {
+    fn default() -> Self {
+        FieldMapping {
+            contracts: "/options".to_string(),
+            spot: "/underlying/price".to_string(),
+            div_yield: "/underlying/dividend_yield".to_string(),
+            quote_time: "/quote_time".to_string(),
+            expiration: "/expiration".to_string(),
+            expiration_format: "%Y-%m-%d".to_string(),
+            strike: "/strike".to_string(),
+            option_type: "/type".to_string(),
+            call_value: "call".to_string(),
+            put_value: "put".to_string(),
+            last: "/last".to_string(),
+            change: "/change".to_string(),
+            volume: "/volume".to_string(),
+            bid: "/bid".to_string(),
+            ask: "/ask".to_string(),
+            open_interest: "/open_interest".to_string(),
+        }
+    }
+}
+
+// Reads a number (or numeric string such as "1,234.5") at a JSON pointer
+fn json_number(value: &Value, pointer: &str) -> Option<f64> {
+    if pointer.is_empty() {
+        return None;
+    }
+    match value.pointer(pointer)? {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().replace(',', "").parse::<f64>().ok(),
+        _ => None,
+    }
+}
+
+fn json_string(value: &Value, pointer: &str) -> Option<String> {
+    if pointer.is_empty() {
+        return None;
+    }
+    match value.pointer(pointer)? {
+        Value::String(s) => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/* Builds an OptionChain from a JSON option chain document using a FieldMapping
+Contracts are grouped by expiration (sorted by date, strikes ascending) and each expiry's yte is measured from the quote time
+*/
+pub fn chain_from_json(ticker: &str, doc: &Value, mapping: &FieldMapping, source: &str) -> Result<OptionChain, Box<dyn Error>> {
+    let contracts = doc
+        .pointer(&mapping.contracts)
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| format!("\nchain_from_json() :: ERROR -> No contract array at '{}'", mapping.contracts))?;
+    let current_price = json_number(doc, &mapping.spot)
+        .ok_or_else(|| format!("\nchain_from_json() :: ERROR -> No underlying price at '{}'", mapping.spot))?;
+    let div_yield = json_number(doc, &mapping.div_yield).unwrap_or(0.0);
+    let quote_time = match json_string(doc, &mapping.quote_time) {
+        Some(t) => DateTime::parse_from_rfc3339(&t)?.with_timezone(&Utc),
+        None => Utc::now(),
+    };
+    let mut by_date: BTreeMap<NaiveDate, (Vec<Contract>, Vec<Contract>)> = BTreeMap::new();
+    for contract in contracts {
+        let exp_str = match json_string(contract, &mapping.expiration) {
+            Some(e) => e,
+            None => {
+                eprintln!("\nchain_from_json() :: ERROR -> Skipping contract without an expiration at '{}': {}", mapping.expiration, contract);
+                continue;
+            },
+        };
+        let exp_date = if mapping.expiration_format == "unix" {
+            exp_str.parse::<i64>().ok().and_then(|secs| DateTime::from_timestamp(secs, 0)).map(|dt| dt.date_naive())
+        } else {
+            NaiveDate::parse_from_str(&exp_str, &mapping.expiration_format).ok()
+        };
+        let exp_date = match exp_date {
+            Some(d) => d,
+            None => {
+                eprintln!("\nchain_from_json() :: ERROR -> Could not parse expiration '{}' with format '{}'", exp_str, mapping.expiration_format);
+                continue;
+            },
+        };
+        let strike = match json_number(contract, &mapping.strike) {
+            Some(k) => k,
+            None => {
+                eprintln!("\nchain_from_json() :: ERROR -> Skipping contract without a strike at '{}': {}", mapping.strike, contract);
+                continue;
+            },
+        };
+        let is_call = match json_string(contract, &mapping.option_type) {
+            Some(t) if t.eq_ignore_ascii_case(&mapping.call_value) => true,
+            Some(t) if t.eq_ignore_ascii_case(&mapping.put_value) => false,
+            t => {
+                eprintln!("\nchain_from_json() :: ERROR -> Skipping contract whose type {:?} at '{}' is neither '{}' nor '{}': {}", t, mapping.option_type, mapping.call_value, mapping.put_value, contract);
+                continue;
+            },
+        };
+        let field = |pointer: &str| json_number(contract, pointer).unwrap_or(0.0);
+        let opt = Contract {
+            last: field(&mapping.last),
+            change: field(&mapping.change),
+            vol: field(&mapping.volume),
+            bid: field(&mapping.bid),
+            ask: field(&mapping.ask),
+            open_int: field(&mapping.open_interest),
+            strike,
+            yte: 0.0,
+            is_call,
+        };
+        let (calls, puts) = by_date.entry(exp_date).or_default();
+        if is_call {
+            calls.push(opt);
+        } else {
+            puts.push(opt);
+        }
+    }
+    let mut expiries = Vec::new();
+    for (exp_date, (mut calls, mut puts)) in by_date {
+        let yte = year_fraction(quote_time, exp_date, DayCount::default());
+        for opt in calls.iter_mut().chain(puts.iter_mut()) {
+            opt.yte = yte;
+        }
+        calls.sort_by(|a, b| a.strike.total_cmp(&b.strike));
+        puts.sort_by(|a, b| a.strike.total_cmp(&b.strike));
+        expiries.push(OptionExpiry { date: exp_date.format(EXPDATEFMT).to_string(), yte, calls, puts });
+    }
+    if expiries.is_empty() {
+        return Err(format!("\nchain_from_json() :: ERROR -> No usable contracts for {} from {}", ticker, source).into());
+    }
+    Ok(OptionChain {
+        expiries,
+        ticker: ticker.to_string(),
+        current_price,
+        div_yield,
+        rate: FEDFUNDS,
+        quote_time,
+        source: source.to_string(),
+    })
+}
+
+// A JSON/REST option chain endpoint; every "{ticker}" in url is replaced with the ticker
+pub struct JsonSource {
+    pub url: String,
+    pub mapping: FieldMapping,
+}
+
+impl OptionChainSource for JsonSource {
+    fn name(&self) -> String {
+        format!("json ({})", self.url)
+    }
+    fn fetch(&self, ticker: &str) -> Result<OptionChain, Box<dyn Error>> {
+        let url = self.url.replace("{ticker}", ticker);
+        let response = reqwest::blocking::get(&url)?.error_for_status()?;
+        let doc: Value = response.json()?;
+        chain_from_json(ticker, &doc, &self.mapping, &url)
+    }
+}
+
+/* Offline snapshots: <dir>/<TICKER>.csv (a versioned chain csv, see options::chain_to_csv())
+or otherwise <dir>/<TICKER>.json (a JSON chain document read with mapping)
+*/
+pub struct FixtureSource {
+    pub dir: String,
+    pub mapping: FieldMapping,
+}
+
+impl OptionChainSource for FixtureSource {
+    fn name(&self) -> String {
+        format!("fixtures ({})", self.dir)
+    }
+    fn fetch(&self, ticker: &str) -> Result<OptionChain, Box<dyn Error>> {
+        let csv_path = Path::new(&self.dir).join(format!("{}.csv", ticker));
+        if csv_path.exists() {
+            return chain_from_csv(&csv_path.to_string_lossy());
+        }
+        let json_path = Path::new(&self.dir).join(format!("{}.json", ticker));
+        let contents = fs::read_to_string(&json_path)
+            .map_err(|e| format!("\nFixtureSource::fetch() :: ERROR -> No {}.csv or {}.json fixture in {}: {}", ticker, ticker, self.dir, e))?;
+        let doc: Value = serde_json::from_str(&contents)?;
+        chain_from_json(ticker, &doc, &self.mapping, &json_path.to_string_lossy())
+    }
+}
+
+/* Option chain source configuration file, selected by its "kind" field:
+{"kind": "browser", "url_prefix": "...", "url_suffix": "..."}
+{"kind": "json", "url": "https://.../chains/{ticker}", "mapping": {"contracts": "/data", ...}}
+{"kind": "fixtures", "dir": "fixtures/chains"}
+Omitted mapping fields keep their FieldMapping defaults
+*/
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum SourceConfig {
+    Browser {
+        url_prefix: Option<String>,
+        url_suffix: Option<String>,
+    },
+    Json {
+        url: String,
+        #[serde(default)]
+        mapping: FieldMapping,
+    },
+    Fixtures {
+        dir: String,
+        #[serde(default)]
+        mapping: FieldMapping,
+    },
+}
+
+/* Loads the option chain source from a configuration file
+Fails when the file does not exist rather than guessing a provider; SOURCEEXAMPLE is a working fixtures configuration
+*/
+pub fn source_from_config(config_path: &str) -> Result<Box<dyn OptionChainSource>, Box<dyn Error>> {
+    if !Path::new(config_path).exists() {
+        return Err(format!("\nsource_from_config() :: ERROR -> No option chain source configuration at {}; copy {} there (offline fixtures) or write a browser/json configuration, or pass --source / set CHAIN_SOURCE", config_path, SOURCEEXAMPLE).into());
+    }
+    let config: SourceConfig = serde_json::from_str(&fs::read_to_string(config_path)?)
+        .map_err(|e| format!("\nsource_from_config() :: ERROR -> Invalid option chain source configuration in {}: {}", config_path, e))?;
+    Ok(match config {
+        SourceConfig::Browser { url_prefix, url_suffix } => {
+            let default = BrowserSource::default();
+            Box::new(BrowserSource {
+                url_prefix: url_prefix.unwrap_or(default.url_prefix),
+                url_suffix: url_suffix.unwrap_or(default.url_suffix),
+            })
+        },
+        SourceConfig::Json { url, mapping } => Box::new(JsonSource { url, mapping }),
+        SourceConfig::Fixtures { dir, mapping } => Box::new(FixtureSource { dir, mapping }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::chain_to_csv;
+    use serde_json::json;
+
+    #[test]
+    fn chain_from_json_skips_contracts_of_unknown_type() {
+        let doc = json!({
+            "underlying": {"price": "1,050.5"},
+            "quote_time": "2024-06-03T19:30:00Z",
+            "data": [
+                {"exp": 1718928000, "k": 1000, "cp": "C", "bid": 60.0, "ask": 61.0},
+                {"exp": 1718928000, "k": 1000, "cp": "p", "bid": 9.0, "ask": 9.5},
+                {"exp": 1718928000, "k": 1100, "cp": "X", "bid": 1.0, "ask": 1.5},
+                {"exp": 1718928000, "k": 1100, "bid": 1.0, "ask": 1.5},
+                {"exp": 1718928000, "cp": "C", "bid": 1.0, "ask": 1.5}
+            ]
+        });
+        let mapping = FieldMapping { contracts: "/data".to_string(), expiration: "/exp".to_string(), expiration_format: "unix".to_string(), strike: "/k".to_string(), option_type: "/cp".to_string(), call_value: "c".to_string(), put_value: "P".to_string(), ..FieldMapping::default() };
+        let chain = chain_from_json("TEST", &doc, &mapping, "test").unwrap();
+        assert_eq!((chain.current_price, chain.div_yield, chain.expiries.len()), (1050.5, 0.0, 1));
+        let expiry = &chain.expiries[0];
+        assert_eq!(expiry.date, "June 21 2024");
+        assert_eq!((expiry.calls.len(), expiry.puts.len(), expiry.calls[0].strike, expiry.puts[0].bid), (1, 1, 1000.0, 9.0));
+        assert!(expiry.calls[0].is_call && !expiry.puts[0].is_call && expiry.yte > 0.0);
+        let untyped = json!({"underlying": {"price": 100.0}, "options": [{"expiration": "2024-06-21", "strike": 100}]});
+        assert!(chain_from_json("TEST", &untyped, &FieldMapping::default(), "test").is_err());
+    }
+
+    #[test]
+    fn source_from_config_requires_a_configuration() {
+        let err = source_from_config("no_such_chain_source.json").err().unwrap().to_string();
+        assert!(err.contains("no_such_chain_source.json") && err.contains(SOURCEEXAMPLE), "{}", err);
+        assert_eq!(source_from_config(SOURCEEXAMPLE).unwrap().name(), "fixtures (fixtures/chains)");
+    }
+
+    #[test]
+    fn fixture_source_loads_the_spy_chain() {
+        let source = FixtureSource { dir: "fixtures/chains".to_string(), mapping: FieldMapping::default() };
+        let chain = source.fetch("SPY").unwrap();
+        assert_eq!((chain.ticker.as_str(), chain.current_price, chain.div_yield, chain.rate), ("SPY", 520.0, 0.013, FEDFUNDS));
+        assert_eq!(chain.quote_time.to_rfc3339(), "2024-06-03T19:30:00+00:00");
+        let dates: Vec<&str> = chain.expiries.iter().map(|e| e.date.as_str()).collect();
+        assert_eq!(dates, ["June 21 2024", "July 19 2024", "September 20 2024", "December 20 2024"]);
+        for expiry in &chain.expiries {
+            assert_eq!((expiry.calls.len(), expiry.puts.len()), (9, 9));
+            assert!(expiry.calls.iter().all(|c| c.is_call) && expiry.puts.iter().all(|p| !p.is_call));
+            assert!(expiry.calls.windows(2).all(|w| w[0].strike < w[1].strike) && expiry.calls[0].strike == 480.0);
+        }
+        let june = NaiveDate::from_ymd_opt(2024, 6, 21).unwrap();
+        assert_eq!(chain.expiries[0].yte, year_fraction(chain.quote_time, june, DayCount::Act365));
+        assert!((chain.expiries[0].yte - (18.0 * 24.0 + 0.5) / 8760.0).abs() < 1e-12);
+        assert_eq!((chain.expiries[0].calls[0].bid, chain.expiries[0].puts[0].ask, chain.expiries[0].puts[0].open_int), (41.18, 0.09, 486.0));
+        let err = source.fetch("QQQ").err().unwrap().to_string();
+        assert!(err.contains("No QQQ.csv or QQQ.json fixture"), "{}", err);
+        // A <TICKER>.csv snapshot takes precedence over the JSON document
+        let dir = std::env::temp_dir().join(format!("markets_sources_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut snapshot = chain.clone();
+        snapshot.current_price = 525.0;
+        chain_to_csv(&snapshot, dir.join("SPY.csv").to_str().unwrap()).unwrap();
+        fs::copy("fixtures/chains/SPY.json", dir.join("SPY.json")).unwrap();
+        let csv_source = FixtureSource { dir: dir.to_string_lossy().to_string(), mapping: FieldMapping::default() };
+        assert_eq!(csv_source.fetch("SPY").unwrap().current_price, 525.0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
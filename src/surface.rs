@@ -0,0 +1,457 @@
+use super::finmath::nelder_mead;
+use super::options::{OptionChain, PricingContext};
+use super::utils::csv_value;
+use csv::Writer;
+use std::collections::HashMap;
+use std::error::Error;
+
+// Fewest out-of-the-money quotes an expiry needs for its five-parameter SVI fit
+const MIN_SLICE_POINTS: usize = 5;
+// Shortest expiry fitted; same-day expiries have too little time value to invert reliably
+const MIN_YTE: f64 = 1.0 / 365.0;
+const FIT_TOL: f64 = 1e-14;
+// Log-moneyness grid (from, to, points) the static arbitrage checks are evaluated on
+const ARB_GRID: (f64, f64, usize) = (-1.5, 1.5, 301);
+const ARB_TOL: f64 = 1e-9;
+
+/* Raw SVI total implied variance of one expiry (Gatheral 2004) in log-moneyness k = ln(K / F)
+w(k) = a + b * (rho * (k - m) + sqrt((k - m)^2 + sigma^2)), with b >= 0, |rho| < 1 and sigma > 0
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SviParams {
+    pub a: f64,
+    pub b: f64,
+    pub rho: f64,
+    pub m: f64,
+    pub sigma: f64,
+}
+
+impl SviParams {
+    pub fn total_variance(&self, k: f64) -> f64 {
+        self.derivatives(k).0
+    }
+    // w, dw/dk and d2w/dk2
+    fn derivatives(&self, k: f64) -> (f64, f64, f64) {
+        let x = k - self.m;
+        let r = (x * x + self.sigma * self.sigma).sqrt();
+        (self.a + self.b * (self.rho * x + r), self.b * (self.rho + x / r), self.b * self.sigma * self.sigma / (r * r * r))
+    }
+    // Butterfly density g(k); negative values are butterfly arbitrage
+    pub fn density(&self, k: f64) -> f64 {
+        let (w, w1, w2) = self.derivatives(k);
+        butterfly_density(k, w, w1, w2)
+    }
+}
+
+/* Surface SVI (Gatheral-Jacquier 2014) with a power-law curvature function
+w(k, theta) = theta / 2 * (1 + rho * phi * k + sqrt((phi * k + rho)^2 + 1 - rho^2)), phi = eta / (theta^gamma * (1 + theta)^(1 - gamma))
+theta = ATM total variance of each expiry; free of butterfly arbitrage when eta * (1 + |rho|) <= 2 with gamma <= 1/2,
+and of calendar arbitrage when theta is non-decreasing in yte
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SsviParams {
+    pub rho: f64,
+    pub eta: f64,
+    pub gamma: f64,
+}
+
+impl SsviParams {
+    pub fn phi(&self, theta: f64) -> f64 {
+        self.eta / (theta.powf(self.gamma) * (1.0 + theta).powf(1.0 - self.gamma))
+    }
+    pub fn total_variance(&self, k: f64, theta: f64) -> f64 {
+        self.derivatives(k, theta).0
+    }
+    fn derivatives(&self, k: f64, theta: f64) -> (f64, f64, f64) {
+        let (phi, rho) = (self.phi(theta), self.rho);
+        let z = phi * k + rho;
+        let s = (z * z + 1.0 - rho * rho).sqrt();
+        (0.5 * theta * (1.0 + rho * phi * k + s), 0.5 * theta * phi * (rho + z / s), 0.5 * theta * phi * phi * (1.0 - rho * rho) / (s * s * s))
+    }
+    pub fn density(&self, k: f64, theta: f64) -> f64 {
+        let (w, w1, w2) = self.derivatives(k, theta);
+        butterfly_density(k, w, w1, w2)
+    }
+    // The Gatheral-Jacquier sufficient condition for a surface free of butterfly arbitrage
+    pub fn butterfly_free(&self) -> bool {
+        self.eta * (1.0 + self.rho.abs()) <= 2.0 + ARB_TOL && (0.0..=0.5).contains(&self.gamma)
+    }
+}
+
+// Gatheral's g(k) = (1 - k w' / 2w)^2 - w'^2 / 4 * (1 / w + 1 / 4) + w'' / 2, proportional to the risk-neutral density
+fn butterfly_density(k: f64, w: f64, w1: f64, w2: f64) -> f64 {
+    if w <= 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    (1.0 - k * w1 / (2.0 * w)).powi(2) - w1 * w1 / 4.0 * (1.0 / w + 0.25) + w2 / 2.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SurfaceModel {
+    Svi,
+    Ssvi,
+}
+
+impl SurfaceModel {
+    pub fn label(&self) -> &'static str {
+        match self {
+            SurfaceModel::Svi => "SVI",
+            SurfaceModel::Ssvi => "SSVI",
+        }
+    }
+}
+
+/* One fitted expiry
+forward = spot * exp((r - q) * yte) from the expiry's pricing inputs
+points = market (k, total variance) of the out-of-the-money quotes fitted (empty when loaded from csv)
+rmse / ssvi_rmse = root mean square total variance error of the SVI slice / the SSVI surface at this expiry
+theta = ATM total variance the SSVI surface is pinned to (the SVI fit at k = 0, made non-decreasing across expiries)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct SviSlice {
+    pub date: String,
+    pub yte: f64,
+    pub forward: f64,
+    pub params: SviParams,
+    pub rmse: f64,
+    pub theta: f64,
+    pub ssvi_rmse: f64,
+    pub points: Vec<(f64, f64)>,
+}
+
+/* Static arbitrage of one slice on the ARB_GRID log-moneyness grid
+min_density = smallest butterfly density g(k); butterfly = grid points with g(k) < 0
+calendar = grid points where total variance falls below the previous expiry's (0 for the first slice)
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SliceArbitrage {
+    pub min_density: f64,
+    pub butterfly: usize,
+    pub calendar: usize,
+}
+
+// SVI slices in yte order with an optional SSVI surface across them
+#[derive(Debug, Clone, PartialEq)]
+pub struct VolSurface {
+    pub spot: f64,
+    pub slices: Vec<SviSlice>,
+    pub ssvi: Option<SsviParams>,
+}
+
+impl VolSurface {
+    /* Linear interpolation in yte of a per-slice value between the bracketing slices; before the first slice the value
+    is scaled toward zero and after the last it is scaled proportionally to yte (flat implied volatility / carry)
+    */
+    fn interpolate(&self, yte: f64, f: impl Fn(&SviSlice) -> f64) -> f64 {
+        let (Some(first), Some(last)) = (self.slices.first(), self.slices.last()) else {
+            return f64::NAN;
+        };
+        if yte <= first.yte {
+            return f(first) * yte / first.yte;
+        }
+        if yte >= last.yte {
+            return f(last) * yte / last.yte;
+        }
+        let j = self.slices.iter().position(|s| s.yte >= yte).unwrap_or(self.slices.len() - 1);
+        let (lo, hi) = (&self.slices[j - 1], &self.slices[j]);
+        let x = (yte - lo.yte) / (hi.yte - lo.yte);
+        (1.0 - x) * f(lo) + x * f(hi)
+    }
+    pub fn forward(&self, yte: f64) -> f64 {
+        self.spot * self.interpolate(yte, |s| (s.forward / self.spot).ln()).exp()
+    }
+    // Total implied variance at log-moneyness k (NaN for SSVI when no SSVI surface was fitted)
+    pub fn total_variance(&self, k: f64, yte: f64, model: SurfaceModel) -> f64 {
+        match (model, self.ssvi) {
+            (SurfaceModel::Svi, _) => self.interpolate(yte, |s| s.params.total_variance(k)),
+            (SurfaceModel::Ssvi, Some(ssvi)) => ssvi.total_variance(k, self.interpolate(yte, |s| s.theta)),
+            (SurfaceModel::Ssvi, None) => f64::NAN,
+        }
+    }
+    // Smooth implied volatility at any strike and yte > 0
+    pub fn iv(&self, strike: f64, yte: f64, model: SurfaceModel) -> f64 {
+        let w = self.total_variance((strike / self.forward(yte)).ln(), yte, model);
+        if yte > 0.0 && w > 0.0 { (w / yte).sqrt() } else { f64::NAN }
+    }
+    // Butterfly and calendar arbitrage of every slice in yte order
+    pub fn arbitrage(&self, model: SurfaceModel) -> Vec<SliceArbitrage> {
+        let (from, to, n) = ARB_GRID;
+        let grid: Vec<f64> = (0..n).map(|i| from + (to - from) * i as f64 / (n - 1) as f64).collect();
+        let slice_curve = |s: &SviSlice| -> Vec<(f64, f64)> {
+            grid.iter()
+                .map(|&k| match (model, self.ssvi) {
+                    (SurfaceModel::Svi, _) => (s.params.total_variance(k), s.params.density(k)),
+                    (SurfaceModel::Ssvi, Some(ssvi)) => (ssvi.total_variance(k, s.theta), ssvi.density(k, s.theta)),
+                    (SurfaceModel::Ssvi, None) => (f64::NAN, f64::NAN),
+                })
+                .collect()
+        };
+        let curves: Vec<Vec<(f64, f64)>> = self.slices.iter().map(slice_curve).collect();
+        curves
+            .iter()
+            .enumerate()
+            .map(|(i, curve)| {
+                let calendar = match i.checked_sub(1) {
+                    Some(prev) => curve.iter().zip(&curves[prev]).filter(|((w, _), (w_prev, _))| *w < w_prev - ARB_TOL).count(),
+                    None => 0,
+                };
+                SliceArbitrage {
+                    min_density: curve.iter().map(|(_, g)| *g).fold(f64::INFINITY, f64::min),
+                    butterfly: curve.iter().filter(|(_, g)| g.is_nan() || *g < -ARB_TOL).count(),
+                    calendar,
+                }
+            })
+            .collect()
+    }
+}
+
+fn rmse(points: &[(f64, f64)], w: impl Fn(f64) -> f64) -> f64 {
+    (points.iter().map(|(k, wk)| (w(*k) - wk).powi(2)).sum::<f64>() / points.len() as f64).sqrt()
+}
+
+/* Least squares SVI fit of (k, total variance) points
+Unconstrained coordinates (a, ln b, atanh rho, m, ln sigma) are minimized by Nelder-Mead from several starting smiles;
+parameters with a negative minimum variance (a + b * sigma * sqrt(1 - rho^2) < 0) or wings steeper than Lee's moment
+bound (b * (1 + |rho|) > 2) are rejected
+*/
+pub fn fit_svi(points: &[(f64, f64)]) -> Result<SviParams, String> {
+    if points.len() < MIN_SLICE_POINTS || points.iter().any(|(k, w)| !k.is_finite() || !w.is_finite() || *w <= 0.0) {
+        return Err(format!("\nfit_svi() :: ERROR -> SVI needs at least {} finite (log-moneyness, positive total variance) points; got {}", MIN_SLICE_POINTS, points.len()));
+    }
+    let params = |u: &[f64]| SviParams { a: u[0], b: u[1].exp(), rho: u[2].tanh(), m: u[3], sigma: u[4].exp() };
+    let scale = points.iter().map(|(_, w)| w * w).sum::<f64>() / points.len() as f64;
+    let objective = |u: &[f64]| {
+        let p = params(u);
+        if p.a + p.b * p.sigma * (1.0 - p.rho * p.rho).sqrt() < 0.0 || p.b * (1.0 + p.rho.abs()) > 2.0 {
+            return f64::INFINITY;
+        }
+        points.iter().map(|(k, w)| (p.total_variance(*k) - w).powi(2)).sum::<f64>() / scale
+    };
+    let (k_min, w_min) = points.iter().copied().min_by(|a, b| a.1.total_cmp(&b.1)).unwrap_or((0.0, 0.0));
+    let mut best: Option<(f64, Vec<f64>)> = None;
+    for rho in [-0.5f64, 0.0, 0.5] {
+        for sigma in [0.05f64, 0.3] {
+            let b: f64 = 0.1;
+            let a = w_min - b * sigma * (1.0 - rho * rho).sqrt();
+            let result = nelder_mead(objective, &[a, b.ln(), f64::atanh(rho), k_min, f64::ln(sigma)], 0.5, FIT_TOL);
+            if best.as_ref().is_none_or(|(f, _)| result.f < *f) {
+                best = Some((result.f, result.x));
+            }
+        }
+    }
+    match best {
+        Some((f, x)) if f.is_finite() => Ok(params(&x)),
+        _ => Err("\nfit_svi() :: ERROR -> No SVI parameters satisfy the minimum variance and moment bounds".to_string()),
+    }
+}
+
+/* Least squares SSVI fit of every slice's points with each slice pinned to its theta
+Unconstrained coordinates (atanh rho, ln eta, atanh(4 gamma - 1)) keep gamma in (0, 1/2); parameters breaking the
+butterfly condition eta * (1 + |rho|) <= 2 are rejected
+*/
+pub fn fit_ssvi(slices: &[SviSlice]) -> Result<SsviParams, String> {
+    let count: usize = slices.iter().map(|s| s.points.len()).sum();
+    if count < 3 || slices.iter().any(|s| s.theta.is_nan() || s.theta <= 0.0) {
+        return Err(format!("\nfit_ssvi() :: ERROR -> SSVI needs at least 3 points on slices with a positive ATM total variance; got {} points", count));
+    }
+    let params = |u: &[f64]| SsviParams { rho: u[0].tanh(), eta: u[1].exp(), gamma: 0.25 * (1.0 + u[2].tanh()) };
+    let scale = slices.iter().flat_map(|s| &s.points).map(|(_, w)| w * w).sum::<f64>() / count as f64;
+    let objective = |u: &[f64]| {
+        let p = params(u);
+        if !p.butterfly_free() {
+            return f64::INFINITY;
+        }
+        slices.iter().flat_map(|s| s.points.iter().map(move |(k, w)| (p.total_variance(*k, s.theta) - w).powi(2))).sum::<f64>() / scale
+    };
+    let mut best: Option<(f64, Vec<f64>)> = None;
+    for rho in [-0.5, 0.0] {
+        for eta in [0.3, 1.0] {
+            let result = nelder_mead(objective, &[f64::atanh(rho), f64::ln(eta), 0.0], 0.5, FIT_TOL);
+            if best.as_ref().is_none_or(|(f, _)| result.f < *f) {
+                best = Some((result.f, result.x));
+            }
+        }
+    }
+    match best {
+        Some((f, x)) if f.is_finite() => Ok(params(&x)),
+        _ => Err("\nfit_ssvi() :: ERROR -> No SSVI parameters satisfy the butterfly condition".to_string()),
+    }
+}
+
+/* Fits an SVI slice to every expiry of the chain and an SSVI surface across them
+Each expiry uses its OptionExpiry::otm_smile() under ctx; expiries shorter than MIN_YTE or with fewer than MIN_SLICE_POINTS quotes are skipped
+A failed SSVI fit only leaves the surface without one
+*/
+pub fn fit_surface(chain: &OptionChain, ctx: &PricingContext) -> Result<VolSurface, String> {
+    let mut slices = Vec::new();
+    for expiry in &chain.expiries {
+        if expiry.yte < MIN_YTE {
+            continue;
+        }
+        let rate = expiry.rate(&ctx.curve);
+        let (spot, div) = chain.pricing_inputs(expiry, ctx);
+        let forward = spot * ((rate - div) * expiry.yte).exp();
+        let points: Vec<(f64, f64)> = expiry
+            .otm_smile(spot, rate, div, ctx.model.as_ref(), ctx.basis)
+            .into_iter()
+            .map(|(strike, iv)| ((strike / forward).ln(), iv * iv * expiry.yte))
+            .collect();
+        match fit_svi(&points) {
+            Ok(params) => {
+                let rmse = rmse(&points, |k| params.total_variance(k));
+                slices.push(SviSlice { date: expiry.date.clone(), yte: expiry.yte, forward, params, rmse, theta: f64::NAN, ssvi_rmse: f64::NAN, points });
+            },
+            Err(e) => eprintln!("{}\n\nfit_surface() :: Skipping the {} {} expiry", e, chain.ticker, expiry.date),
+        }
+    }
+    if slices.is_empty() {
+        return Err(format!("\nfit_surface() :: ERROR -> No {} expiry has enough converged out-of-the-money implied volatilities to fit", chain.ticker));
+    }
+    slices.sort_by(|a, b| a.yte.total_cmp(&b.yte));
+    let mut theta: f64 = 0.0;
+    for slice in slices.iter_mut() {
+        theta = theta.max(slice.params.total_variance(0.0));
+        slice.theta = theta;
+    }
+    let ssvi = match fit_ssvi(&slices) {
+        Ok(ssvi) => {
+            for slice in slices.iter_mut() {
+                slice.ssvi_rmse = rmse(&slice.points, |k| ssvi.total_variance(k, slice.theta));
+            }
+            Some(ssvi)
+        },
+        Err(e) => {
+            eprintln!("{}\n\nfit_surface() :: Continuing with the {} SVI slices only", e, chain.ticker);
+            None
+        },
+    };
+    let spot = chain.expiries.first().map_or(chain.current_price, |e| chain.pricing_inputs(e, ctx).0);
+    Ok(VolSurface { spot, slices, ssvi })
+}
+
+/* Saves the surface as one row of parameters per expiry
+Expiry, Yte, Spot, Forward, SVI A, B, Rho, M, Sigma, Points, Rmse and arbitrage (MinDensity, ButterflyArb and CalendarArb
+grid point counts), then Theta and the SSVI Rho, Eta, Gamma (repeated on every row), Rmse and arbitrage columns
+*/
+pub fn surface_to_csv(surface: &VolSurface, csv_name: &str) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_path(csv_name)?;
+    wtr.write_record(["Expiry", "Yte", "Spot", "Forward", "A", "B", "Rho", "M", "Sigma", "Points", "Rmse", "MinDensity", "ButterflyArb", "CalendarArb", "Theta", "SsviRho", "SsviEta", "SsviGamma", "SsviRmse", "SsviMinDensity", "SsviButterflyArb", "SsviCalendarArb"])?;
+    let svi_arb = surface.arbitrage(SurfaceModel::Svi);
+    let ssvi_arb = surface.arbitrage(SurfaceModel::Ssvi);
+    for ((slice, arb), sarb) in surface.slices.iter().zip(&svi_arb).zip(&ssvi_arb) {
+        let p = slice.params;
+        let mut record = vec![slice.date.clone(), csv_value(slice.yte), csv_value(surface.spot), csv_value(slice.forward)];
+        record.extend([p.a, p.b, p.rho, p.m, p.sigma].map(csv_value));
+        record.extend([slice.points.len().to_string(), csv_value(slice.rmse), csv_value(arb.min_density), arb.butterfly.to_string(), arb.calendar.to_string(), csv_value(slice.theta)]);
+        match surface.ssvi {
+            Some(s) => {
+                record.extend([s.rho, s.eta, s.gamma, slice.ssvi_rmse, sarb.min_density].map(csv_value));
+                record.extend([sarb.butterfly.to_string(), sarb.calendar.to_string()]);
+            },
+            None => record.extend(vec![String::new(); 7]),
+        }
+        wtr.write_record(&record)?;
+    }
+    wtr.flush()?;
+    println!("\nsurface_to_csv() :: Successfully created {} for {} expiries", csv_name, surface.slices.len());
+    Ok(())
+}
+
+// Loads a surface saved by surface_to_csv() (without the fitted market points)
+pub fn surface_from_csv(csv_name: &str) -> Result<VolSurface, Box<dyn Error>> {
+    let mut rdr = csv::Reader::from_path(csv_name)?;
+    let (mut spot, mut slices, mut ssvi) = (f64::NAN, Vec::new(), None);
+    for result in rdr.deserialize::<HashMap<String, String>>() {
+        let row = result?;
+        let value = |key: &str| row.get(key).and_then(|v| v.trim().parse::<f64>().ok()).unwrap_or(f64::NAN);
+        let params = SviParams { a: value("A"), b: value("B"), rho: value("Rho"), m: value("M"), sigma: value("Sigma") };
+        if value("Yte").is_nan() || value("Yte") <= 0.0 || [params.a, params.b, params.rho, params.m, params.sigma].iter().any(|x| !x.is_finite()) {
+            return Err(format!("\nsurface_from_csv() :: ERROR -> {} has an expiry without a yte or SVI parameters", csv_name).into());
+        }
+        spot = value("Spot");
+        let s = SsviParams { rho: value("SsviRho"), eta: value("SsviEta"), gamma: value("SsviGamma") };
+        if s.rho.is_finite() && s.eta.is_finite() && s.gamma.is_finite() {
+            ssvi = Some(s);
+        }
+        slices.push(SviSlice {
+            date: row.get("Expiry").cloned().unwrap_or_default(),
+            yte: value("Yte"),
+            forward: value("Forward"),
+            params,
+            rmse: value("Rmse"),
+            theta: value("Theta"),
+            ssvi_rmse: value("SsviRmse"),
+            points: Vec::new(),
+        });
+    }
+    if slices.is_empty() || !spot.is_finite() {
+        return Err(format!("\nsurface_from_csv() :: ERROR -> {} has no fitted expiries", csv_name).into());
+    }
+    Ok(VolSurface { spot, slices, ssvi })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::test_fixtures::{bs_context, bs_expiry, test_chain};
+    use std::env;
+    use std::fs;
+
+    const TRUE_SSVI: SsviParams = SsviParams { rho: -0.4, eta: 0.8, gamma: 0.4 };
+
+    // Mid-quoted chain priced off an SSVI surface with a flat 20% ATM volatility
+    fn ssvi_chain() -> (OptionChain, PricingContext) {
+        let strikes: Vec<f64> = (0..13).map(|i| 70.0 + 5.0 * i as f64).collect();
+        let expiries = [("A", 0.25), ("B", 0.5), ("C", 1.0)]
+            .iter()
+            .map(|(date, t)| {
+                let forward = 100.0 * (0.03f64 * t).exp();
+                bs_expiry(date, *t, 0.03, 0.0, &strikes, 0.0, |strike| (TRUE_SSVI.total_variance((strike / forward).ln(), 0.04 * t) / t).sqrt())
+            })
+            .collect();
+        (test_chain(expiries, 100.0, 0.03), bs_context(0.03))
+    }
+
+    #[test]
+    fn svi_density_flags_butterfly_arbitrage() {
+        // Axel Vogt's arbitrageable slice from Gatheral-Jacquier (2014) against a fit of plain SVI points
+        let vogt = SviParams { a: -0.0410, b: 0.1331, rho: 0.3060, m: 0.3586, sigma: 0.4153 };
+        assert!(vogt.density(0.9) < 0.0 && vogt.density(0.0) > 0.0);
+        let slice = SviSlice { date: "V".to_string(), yte: 1.0, forward: 100.0, params: vogt, rmse: 0.0, theta: vogt.total_variance(0.0), ssvi_rmse: f64::NAN, points: Vec::new() };
+        let arb = VolSurface { spot: 100.0, slices: vec![slice], ssvi: None }.arbitrage(SurfaceModel::Svi);
+        assert!(arb[0].butterfly > 0 && arb[0].min_density < 0.0 && arb[0].calendar == 0);
+        let truth = SviParams { a: 0.02, b: 0.1, rho: -0.3, m: 0.05, sigma: 0.2 };
+        let points: Vec<(f64, f64)> = (-8..=8).map(|i| i as f64 * 0.1).map(|k| (k, truth.total_variance(k))).collect();
+        let fit = fit_svi(&points).unwrap();
+        assert!(rmse(&points, |k| fit.total_variance(k)) < 1e-6, "{:?}", fit);
+        assert!((-15..=15).all(|i| fit.density(i as f64 * 0.1) > 0.0));
+        assert!(fit_svi(&points[..4]).is_err());
+    }
+
+    #[test]
+    fn surface_recovers_ssvi_and_round_trips_through_csv() {
+        let (chain, ctx) = ssvi_chain();
+        let surface = fit_surface(&chain, &ctx).unwrap();
+        assert_eq!(surface.slices.len(), 3);
+        let ssvi = surface.ssvi.unwrap();
+        assert!((ssvi.rho - TRUE_SSVI.rho).abs() < 0.01 && (ssvi.eta - TRUE_SSVI.eta).abs() < 0.02 && (ssvi.gamma - TRUE_SSVI.gamma).abs() < 0.02, "{:?}", ssvi);
+        for slice in &surface.slices {
+            assert!(slice.rmse < 1e-5 && (slice.theta - 0.04 * slice.yte).abs() < 1e-4, "{:?}", slice);
+        }
+        for model in [SurfaceModel::Svi, SurfaceModel::Ssvi] {
+            assert!(surface.arbitrage(model).iter().all(|a| a.butterfly == 0 && a.calendar == 0), "{:?}", model);
+            // Between expiries the flat ATM volatility and the smile shape are preserved
+            assert!((surface.iv(surface.forward(0.75), 0.75, model) - 0.2).abs() < 1e-3);
+            let expected = (TRUE_SSVI.total_variance((90.0 / surface.forward(0.5)).ln(), 0.02) / 0.5).sqrt();
+            assert!((surface.iv(90.0, 0.5, model) - expected).abs() < 1e-3);
+        }
+        let dir = env::temp_dir().join(format!("markets_surface_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let csv_name = dir.join("TEST_svi_2024-06-03_10-00-00.csv");
+        surface_to_csv(&surface, csv_name.to_str().unwrap()).unwrap();
+        let loaded = surface_from_csv(csv_name.to_str().unwrap()).unwrap();
+        assert_eq!(loaded.ssvi, surface.ssvi);
+        assert!((loaded.iv(110.0, 0.6, SurfaceModel::Svi) - surface.iv(110.0, 0.6, SurfaceModel::Svi)).abs() < 1e-12);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
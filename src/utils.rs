@@ -6,25 +6,32 @@ pub fn str_to_float(s: &str) -> f64 {
   s.replace(",", "").parse::<f64>().unwrap_or(0.0)
 }
 
-pub fn clear_directory_or_create(dir_name: &str) -> io::Result<()> {
-    let dir = Path::new(dir_name);
-    if dir.exists() {
-        println!("clear_directory_or_create() :: Cleaning directory {}", dir_name);
-        for entry in fs::read_dir(dir)? {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_dir() {
-                fs::remove_dir_all(&path)?;
-            } else {
-                fs::remove_file(&path)?;
-            }
-        }
+// Formats a csv value, leaving non-finite values (rows without enough history, unsolved fits) blank as missing data
+pub fn csv_value(x: f64) -> String {
+    if x.is_finite() {
+        x.to_string()
     } else {
-        println!("clean_directory_or_create() :: Creating directory {}", dir_name);
-        fs::create_dir_all(dir)?;
+        String::new()
     }
-    println!("clean_directory_or_create() :: Successfully created/cleaned directory {}", dir_name);
-    Ok(())
+}
+
+/* Removes the <ticker>_<label>_<stamp>.png charts of one ticker's run from img_dir, leaving other tickers' and runs' charts
+Labels never contain '_', so a ticker that prefixes another (BRK vs BRK_B) keeps the other's charts
+*/
+pub fn remove_run_charts(img_dir: &str, ticker: &str, stamp: &str) -> io::Result<usize> {
+    let (prefix, suffix) = (format!("{}_", ticker), format!("_{}.png", stamp));
+    let mut removed = 0;
+    for entry in fs::read_dir(img_dir)? {
+        let path = entry?.path();
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let label = name.strip_prefix(&prefix).and_then(|n| n.strip_suffix(&suffix));
+        if path.is_file() && label.is_some_and(|l| !l.is_empty() && !l.contains('_')) {
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    println!("remove_run_charts() :: Removed {} {} charts stamped {} from {}", removed, ticker, stamp, img_dir);
+    Ok(removed)
 }
 
 pub fn create_directory_if_dne(dir_name: &str) -> io::Result<()> {
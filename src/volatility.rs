@@ -0,0 +1,278 @@
+use super::avantage::{daily_returns, mean, periods_per_year, Bar, TimeSeries, TRADING_DAYS};
+use super::options::AtmIv;
+use super::utils::csv_value;
+use csv::Writer;
+use std::error::Error;
+use std::str::FromStr;
+
+// Trading-day windows of the volatility cone
+pub const CONE_WINDOWS: [usize; 6] = [10, 20, 30, 60, 90, 120];
+// RiskMetrics daily decay factor for ewma_vol()
+pub const EWMA_LAMBDA: f64 = 0.94;
+
+/* Realized volatility estimators, all annualized with the bars' periods per year (avantage::periods_per_year())
+CloseToClose = sample standard deviation of log close-to-close returns
+Parkinson = high-low range estimator (no drift, no opening jumps)
+GarmanKlass = range plus open-to-close estimator (no drift, no opening jumps)
+RogersSatchell = range estimator unbiased under drift (no opening jumps)
+YangZhang = overnight + open-to-close + Rogers-Satchell combination, unbiased under drift and opening jumps
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RvEstimator {
+    CloseToClose,
+    Parkinson,
+    GarmanKlass,
+    RogersSatchell,
+    YangZhang,
+}
+
+impl RvEstimator {
+    pub const ALL: [RvEstimator; 5] = [RvEstimator::CloseToClose, RvEstimator::Parkinson, RvEstimator::GarmanKlass, RvEstimator::RogersSatchell, RvEstimator::YangZhang];
+    pub fn label(&self) -> &'static str {
+        match self {
+            RvEstimator::CloseToClose => "CloseToClose",
+            RvEstimator::Parkinson => "Parkinson",
+            RvEstimator::GarmanKlass => "GarmanKlass",
+            RvEstimator::RogersSatchell => "RogersSatchell",
+            RvEstimator::YangZhang => "YangZhang",
+        }
+    }
+}
+
+impl FromStr for RvEstimator {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace(['-', '_'], "").as_str() {
+            "cc" | "closetoclose" => Ok(RvEstimator::CloseToClose),
+            "parkinson" => Ok(RvEstimator::Parkinson),
+            "gk" | "garmanklass" => Ok(RvEstimator::GarmanKlass),
+            "rs" | "rogerssatchell" => Ok(RvEstimator::RogersSatchell),
+            "yz" | "yangzhang" => Ok(RvEstimator::YangZhang),
+            _ => Err(format!("\nRvEstimator::from_str() :: ERROR -> Unknown realized volatility estimator '{}'; expected cc, parkinson, gk, rs or yz", s)),
+        }
+    }
+}
+
+// Sample variance (n - 1 denominator)
+fn sample_var(data: &[f64]) -> f64 {
+    let avg = mean(data);
+    data.iter().map(|x| (x - avg).powi(2)).sum::<f64>() / (data.len() - 1) as f64
+}
+
+fn rogers_satchell_term(bar: &Bar) -> f64 {
+    (bar.high / bar.close).ln() * (bar.high / bar.open).ln() + (bar.low / bar.close).ln() * (bar.low / bar.open).ln()
+}
+
+/* Annualized realized volatility of the latest `window` periods of bars
+Every estimator needs window + 1 bars (the extra leading bar supplies the previous close) and returns NaN otherwise
+*/
+pub fn realized_vol(bars: &[Bar], window: usize, estimator: RvEstimator) -> f64 {
+    if window < 2 || bars.len() < window + 1 {
+        return f64::NAN;
+    }
+    let bars = &bars[bars.len() - window - 1..];
+    let periods = &bars[1..];
+    let n = window as f64;
+    let var = match estimator {
+        RvEstimator::CloseToClose => {
+            let returns: Vec<f64> = bars.windows(2).map(|w| (w[1].close / w[0].close).ln()).collect();
+            sample_var(&returns)
+        },
+        RvEstimator::Parkinson => periods.iter().map(|b| (b.high / b.low).ln().powi(2)).sum::<f64>() / (4.0 * n * 2f64.ln()),
+        RvEstimator::GarmanKlass => {
+            periods
+                .iter()
+                .map(|b| 0.5 * (b.high / b.low).ln().powi(2) - (2.0 * 2f64.ln() - 1.0) * (b.close / b.open).ln().powi(2))
+                .sum::<f64>()
+                / n
+        },
+        RvEstimator::RogersSatchell => periods.iter().map(rogers_satchell_term).sum::<f64>() / n,
+        RvEstimator::YangZhang => {
+            let overnight: Vec<f64> = bars.windows(2).map(|w| (w[1].open / w[0].close).ln()).collect();
+            let open_close: Vec<f64> = periods.iter().map(|b| (b.close / b.open).ln()).collect();
+            let rs = periods.iter().map(rogers_satchell_term).sum::<f64>() / n;
+            let k = 0.34 / (1.34 + (n + 1.0) / (n - 1.0));
+            sample_var(&overnight) + k * sample_var(&open_close) + (1.0 - k) * rs
+        },
+    };
+    (var.max(0.0) * periods_per_year(bars)).sqrt()
+}
+
+// Rolling realized_vol() aligned with the bars (NaN until window + 1 bars are available)
+pub fn rolling_vol(ts: &TimeSeries, window: usize, estimator: RvEstimator) -> Vec<f64> {
+    (0..ts.len()).map(|i| realized_vol(&ts.bars[..=i], window, estimator)).collect()
+}
+
+/* Annualized exponentially weighted volatility of log close-to-close returns, aligned with the bars
+var[t] = lambda * var[t-1] + (1 - lambda) * r[t]^2, seeded with the mean squared return of the first `seed` returns
+*/
+pub fn ewma_vol(ts: &TimeSeries, lambda: f64, seed: usize) -> Vec<f64> {
+    let returns = daily_returns(ts);
+    let mut vols = vec![f64::NAN; ts.len()];
+    let seed = seed.max(1);
+    if returns.len() < seed {
+        return vols;
+    }
+    let periods = ts.periods_per_year();
+    let mut var = returns[..seed].iter().map(|r| r * r).sum::<f64>() / seed as f64;
+    vols[seed] = (var * periods).sqrt();
+    for (i, r) in returns.iter().enumerate().skip(seed) {
+        var = lambda * var + (1.0 - lambda) * r * r;
+        vols[i + 1] = (var * periods).sqrt();
+    }
+    vols
+}
+
+// Linearly interpolated percentile (p in [0, 1]) of ascending sorted data
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+    let pos = p.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+    sorted[lo] + (pos - lo as f64) * (sorted[hi] - sorted[lo])
+}
+
+/* One window of a volatility cone: the distribution of every rolling realized volatility of that length in the series
+min .. max = percentiles (0, 25, 50, 75, 100) of the rolling values; current = the latest one
+*/
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VolCone {
+    pub window: usize,
+    pub min: f64,
+    pub p25: f64,
+    pub median: f64,
+    pub p75: f64,
+    pub max: f64,
+    pub current: f64,
+}
+
+// Volatility cone of a daily series (TimeSeries::is_daily()) for each trading-day window; windows longer than the history are skipped
+pub fn vol_cone(ts: &TimeSeries, estimator: RvEstimator, windows: &[usize]) -> Vec<VolCone> {
+    let mut cones = Vec::new();
+    for &window in windows {
+        let mut vols: Vec<f64> = rolling_vol(ts, window, estimator).into_iter().filter(|v| v.is_finite()).collect();
+        let current = match vols.last() {
+            Some(v) => *v,
+            None => continue,
+        };
+        vols.sort_by(|a, b| a.total_cmp(b));
+        cones.push(VolCone {
+            window,
+            min: percentile(&vols, 0.0),
+            p25: percentile(&vols, 0.25),
+            median: percentile(&vols, 0.5),
+            p75: percentile(&vols, 0.75),
+            max: percentile(&vols, 1.0),
+            current,
+        });
+    }
+    cones
+}
+
+/* Saves a volatility cone next to the chain's ATM implied volatility term structure, both against trading days
+Cone rows have Source = the estimator label and fill Min .. Current; ATM rows have Source = AtmIV, the expiry date
+and fill only Current (days = yte * TRADING_DAYS), so graphing::generate_iv_rv_plot() can chart IV against RV directly
+*/
+pub fn iv_rv_to_csv(cones: &[VolCone], estimator: RvEstimator, atm: &[AtmIv], csv_name: &str) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_path(csv_name)?;
+    wtr.write_record(["Source", "Expiry", "Days", "Min", "P25", "Median", "P75", "Max", "Current"])?;
+    for cone in cones {
+        let mut record = vec![estimator.label().to_string(), String::new(), cone.window.to_string()];
+        record.extend([cone.min, cone.p25, cone.median, cone.p75, cone.max, cone.current].map(csv_value));
+        wtr.write_record(&record)?;
+    }
+    for point in atm.iter().filter(|p| p.iv.is_finite()) {
+        wtr.write_record(["AtmIV".to_string(), point.date.clone(), csv_value(point.yte * TRADING_DAYS), String::new(), String::new(), String::new(), String::new(), String::new(), csv_value(point.iv)])?;
+    }
+    wtr.flush()?;
+    println!("\niv_rv_to_csv() :: Successfully created {} with {} {} cone windows and {} ATM implied volatilities", csv_name, cones.len(), estimator.label(), atm.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, NaiveDate};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    // Daily bars of a driftless GBM sampled `steps` times a day, with overnight gaps of the same volatility
+    fn simulated(sigma: f64, days: usize, steps: usize, overnight: f64, seed: u64) -> TimeSeries {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut normal = move || {
+            let (u1, u2): (f64, f64) = (rng.gen_range(f64::EPSILON..1.0), rng.gen());
+            (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+        };
+        let start = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+        let (mut price, mut bars) = (100.0f64, Vec::new());
+        let (day_sd, night_sd) = ((sigma * sigma * (1.0 - overnight) / TRADING_DAYS).sqrt(), (sigma * sigma * overnight / TRADING_DAYS).sqrt());
+        for d in 0..days {
+            price *= (night_sd * normal() - 0.5 * night_sd * night_sd).exp();
+            let open = price;
+            let (mut high, mut low) = (open, open);
+            let step_sd = day_sd / (steps as f64).sqrt();
+            for _ in 0..steps {
+                price *= (step_sd * normal() - 0.5 * step_sd * step_sd).exp();
+                high = high.max(price);
+                low = low.min(price);
+            }
+            bars.push(Bar::new(start + Duration::days(d as i64), open, high, low, price, 1e6));
+        }
+        TimeSeries::new("SIM", bars)
+    }
+
+    #[test]
+    fn range_estimators_match_closed_forms() {
+        let a: f64 = 0.01;
+        let bar = |d: i64| Bar::new(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap() + Duration::days(d), 100.0, 100.0 * a.exp(), 100.0 * (-a).exp(), 100.0, 1.0);
+        let bars: Vec<Bar> = (0..11).map(bar).collect();
+        let annual = |var: f64| (var * TRADING_DAYS).sqrt();
+        assert!((realized_vol(&bars, 10, RvEstimator::Parkinson) - annual(a * a / 2f64.ln())).abs() < 1e-12);
+        assert!((realized_vol(&bars, 10, RvEstimator::GarmanKlass) - annual(2.0 * a * a)).abs() < 1e-12);
+        assert!((realized_vol(&bars, 10, RvEstimator::RogersSatchell) - annual(2.0 * a * a)).abs() < 1e-12);
+        assert_eq!(realized_vol(&bars, 10, RvEstimator::CloseToClose), 0.0);
+        assert!(realized_vol(&bars, 11, RvEstimator::YangZhang).is_nan());
+    }
+
+    #[test]
+    fn estimators_recover_simulated_volatility() {
+        let ts = simulated(0.3, 600, 390, 0.2, 7);
+        let window = 599;
+        // Range estimators ignore the overnight gap, so only close-to-close and Yang-Zhang see the full variance
+        for est in [RvEstimator::CloseToClose, RvEstimator::YangZhang] {
+            let vol = realized_vol(&ts.bars, window, est);
+            assert!((vol - 0.3).abs() < 0.03, "{} = {}", est.label(), vol);
+        }
+        let intraday = 0.3 * 0.8f64.sqrt();
+        for est in [RvEstimator::Parkinson, RvEstimator::GarmanKlass, RvEstimator::RogersSatchell] {
+            let vol = realized_vol(&ts.bars, window, est);
+            assert!((vol - intraday).abs() < 0.03, "{} = {}", est.label(), vol);
+        }
+        assert_eq!(rolling_vol(&ts, 30, RvEstimator::YangZhang)[599], realized_vol(&ts.bars, 30, RvEstimator::YangZhang));
+    }
+
+    #[test]
+    fn ewma_and_cones() {
+        // Alternating +-1% log returns have a constant 1% daily EWMA volatility
+        let bars: Vec<Bar> = (0..40)
+            .map(|i| {
+                let c = 100.0 * if i % 2 == 0 { 1.0 } else { 0.01f64.exp() };
+                Bar::new(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap() + Duration::days(i), c, c, c, c, 1.0)
+            })
+            .collect();
+        let vols = ewma_vol(&TimeSeries::new("ALT", bars), EWMA_LAMBDA, 10);
+        assert!(vols[9].is_nan());
+        assert!((vols[39] - 0.01 * TRADING_DAYS.sqrt()).abs() < 1e-12);
+        let ts = simulated(0.2, 300, 50, 0.0, 11);
+        let cones = vol_cone(&ts, RvEstimator::CloseToClose, &CONE_WINDOWS);
+        assert_eq!(cones.iter().map(|c| c.window).collect::<Vec<_>>(), CONE_WINDOWS.to_vec());
+        for cone in &cones {
+            assert!(cone.min <= cone.p25 && cone.p25 <= cone.median && cone.median <= cone.p75 && cone.p75 <= cone.max);
+            assert!(cone.min <= cone.current && cone.current <= cone.max);
+        }
+        // Longer windows average out sampling noise, so the cone narrows
+        assert!(cones[5].max - cones[5].min < cones[0].max - cones[0].min);
+        assert_eq!(percentile(&[1.0, 2.0, 3.0, 4.0], 0.5), 2.5);
+    }
+}